@@ -0,0 +1,661 @@
+//! Encodes and decodes the BC1 to BC5 and BC7 block-compressed formats on
+//! the CPU.
+//!
+//! Each block covers 4x4 texels, given in rows from the top left. The block
+//! functions work on one block; the surface functions convert whole RGBA8
+//! surfaces, and `compress` and `decompress` whole `Texture`s. BC7 is a
+//! codec of its own, in `bc7`.
+//!
+//! Decoding follows the D3D rules: BC2 and BC3 always use four colors, and
+//! BC4 and BC5 decode into the red and green channels. The encoder picks
+//! endpoints along the principal axis of each block's colors and then refines
+//! them with a least-squares fit, which is good enough for debug textures and
+//! imported content.
+
+use crate::format::*;
+use crate::texture::{FormatLayout, Subresource, Texture};
+use std::convert::TryInto;
+
+mod bc7;
+#[cfg(test)]
+mod tests;
+
+pub use bc7::{decode_bc7, encode_bc7};
+
+pub type Rgba = [u8; 4];
+
+pub fn decode_bc1(block: &[u8; 8]) -> [Rgba; 16] {
+    decode_color(block, true)
+}
+
+pub fn decode_bc2(block: &[u8; 16]) -> [Rgba; 16] {
+    let mut texels = decode_color(block[8..].try_into().unwrap(), false);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (i * 4)) & 0xf) as u8 * 17;
+    }
+    texels
+}
+
+pub fn decode_bc3(block: &[u8; 16]) -> [Rgba; 16] {
+    let mut texels = decode_color(block[8..].try_into().unwrap(), false);
+    let alpha = decode_bc4(block[..8].try_into().unwrap());
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+pub fn decode_bc4(block: &[u8; 8]) -> [u8; 16] {
+    let palette = bc4_palette(block[0], block[1]);
+    let indices = u64::from_le_bytes(*block) >> 16;
+    std::array::from_fn(|i| palette[((indices >> (i * 3)) & 7) as usize])
+}
+
+pub fn decode_bc4_snorm(block: &[u8; 8]) -> [i8; 16] {
+    let palette = bc4_snorm_palette(block[0] as i8, block[1] as i8);
+    let indices = u64::from_le_bytes(*block) >> 16;
+    std::array::from_fn(|i| palette[((indices >> (i * 3)) & 7) as usize])
+}
+
+/// Returns the red and green channels.
+pub fn decode_bc5(block: &[u8; 16]) -> [[u8; 2]; 16] {
+    let red = decode_bc4(block[..8].try_into().unwrap());
+    let green = decode_bc4(block[8..].try_into().unwrap());
+    std::array::from_fn(|i| [red[i], green[i]])
+}
+
+pub fn decode_bc5_snorm(block: &[u8; 16]) -> [[i8; 2]; 16] {
+    let red = decode_bc4_snorm(block[..8].try_into().unwrap());
+    let green = decode_bc4_snorm(block[8..].try_into().unwrap());
+    std::array::from_fn(|i| [red[i], green[i]])
+}
+
+/// Texels with alpha below 128 are encoded as transparent black, which is
+/// the only transparency BC1 has.
+pub fn encode_bc1(texels: &[Rgba; 16]) -> [u8; 8] {
+    let transparent: [bool; 16] = std::array::from_fn(|i| texels[i][3] < 128);
+    encode_color(
+        texels,
+        transparent.iter().any(|&t| t).then_some(&transparent),
+    )
+}
+
+pub fn encode_bc2(texels: &[Rgba; 16]) -> [u8; 16] {
+    let mut alpha = 0u64;
+    for (i, texel) in texels.iter().enumerate() {
+        alpha |= ((texel[3] as u64 * 15 + 127) / 255) << (i * 4);
+    }
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&alpha.to_le_bytes());
+    block[8..].copy_from_slice(&encode_color(texels, None));
+    block
+}
+
+pub fn encode_bc3(texels: &[Rgba; 16]) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&encode_bc4(&texels.map(|texel| texel[3])));
+    block[8..].copy_from_slice(&encode_color(texels, None));
+    block
+}
+
+pub fn encode_bc4(values: &[u8; 16]) -> [u8; 8] {
+    let values = values.map(|v| v as i32);
+    encode_alpha(&values, 0, 255, |e0, e1| {
+        bc4_palette(e0 as u8, e1 as u8).map(|v| v as i32)
+    })
+}
+
+/// -128 is encoded as -127, which is the same value in SNORM.
+pub fn encode_bc4_snorm(values: &[i8; 16]) -> [u8; 8] {
+    let values = values.map(|v| (v as i32).max(-127));
+    encode_alpha(&values, -127, 127, |e0, e1| {
+        bc4_snorm_palette(e0 as i8, e1 as i8).map(|v| v as i32)
+    })
+}
+
+pub fn encode_bc5(values: &[[u8; 2]; 16]) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&encode_bc4(&values.map(|v| v[0])));
+    block[8..].copy_from_slice(&encode_bc4(&values.map(|v| v[1])));
+    block
+}
+
+pub fn encode_bc5_snorm(values: &[[i8; 2]; 16]) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&encode_bc4_snorm(&values.map(|v| v[0])));
+    block[8..].copy_from_slice(&encode_bc4_snorm(&values.map(|v| v[1])));
+    block
+}
+
+/// Decodes a surface of one of the UNORM or sRGB formats from BC1 to BC5,
+/// or BC7, into tightly packed RGBA8. BC4 and BC5 fill in the channels they
+/// don't have as D3D does, with 0 for green and blue and 255 for alpha.
+/// Returns `None` for other formats, or if `data` is too small.
+pub fn decode_surface(
+    format: DXGI_FORMAT,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    row_pitch: usize,
+) -> Option<Vec<u8>> {
+    let decode: fn(&[u8]) -> [Rgba; 16] = match format {
+        DXGI_FORMAT_BC1_UNORM | DXGI_FORMAT_BC1_UNORM_SRGB => {
+            |block| decode_bc1(block.try_into().unwrap())
+        }
+        DXGI_FORMAT_BC2_UNORM | DXGI_FORMAT_BC2_UNORM_SRGB => {
+            |block| decode_bc2(block.try_into().unwrap())
+        }
+        DXGI_FORMAT_BC3_UNORM | DXGI_FORMAT_BC3_UNORM_SRGB => {
+            |block| decode_bc3(block.try_into().unwrap())
+        }
+        DXGI_FORMAT_BC4_UNORM => {
+            |block| decode_bc4(block.try_into().unwrap()).map(|r| [r, 0, 0, 255])
+        }
+        DXGI_FORMAT_BC5_UNORM => {
+            |block| decode_bc5(block.try_into().unwrap()).map(|[r, g]| [r, g, 0, 255])
+        }
+        DXGI_FORMAT_BC7_UNORM | DXGI_FORMAT_BC7_UNORM_SRGB => {
+            |block| decode_bc7(block.try_into().unwrap())
+        }
+        _ => return None,
+    };
+    let block_size = FormatLayout::of(format)?.bytes_per_block as usize;
+
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = vec![0; width * height * 4];
+    for block_y in 0..height.div_ceil(4) {
+        let row = data.get(block_y * row_pitch..)?;
+        for block_x in 0..width.div_ceil(4) {
+            let block = row.get(block_x * block_size..(block_x + 1) * block_size)?;
+            let texels = decode(block);
+            for (i, texel) in texels.iter().enumerate() {
+                let (x, y) = (block_x * 4 + i % 4, block_y * 4 + i / 4);
+                if x < width && y < height {
+                    let offset = (y * width + x) * 4;
+                    pixels[offset..offset + 4].copy_from_slice(texel);
+                }
+            }
+        }
+    }
+    Some(pixels)
+}
+
+/// Encodes a tightly packed RGBA8 surface into one of the UNORM or sRGB
+/// formats from BC1 to BC5, or BC7, with rows of blocks tightly packed. BC4
+/// and BC5 take the red and green channels. Blocks that hang over the edge
+/// repeat the last row and column. Returns `None` for other formats, or if
+/// `pixels` is too small.
+pub fn encode_surface(
+    format: DXGI_FORMAT,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Option<Vec<u8>> {
+    let encode: fn(&[Rgba; 16], &mut Vec<u8>) = match format {
+        DXGI_FORMAT_BC1_UNORM | DXGI_FORMAT_BC1_UNORM_SRGB => {
+            |texels, out| out.extend_from_slice(&encode_bc1(texels))
+        }
+        DXGI_FORMAT_BC2_UNORM | DXGI_FORMAT_BC2_UNORM_SRGB => {
+            |texels, out| out.extend_from_slice(&encode_bc2(texels))
+        }
+        DXGI_FORMAT_BC3_UNORM | DXGI_FORMAT_BC3_UNORM_SRGB => {
+            |texels, out| out.extend_from_slice(&encode_bc3(texels))
+        }
+        DXGI_FORMAT_BC4_UNORM => {
+            |texels, out| out.extend_from_slice(&encode_bc4(&texels.map(|t| t[0])))
+        }
+        DXGI_FORMAT_BC5_UNORM => {
+            |texels, out| out.extend_from_slice(&encode_bc5(&texels.map(|t| [t[0], t[1]])))
+        }
+        DXGI_FORMAT_BC7_UNORM | DXGI_FORMAT_BC7_UNORM_SRGB => {
+            |texels, out| out.extend_from_slice(&encode_bc7(texels))
+        }
+        _ => return None,
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    if pixels.len() < width * height * 4 || width == 0 || height == 0 {
+        return None;
+    }
+    let layout = FormatLayout::of(format)?;
+    let mut data = Vec::with_capacity(layout.surface_size(width as u32, height as u32) as usize);
+    for block_y in 0..height.div_ceil(4) {
+        for block_x in 0..width.div_ceil(4) {
+            let texels = std::array::from_fn(|i| {
+                let x = (block_x * 4 + i % 4).min(width - 1);
+                let y = (block_y * 4 + i / 4).min(height - 1);
+                let offset = (y * width + x) * 4;
+                pixels[offset..offset + 4].try_into().unwrap()
+            });
+            encode(&texels, &mut data);
+        }
+    }
+    Some(data)
+}
+
+/// Compresses every mip of an RGBA8 texture, keeping it sRGB if it was.
+/// Returns `None` if the texture isn't `DXGI_FORMAT_R8G8B8A8_UNORM` or
+/// `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`, or `format` isn't one that
+/// `encode_surface` supports.
+pub fn compress(texture: &Texture, format: DXGI_FORMAT) -> Option<Texture> {
+    let srgb = match texture.format {
+        DXGI_FORMAT_R8G8B8A8_UNORM => false,
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => true,
+        _ => return None,
+    };
+    let format = if srgb { to_srgb(format)? } else { format };
+    let layout = FormatLayout::of(format)?;
+
+    let subresources = texture
+        .subresources
+        .iter()
+        .map(|subresource| {
+            let row_size = subresource.width as usize * 4;
+            let pixels: Vec<u8> = subresource
+                .rows()
+                .flat_map(|row| &row[..row_size])
+                .copied()
+                .collect();
+            Some(Subresource {
+                width: subresource.width,
+                height: subresource.height,
+                row_pitch: layout.row_pitch(subresource.width),
+                data: encode_surface(format, subresource.width, subresource.height, &pixels)?,
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    Some(Texture {
+        format,
+        width: texture.width,
+        height: texture.height,
+        subresources,
+    })
+}
+
+/// Decompresses every mip of a texture in one of the formats that
+/// `decode_surface` supports into RGBA8, keeping it sRGB if it was.
+pub fn decompress(texture: &Texture) -> Option<Texture> {
+    let format = if to_srgb(texture.format) == Some(texture.format) {
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+    } else {
+        DXGI_FORMAT_R8G8B8A8_UNORM
+    };
+
+    let subresources = texture
+        .subresources
+        .iter()
+        .map(|subresource| {
+            let (width, height) = (subresource.width, subresource.height);
+            Some(Subresource {
+                width,
+                height,
+                row_pitch: width as u64 * 4,
+                data: decode_surface(
+                    texture.format,
+                    width,
+                    height,
+                    &subresource.data,
+                    subresource.row_pitch as usize,
+                )?,
+            })
+        })
+        .collect::<Option<_>>()?;
+
+    Some(Texture {
+        format,
+        width: texture.width,
+        height: texture.height,
+        subresources,
+    })
+}
+
+/// The sRGB version of a BC1 to BC3 or BC7 format, which may be `format`
+/// itself.
+fn to_srgb(format: DXGI_FORMAT) -> Option<DXGI_FORMAT> {
+    match format {
+        DXGI_FORMAT_BC1_UNORM | DXGI_FORMAT_BC1_UNORM_SRGB => Some(DXGI_FORMAT_BC1_UNORM_SRGB),
+        DXGI_FORMAT_BC2_UNORM | DXGI_FORMAT_BC2_UNORM_SRGB => Some(DXGI_FORMAT_BC2_UNORM_SRGB),
+        DXGI_FORMAT_BC3_UNORM | DXGI_FORMAT_BC3_UNORM_SRGB => Some(DXGI_FORMAT_BC3_UNORM_SRGB),
+        DXGI_FORMAT_BC7_UNORM | DXGI_FORMAT_BC7_UNORM_SRGB => Some(DXGI_FORMAT_BC7_UNORM_SRGB),
+        _ => None,
+    }
+}
+
+fn expand_565(color: u16) -> [i32; 3] {
+    let (r, g, b) = (
+        (color >> 11) as i32,
+        (color >> 5 & 0x3f) as i32,
+        (color & 0x1f) as i32,
+    );
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+fn quantize_565(color: [f32; 3]) -> u16 {
+    let quantize = |value: f32, max: f32| (value.clamp(0.0, 255.0) * max / 255.0 + 0.5) as u16;
+    quantize(color[0], 31.0) << 11 | quantize(color[1], 63.0) << 5 | quantize(color[2], 31.0)
+}
+
+/// The four colors a color block can pick from. In three-color mode the last
+/// one is transparent black.
+fn color_palette(c0: u16, c1: u16, four_colors: bool) -> [[i32; 4]; 4] {
+    let (e0, e1) = (expand_565(c0), expand_565(c1));
+    let mix = |a: i32, b: i32, wa: i32, wb: i32| (a * wa + b * wb + (wa + wb) / 2) / (wa + wb);
+    let blend = |wa, wb| {
+        [
+            mix(e0[0], e1[0], wa, wb),
+            mix(e0[1], e1[1], wa, wb),
+            mix(e0[2], e1[2], wa, wb),
+            255,
+        ]
+    };
+    let opaque = |c: [i32; 3]| [c[0], c[1], c[2], 255];
+    if four_colors {
+        [opaque(e0), opaque(e1), blend(2, 1), blend(1, 2)]
+    } else {
+        [opaque(e0), opaque(e1), blend(1, 1), [0, 0, 0, 0]]
+    }
+}
+
+/// BC1 picks three-color mode when the first endpoint isn't greater than
+/// the second; BC2 and BC3 always use four colors.
+fn decode_color(block: &[u8; 8], bc1: bool) -> [Rgba; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = color_palette(c0, c1, !bc1 || c0 > c1);
+    let indices = u32::from_le_bytes(block[4..].try_into().unwrap());
+    std::array::from_fn(|i| palette[(indices >> (i * 2) & 3) as usize].map(|c| c as u8))
+}
+
+/// Encodes the colors of a block. With `transparent`, the block uses
+/// three-color mode and those texels get the transparent index; the other
+/// texels' colors alone decide the endpoints.
+fn encode_color(texels: &[Rgba; 16], transparent: Option<&[bool; 16]>) -> [u8; 8] {
+    let opaque: Vec<[f32; 3]> = texels
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| transparent.is_none_or(|t| !t[*i]))
+        .map(|(_, t)| [t[0] as f32, t[1] as f32, t[2] as f32])
+        .collect();
+    let four_colors = transparent.is_none();
+
+    let (mut c0, mut c1) = if opaque.is_empty() {
+        (0, 0)
+    } else {
+        let (low, high) = principal_extremes(&opaque);
+        (quantize_565(high), quantize_565(low))
+    };
+
+    let mut best = pick_color_indices(texels, transparent, c0, c1, four_colors);
+    // Fit endpoints to the chosen indices by least squares, and keep the
+    // result while it improves.
+    for _ in 0..2 {
+        let (e0, e1) = match fit_endpoints(texels, transparent, &best.1, four_colors) {
+            Some(endpoints) => endpoints,
+            None => break,
+        };
+        let (n0, n1) = (quantize_565(e0), quantize_565(e1));
+        let candidate = pick_color_indices(texels, transparent, n0, n1, four_colors);
+        if candidate.0 >= best.0 {
+            break;
+        }
+        best = candidate;
+        c0 = n0;
+        c1 = n1;
+    }
+    let mut indices = best.1;
+
+    // The mode is chosen by the order of the endpoints, so put them in the
+    // order the block needs and swap the indices to match.
+    if four_colors && c0 < c1 || !four_colors && c0 > c1 {
+        std::mem::swap(&mut c0, &mut c1);
+        for index in &mut indices {
+            *index = match (*index, four_colors) {
+                (0, _) => 1,
+                (1, _) => 0,
+                (2, true) => 3,
+                (3, true) => 2,
+                (index, false) => index,
+                _ => unreachable!(),
+            };
+        }
+    }
+    if four_colors && c0 == c1 {
+        // Equal endpoints mean three-color mode, where index 0 is still the
+        // endpoint color.
+        indices = [0; 16];
+    }
+
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+    let packed = indices
+        .iter()
+        .enumerate()
+        .fold(0u32, |packed, (i, &index)| {
+            packed | (index as u32) << (i * 2)
+        });
+    block[4..].copy_from_slice(&packed.to_le_bytes());
+    block
+}
+
+/// Picks the nearest palette color for each texel, and returns the total
+/// squared error with the indices.
+fn pick_color_indices(
+    texels: &[Rgba; 16],
+    transparent: Option<&[bool; 16]>,
+    c0: u16,
+    c1: u16,
+    four_colors: bool,
+) -> (i32, [u8; 16]) {
+    let palette = color_palette(c0, c1, four_colors);
+    let colors = if four_colors { 4 } else { 3 };
+    let mut error = 0;
+    let mut indices = [0; 16];
+    for (i, texel) in texels.iter().enumerate() {
+        if transparent.is_some_and(|t| t[i]) {
+            indices[i] = 3;
+            continue;
+        }
+        let (index, distance) = palette[..colors]
+            .iter()
+            .map(|color| {
+                (0..3)
+                    .map(|c| (color[c] - texel[c] as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .enumerate()
+            .min_by_key(|&(_, distance)| distance)
+            .unwrap();
+        indices[i] = index as u8;
+        error += distance;
+    }
+    (error, indices)
+}
+
+/// Solves for the endpoints that best reproduce the texels with the given
+/// indices. Returns `None` when the indices don't pin the endpoints down.
+fn fit_endpoints(
+    texels: &[Rgba; 16],
+    transparent: Option<&[bool; 16]>,
+    indices: &[u8; 16],
+    four_colors: bool,
+) -> Option<([f32; 3], [f32; 3])> {
+    let weights: [f32; 4] = if four_colors {
+        [1.0, 0.0, 2.0 / 3.0, 1.0 / 3.0]
+    } else {
+        [1.0, 0.0, 0.5, 0.0]
+    };
+    // Each texel is approximately w * e0 + (1 - w) * e1.
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0f32; 3], [0.0f32; 3]);
+    for (i, texel) in texels.iter().enumerate() {
+        if transparent.is_some_and(|t| t[i]) {
+            continue;
+        }
+        let a = weights[indices[i] as usize];
+        let b = 1.0 - a;
+        aa += a * a;
+        ab += a * b;
+        bb += b * b;
+        for c in 0..3 {
+            ax[c] += a * texel[c] as f32;
+            bx[c] += b * texel[c] as f32;
+        }
+    }
+    let determinant = aa * bb - ab * ab;
+    if determinant.abs() < 1e-6 {
+        return None;
+    }
+    let e0 = std::array::from_fn(|c| (ax[c] * bb - bx[c] * ab) / determinant);
+    let e1 = std::array::from_fn(|c| (bx[c] * aa - ax[c] * ab) / determinant);
+    Some((e0, e1))
+}
+
+/// The colors at either end of the line through `colors` along their
+/// principal axis.
+fn principal_extremes(colors: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let count = colors.len() as f32;
+    let mean: [f32; 3] = std::array::from_fn(|c| colors.iter().map(|t| t[c]).sum::<f32>() / count);
+    let mut covariance = [[0.0f32; 3]; 3];
+    for color in colors {
+        let d: [f32; 3] = std::array::from_fn(|c| color[c] - mean[c]);
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += d[i] * d[j];
+            }
+        }
+    }
+
+    // Power iteration finds the axis the colors spread out along most. It
+    // starts from the channel that varies most: a fixed start such as gray
+    // can be at right angles to the axis, as it is for red and blue, and then
+    // never leaves zero.
+    let widest = (0..3)
+        .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+        .unwrap();
+    let mut axis: [f32; 3] = std::array::from_fn(|c| (c == widest) as u32 as f32);
+    for _ in 0..8 {
+        let next: [f32; 3] =
+            std::array::from_fn(|i| (0..3).map(|j| covariance[i][j] * axis[j]).sum());
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / length);
+    }
+
+    let project = |color: &[f32; 3]| (0..3).map(|c| (color[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (low, high) = colors
+        .iter()
+        .map(project)
+        .fold((f32::MAX, f32::MIN), |(low, high), t| {
+            (low.min(t), high.max(t))
+        });
+    let point = |t: f32| std::array::from_fn(|c| mean[c] + axis[c] * t);
+    (point(low), point(high))
+}
+
+fn bc4_palette(e0: u8, e1: u8) -> [u8; 8] {
+    let (a, b) = (e0 as u32, e1 as u32);
+    let mix = |wa: u32, wb: u32| ((a * wa + b * wb + (wa + wb) / 2) / (wa + wb)) as u8;
+    if e0 > e1 {
+        [
+            e0,
+            e1,
+            mix(6, 1),
+            mix(5, 2),
+            mix(4, 3),
+            mix(3, 4),
+            mix(2, 5),
+            mix(1, 6),
+        ]
+    } else {
+        [e0, e1, mix(4, 1), mix(3, 2), mix(2, 3), mix(1, 4), 0, 255]
+    }
+}
+
+fn bc4_snorm_palette(e0: i8, e1: i8) -> [i8; 8] {
+    // -128 decodes as -127.
+    let (e0, e1) = (e0.max(-127), e1.max(-127));
+    let (a, b) = (e0 as i32, e1 as i32);
+    let mix = |wa: i32, wb: i32| {
+        let sum = a * wa + b * wb;
+        let total = wa + wb;
+        // Round to nearest, away from zero at halves.
+        ((sum + sum.signum() * total / 2) / total) as i8
+    };
+    if e0 > e1 {
+        [
+            e0,
+            e1,
+            mix(6, 1),
+            mix(5, 2),
+            mix(4, 3),
+            mix(3, 4),
+            mix(2, 5),
+            mix(1, 6),
+        ]
+    } else {
+        [
+            e0,
+            e1,
+            mix(4, 1),
+            mix(3, 2),
+            mix(2, 3),
+            mix(1, 4),
+            -127,
+            127,
+        ]
+    }
+}
+
+/// Encodes a BC4 block from values between `min` and `max`, trying both the
+/// eight-value mode between the extremes and the six-value mode that has
+/// `min` and `max` to itself, and keeping whichever is closer.
+fn encode_alpha(
+    values: &[i32; 16],
+    min: i32,
+    max: i32,
+    palette: impl Fn(i32, i32) -> [i32; 8],
+) -> [u8; 8] {
+    let low = *values.iter().min().unwrap();
+    let high = *values.iter().max().unwrap();
+
+    let mut candidates = vec![(high, low)];
+    let inner: Vec<i32> = values
+        .iter()
+        .copied()
+        .filter(|&v| v != min && v != max)
+        .collect();
+    if inner.len() < values.len() {
+        let inner_low = inner.iter().copied().min().unwrap_or(min);
+        let inner_high = inner.iter().copied().max().unwrap_or(min);
+        candidates.push((inner_low, inner_high));
+    }
+
+    let mut best: Option<(i32, [u8; 8])> = None;
+    for (e0, e1) in candidates {
+        let palette = palette(e0, e1);
+        let mut error = 0;
+        let mut indices = 0u64;
+        for (i, &value) in values.iter().enumerate() {
+            let (index, distance) = palette
+                .iter()
+                .map(|&p| (p - value).pow(2))
+                .enumerate()
+                .min_by_key(|&(_, distance)| distance)
+                .unwrap();
+            error += distance;
+            indices |= (index as u64) << (16 + i * 3);
+        }
+        if best.is_none_or(|(best_error, _)| error < best_error) {
+            let mut block = indices.to_le_bytes();
+            block[0] = e0 as u8;
+            block[1] = e1 as u8;
+            best = Some((error, block));
+        }
+    }
+    best.unwrap().1
+}
@@ -0,0 +1,1048 @@
+//! Encodes and decodes BC7 blocks.
+//!
+//! Each block picks one of eight modes. The modes trade how many subsets
+//! the block is split into against how precise the endpoints and indices
+//! are, and modes 4 and 5 give alpha indices of their own. The decoder
+//! handles every mode; the encoder tries the modes that suit the block, with
+//! the partitions whose subsets lie closest to lines, and keeps whichever
+//! result is closest.
+
+use super::Rgba;
+use std::ops::Range;
+
+struct Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    p_bits: PBits,
+    index_bits: u32,
+    /// Modes 4 and 5 have a second set of indices, so that color and alpha
+    /// are interpolated separately.
+    secondary_index_bits: u32,
+}
+
+/// The extra low bit that some modes add to every channel of an endpoint.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PBits {
+    None,
+    /// One for both endpoints of a subset.
+    Shared,
+    /// One for each endpoint.
+    Unique,
+}
+
+const MODES: [Mode; 8] = [
+    Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        p_bits: PBits::Unique,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        p_bits: PBits::Shared,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        p_bits: PBits::None,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        p_bits: PBits::Unique,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        p_bits: PBits::None,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        p_bits: PBits::None,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        p_bits: PBits::Unique,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        p_bits: PBits::Unique,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+const WEIGHTS_2: [i32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [i32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// The subset of each texel in the two-subset partitions.
+#[rustfmt::skip]
+const PARTITIONS_2: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1],
+    [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1],
+    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0],
+    [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0],
+    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0],
+    [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1],
+    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0],
+    [0, 0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 0, 0],
+    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
+    [0, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0],
+    [0, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1],
+    [0, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0],
+    [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0],
+    [0, 1, 0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0],
+    [0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 1],
+    [0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0, 0, 1, 0, 1],
+    [0, 1, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 1, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0],
+    [0, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 0, 1, 1, 1, 1, 0, 1, 1, 1, 0, 0],
+    [0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1, 0, 1, 1, 0],
+    [0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 1, 1],
+    [0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1],
+    [0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0],
+    [0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0],
+    [0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1],
+    [0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 0],
+    [0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 0, 0, 1],
+    [0, 1, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1],
+    [0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 0, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0],
+    [0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1],
+];
+
+/// The subset of each texel in the three-subset partitions. Mode 0 can only
+/// use the first sixteen.
+#[rustfmt::skip]
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// The anchor texel of the second subset of each two-subset partition. The
+/// first subset's anchor is always texel 0.
+#[rustfmt::skip]
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 15,
+    15,  2,  8,  2,  2,  8,  8, 15,
+     2,  8,  2,  2,  8,  8,  2,  2,
+    15, 15,  6,  8,  2,  8, 15, 15,
+     2,  8,  2,  2,  2, 15, 15,  6,
+     6,  2,  6,  8, 15, 15,  2,  2,
+    15, 15, 15, 15, 15,  2,  2, 15,
+];
+
+/// The anchor texels of the second and third subsets of each three-subset
+/// partition.
+#[rustfmt::skip]
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+    [8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+    [3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+    [5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+    [8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+    [15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+    [3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+    [5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8],
+];
+
+/// How many of the partitions that look best on a quick estimate the encoder
+/// tries in full, for each mode that has partitions.
+const PARTITIONS_TRIED: usize = 4;
+
+pub fn decode_bc7(block: &[u8; 16]) -> [Rgba; 16] {
+    let mode_number = block[0].trailing_zeros() as usize;
+    if mode_number >= MODES.len() {
+        // The reserved mode decodes as transparent black.
+        return [[0; 4]; 16];
+    }
+    let mode = &MODES[mode_number];
+    let mut bits = BitReader {
+        bits: u128::from_le_bytes(*block),
+        position: mode_number as u32 + 1,
+    };
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits) as usize;
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // Each channel is stored for every endpoint before the next channel.
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u8; 4]; 6];
+    for channel in 0..4 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(mode.channel_bits(channel)) as u8;
+        }
+    }
+    let mut p_bits = [0u8; 6];
+    match mode.p_bits {
+        PBits::None => {}
+        PBits::Shared => {
+            for pair in p_bits[..endpoint_count].chunks_exact_mut(2) {
+                pair.fill(bits.read(1) as u8);
+            }
+        }
+        PBits::Unique => {
+            for p_bit in &mut p_bits[..endpoint_count] {
+                *p_bit = bits.read(1) as u8;
+            }
+        }
+    }
+    let endpoints: [[i32; 4]; 6] = std::array::from_fn(|endpoint| {
+        std::array::from_fn(|channel| {
+            mode.unquantize(channel, endpoints[endpoint][channel], p_bits[endpoint])
+        })
+    });
+
+    let mut indices = [0u8; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, texel);
+        *index = bits.read(mode.index_bits - anchor as u32) as u8;
+    }
+    let mut secondary_indices = [0u8; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = bits.read(mode.secondary_index_bits - (texel == 0) as u32) as u8;
+        }
+    }
+    let (color_index_bits, alpha_index_bits) = mode.index_bits_for(index_selection);
+    let (color_indices, alpha_indices) = match (mode.secondary_index_bits, index_selection) {
+        (0, _) => (&indices, &indices),
+        (_, 0) => (&indices, &secondary_indices),
+        _ => (&secondary_indices, &indices),
+    };
+
+    std::array::from_fn(|texel| {
+        let subset = subset_of(mode.subsets, partition, texel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        let mut color: Rgba = std::array::from_fn(|channel| {
+            let weight = if channel < 3 {
+                weight(color_index_bits, color_indices[texel])
+            } else {
+                weight(alpha_index_bits, alpha_indices[texel])
+            };
+            interpolate(e0[channel], e1[channel], weight) as u8
+        });
+        if rotation > 0 {
+            color.swap(3, rotation - 1);
+        }
+        color
+    })
+}
+
+/// Encodes a block with whichever mode, partition and rotation reproduce
+/// the texels most closely, out of those the encoder tries. Opaque blocks
+/// try the modes without alpha, and others the modes with it.
+pub fn encode_bc7(texels: &[Rgba; 16]) -> [u8; 16] {
+    if texels.iter().all(|texel| texel == &texels[0]) {
+        return encode_single_color(texels[0]).pack();
+    }
+
+    let mut best = encode_mode(6, 0, 0, 0, texels);
+    if best.error == 0 {
+        return best.pack();
+    }
+    let mut consider = |candidate: Encoding| {
+        if candidate.error < best.error {
+            best = candidate;
+        }
+    };
+
+    if texels.iter().all(|texel| texel[3] == 255) {
+        for partition in best_partitions(texels, 2, 64, 0..3) {
+            consider(encode_mode(1, partition, 0, 0, texels));
+            consider(encode_mode(3, partition, 0, 0, texels));
+        }
+        for partition in best_partitions(texels, 3, 64, 0..3) {
+            consider(encode_mode(2, partition, 0, 0, texels));
+        }
+        for partition in best_partitions(texels, 3, 16, 0..3) {
+            consider(encode_mode(0, partition, 0, 0, texels));
+        }
+    } else {
+        // The rotations swap alpha with one of the color channels, so that
+        // whichever channel varies on its own can have its own indices.
+        for rotation in 0..4 {
+            consider(encode_mode(5, 0, rotation, 0, texels));
+            consider(encode_mode(4, 0, rotation, 0, texels));
+            consider(encode_mode(4, 0, rotation, 1, texels));
+        }
+        for partition in best_partitions(texels, 2, 64, 0..4) {
+            consider(encode_mode(7, partition, 0, 0, texels));
+        }
+    }
+    best.pack()
+}
+
+impl Mode {
+    fn channel_bits(&self, channel: usize) -> u32 {
+        if channel < 3 {
+            self.color_bits
+        } else {
+            self.alpha_bits
+        }
+    }
+
+    /// The sizes of the color and alpha indices.
+    fn index_bits_for(&self, index_selection: u32) -> (u32, u32) {
+        match (self.secondary_index_bits, index_selection) {
+            (0, _) => (self.index_bits, self.index_bits),
+            (secondary, 0) => (self.index_bits, secondary),
+            (secondary, _) => (secondary, self.index_bits),
+        }
+    }
+
+    /// Expands a channel of an endpoint to eight bits by repeating its top
+    /// bits below it. Modes without alpha decode it as 255.
+    fn unquantize(&self, channel: usize, value: u8, p_bit: u8) -> i32 {
+        let bits = self.channel_bits(channel);
+        if bits == 0 {
+            return 255;
+        }
+        let (value, bits) = if self.p_bits == PBits::None {
+            (value as u32, bits)
+        } else {
+            ((value as u32) << 1 | p_bit as u32, bits + 1)
+        };
+        let value = value << (8 - bits);
+        (value | value >> bits) as i32
+    }
+
+    /// The stored value whose expansion is closest to `value`.
+    fn quantize(&self, channel: usize, value: f32, p_bit: u8) -> u8 {
+        let bits = self.channel_bits(channel);
+        let max = (1u32 << bits) - 1;
+        let estimate = if self.p_bits == PBits::None {
+            value * max as f32 / 255.0
+        } else {
+            (value * ((max << 1) + 1) as f32 / 255.0 - p_bit as f32) / 2.0
+        };
+        let estimate = estimate.round().clamp(0.0, max as f32) as u32;
+        // Rounding the scaled value can land one step away from the closest
+        // expansion, so check either side of it.
+        (estimate.saturating_sub(1)..=(estimate + 1).min(max))
+            .min_by(|&a, &b| {
+                let distance =
+                    |v: u32| (self.unquantize(channel, v as u8, p_bit) as f32 - value).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap() as u8
+    }
+}
+
+fn weight(index_bits: u32, index: u8) -> i32 {
+    match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+fn interpolate(e0: i32, e1: i32, weight: i32) -> i32 {
+    ((64 - weight) * e0 + weight * e1 + 32) >> 6
+}
+
+fn subset_of(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        2 => PARTITIONS_2[partition][texel] as usize,
+        3 => PARTITIONS_3[partition][texel] as usize,
+        _ => 0,
+    }
+}
+
+fn anchor(subsets: usize, partition: usize, subset: usize) -> usize {
+    match (subsets, subset) {
+        (_, 0) => 0,
+        (2, _) => ANCHORS_2[partition] as usize,
+        _ => ANCHORS_3[partition][subset - 1] as usize,
+    }
+}
+
+/// The anchor texels store their indices with the top bit left out, which
+/// the encoder makes sure is zero.
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    (0..subsets).any(|subset| anchor(subsets, partition, subset) == texel)
+}
+
+/// Reads fields from the least significant bit of the block onwards.
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = self.bits.checked_shr(self.position).unwrap_or(0) as u32 & ((1 << count) - 1);
+        self.position += count;
+        value
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bits: u128,
+    position: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u128 & ((1 << count) - 1)) << self.position;
+        self.position += count;
+    }
+}
+
+/// A block encoded with one mode, before it is packed into bits.
+struct Encoding {
+    mode: usize,
+    partition: usize,
+    rotation: usize,
+    index_selection: u32,
+    /// Two endpoints for each subset, as they are stored.
+    endpoints: [[u8; 4]; 6],
+    p_bits: [u8; 6],
+    indices: [u8; 16],
+    /// For modes 4 and 5, the indices of the alpha channel.
+    alpha_indices: [u8; 16],
+    /// The total squared error over all four channels.
+    error: u32,
+}
+
+impl Encoding {
+    fn pack(mut self) -> [u8; 16] {
+        let mode = &MODES[self.mode];
+        let separate_alpha = mode.secondary_index_bits > 0;
+        let (color_index_bits, alpha_index_bits) = mode.index_bits_for(self.index_selection);
+
+        // Each anchor's index is stored without its top bit, so where that
+        // bit is set, swap the subset's endpoints and flip its indices.
+        let color_channels = if separate_alpha { 0..3 } else { 0..4 };
+        for subset in 0..mode.subsets {
+            let anchor = anchor(mode.subsets, self.partition, subset);
+            let max = (1 << color_index_bits) - 1;
+            if self.indices[anchor] > max >> 1 {
+                for texel in 0..16 {
+                    if subset_of(mode.subsets, self.partition, texel) == subset {
+                        self.indices[texel] = max - self.indices[texel];
+                    }
+                }
+                let (e0, e1) = self.endpoints.split_at_mut(subset * 2 + 1);
+                for channel in color_channels.clone() {
+                    std::mem::swap(&mut e0[subset * 2][channel], &mut e1[0][channel]);
+                }
+                self.p_bits.swap(subset * 2, subset * 2 + 1);
+            }
+        }
+        let max = (1 << alpha_index_bits) - 1;
+        if separate_alpha && self.alpha_indices[0] > max >> 1 {
+            for index in &mut self.alpha_indices {
+                *index = max - *index;
+            }
+            let [e0, e1, ..] = &mut self.endpoints;
+            std::mem::swap(&mut e0[3], &mut e1[3]);
+        }
+
+        let mut bits = BitWriter::default();
+        bits.write(1 << self.mode, self.mode as u32 + 1);
+        bits.write(self.partition as u32, mode.partition_bits);
+        bits.write(self.rotation as u32, mode.rotation_bits);
+        bits.write(self.index_selection, mode.index_selection_bits);
+        let endpoint_count = mode.subsets * 2;
+        for channel in 0..4 {
+            for endpoint in &self.endpoints[..endpoint_count] {
+                bits.write(endpoint[channel] as u32, mode.channel_bits(channel));
+            }
+        }
+        match mode.p_bits {
+            PBits::None => {}
+            PBits::Shared => {
+                for pair in self.p_bits[..endpoint_count].chunks_exact(2) {
+                    bits.write(pair[0] as u32, 1);
+                }
+            }
+            PBits::Unique => {
+                for &p_bit in &self.p_bits[..endpoint_count] {
+                    bits.write(p_bit as u32, 1);
+                }
+            }
+        }
+        let (primary, secondary) = if separate_alpha && self.index_selection == 1 {
+            (&self.alpha_indices, &self.indices)
+        } else {
+            (&self.indices, &self.alpha_indices)
+        };
+        for (texel, &index) in primary.iter().enumerate() {
+            let anchor = is_anchor(mode.subsets, self.partition, texel);
+            bits.write(index as u32, mode.index_bits - anchor as u32);
+        }
+        if separate_alpha {
+            for (texel, &index) in secondary.iter().enumerate() {
+                bits.write(
+                    index as u32,
+                    mode.secondary_index_bits - (texel == 0) as u32,
+                );
+            }
+        }
+        bits.bits.to_le_bytes()
+    }
+}
+
+/// Encodes a block of one color exactly, which the usual endpoint fitting
+/// can miss by a step. Mode 5 stores alpha at full precision, and each
+/// color channel is a blend a third of the way between two endpoints that
+/// straddle it.
+fn encode_single_color(color: Rgba) -> Encoding {
+    let mode = &MODES[5];
+    let mut encoding = Encoding {
+        mode: 5,
+        partition: 0,
+        rotation: 0,
+        index_selection: 0,
+        endpoints: [[0; 4]; 6],
+        p_bits: [0; 6],
+        indices: [1; 16],
+        alpha_indices: [0; 16],
+        error: 0,
+    };
+    for (channel, &target) in color[..3].iter().enumerate() {
+        let target = target as i32;
+        let blend = |e0: u8, e1: u8| {
+            interpolate(
+                mode.unquantize(channel, e0, 0),
+                mode.unquantize(channel, e1, 0),
+                WEIGHTS_2[1],
+            )
+        };
+        // For each first endpoint, the second one that comes closest is
+        // near where the blend would be exact.
+        let (e0, e1) = (0..128u8)
+            .flat_map(|e0| {
+                let ideal = (target * 64 - (64 - WEIGHTS_2[1]) * mode.unquantize(channel, e0, 0))
+                    / WEIGHTS_2[1];
+                let e1 = mode.quantize(channel, ideal.clamp(0, 255) as f32, 0);
+                (e1.saturating_sub(1)..=e1.saturating_add(1).min(127)).map(move |e1| (e0, e1))
+            })
+            .min_by_key(|&(e0, e1)| (blend(e0, e1) - target).abs())
+            .unwrap();
+        encoding.endpoints[0][channel] = e0;
+        encoding.endpoints[1][channel] = e1;
+        encoding.error += ((blend(e0, e1) - target).pow(2)) as u32;
+    }
+    encoding.endpoints[0][3] = color[3];
+    encoding.endpoints[1][3] = color[3];
+    encoding
+}
+
+fn encode_mode(
+    mode_number: usize,
+    partition: usize,
+    rotation: usize,
+    index_selection: u32,
+    texels: &[Rgba; 16],
+) -> Encoding {
+    let mode = &MODES[mode_number];
+    let mut texels = *texels;
+    if rotation > 0 {
+        for texel in &mut texels {
+            texel.swap(3, rotation - 1);
+        }
+    }
+    let mut encoding = Encoding {
+        mode: mode_number,
+        partition,
+        rotation,
+        index_selection,
+        endpoints: [[0; 4]; 6],
+        p_bits: [0; 6],
+        indices: [0; 16],
+        alpha_indices: [0; 16],
+        error: 0,
+    };
+
+    let separate_alpha = mode.secondary_index_bits > 0;
+    let (color_index_bits, alpha_index_bits) = mode.index_bits_for(index_selection);
+    let channels = if mode.alpha_bits > 0 && !separate_alpha {
+        0..4
+    } else {
+        0..3
+    };
+    if mode.alpha_bits == 0 {
+        encoding.error += texels
+            .iter()
+            .map(|texel| (255 - texel[3] as u32).pow(2))
+            .sum::<u32>();
+    }
+
+    let points = texels.map(|texel| texel.map(|v| v as f32));
+    for subset in 0..mode.subsets {
+        let (mut members, mut subset_points) = ([0; 16], [[0.0; 4]; 16]);
+        let mut count = 0;
+        for (texel, point) in points.iter().enumerate() {
+            if subset_of(mode.subsets, partition, texel) == subset {
+                members[count] = texel;
+                subset_points[count] = *point;
+                count += 1;
+            }
+        }
+        let members = &members[..count];
+        let fit = fit(
+            mode,
+            &subset_points[..count],
+            channels.clone(),
+            color_index_bits,
+        );
+        for channel in channels.clone() {
+            encoding.endpoints[subset * 2][channel] = fit.endpoints[0][channel];
+            encoding.endpoints[subset * 2 + 1][channel] = fit.endpoints[1][channel];
+        }
+        encoding.p_bits[subset * 2..subset * 2 + 2].copy_from_slice(&fit.p_bits);
+        for (&texel, &index) in members.iter().zip(&fit.indices) {
+            encoding.indices[texel] = index;
+        }
+        encoding.error += fit.error;
+    }
+
+    if separate_alpha {
+        let fit = fit(mode, &points, 3..4, alpha_index_bits);
+        encoding.endpoints[0][3] = fit.endpoints[0][3];
+        encoding.endpoints[1][3] = fit.endpoints[1][3];
+        encoding.alpha_indices = fit.indices;
+        encoding.error += fit.error;
+    }
+    encoding
+}
+
+/// Endpoints and indices for one subset, in some of the channels.
+struct Fit {
+    endpoints: [[u8; 4]; 2],
+    p_bits: [u8; 2],
+    /// In the order of the points that were fitted.
+    indices: [u8; 16],
+    error: u32,
+}
+
+/// Fits endpoints to `points` in `channels`: first the ends of their
+/// principal axis, and then least-squares fits to the indices those give,
+/// for as long as that improves.
+fn fit(mode: &Mode, points: &[[f32; 4]], channels: Range<usize>, index_bits: u32) -> Fit {
+    let line = Line::through(points, channels.clone());
+    let (low, high) = line.extremes(points);
+
+    let mut best = quantize_endpoints(mode, points, channels.clone(), index_bits, low, high);
+    for _ in 0..2 {
+        let (e0, e1) = match least_squares(points, channels.clone(), index_bits, &best.indices) {
+            Some(endpoints) => endpoints,
+            None => break,
+        };
+        let candidate = quantize_endpoints(mode, points, channels.clone(), index_bits, e0, e1);
+        if candidate.error >= best.error {
+            break;
+        }
+        best = candidate;
+    }
+    best
+}
+
+/// Quantizes the endpoints, with the P-bits that keep them closest to
+/// where they were, and picks indices for them.
+fn quantize_endpoints(
+    mode: &Mode,
+    points: &[[f32; 4]],
+    channels: Range<usize>,
+    index_bits: u32,
+    e0: [f32; 4],
+    e1: [f32; 4],
+) -> Fit {
+    // Quantizes both endpoints, and returns how far they moved.
+    let quantize = |p_bits: [u8; 2]| {
+        let mut endpoints = [[0u8; 4]; 2];
+        let mut error = 0.0;
+        for (i, endpoint) in [e0, e1].iter().enumerate() {
+            for channel in channels.clone() {
+                endpoints[i][channel] = mode.quantize(channel, endpoint[channel], p_bits[i]);
+                let value = mode.unquantize(channel, endpoints[i][channel], p_bits[i]) as f32;
+                error += (value - endpoint[channel]).powi(2);
+            }
+        }
+        (endpoints, p_bits, error)
+    };
+    // A P-bit is shared by every channel of an endpoint, and alpha only
+    // reaches 255 with it set, so opaque points keep both P-bits set.
+    let opaque = channels.contains(&3) && points.iter().all(|point| point[3] == 255.0);
+    let p_bit_choices: &[[u8; 2]] = match mode.p_bits {
+        PBits::None => &[[0, 0]],
+        PBits::Shared => &[[0, 0], [1, 1]],
+        PBits::Unique if opaque => &[[1, 1]],
+        PBits::Unique => &[[0, 0], [0, 1], [1, 0], [1, 1]],
+    };
+    let (endpoints, p_bits, _) = p_bit_choices
+        .iter()
+        .map(|&p_bits| quantize(p_bits))
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .unwrap();
+    pick_indices(mode, points, channels, index_bits, endpoints, p_bits)
+}
+
+/// Picks the nearest interpolated value for each point.
+fn pick_indices(
+    mode: &Mode,
+    points: &[[f32; 4]],
+    channels: Range<usize>,
+    index_bits: u32,
+    endpoints: [[u8; 4]; 2],
+    p_bits: [u8; 2],
+) -> Fit {
+    let e0: [i32; 4] =
+        std::array::from_fn(|channel| mode.unquantize(channel, endpoints[0][channel], p_bits[0]));
+    let e1: [i32; 4] =
+        std::array::from_fn(|channel| mode.unquantize(channel, endpoints[1][channel], p_bits[1]));
+    let mut palette = [[0; 4]; 16];
+    let palette = &mut palette[..1 << index_bits];
+    for (index, color) in palette.iter_mut().enumerate() {
+        let weight = weight(index_bits, index as u8);
+        for channel in channels.clone() {
+            color[channel] = interpolate(e0[channel], e1[channel], weight);
+        }
+    }
+
+    let mut fit = Fit {
+        endpoints,
+        p_bits,
+        indices: [0; 16],
+        error: 0,
+    };
+    for (point, index) in points.iter().zip(&mut fit.indices) {
+        let (nearest, distance) = palette
+            .iter()
+            .map(|color| {
+                channels
+                    .clone()
+                    .map(|channel| (color[channel] - point[channel] as i32).pow(2) as u32)
+                    .sum::<u32>()
+            })
+            .enumerate()
+            .min_by_key(|&(_, distance)| distance)
+            .unwrap();
+        *index = nearest as u8;
+        fit.error += distance;
+    }
+    fit
+}
+
+/// Solves for the endpoints that best reproduce the points with the given
+/// indices. Returns `None` when the indices don't pin the endpoints down.
+fn least_squares(
+    points: &[[f32; 4]],
+    channels: Range<usize>,
+    index_bits: u32,
+    indices: &[u8; 16],
+) -> Option<([f32; 4], [f32; 4])> {
+    // Each point is approximately a * e0 + b * e1.
+    let (mut aa, mut ab, mut bb) = (0.0, 0.0, 0.0);
+    let (mut ax, mut bx) = ([0.0f32; 4], [0.0f32; 4]);
+    for (point, &index) in points.iter().zip(indices) {
+        let b = weight(index_bits, index) as f32 / 64.0;
+        let a = 1.0 - b;
+        aa += a * a;
+        ab += a * b;
+        bb += b * b;
+        for channel in channels.clone() {
+            ax[channel] += a * point[channel];
+            bx[channel] += b * point[channel];
+        }
+    }
+    let determinant = aa * bb - ab * ab;
+    if determinant.abs() < 1e-6 {
+        return None;
+    }
+    let e0 = std::array::from_fn(|c| ((ax[c] * bb - bx[c] * ab) / determinant).clamp(0.0, 255.0));
+    let e1 = std::array::from_fn(|c| ((bx[c] * aa - ax[c] * ab) / determinant).clamp(0.0, 255.0));
+    Some((e0, e1))
+}
+
+/// The line that best fits some points in some of their channels.
+struct Line {
+    mean: [f32; 4],
+    axis: [f32; 4],
+    /// The total squared distance of the points from the line.
+    residual: f32,
+}
+
+impl Line {
+    fn through(points: &[[f32; 4]], channels: Range<usize>) -> Self {
+        let mut sums = Sums::default();
+        for point in points {
+            sums.add(&masked(point, &channels));
+        }
+        Line::from_sums(&sums, channels)
+    }
+
+    fn from_sums(sums: &Sums, channels: Range<usize>) -> Self {
+        let count = sums.count.max(1) as f32;
+        let mean = sums.sum.map(|sum| sum / count);
+        // The sums leave out the channels outside `channels`, so they come
+        // out as zero throughout.
+        let covariance: [[f32; 4]; 4] = std::array::from_fn(|i| {
+            std::array::from_fn(|j| sums.products[i][j] - sums.sum[i] * mean[j])
+        });
+        let variance: f32 = (0..4).map(|c| covariance[c][c]).sum();
+
+        // Power iteration finds the axis the points spread out along most,
+        // and the variance along it. It starts from the channel that varies
+        // most, as `principal_extremes` does for the other formats.
+        let widest = channels
+            .clone()
+            .max_by(|&a, &b| covariance[a][a].total_cmp(&covariance[b][b]))
+            .unwrap_or(0);
+        let mut axis: [f32; 4] = std::array::from_fn(|c| (c == widest) as u32 as f32);
+        let mut spread = 0.0;
+        for _ in 0..3 {
+            let next: [f32; 4] =
+                std::array::from_fn(|i| (0..4).map(|j| covariance[i][j] * axis[j]).sum());
+            let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if length < 1e-6 {
+                break;
+            }
+            spread = length;
+            axis = next.map(|v| v / length);
+        }
+
+        Line {
+            mean,
+            axis,
+            residual: (variance - spread).max(0.0),
+        }
+    }
+
+    /// The points on the line at either end of where `points` project onto
+    /// it.
+    fn extremes(&self, points: &[[f32; 4]]) -> ([f32; 4], [f32; 4]) {
+        let project = |point: &[f32; 4]| {
+            (0..4)
+                .map(|c| (point[c] - self.mean[c]) * self.axis[c])
+                .sum::<f32>()
+        };
+        let (low, high) = points
+            .iter()
+            .map(project)
+            .fold((f32::MAX, f32::MIN), |(low, high), t| {
+                (low.min(t), high.max(t))
+            });
+        if low > high {
+            return (self.mean, self.mean);
+        }
+        let point =
+            |t: f32| std::array::from_fn(|c| (self.mean[c] + self.axis[c] * t).clamp(0.0, 255.0));
+        (point(low), point(high))
+    }
+}
+
+/// Running sums over points, from which `Line` finds their mean and
+/// covariance.
+#[derive(Default)]
+struct Sums {
+    count: usize,
+    sum: [f32; 4],
+    products: [[f32; 4]; 4],
+}
+
+impl Sums {
+    fn add(&mut self, point: &[f32; 4]) {
+        self.count += 1;
+        for i in 0..4 {
+            self.sum[i] += point[i];
+            for j in 0..4 {
+                self.products[i][j] += point[i] * point[j];
+            }
+        }
+    }
+}
+
+/// `point` with the channels outside `channels` set to zero.
+fn masked(point: &[f32; 4], channels: &Range<usize>) -> [f32; 4] {
+    std::array::from_fn(|channel| {
+        if channels.contains(&channel) {
+            point[channel]
+        } else {
+            0.0
+        }
+    })
+}
+
+/// The first `count` partitions, ranked by how close their subsets lie to
+/// lines, and cut down to the best few.
+fn best_partitions(
+    texels: &[Rgba; 16],
+    subsets: usize,
+    count: usize,
+    channels: Range<usize>,
+) -> Vec<usize> {
+    let points = texels.map(|texel| masked(&texel.map(|v| v as f32), &channels));
+    let mut ranked: Vec<(f32, usize)> = (0..count)
+        .map(|partition| {
+            let mut sums: [Sums; 3] = Default::default();
+            for (texel, point) in points.iter().enumerate() {
+                sums[subset_of(subsets, partition, texel)].add(point);
+            }
+            let residual = sums[..subsets]
+                .iter()
+                .map(|sums| Line::from_sums(sums, channels.clone()).residual)
+                .sum::<f32>();
+            (residual, partition)
+        })
+        .collect();
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+    ranked
+        .into_iter()
+        .take(PARTITIONS_TRIED)
+        .map(|(_, partition)| partition)
+        .collect()
+}
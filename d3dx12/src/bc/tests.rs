@@ -0,0 +1,592 @@
+use super::*;
+
+const GRADIENT: &[u8] = include_bytes!("../../tests/fixtures/bc/gradient.png");
+const NOISE: &[u8] = include_bytes!("../../tests/fixtures/bc/noise.png");
+const SHAPES: &[u8] = include_bytes!("../../tests/fixtures/bc/shapes.png");
+
+const RGB: &[usize] = &[0, 1, 2];
+const RGBA: &[usize] = &[0, 1, 2, 3];
+
+struct Image {
+    name: &'static str,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+fn images() -> Vec<Image> {
+    [("gradient", GRADIENT), ("noise", NOISE), ("shapes", SHAPES)]
+        .iter()
+        .map(|&(name, bytes)| {
+            let texture = crate::image::read(bytes, false).unwrap();
+            Image {
+                name,
+                width: texture.desc.width,
+                height: texture.desc.height,
+                pixels: texture.subresources[0].data.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Peak signal-to-noise ratio in dB over `channels` of two RGBA8 surfaces.
+fn psnr(a: &[u8], b: &[u8], channels: &[usize]) -> f64 {
+    assert_eq!(a.len(), b.len());
+    let mut squared_error = 0u64;
+    for (a, b) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+        for &channel in channels {
+            squared_error += (a[channel] as i64 - b[channel] as i64).pow(2) as u64;
+        }
+    }
+    let mse = squared_error as f64 / (a.len() / 4 * channels.len()) as f64;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+fn round_trip(format: DXGI_FORMAT, image: &Image) -> Vec<u8> {
+    let data = encode_surface(format, image.width, image.height, &image.pixels).unwrap();
+    let row_pitch = FormatLayout::of(format).unwrap().row_pitch(image.width) as usize;
+    decode_surface(format, image.width, image.height, &data, row_pitch).unwrap()
+}
+
+fn opaque(pixels: &[u8]) -> Vec<u8> {
+    let mut pixels = pixels.to_vec();
+    for texel in pixels.chunks_exact_mut(4) {
+        texel[3] = 255;
+    }
+    pixels
+}
+
+/// The lowest PSNR each format may reach on each image, a little under what
+/// the encoder manages now, so that a change that makes it worse fails.
+/// BC1 is measured on the images made opaque, since its one bit of alpha
+/// would turn the soft edges black.
+const THRESHOLDS: &[(&str, [f64; 6])] = &[
+    ("gradient", [37.0, 36.0, 38.0, 50.0, 50.0, 42.0]),
+    ("noise", [31.0, 32.0, 32.0, 39.0, 40.0, 35.0]),
+    ("shapes", [27.0, 28.0, 28.0, 45.0, 45.0, 42.0]),
+];
+
+#[test]
+fn encoded_images_are_close_to_the_originals() {
+    let formats: [(DXGI_FORMAT, &[usize]); 6] = [
+        (DXGI_FORMAT_BC1_UNORM, RGB),
+        (DXGI_FORMAT_BC2_UNORM, RGBA),
+        (DXGI_FORMAT_BC3_UNORM, RGBA),
+        (DXGI_FORMAT_BC4_UNORM, &[0]),
+        (DXGI_FORMAT_BC5_UNORM, &[0, 1]),
+        (DXGI_FORMAT_BC7_UNORM, RGBA),
+    ];
+    for image in images() {
+        let (_, thresholds) = THRESHOLDS
+            .iter()
+            .find(|(name, _)| *name == image.name)
+            .unwrap();
+        for (&(format, channels), &threshold) in formats.iter().zip(thresholds) {
+            let original = match format {
+                DXGI_FORMAT_BC1_UNORM => opaque(&image.pixels),
+                _ => image.pixels.clone(),
+            };
+            let image = Image {
+                pixels: original.clone(),
+                ..image
+            };
+            let psnr = psnr(&original, &round_trip(format, &image), channels);
+            assert!(
+                psnr >= threshold,
+                "{} in {:?}: {:.2} dB, expected at least {}",
+                image.name,
+                format,
+                psnr,
+                threshold
+            );
+        }
+    }
+}
+
+#[test]
+fn bc1_keeps_one_bit_alpha() {
+    let shapes = images().into_iter().find(|i| i.name == "shapes").unwrap();
+    let decoded = round_trip(DXGI_FORMAT_BC1_UNORM, &shapes);
+    for (original, decoded) in shapes.pixels.chunks_exact(4).zip(decoded.chunks_exact(4)) {
+        assert_eq!(original[3], decoded[3]);
+        if decoded[3] == 0 {
+            assert_eq!(decoded, [0, 0, 0, 0]);
+        }
+    }
+}
+
+#[test]
+fn two_color_blocks_keep_both_colors() {
+    // These colors differ at right angles to gray, which the principal axis
+    // mustn't depend on starting from.
+    let pairs = [
+        ([255, 0, 0, 255], [0, 0, 255, 255]),
+        ([0, 255, 0, 255], [0, 0, 255, 255]),
+    ];
+    for &(a, b) in &pairs {
+        let texels: [Rgba; 16] = std::array::from_fn(|i| if i % 4 < 2 { a } else { b });
+        assert_eq!(decode_bc1(&encode_bc1(&texels)), texels);
+        assert_eq!(decode_bc3(&encode_bc3(&texels)), texels);
+        assert_eq!(decode_bc7(&encode_bc7(&texels)), texels);
+    }
+}
+
+/// Indices 0, 1, 2 and 3 across each row.
+const COLOR_INDICES: [u8; 4] = [0xe4; 4];
+
+#[test]
+fn bc1_four_color_blocks() {
+    // Red and then blue, so the first endpoint is greater.
+    let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+    let palette = [
+        [255, 0, 0, 255],
+        [0, 0, 255, 255],
+        [170, 0, 85, 255],
+        [85, 0, 170, 255],
+    ];
+    assert_eq!(decode_bc1(&block), std::array::from_fn(|i| palette[i % 4]));
+}
+
+#[test]
+fn bc1_three_color_blocks() {
+    let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4];
+    let palette = [
+        [0, 0, 255, 255],
+        [255, 0, 0, 255],
+        [128, 0, 128, 255],
+        [0, 0, 0, 0],
+    ];
+    assert_eq!(decode_bc1(&block), std::array::from_fn(|i| palette[i % 4]));
+}
+
+#[test]
+fn bc2_blocks() {
+    // Alpha 0 to 15 in order, and the colors of the three-color BC1 block,
+    // which give four colors here.
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&0xfedc_ba98_7654_3210u64.to_le_bytes());
+    block[8..12].copy_from_slice(&[0x1f, 0x00, 0x00, 0xf8]);
+    block[12..].copy_from_slice(&COLOR_INDICES);
+    let palette = [[0, 0, 255], [255, 0, 0], [85, 0, 170], [170, 0, 85]];
+    let expected: [Rgba; 16] = std::array::from_fn(|i| {
+        let [r, g, b] = palette[i % 4];
+        [r, g, b, i as u8 * 17]
+    });
+    assert_eq!(decode_bc2(&block), expected);
+}
+
+/// Indices 0 to 7 across the first two rows, and 7 to 0 across the last two.
+const ALPHA_INDICES: [u8; 6] = [0x88, 0xc6, 0xfa, 0x77, 0x39, 0x05];
+
+#[test]
+fn bc3_blocks() {
+    let mut block = [0; 16];
+    block[..2].copy_from_slice(&[255, 0]);
+    block[2..8].copy_from_slice(&ALPHA_INDICES);
+    // Red everywhere.
+    block[8..12].copy_from_slice(&[0x00, 0xf8, 0x1f, 0x00]);
+    let alpha = [255, 0, 219, 182, 146, 109, 73, 36];
+    let expected: [Rgba; 16] = std::array::from_fn(|i| {
+        let index = if i < 8 { i } else { 15 - i };
+        [255, 0, 0, alpha[index]]
+    });
+    assert_eq!(decode_bc3(&block), expected);
+}
+
+fn bc4_block(e0: u8, e1: u8) -> [u8; 8] {
+    let mut block = [e0, e1, 0, 0, 0, 0, 0, 0];
+    block[2..].copy_from_slice(&ALPHA_INDICES);
+    block
+}
+
+fn in_index_order<T: Copy>(palette: [T; 8]) -> [T; 16] {
+    std::array::from_fn(|i| palette[if i < 8 { i } else { 15 - i }])
+}
+
+#[test]
+fn bc4_blocks() {
+    assert_eq!(
+        decode_bc4(&bc4_block(200, 10)),
+        in_index_order([200, 10, 173, 146, 119, 91, 64, 37])
+    );
+    assert_eq!(
+        decode_bc4(&bc4_block(64, 192)),
+        in_index_order([64, 192, 90, 115, 141, 166, 0, 255])
+    );
+}
+
+#[test]
+fn bc4_snorm_blocks() {
+    assert_eq!(
+        decode_bc4_snorm(&bc4_block(100, (-100i8) as u8)),
+        in_index_order([100, -100, 71, 43, 14, -14, -43, -71])
+    );
+    // -128 is -127, and the last two are the extremes.
+    assert_eq!(
+        decode_bc4_snorm(&bc4_block((-128i8) as u8, 127)),
+        in_index_order([-127, 127, -76, -25, 25, 76, -127, 127])
+    );
+}
+
+#[test]
+fn bc5_blocks() {
+    let mut block = [0; 16];
+    block[..8].copy_from_slice(&bc4_block(200, 10));
+    block[8..].copy_from_slice(&bc4_block(64, 192));
+    let red = in_index_order([200, 10, 173, 146, 119, 91, 64, 37]);
+    let green = in_index_order([64, 192, 90, 115, 141, 166, 0, 255]);
+    assert_eq!(
+        decode_bc5(&block),
+        std::array::from_fn(|i| [red[i], green[i]])
+    );
+}
+
+const BC7_WEIGHTS_2: [i32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [i32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Index patterns that use every index, and that have indices small enough
+/// for one bit less at texels 0, 3, 8 and 15, the anchors of the partitions
+/// used below.
+const BC7_INDICES_2: [u8; 16] = [0, 1, 2, 1, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2, 1, 0];
+const BC7_INDICES_3: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 3, 6, 5, 4, 3, 2, 1, 0];
+const BC7_INDICES_4: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Two-subset partitions 0, 13 and 14, and three-subset partitions 0 and 1.
+const BC7_PARTITION_2_0: [usize; 16] = [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1];
+const BC7_PARTITION_2_13: [usize; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1];
+const BC7_PARTITION_2_14: [usize; 16] = [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1];
+const BC7_PARTITION_3_0: [usize; 16] = [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2];
+const BC7_PARTITION_3_1: [usize; 16] = [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1];
+
+/// Packs `(value, bit count)` fields into a block, from the lowest bit up.
+fn bc7_block(fields: &[(u32, u32)]) -> [u8; 16] {
+    let mut block = 0u128;
+    let mut position = 0;
+    for &(value, bits) in fields {
+        assert!(value < 1 << bits);
+        block |= (value as u128) << position;
+        position += bits;
+    }
+    assert_eq!(position, 128);
+    block.to_le_bytes()
+}
+
+/// Each channel of every endpoint before the next channel, as BC7 stores
+/// them. Channels of 0 bits aren't stored.
+fn bc7_endpoint_fields(endpoints: &[[u32; 4]], bits: [u32; 4]) -> Vec<(u32, u32)> {
+    (0..4)
+        .flat_map(|channel| endpoints.iter().map(move |e| (e[channel], bits[channel])))
+        .collect()
+}
+
+fn bc7_bit_fields(values: &[u32]) -> Vec<(u32, u32)> {
+    values.iter().map(|&value| (value, 1)).collect()
+}
+
+/// Index fields, with the top bit of each anchor's index left out.
+fn bc7_index_fields(indices: &[u8; 16], bits: u32, anchors: &[usize]) -> Vec<(u32, u32)> {
+    (0..16)
+        .map(|texel| {
+            (
+                indices[texel] as u32,
+                bits - anchors.contains(&texel) as u32,
+            )
+        })
+        .collect()
+}
+
+/// The endpoints widened to eight bits: each channel gets the endpoint's
+/// p-bit, if there are any, and then repeats its top bits. Channels that
+/// aren't stored are 255.
+fn bc7_unquantize(endpoints: &[[u32; 4]], p_bits: &[u32], bits: [u32; 4]) -> Vec<[i32; 4]> {
+    endpoints
+        .iter()
+        .enumerate()
+        .map(|(endpoint, channels)| {
+            std::array::from_fn(|channel| {
+                if bits[channel] == 0 {
+                    return 255;
+                }
+                let (value, bits) = match p_bits.get(endpoint) {
+                    Some(&p_bit) => (channels[channel] << 1 | p_bit, bits[channel] + 1),
+                    None => (channels[channel], bits[channel]),
+                };
+                let value = value << (8 - bits);
+                (value | value >> bits) as i32
+            })
+        })
+        .collect()
+}
+
+/// What each texel decodes to, interpolating between the endpoints of its
+/// subset with the color and alpha weights its indices pick.
+fn bc7_texels(
+    endpoints: &[[i32; 4]],
+    subsets: &[usize; 16],
+    (color_indices, color_weights): (&[u8; 16], &[i32]),
+    (alpha_indices, alpha_weights): (&[u8; 16], &[i32]),
+) -> [Rgba; 16] {
+    std::array::from_fn(|texel| {
+        let (e0, e1) = (
+            endpoints[subsets[texel] * 2],
+            endpoints[subsets[texel] * 2 + 1],
+        );
+        std::array::from_fn(|channel| {
+            let weight = if channel < 3 {
+                color_weights[color_indices[texel] as usize]
+            } else {
+                alpha_weights[alpha_indices[texel] as usize]
+            };
+            ((e0[channel] * (64 - weight) + e1[channel] * weight + 32) >> 6) as u8
+        })
+    })
+}
+
+#[test]
+fn bc7_mode_0_blocks() {
+    // Three subsets of RGB 4.4.4 endpoints, each with a p-bit of its own.
+    let endpoints = [
+        [0, 0, 0, 0],
+        [15, 15, 15, 0],
+        [15, 0, 0, 0],
+        [0, 15, 0, 0],
+        [5, 10, 15, 0],
+        [10, 5, 0, 0],
+    ];
+    let p_bits = [0, 1, 1, 0, 0, 1];
+    let bits = [4, 4, 4, 0];
+    let block = bc7_block(
+        &[
+            vec![(1, 1), (0, 4)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_bit_fields(&p_bits),
+            bc7_index_fields(&BC7_INDICES_3, 3, &[0, 3, 15]),
+        ]
+        .concat(),
+    );
+    let indices = (&BC7_INDICES_3, &BC7_WEIGHTS_3[..]);
+    assert_eq!(
+        decode_bc7(&block),
+        bc7_texels(
+            &bc7_unquantize(&endpoints, &p_bits, bits),
+            &BC7_PARTITION_3_0,
+            indices,
+            indices
+        )
+    );
+}
+
+#[test]
+fn bc7_mode_1_blocks() {
+    // Two subsets of RGB 6.6.6 endpoints, with a p-bit for each subset.
+    let endpoints = [
+        [0, 0, 0, 0],
+        [63, 63, 63, 0],
+        [63, 0, 32, 0],
+        [0, 63, 16, 0],
+    ];
+    let bits = [6, 6, 6, 0];
+    let block = bc7_block(
+        &[
+            vec![(2, 2), (13, 6)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_bit_fields(&[1, 0]),
+            bc7_index_fields(&BC7_INDICES_3, 3, &[0, 15]),
+        ]
+        .concat(),
+    );
+    let indices = (&BC7_INDICES_3, &BC7_WEIGHTS_3[..]);
+    assert_eq!(
+        decode_bc7(&block),
+        bc7_texels(
+            &bc7_unquantize(&endpoints, &[1, 1, 0, 0], bits),
+            &BC7_PARTITION_2_13,
+            indices,
+            indices
+        )
+    );
+}
+
+#[test]
+fn bc7_mode_2_blocks() {
+    // Three subsets of RGB 5.5.5 endpoints, with no p-bits.
+    let endpoints = [
+        [31, 0, 0, 0],
+        [0, 31, 0, 0],
+        [0, 0, 31, 0],
+        [31, 31, 0, 0],
+        [3, 7, 11, 0],
+        [29, 17, 5, 0],
+    ];
+    let bits = [5, 5, 5, 0];
+    let block = bc7_block(
+        &[
+            vec![(4, 3), (1, 6)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_index_fields(&BC7_INDICES_2, 2, &[0, 3, 8]),
+        ]
+        .concat(),
+    );
+    let indices = (&BC7_INDICES_2, &BC7_WEIGHTS_2[..]);
+    assert_eq!(
+        decode_bc7(&block),
+        bc7_texels(
+            &bc7_unquantize(&endpoints, &[], bits),
+            &BC7_PARTITION_3_1,
+            indices,
+            indices
+        )
+    );
+}
+
+#[test]
+fn bc7_mode_3_blocks() {
+    // Two subsets of RGB 7.7.7 endpoints, each with a p-bit of its own.
+    let endpoints = [
+        [0, 64, 127, 0],
+        [127, 64, 0, 0],
+        [10, 20, 30, 0],
+        [90, 100, 110, 0],
+    ];
+    let p_bits = [1, 0, 0, 1];
+    let bits = [7, 7, 7, 0];
+    let block = bc7_block(
+        &[
+            vec![(8, 4), (0, 6)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_bit_fields(&p_bits),
+            bc7_index_fields(&BC7_INDICES_2, 2, &[0, 15]),
+        ]
+        .concat(),
+    );
+    let indices = (&BC7_INDICES_2, &BC7_WEIGHTS_2[..]);
+    assert_eq!(
+        decode_bc7(&block),
+        bc7_texels(
+            &bc7_unquantize(&endpoints, &p_bits, bits),
+            &BC7_PARTITION_2_0,
+            indices,
+            indices
+        )
+    );
+}
+
+#[test]
+fn bc7_mode_4_blocks() {
+    // RGB 5.5.5 and alpha 6 endpoints. The index selection bit gives color
+    // the 3-bit indices, which come second, and the rotation swaps alpha
+    // and blue.
+    let endpoints = [[31, 0, 10, 0], [0, 31, 20, 63]];
+    let bits = [5, 5, 5, 6];
+    let block = bc7_block(
+        &[
+            vec![(16, 5), (3, 2), (1, 1)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_index_fields(&BC7_INDICES_2, 2, &[0]),
+            bc7_index_fields(&BC7_INDICES_3, 3, &[0]),
+        ]
+        .concat(),
+    );
+    let mut expected = bc7_texels(
+        &bc7_unquantize(&endpoints, &[], bits),
+        &[0; 16],
+        (&BC7_INDICES_3, &BC7_WEIGHTS_3),
+        (&BC7_INDICES_2, &BC7_WEIGHTS_2),
+    );
+    for texel in &mut expected {
+        texel.swap(2, 3);
+    }
+    assert_eq!(decode_bc7(&block), expected);
+}
+
+#[test]
+fn bc7_mode_5_blocks() {
+    // RGB 7.7.7 and alpha 8 endpoints, with color and alpha indices of
+    // their own, and the rotation swapping alpha and red.
+    let endpoints = [[0, 64, 127, 0], [127, 0, 32, 255]];
+    let alpha_indices = [1, 0, 3, 2, 1, 0, 3, 2, 1, 0, 3, 2, 1, 0, 3, 2];
+    let bits = [7, 7, 7, 8];
+    let block = bc7_block(
+        &[
+            vec![(32, 6), (1, 2)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_index_fields(&BC7_INDICES_2, 2, &[0]),
+            bc7_index_fields(&alpha_indices, 2, &[0]),
+        ]
+        .concat(),
+    );
+    let mut expected = bc7_texels(
+        &bc7_unquantize(&endpoints, &[], bits),
+        &[0; 16],
+        (&BC7_INDICES_2, &BC7_WEIGHTS_2),
+        (&alpha_indices, &BC7_WEIGHTS_2),
+    );
+    for texel in &mut expected {
+        texel.swap(0, 3);
+    }
+    assert_eq!(decode_bc7(&block), expected);
+}
+
+#[test]
+fn bc7_mode_6_blocks() {
+    // RGBA 7.7.7.7 endpoints, each with a p-bit of its own, and 4-bit
+    // indices.
+    let endpoints = [[10, 20, 30, 127], [100, 80, 60, 0]];
+    let p_bits = [1, 0];
+    let bits = [7, 7, 7, 7];
+    let block = bc7_block(
+        &[
+            vec![(64, 7)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_bit_fields(&p_bits),
+            bc7_index_fields(&BC7_INDICES_4, 4, &[0]),
+        ]
+        .concat(),
+    );
+    let indices = (&BC7_INDICES_4, &BC7_WEIGHTS_4[..]);
+    assert_eq!(
+        decode_bc7(&block),
+        bc7_texels(
+            &bc7_unquantize(&endpoints, &p_bits, bits),
+            &[0; 16],
+            indices,
+            indices
+        )
+    );
+}
+
+#[test]
+fn bc7_mode_7_blocks() {
+    // Two subsets of RGBA 5.5.5.5 endpoints, each with a p-bit of its own.
+    let endpoints = [
+        [31, 0, 0, 31],
+        [0, 0, 31, 0],
+        [0, 31, 0, 16],
+        [31, 31, 31, 31],
+    ];
+    let p_bits = [1, 0, 1, 1];
+    let bits = [5, 5, 5, 5];
+    let block = bc7_block(
+        &[
+            vec![(128, 8), (14, 6)],
+            bc7_endpoint_fields(&endpoints, bits),
+            bc7_bit_fields(&p_bits),
+            bc7_index_fields(&BC7_INDICES_2, 2, &[0, 15]),
+        ]
+        .concat(),
+    );
+    let indices = (&BC7_INDICES_2, &BC7_WEIGHTS_2[..]);
+    assert_eq!(
+        decode_bc7(&block),
+        bc7_texels(
+            &bc7_unquantize(&endpoints, &p_bits, bits),
+            &BC7_PARTITION_2_14,
+            indices,
+            indices
+        )
+    );
+
+    // A first byte of 0 is the reserved mode.
+    assert_eq!(decode_bc7(&[0; 16]), [[0; 4]; 16]);
+}
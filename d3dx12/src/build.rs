@@ -1,18 +1,409 @@
-use std::path::Path;
-use std::{env::var, fs::copy};
+//! Helpers for build scripts that deploy a sample's data files next to its
+//! executable:
+//!
+//! ```ignore
+//! fn main() {
+//!     AssetSet::new()
+//!         .file("squidroom.bin")
+//!         .glob("textures/**/*.dds")
+//!         .shader("src/shaders.hlsl")
+//!         .deploy()
+//!         .expect("deploy assets");
+//! }
+//! ```
 
+use crate::cache::ContentHash;
+use crate::shader::{normalize_path, parent_path, path_to_shader_path, DiskFiles, IncludeResolver};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+mod manifest;
+pub use manifest::*;
+
+#[derive(Debug, Clone)]
+enum AssetSource {
+    File(String),
+    Dir { path: String, dest: String },
+    Glob(String),
+    Shader(String),
+}
+
+/// The files a sample needs at run time. Source paths are relative to the
+/// crate being built.
+#[derive(Debug, Clone, Default)]
+pub struct AssetSet {
+    sources: Vec<AssetSource>,
+    include_dirs: Vec<PathBuf>,
+}
+
+/// One file to copy, as worked out by `AssetSet::plan`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedAsset {
+    pub source: PathBuf,
+    /// Relative to the deploy directory, with `/` separators.
+    pub dest: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AssetPlan {
+    pub assets: Vec<PlannedAsset>,
+    /// Files and directories whose changes should rerun the build script.
+    pub watch: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+/// What `AssetSet::deploy_to` did.
+#[derive(Debug, Clone, Default)]
+pub struct Deployment {
+    pub manifest: AssetManifest,
+    /// Deployed paths that were written; everything else was already up to
+    /// date.
+    pub copied: Vec<String>,
+}
+
+impl AssetSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Deployed at the top of the deploy directory.
+    pub fn file(mut self, path: &str) -> Self {
+        self.sources.push(AssetSource::File(path.to_string()));
+        self
+    }
+
+    /// Everything under `path`, deployed under `dest` (which may be empty).
+    pub fn dir(mut self, path: &str, dest: &str) -> Self {
+        self.sources.push(AssetSource::Dir {
+            path: path.to_string(),
+            dest: dest.to_string(),
+        });
+        self
+    }
+
+    /// Files matching a pattern such as `textures/*.dds`. `*` and `?` match
+    /// within one path component and `**` matches any number of them. Matches
+    /// are deployed relative to the part of the pattern before the first
+    /// wildcard.
+    pub fn glob(mut self, pattern: &str) -> Self {
+        self.sources.push(AssetSource::Glob(pattern.to_string()));
+        self
+    }
+
+    /// A shader source, deployed together with the files it includes from its
+    /// own directory. Every file it includes, from anywhere, reruns the build
+    /// script when it changes.
+    pub fn shader(mut self, path: &str) -> Self {
+        self.sources.push(AssetSource::Shader(path.to_string()));
+        self
+    }
+
+    /// Where `#include <...>` in shaders is looked for.
+    pub fn include_dir(mut self, path: &str) -> Self {
+        self.include_dirs.push(PathBuf::from(path));
+        self
+    }
+
+    /// Works out what to deploy without touching the destination. Sources that
+    /// do not exist are errors, as is deploying two files to the same place.
+    pub fn plan(&self, source_root: &Path) -> io::Result<AssetPlan> {
+        let mut plan = AssetPlan::default();
+
+        for source in &self.sources {
+            match source {
+                AssetSource::File(path) => {
+                    let source = source_root.join(path);
+                    let name = source
+                        .file_name()
+                        .ok_or_else(|| invalid_input(format!("'{}' is not a file name", path)))?;
+                    if !source.is_file() {
+                        return Err(not_found(&source));
+                    }
+                    plan.add(source.clone(), name.to_string_lossy().into_owned())?;
+                    plan.watch.push(source);
+                }
+                AssetSource::Dir { path, dest } => {
+                    let dir = source_root.join(path);
+                    if !dir.is_dir() {
+                        return Err(not_found(&dir));
+                    }
+                    for file in walk_files(&dir)? {
+                        let relative = relative_path(&dir, &file);
+                        plan.add(file, join_dest(dest, &relative))?;
+                    }
+                    plan.watch.push(dir);
+                }
+                AssetSource::Glob(pattern) => {
+                    let pattern = normalize_path(pattern);
+                    let segments: Vec<&str> = pattern.split('/').collect();
+                    let literal = segments
+                        .iter()
+                        .take_while(|segment| !has_wildcard(segment))
+                        .count();
+
+                    if literal == segments.len() {
+                        // No wildcards, so it names a single file.
+                        let source = source_root.join(&pattern);
+                        if !source.is_file() {
+                            return Err(not_found(&source));
+                        }
+                        plan.add(source.clone(), segments[literal - 1].to_string())?;
+                        plan.watch.push(source);
+                        continue;
+                    }
+
+                    let base = source_root.join(segments[..literal].join("/"));
+                    if !base.is_dir() {
+                        return Err(not_found(&base));
+                    }
+
+                    let mut matched = false;
+                    for file in walk_files(&base)? {
+                        let relative = relative_path(&base, &file);
+                        let components: Vec<&str> = relative.split('/').collect();
+                        if glob_matches(&segments[literal..], &components) {
+                            plan.add(file, relative)?;
+                            matched = true;
+                        }
+                    }
+                    if !matched {
+                        plan.warnings
+                            .push(format!("'{}' did not match any files", pattern));
+                    }
+                    plan.watch.push(base);
+                }
+                AssetSource::Shader(path) => {
+                    let include_dirs: Vec<PathBuf> = self
+                        .include_dirs
+                        .iter()
+                        .map(|dir| source_root.join(dir))
+                        .collect();
+                    let root = path_to_shader_path(&source_root.join(path));
+                    let dependencies =
+                        IncludeResolver::new(&DiskFiles, &include_dirs).dependencies(&root);
+                    if dependencies.files.is_empty() {
+                        return Err(not_found(Path::new(&root)));
+                    }
+
+                    let dir = parent_path(&root);
+                    for file in &dependencies.files {
+                        let dest = if file == &root {
+                            file.rsplit('/').next().map(str::to_string)
+                        } else {
+                            file.strip_prefix(dir)
+                                .and_then(|rest| rest.strip_prefix('/'))
+                                .map(str::to_string)
+                        };
+                        // Includes from elsewhere, e.g. a shared include
+                        // directory, are still watched.
+                        if let Some(dest) = dest {
+                            plan.add(PathBuf::from(file), dest)?;
+                        }
+                        plan.watch.push(PathBuf::from(file));
+                    }
+                    for name in dependencies.unresolved {
+                        plan.warnings
+                            .push(format!("{}: cannot find include '{}'", path, name));
+                    }
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Copies whatever changed into `dest_dir` and writes the manifest there
+    /// as `manifest_name`. Files are compared by content hash against the
+    /// previous manifest, so an unchanged file is never rewritten.
+    pub fn deploy_to(
+        &self,
+        source_root: &Path,
+        dest_dir: &Path,
+        manifest_name: &str,
+    ) -> io::Result<Deployment> {
+        let plan = self.plan(source_root)?;
+        deploy_plan(&plan, dest_dir, manifest_name)
+    }
+
+    /// Deploys from a build script: sources are relative to the crate, files
+    /// go to the target directory the executable is built in, the manifest is
+    /// named after the package, and cargo is told what to watch.
+    pub fn deploy(&self) -> io::Result<Deployment> {
+        let source_root = PathBuf::from(env_var("CARGO_MANIFEST_DIR")?);
+        let plan = self.plan(&source_root)?;
+
+        println!("cargo:rerun-if-changed=build.rs");
+        for path in &plan.watch {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+        for warning in &plan.warnings {
+            println!("cargo:warning={}", warning);
+        }
+
+        let manifest_name = AssetManifest::file_name(&env_var("CARGO_PKG_NAME")?);
+        deploy_plan(&plan, &deploy_dir()?, &manifest_name)
+    }
+}
+
+/// Copies one file, relative to the crate, next to the executable.
+#[deprecated(note = "use `AssetSet::new().file(path).deploy()`")]
 pub fn copy_data_file(source_path: &str) {
-    println!("!cargo:rerun-if-changed={}", source_path);
+    AssetSet::new().file(source_path).deploy().expect("Copy");
+}
 
-    let out_dir = var("OUT_DIR").unwrap();
-    let out_dir = Path::new(&out_dir);
-    let dest_filename = Path::new(source_path).file_name().expect("dest_filename");
-    let dest = out_dir
+/// The directory cargo puts the package's executables in, e.g.
+/// `target/debug`. Only valid in a build script.
+pub fn deploy_dir() -> io::Result<PathBuf> {
+    let out_dir = PathBuf::from(env_var("OUT_DIR")?);
+    // OUT_DIR is <profile dir>/build/<package>-<hash>/out.
+    out_dir
         .ancestors()
         .nth(3)
-        .expect("dest directory")
-        .join(dest_filename);
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            invalid_input(format!(
+                "cannot find the target directory from OUT_DIR '{}'",
+                out_dir.display()
+            ))
+        })
+}
+
+fn deploy_plan(plan: &AssetPlan, dest_dir: &Path, manifest_name: &str) -> io::Result<Deployment> {
+    let manifest_path = dest_dir.join(manifest_name);
+    let previous = AssetManifest::read(&manifest_path).unwrap_or_default();
+    let mut deployment = Deployment::default();
+
+    for asset in &plan.assets {
+        let bytes = fs::read(&asset.source).map_err(|e| with_path(e, &asset.source))?;
+        let hash = ContentHash::of(&bytes);
+        let dest = dest_dir.join(&asset.dest);
+
+        let unchanged = previous
+            .get(&asset.dest)
+            .is_some_and(|entry| entry.hash == hash)
+            && fs::metadata(&dest).is_ok_and(|m| m.len() == bytes.len() as u64);
+        if !unchanged {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&dest, &bytes).map_err(|e| with_path(e, &dest))?;
+            deployment.copied.push(asset.dest.clone());
+        }
+
+        deployment.manifest.entries.push(AssetEntry {
+            path: asset.dest.clone(),
+            hash,
+            size: bytes.len() as u64,
+            source: asset.source.clone(),
+        });
+    }
+
+    fs::create_dir_all(dest_dir)?;
+    deployment
+        .manifest
+        .write(&manifest_path)
+        .map_err(|e| with_path(e, &manifest_path))?;
+    Ok(deployment)
+}
+
+impl AssetPlan {
+    fn add(&mut self, source: PathBuf, dest: String) -> io::Result<()> {
+        if let Some(existing) = self.assets.iter().find(|asset| asset.dest == dest) {
+            // The same file reached twice, e.g. by two globs, is fine.
+            if existing.source == source {
+                return Ok(());
+            }
+            return Err(invalid_input(format!(
+                "'{}' and '{}' are both deployed as '{}'",
+                existing.source.display(),
+                source.display(),
+                dest
+            )));
+        }
+        self.assets.push(PlannedAsset { source, dest });
+        Ok(())
+    }
+}
+
+/// Every file under `dir`, sorted so that plans come out the same each time.
+fn walk_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).map_err(|e| with_path(e, &dir))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn relative_path(base: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    path_to_shader_path(relative)
+}
+
+fn join_dest(dir: &str, name: &str) -> String {
+    normalize_path(&format!("{}/{}", dir, name))
+}
+
+fn has_wildcard(segment: &str) -> bool {
+    segment.contains(['*', '?'])
+}
+
+fn glob_matches(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| glob_matches(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path)) => {
+                let segment: Vec<char> = segment.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                segment_matches(&segment, &name) && glob_matches(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+fn segment_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| segment_matches(rest, &name[skip..])),
+        Some((&c, rest)) => match name.split_first() {
+            Some((&n, name)) => (c == '?' || c == n) && segment_matches(rest, name),
+            None => false,
+        },
+    }
+}
+
+fn env_var(name: &str) -> io::Result<String> {
+    env::var(name).map_err(|_| {
+        invalid_input(format!(
+            "{} is not set; deploy() only works in a build script",
+            name
+        ))
+    })
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("asset '{}' does not exist", path.display()),
+    )
+}
 
-    println!("dest: {}", dest.to_str().expect("to str"));
-    copy(source_path, dest).expect("Copy");
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
 }
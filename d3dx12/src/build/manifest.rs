@@ -0,0 +1,194 @@
+use crate::cache::ContentHash;
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+const HEADER: &str = "d3dx12-assets 1";
+
+/// One deployed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetEntry {
+    /// Relative to the directory the manifest is in, with `/` separators.
+    pub path: String,
+    pub hash: ContentHash,
+    pub size: u64,
+    /// The file it was copied from, so that tools can find the original.
+    pub source: PathBuf,
+}
+
+/// The list of files a build script deployed, written next to the executable
+/// as `<package>.assets`. Each line after the header is a tab separated hash,
+/// size, deployed path and source path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssetManifest {
+    pub entries: Vec<AssetEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestError {
+    /// 1-based.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "asset manifest line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl AssetManifest {
+    pub fn file_name(package: &str) -> String {
+        format!("{}.assets", package)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&AssetEntry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ManifestError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => {
+                return Err(ManifestError {
+                    line: 1,
+                    message: format!("expected '{}'", HEADER),
+                })
+            }
+        }
+
+        let mut entries = Vec::new();
+        for (index, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let error = |message: &str| ManifestError {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return Err(error("expected hash, size, path and source"));
+            }
+            entries.push(AssetEntry {
+                hash: ContentHash::from_hex(fields[0]).ok_or_else(|| error("bad hash"))?,
+                size: fields[1].parse().map_err(|_| error("bad size"))?,
+                path: fields[2].to_string(),
+                source: PathBuf::from(fields[3]),
+            });
+        }
+        Ok(AssetManifest { entries })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for AssetManifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{}\t{}\t{}\t{}",
+                entry.hash,
+                entry.size,
+                entry.path,
+                entry.source.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> AssetManifest {
+        AssetManifest {
+            entries: vec![
+                AssetEntry {
+                    path: "squidroom.bin".to_string(),
+                    hash: ContentHash::of(b"squidroom"),
+                    size: 9,
+                    source: PathBuf::from("/src/samples/squidroom.bin"),
+                },
+                AssetEntry {
+                    path: "textures/floor albedo.dds".to_string(),
+                    hash: ContentHash::of(b""),
+                    size: 0,
+                    source: PathBuf::from(r"C:\src\textures\floor albedo.dds"),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let manifest = manifest();
+        let text = manifest.to_string();
+        assert_eq!(
+            text,
+            format!(
+                "d3dx12-assets 1\n{}\t9\tsquidroom.bin\t/src/samples/squidroom.bin\n\
+                 {}\t0\ttextures/floor albedo.dds\tC:\\src\\textures\\floor albedo.dds\n",
+                ContentHash::of(b"squidroom"),
+                ContentHash::of(b"")
+            )
+        );
+        assert_eq!(AssetManifest::parse(&text), Ok(manifest));
+
+        let empty = AssetManifest::default();
+        assert_eq!(empty.to_string(), "d3dx12-assets 1\n");
+        assert_eq!(AssetManifest::parse(&empty.to_string()), Ok(empty));
+    }
+
+    #[test]
+    fn parses_crlf_and_blank_lines() {
+        let text = manifest().to_string().replace('\n', "\r\n\r\n");
+        assert_eq!(AssetManifest::parse(&text), Ok(manifest()));
+    }
+
+    #[test]
+    fn finds_entries() {
+        let manifest = manifest();
+        assert_eq!(manifest.get("squidroom.bin").unwrap().size, 9);
+        assert!(manifest.get("textures/missing.dds").is_none());
+        assert_eq!(
+            AssetManifest::file_name("d3d12-multithreading"),
+            "d3d12-multithreading.assets"
+        );
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let hash = ContentHash::of(b"squidroom");
+        let error = |text: &str| AssetManifest::parse(text).unwrap_err();
+
+        assert_eq!(error("").line, 1);
+        assert_eq!(
+            error("d3dx12-assets 2\n").message,
+            "expected 'd3dx12-assets 1'"
+        );
+
+        let bad_fields = error(&format!("d3dx12-assets 1\n\n{}\t9\tsquidroom.bin\n", hash));
+        assert_eq!(bad_fields.line, 3);
+        assert_eq!(bad_fields.message, "expected hash, size, path and source");
+
+        let bad_hash = error("d3dx12-assets 1\nabc\t9\tsquidroom.bin\tsquidroom.bin\n");
+        assert_eq!((bad_hash.line, bad_hash.message.as_str()), (2, "bad hash"));
+
+        let bad_size = error(&format!("d3dx12-assets 1\n{}\t-9\ta\tb\n", hash));
+        assert_eq!((bad_size.line, bad_size.message.as_str()), (2, "bad size"));
+        assert_eq!(bad_size.to_string(), "asset manifest line 2: bad size");
+    }
+}
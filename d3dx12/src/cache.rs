@@ -0,0 +1,415 @@
+//! Content-addressed caches on disk. Entries are keyed by a `ContentHash` of
+//! everything that went into producing them, so there is never any need to
+//! invalidate them: a change to the inputs simply produces a different key.
+
+use std::{
+    convert::TryInto,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Bump this whenever the entry layout, or what goes into any key, changes.
+/// Each version gets its own directory, so old entries are never misread.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"D3DXCACH";
+const HEADER_SIZE: usize = 8 + 4 + 16 + 8 + 16;
+
+/// A 128-bit FNV-1a hash. Unlike `std::hash`, it is the same for every build
+/// and every platform, which is what a key stored on disk needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash(pub [u8; 16]);
+
+impl ContentHash {
+    pub fn of(bytes: &[u8]) -> Self {
+        let mut hasher = ContentHasher::new();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    /// Parses the form written by `Display`.
+    pub fn from_hex(text: &str) -> Option<Self> {
+        if text.len() != 32 || !text.is_ascii() {
+            return None;
+        }
+        let mut hash = [0; 16];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(ContentHash(hash))
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+#[derive(Debug, Clone)]
+pub struct ContentHasher(u128);
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        ContentHasher(FNV_OFFSET_BASIS)
+    }
+}
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write(&[value as u8]);
+    }
+
+    /// Writes the length first, so that ("ab", "c") and ("a", "bc") hash
+    /// differently.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u64(bytes.len() as u64);
+        self.write(bytes);
+    }
+
+    pub fn write_str(&mut self, text: &str) {
+        self.write_bytes(text.as_bytes());
+    }
+
+    pub fn finish(&self) -> ContentHash {
+        ContentHash(self.0.to_le_bytes())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u32),
+    /// The entry was stored under a different key, e.g. a file was renamed.
+    KeyMismatch,
+    /// The payload does not match its checksum, e.g. after a partial write.
+    Corrupt,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::Truncated => write!(f, "cache entry is truncated"),
+            CacheError::BadMagic => write!(f, "not a cache entry"),
+            CacheError::UnsupportedVersion(version) => {
+                write!(f, "cache entry has unsupported version {}", version)
+            }
+            CacheError::KeyMismatch => write!(f, "cache entry belongs to a different key"),
+            CacheError::Corrupt => write!(f, "cache entry checksum does not match"),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// Lays out an entry as: magic, format version, key, payload length, payload
+/// checksum, payload. All integers are little-endian.
+pub fn encode_entry(key: &ContentHash, payload: &[u8]) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(HEADER_SIZE + payload.len());
+    entry.extend_from_slice(MAGIC);
+    entry.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    entry.extend_from_slice(&key.0);
+    entry.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    entry.extend_from_slice(&ContentHash::of(payload).0);
+    entry.extend_from_slice(payload);
+    entry
+}
+
+/// Returns the payload of an entry written by `encode_entry`.
+pub fn decode_entry<'a>(key: &ContentHash, entry: &'a [u8]) -> Result<&'a [u8], CacheError> {
+    if entry.len() < HEADER_SIZE {
+        return Err(CacheError::Truncated);
+    }
+    let (header, payload) = entry.split_at(HEADER_SIZE);
+
+    if &header[0..8] != MAGIC {
+        return Err(CacheError::BadMagic);
+    }
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    if version != FORMAT_VERSION {
+        return Err(CacheError::UnsupportedVersion(version));
+    }
+    if header[12..28] != key.0 {
+        return Err(CacheError::KeyMismatch);
+    }
+    let length = u64::from_le_bytes(header[28..36].try_into().unwrap());
+    if payload.len() as u64 != length {
+        return Err(CacheError::Truncated);
+    }
+    if header[36..52] != ContentHash::of(payload).0 {
+        return Err(CacheError::Corrupt);
+    }
+    Ok(payload)
+}
+
+/// A directory of cache entries, one file per key.
+#[derive(Debug, Clone)]
+pub struct CacheDir {
+    path: PathBuf,
+}
+
+impl CacheDir {
+    /// Uses `<root>/<name>-v<FORMAT_VERSION>`. Nothing is created until the
+    /// first entry is stored.
+    pub fn new<P: AsRef<Path>>(root: P, name: &str) -> Self {
+        CacheDir {
+            path: root.as_ref().join(format!("{}-v{}", name, FORMAT_VERSION)),
+        }
+    }
+
+    /// `D3DX12_CACHE_DIR` if it is set, otherwise a directory under the local
+    /// application data folder (or the temporary directory elsewhere).
+    pub fn default_root() -> PathBuf {
+        if let Some(dir) = std::env::var_os("D3DX12_CACHE_DIR") {
+            return PathBuf::from(dir);
+        }
+        match std::env::var_os("LOCALAPPDATA") {
+            Some(dir) => PathBuf::from(dir).join("d3dx12").join("cache"),
+            None => std::env::temp_dir().join("d3dx12-cache"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entry_path(&self, key: &ContentHash) -> PathBuf {
+        self.path.join(format!("{}.bin", key))
+    }
+
+    /// Missing and unreadable entries are both misses; entries that fail to
+    /// decode are deleted so they are not read again.
+    pub fn load(&self, key: &ContentHash) -> Option<Vec<u8>> {
+        let path = self.entry_path(key);
+        let entry = fs::read(&path).ok()?;
+        match decode_entry(key, &entry) {
+            Ok(payload) => Some(payload.to_vec()),
+            Err(_) => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Writes to a temporary file first and renames it into place, so that
+    /// another process never sees half an entry.
+    pub fn store(&self, key: &ContentHash, payload: &[u8]) -> io::Result<()> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        fs::create_dir_all(&self.path)?;
+        let temporary = self.path.join(format!(
+            "{}.{}-{}.tmp",
+            key,
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temporary, encode_entry(key, payload))?;
+
+        let result = fs::rename(&temporary, self.entry_path(key));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    pub fn remove(&self, key: &ContentHash) -> io::Result<()> {
+        fs::remove_file(self.entry_path(key))
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temporary directory, removed when
+    /// dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "d3dx12-cache-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn hash(text: &str) -> ContentHash {
+        ContentHash::of(text.as_bytes())
+    }
+
+    #[test]
+    fn hashes_are_fnv1a_128() {
+        assert_eq!(ContentHash::of(b"").0, FNV_OFFSET_BASIS.to_le_bytes());
+        assert_eq!(
+            ContentHash::of(b"a").0,
+            0xd228cb696f1a8caf78912b704e4a8964_u128.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn hashing_is_incremental_and_length_prefixed() {
+        let mut hasher = ContentHasher::new();
+        hasher.write(b"ab");
+        hasher.write(b"c");
+        assert_eq!(hasher.finish(), ContentHash::of(b"abc"));
+
+        let mut first = ContentHasher::new();
+        first.write_str("ab");
+        first.write_str("c");
+        let mut second = ContentHasher::new();
+        second.write_str("a");
+        second.write_str("bc");
+        assert_ne!(first.finish(), second.finish());
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let key = hash("shader");
+        let hex = key.to_string();
+        assert_eq!(hex.len(), 32);
+        assert_eq!(ContentHash::from_hex(&hex), Some(key));
+        assert_eq!(ContentHash::from_hex(&hex.to_uppercase()), Some(key));
+
+        assert_eq!(ContentHash::from_hex(&hex[1..]), None);
+        assert_eq!(ContentHash::from_hex(&format!("{}0", hex)), None);
+        assert_eq!(ContentHash::from_hex(&"g".repeat(32)), None);
+        assert_eq!(ContentHash::from_hex(&"é".repeat(16)), None);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let key = hash("entry");
+        for payload in [&b""[..], b"bytecode", &[0xff; 1000]] {
+            let entry = encode_entry(&key, payload);
+            assert_eq!(entry.len(), HEADER_SIZE + payload.len());
+            assert_eq!(&entry[0..8], MAGIC);
+            assert_eq!(decode_entry(&key, &entry), Ok(payload));
+        }
+    }
+
+    #[test]
+    fn bad_entries_are_rejected() {
+        let key = hash("entry");
+        let entry = encode_entry(&key, b"bytecode");
+
+        assert_eq!(
+            decode_entry(&key, &entry[..HEADER_SIZE - 1]),
+            Err(CacheError::Truncated)
+        );
+        assert_eq!(
+            decode_entry(&key, &entry[..entry.len() - 1]),
+            Err(CacheError::Truncated)
+        );
+        let mut longer = entry.clone();
+        longer.push(0);
+        assert_eq!(decode_entry(&key, &longer), Err(CacheError::Truncated));
+
+        let mut bad_magic = entry.clone();
+        bad_magic[0] ^= 1;
+        assert_eq!(decode_entry(&key, &bad_magic), Err(CacheError::BadMagic));
+
+        let mut bad_version = entry.clone();
+        bad_version[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            decode_entry(&key, &bad_version),
+            Err(CacheError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+
+        assert_eq!(
+            decode_entry(&hash("other"), &entry),
+            Err(CacheError::KeyMismatch)
+        );
+
+        let mut corrupt = entry.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert_eq!(decode_entry(&key, &corrupt), Err(CacheError::Corrupt));
+    }
+
+    #[test]
+    fn directory_stores_and_loads() {
+        let root = TempDir::new();
+        let cache = CacheDir::new(&root.0, "shaders");
+        assert_eq!(
+            cache.path(),
+            root.0.join(format!("shaders-v{}", FORMAT_VERSION))
+        );
+
+        let key = hash("entry");
+        assert_eq!(cache.load(&key), None);
+        assert!(!cache.path().exists());
+
+        cache.store(&key, b"bytecode").unwrap();
+        assert_eq!(cache.load(&key).as_deref(), Some(&b"bytecode"[..]));
+        cache.store(&key, b"replaced").unwrap();
+        assert_eq!(cache.load(&key).as_deref(), Some(&b"replaced"[..]));
+        assert_eq!(fs::read_dir(cache.path()).unwrap().count(), 1);
+
+        cache.remove(&key).unwrap();
+        assert_eq!(cache.load(&key), None);
+        cache.clear().unwrap();
+        cache.clear().unwrap();
+        assert!(!cache.path().exists());
+    }
+
+    #[test]
+    fn bad_files_are_misses_and_are_deleted() {
+        let root = TempDir::new();
+        let cache = CacheDir::new(&root.0, "shaders");
+        let key = hash("entry");
+        cache.store(&key, b"bytecode").unwrap();
+
+        // An entry renamed to another key's file.
+        let other = hash("other");
+        fs::rename(cache.entry_path(&key), cache.entry_path(&other)).unwrap();
+        assert_eq!(cache.load(&other), None);
+        assert!(!cache.entry_path(&other).exists());
+
+        fs::write(cache.entry_path(&key), b"half an entry").unwrap();
+        assert_eq!(cache.load(&key), None);
+        assert!(!cache.entry_path(&key).exists());
+    }
+}
@@ -0,0 +1,498 @@
+//! Converts texel data between formats on the CPU.
+//!
+//! Texels are read into linear RGBA floats and written back from them, as a
+//! shader would see them: sRGB formats are converted to and from linear,
+//! UNORM formats map onto 0 to 1, and channels a format doesn't have read as
+//! 0, or 1 for alpha. Writing rounds to nearest, clamps UNORM values and maps
+//! NaN to 0 for them, following the D3D conversion rules.
+//!
+//! The scalar conversions are public too, for data that isn't a texture.
+
+use crate::format::*;
+use crate::texture::{FormatLayout, Subresource, Texture};
+use std::convert::TryInto;
+
+/// Converts every mip of `texture` to `format`. Returns `None` if either
+/// format isn't one of:
+///
+/// * `R8G8B8A8`, `B8G8R8A8`, `R8G8` and `R8`, UNORM or sRGB where there is one;
+/// * `R32G32B32A32`, `R32G32B32`, `R32G32` and `R32` float;
+/// * `R16G16B16A16`, `R16G16` and `R16` float;
+/// * `R10G10B10A2_UNORM` and `R11G11B10_FLOAT`.
+pub fn convert(texture: &Texture, format: DXGI_FORMAT) -> Option<Texture> {
+    let subresources = texture
+        .subresources
+        .iter()
+        .map(|subresource| {
+            let texels = read_texels(texture.format, subresource)?;
+            write_texels(format, subresource.width, subresource.height, &texels)
+        })
+        .collect::<Option<_>>()?;
+    Some(Texture {
+        format,
+        subresources,
+        ..texture.clone()
+    })
+}
+
+/// Reads the texels of a subresource in one of the formats that `convert`
+/// supports, in rows from the top left.
+pub fn read_texels(format: DXGI_FORMAT, subresource: &Subresource) -> Option<Vec<[f32; 4]>> {
+    let layout = TexelLayout::of(format)?;
+    let (width, height) = (subresource.width as usize, subresource.height as usize);
+    let row_size = width * layout.size;
+    let mut texels = Vec::with_capacity(width * height);
+    for row in subresource.rows().take(height) {
+        texels.extend(
+            row.get(..row_size)?
+                .chunks_exact(layout.size)
+                .map(|texel| layout.read(texel)),
+        );
+    }
+    if texels.len() != width * height {
+        return None;
+    }
+    Some(texels)
+}
+
+/// Writes texels, in rows from the top left, as a tightly packed
+/// subresource in one of the formats that `convert` supports.
+pub fn write_texels(
+    format: DXGI_FORMAT,
+    width: u32,
+    height: u32,
+    texels: &[[f32; 4]],
+) -> Option<Subresource> {
+    let layout = TexelLayout::of(format)?;
+    if texels.len() != width as usize * height as usize {
+        return None;
+    }
+    let mut data = Vec::with_capacity(texels.len() * layout.size);
+    for texel in texels {
+        layout.write(texel, &mut data);
+    }
+    Some(Subresource {
+        width,
+        height,
+        row_pitch: FormatLayout::of(format)?.row_pitch(width),
+        data,
+    })
+}
+
+/// How a format that `convert` supports stores a texel.
+#[derive(Debug, Clone, Copy)]
+struct TexelLayout {
+    kind: Kind,
+    channels: usize,
+    /// Bytes per texel.
+    size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Unorm8,
+    Srgb8,
+    Bgra8,
+    Bgra8Srgb,
+    Float16,
+    Float32,
+    R10G10B10A2,
+    R11G11B10,
+}
+
+impl TexelLayout {
+    fn of(format: DXGI_FORMAT) -> Option<Self> {
+        let (kind, channels) = match format {
+            DXGI_FORMAT_R8G8B8A8_UNORM => (Kind::Unorm8, 4),
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => (Kind::Srgb8, 4),
+            DXGI_FORMAT_B8G8R8A8_UNORM => (Kind::Bgra8, 4),
+            DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => (Kind::Bgra8Srgb, 4),
+            DXGI_FORMAT_R8G8_UNORM => (Kind::Unorm8, 2),
+            DXGI_FORMAT_R8_UNORM => (Kind::Unorm8, 1),
+            DXGI_FORMAT_R32G32B32A32_FLOAT => (Kind::Float32, 4),
+            DXGI_FORMAT_R32G32B32_FLOAT => (Kind::Float32, 3),
+            DXGI_FORMAT_R32G32_FLOAT => (Kind::Float32, 2),
+            DXGI_FORMAT_R32_FLOAT => (Kind::Float32, 1),
+            DXGI_FORMAT_R16G16B16A16_FLOAT => (Kind::Float16, 4),
+            DXGI_FORMAT_R16G16_FLOAT => (Kind::Float16, 2),
+            DXGI_FORMAT_R16_FLOAT => (Kind::Float16, 1),
+            DXGI_FORMAT_R10G10B10A2_UNORM => (Kind::R10G10B10A2, 4),
+            DXGI_FORMAT_R11G11B10_FLOAT => (Kind::R11G11B10, 3),
+            _ => return None,
+        };
+        let size = match kind {
+            Kind::Float16 => channels * 2,
+            Kind::Float32 => channels * 4,
+            Kind::R10G10B10A2 | Kind::R11G11B10 => 4,
+            _ => channels,
+        };
+        Some(TexelLayout {
+            kind,
+            channels,
+            size,
+        })
+    }
+
+    fn read(&self, texel: &[u8]) -> [f32; 4] {
+        let mut rgba = [0.0, 0.0, 0.0, 1.0];
+        match self.kind {
+            Kind::Unorm8 => {
+                for (value, &byte) in rgba.iter_mut().zip(texel) {
+                    *value = unorm8_to_f32(byte);
+                }
+            }
+            Kind::Srgb8 | Kind::Bgra8 | Kind::Bgra8Srgb => {
+                rgba = std::array::from_fn(|channel| unorm8_to_f32(texel[channel]));
+                if matches!(self.kind, Kind::Bgra8 | Kind::Bgra8Srgb) {
+                    rgba.swap(0, 2);
+                }
+                if matches!(self.kind, Kind::Srgb8 | Kind::Bgra8Srgb) {
+                    for value in &mut rgba[..3] {
+                        *value = srgb_to_linear(*value);
+                    }
+                }
+            }
+            Kind::Float16 => {
+                for (value, half) in rgba.iter_mut().zip(texel.chunks_exact(2)) {
+                    *value = f16_to_f32(u16::from_le_bytes(half.try_into().unwrap()));
+                }
+            }
+            Kind::Float32 => {
+                for (value, float) in rgba.iter_mut().zip(texel.chunks_exact(4)) {
+                    *value = f32::from_le_bytes(float.try_into().unwrap());
+                }
+            }
+            Kind::R10G10B10A2 => {
+                rgba = unpack_r10g10b10a2(u32::from_le_bytes(texel.try_into().unwrap()));
+            }
+            Kind::R11G11B10 => {
+                let [r, g, b] = unpack_r11g11b10(u32::from_le_bytes(texel.try_into().unwrap()));
+                rgba = [r, g, b, 1.0];
+            }
+        }
+        rgba
+    }
+
+    fn write(&self, rgba: &[f32; 4], data: &mut Vec<u8>) {
+        let channels = &rgba[..self.channels];
+        match self.kind {
+            Kind::Unorm8 => data.extend(channels.iter().map(|&value| f32_to_unorm8(value))),
+            Kind::Srgb8 | Kind::Bgra8 | Kind::Bgra8Srgb => {
+                let mut rgba = *rgba;
+                if matches!(self.kind, Kind::Srgb8 | Kind::Bgra8Srgb) {
+                    for value in &mut rgba[..3] {
+                        *value = linear_to_srgb(*value);
+                    }
+                }
+                if matches!(self.kind, Kind::Bgra8 | Kind::Bgra8Srgb) {
+                    rgba.swap(0, 2);
+                }
+                data.extend(rgba.iter().map(|&value| f32_to_unorm8(value)));
+            }
+            Kind::Float16 => {
+                for &value in channels {
+                    data.extend_from_slice(&f32_to_f16(value).to_le_bytes());
+                }
+            }
+            Kind::Float32 => {
+                for &value in channels {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Kind::R10G10B10A2 => data.extend_from_slice(&pack_r10g10b10a2(*rgba).to_le_bytes()),
+            Kind::R11G11B10 => {
+                let [r, g, b, _] = *rgba;
+                data.extend_from_slice(&pack_r11g11b10([r, g, b]).to_le_bytes());
+            }
+        }
+    }
+}
+
+pub fn unorm8_to_f32(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+/// Clamps to 0 to 1 and rounds to the nearest step. NaN becomes 0.
+pub fn f32_to_unorm8(value: f32) -> u8 {
+    to_unorm(value, 8) as u8
+}
+
+/// Converts with the exact sRGB curve, not the 2.2 gamma approximation.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn f16_to_f32(half: u16) -> f32 {
+    let value = from_small_float(half as u32 & 0x7fff, 10);
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Rounds to the nearest half, ties to even. Values too large for a half
+/// become infinity, and NaNs keep as much of their payload as fits.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let sign = (value.to_bits() >> 16 & 0x8000) as u16;
+    sign | to_small_float(value.abs(), 10) as u16
+}
+
+/// Unpacks `R10G10B10A2_UNORM`, with red in the lowest bits.
+pub fn unpack_r10g10b10a2(packed: u32) -> [f32; 4] {
+    [
+        (packed & 0x3ff) as f32 / 1023.0,
+        (packed >> 10 & 0x3ff) as f32 / 1023.0,
+        (packed >> 20 & 0x3ff) as f32 / 1023.0,
+        (packed >> 30) as f32 / 3.0,
+    ]
+}
+
+pub fn pack_r10g10b10a2(rgba: [f32; 4]) -> u32 {
+    to_unorm(rgba[0], 10)
+        | to_unorm(rgba[1], 10) << 10
+        | to_unorm(rgba[2], 10) << 20
+        | to_unorm(rgba[3], 2) << 30
+}
+
+/// Unpacks `R11G11B10_FLOAT`: red and green have six bits of mantissa and
+/// blue five, all with five bits of exponent and no sign.
+pub fn unpack_r11g11b10(packed: u32) -> [f32; 3] {
+    [
+        from_small_float(packed & 0x7ff, 6),
+        from_small_float(packed >> 11 & 0x7ff, 6),
+        from_small_float(packed >> 22, 5),
+    ]
+}
+
+/// Negative values become 0 and finite values too large become the largest
+/// there is, as D3D converts them; otherwise it rounds as `f32_to_f16` does.
+pub fn pack_r11g11b10(rgb: [f32; 3]) -> u32 {
+    let channel = |value: f32, mantissa_bits: u32| {
+        let infinity = 0x1f << mantissa_bits;
+        if value.is_nan() {
+            to_small_float(value, mantissa_bits)
+        } else if value <= 0.0 {
+            0
+        } else if value.is_infinite() {
+            infinity
+        } else {
+            to_small_float(value, mantissa_bits).min(infinity - 1)
+        }
+    };
+    channel(rgb[0], 6) | channel(rgb[1], 6) << 11 | channel(rgb[2], 5) << 22
+}
+
+fn to_unorm(value: f32, bits: u32) -> u32 {
+    let max = ((1 << bits) - 1) as f32;
+    // `as` turns NaN into 0.
+    (value.clamp(0.0, 1.0) * max).round() as u32
+}
+
+/// Expands a float with no sign bit, five bits of exponent and
+/// `mantissa_bits` of mantissa, as halves and the channels of
+/// `R11G11B10_FLOAT` are stored.
+fn from_small_float(bits: u32, mantissa_bits: u32) -> f32 {
+    let exponent = bits >> mantissa_bits;
+    let mantissa = bits & ((1 << mantissa_bits) - 1);
+    match exponent {
+        // Zero, or subnormal.
+        0 => mantissa as f32 * 2f32.powi(-14 - mantissa_bits as i32),
+        0x1f => f32::from_bits(0x7f80_0000 | mantissa << (23 - mantissa_bits)),
+        _ => f32::from_bits((exponent + 127 - 15) << 23 | mantissa << (23 - mantissa_bits)),
+    }
+}
+
+/// The reverse of `from_small_float` for a value that isn't negative,
+/// rounding to the nearest, ties to even. Values too large become infinity.
+fn to_small_float(value: f32, mantissa_bits: u32) -> u32 {
+    let bits = value.to_bits() & 0x7fff_ffff;
+    let exponent = (bits >> 23) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    let infinity = 0x1f << mantissa_bits;
+    let shift = 23 - mantissa_bits;
+
+    if exponent == 0xff {
+        // A NaN has to keep a mantissa bit set to stay a NaN.
+        let nan = if mantissa != 0 {
+            (mantissa >> shift).max(1)
+        } else {
+            0
+        };
+        return infinity | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return infinity;
+    }
+    if exponent <= 0 {
+        // Subnormal, or too small for even that.
+        if exponent < -(mantissa_bits as i32) {
+            return 0;
+        }
+        return round_shift(mantissa | 0x80_0000, shift + (1 - exponent) as u32);
+    }
+    // A carry out of the mantissa moves up into the exponent, which is what
+    // rounding should do, right up to infinity.
+    ((exponent as u32) << mantissa_bits) + round_shift(mantissa, shift)
+}
+
+/// `value >> shift`, rounded to the nearest, ties to even.
+fn round_shift(value: u32, shift: u32) -> u32 {
+    let half = 1 << (shift - 1);
+    let rest = value & ((1 << shift) - 1);
+    let shifted = value >> shift;
+    if rest > half || rest == half && shifted & 1 == 1 {
+        shifted + 1
+    } else {
+        shifted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_half_round_trips() {
+        for half in 0..=u16::MAX {
+            let value = f16_to_f32(half);
+            let exponent = half >> 10 & 0x1f;
+            let mantissa = half & 0x3ff;
+            // NaNs keep their sign and payload too.
+            assert_eq!(value.is_nan(), exponent == 0x1f && mantissa != 0);
+            assert_eq!(f32_to_f16(value), half, "{:#06x} is {}", half, value);
+        }
+    }
+
+    #[test]
+    fn half_values() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0400), 2f32.powi(-14));
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert_eq!(f16_to_f32(0x8000).to_bits(), (-0.0f32).to_bits());
+
+        assert_eq!(f32_to_f16(65519.0), 0x7bff);
+        assert_eq!(f32_to_f16(-1e10), 0xfc00);
+        assert_eq!(f32_to_f16(2f32.powi(-26)), 0);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7fff, 0x7e00);
+    }
+
+    #[test]
+    fn halfway_between_halves_rounds_to_even() {
+        for half in 0..0x7bffu16 {
+            let between = (f16_to_f32(half) + f16_to_f32(half + 1)) / 2.0;
+            let even = if half & 1 == 0 { half } else { half + 1 };
+            assert_eq!(f32_to_f16(between), even, "{}", between);
+            assert_eq!(f32_to_f16(-between), even | 0x8000, "{}", -between);
+        }
+        // Halfway between the largest half and the next power of two rounds
+        // up to infinity, and anything under it down.
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(65520.0 - 2f32.powi(-8)), 0x7bff);
+    }
+
+    #[test]
+    fn r10g10b10a2_round_trips() {
+        for value in 0..1024 {
+            for alpha in 0..4 {
+                let packed = value | (1023 - value) << 10 | (value * 7 % 1024) << 20 | alpha << 30;
+                assert_eq!(pack_r10g10b10a2(unpack_r10g10b10a2(packed)), packed);
+            }
+        }
+        assert_eq!(unpack_r10g10b10a2(0xffff_ffff), [1.0; 4]);
+        assert_eq!(
+            pack_r10g10b10a2([-1.0, 2.0, f32::NAN, 0.5]),
+            1023 << 10 | 2 << 30
+        );
+    }
+
+    #[test]
+    fn r11g11b10_round_trips() {
+        for value in 0..0x800 {
+            let packed = value | value << 11 | (value >> 1) << 22;
+            let rgb = unpack_r11g11b10(packed);
+            let nan = [value & 0x3f != 0, value & 0x3f != 0, value >> 1 & 0x1f != 0];
+            for (channel, &nan) in rgb.iter().zip(&nan) {
+                assert_eq!(channel.is_nan(), value >> 6 == 0x1f && nan);
+            }
+            assert_eq!(pack_r11g11b10(rgb), packed, "{:#x}", packed);
+        }
+    }
+
+    #[test]
+    fn r11g11b10_clamps() {
+        let largest = unpack_r11g11b10(0x7bf | 0x7bf << 11 | 0x3df << 22);
+        assert_eq!(largest, [65024.0, 65024.0, 64512.0]);
+        assert_eq!(pack_r11g11b10([-1.0, -0.0, f32::NEG_INFINITY]), 0);
+        assert_eq!(
+            pack_r11g11b10([1e10, 65535.0, 1e10]),
+            0x7bf | 0x7bf << 11 | 0x3df << 22
+        );
+        assert_eq!(
+            pack_r11g11b10([f32::INFINITY; 3]),
+            0x7c0 | 0x7c0 << 11 | 0x3e0 << 22
+        );
+    }
+
+    #[test]
+    fn unorm8_round_trips() {
+        for value in 0..=u8::MAX {
+            assert_eq!(f32_to_unorm8(unorm8_to_f32(value)), value);
+        }
+        assert_eq!(unorm8_to_f32(255), 1.0);
+        assert_eq!(f32_to_unorm8(-1.0), 0);
+        assert_eq!(f32_to_unorm8(2.0), 255);
+        assert_eq!(f32_to_unorm8(f32::NAN), 0);
+        assert_eq!(f32_to_unorm8(0.5), 128);
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for value in 0..=u8::MAX {
+            let linear = srgb_to_linear(unorm8_to_f32(value));
+            assert_eq!(f32_to_unorm8(linear_to_srgb(linear)), value);
+        }
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-6);
+        assert!((linear_to_srgb(0.5) - 0.735_357).abs() < 1e-6);
+        // The two pieces of each curve meet.
+        assert!((srgb_to_linear(0.04045) - srgb_to_linear(0.040_451)).abs() < 1e-6);
+        assert!((linear_to_srgb(0.003_130_8) - linear_to_srgb(0.003_130_9)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn textures_round_trip_through_float() {
+        let texels: Vec<[f32; 4]> = (0..=255u8)
+            .map(|v| [v, 255 - v, v / 2, v ^ 0x5a].map(unorm8_to_f32))
+            .collect();
+        for &format in &[DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB] {
+            let rgba8 = write_texels(format, 16, 16, &texels).unwrap();
+            let texture = Texture {
+                format,
+                width: 16,
+                height: 16,
+                subresources: vec![rgba8],
+            };
+            let float = convert(&texture, DXGI_FORMAT_R32G32B32A32_FLOAT).unwrap();
+            assert_eq!(convert(&float, format).unwrap(), texture);
+        }
+    }
+}
@@ -0,0 +1,631 @@
+//! Reads DDS files: the legacy header with its pixel formats, and the DX10
+//! extension header, with mip chains, arrays, cube maps and volume textures.
+//!
+//! The surfaces in a DDS file are stored tightly packed, in the same order
+//! that D3D12 numbers subresources, so `read` can hand out slices of the
+//! file.
+
+use crate::format::*;
+use crate::texture::{
+    mip_size, FormatLayout, SubresourceData, TextureData, TextureDesc, TextureDimension,
+};
+use std::{borrow::Cow, convert::TryInto, fmt, fs, io, path::Path};
+
+pub const DDS_MAGIC: &[u8; 4] = b"DDS ";
+
+const HEADER_SIZE: usize = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+const DDPF_BUMPDUDV: u32 = 0x80000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xfc00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+const DX10_DIMENSION_TEXTURE1D: u32 = 2;
+const DX10_DIMENSION_TEXTURE2D: u32 = 3;
+const DX10_DIMENSION_TEXTURE3D: u32 = 4;
+const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+
+#[derive(Debug)]
+pub enum DdsError {
+    Io(io::Error),
+    NotDds,
+    InvalidHeader(&'static str),
+    /// A legacy pixel format with no DXGI equivalent.
+    UnsupportedPixelFormat {
+        flags: u32,
+        four_cc: [u8; 4],
+        bit_count: u32,
+    },
+    UnsupportedFormat(DXGI_FORMAT),
+    UnsupportedDimension(u32),
+    /// The file ends before the data for `subresource`.
+    Truncated {
+        subresource: usize,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for DdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DdsError::Io(e) => write!(f, "{}", e),
+            DdsError::NotDds => write!(f, "not a DDS file"),
+            DdsError::InvalidHeader(message) => write!(f, "invalid DDS header: {}", message),
+            DdsError::UnsupportedPixelFormat {
+                flags,
+                four_cc,
+                bit_count,
+            } => write!(
+                f,
+                "unsupported pixel format (flags {:#x}, four CC {:?}, {} bits)",
+                flags,
+                String::from_utf8_lossy(four_cc),
+                bit_count
+            ),
+            DdsError::UnsupportedFormat(format) => {
+                write!(f, "unsupported DXGI format {}", format.0)
+            }
+            DdsError::UnsupportedDimension(dimension) => {
+                write!(f, "unsupported resource dimension {}", dimension)
+            }
+            DdsError::Truncated {
+                subresource,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "subresource {} needs the file to be {} bytes but it is {}",
+                subresource, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DdsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DdsError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DdsError {
+    fn from(error: io::Error) -> Self {
+        DdsError::Io(error)
+    }
+}
+
+impl From<DdsError> for io::Error {
+    fn from(error: DdsError) -> Self {
+        match error {
+            DdsError::Io(e) => e,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<TextureData<'static>, DdsError> {
+    let bytes = fs::read(path)?;
+    Ok(read(&bytes)?.into_owned())
+}
+
+/// Reads a DDS file that is already in memory. The subresources borrow from
+/// `bytes`.
+pub fn read(bytes: &[u8]) -> Result<TextureData<'_>, DdsError> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return Err(DdsError::NotDds);
+    }
+    let header = bytes
+        .get(4..4 + HEADER_SIZE)
+        .ok_or(DdsError::InvalidHeader("truncated header"))?;
+    let field =
+        |index: usize| u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+
+    if field(0) != HEADER_SIZE as u32 || field(18) != PIXEL_FORMAT_SIZE {
+        return Err(DdsError::InvalidHeader("wrong header size"));
+    }
+    let flags = field(1);
+    if flags & (DDSD_WIDTH | DDSD_HEIGHT) != (DDSD_WIDTH | DDSD_HEIGHT) {
+        return Err(DdsError::InvalidHeader("no width or height"));
+    }
+    let height = field(2);
+    let width = field(3);
+    let depth = if flags & DDSD_DEPTH != 0 { field(5) } else { 1 };
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        field(6).max(1)
+    } else {
+        1
+    };
+    let pixel_format = PixelFormat {
+        flags: field(19),
+        four_cc: header[80..84].try_into().unwrap(),
+        bit_count: field(21),
+        masks: [field(22), field(23), field(24), field(25)],
+    };
+    let caps2 = field(27);
+
+    let mut data_offset = 4 + HEADER_SIZE;
+    let (format, dimension, array_size, is_cube) = if pixel_format.flags & DDPF_FOURCC != 0
+        && &pixel_format.four_cc == b"DX10"
+    {
+        let dx10 = bytes
+            .get(data_offset..data_offset + DX10_HEADER_SIZE)
+            .ok_or(DdsError::InvalidHeader("truncated DX10 header"))?;
+        data_offset += DX10_HEADER_SIZE;
+        let field =
+            |index: usize| u32::from_le_bytes(dx10[index * 4..index * 4 + 4].try_into().unwrap());
+
+        let format = DXGI_FORMAT(field(0) as i32);
+        let is_cube = field(2) & DX10_MISC_TEXTURECUBE != 0;
+        let array_size = field(3);
+        if array_size == 0 {
+            return Err(DdsError::InvalidHeader("array size of 0"));
+        }
+        let dimension = match field(1) {
+            DX10_DIMENSION_TEXTURE1D if height == 1 => TextureDimension::Texture1D,
+            DX10_DIMENSION_TEXTURE1D => {
+                return Err(DdsError::InvalidHeader("1D texture with a height"))
+            }
+            DX10_DIMENSION_TEXTURE2D => TextureDimension::Texture2D,
+            DX10_DIMENSION_TEXTURE3D if array_size == 1 && !is_cube => TextureDimension::Texture3D,
+            DX10_DIMENSION_TEXTURE3D => {
+                return Err(DdsError::InvalidHeader("array of volume textures"))
+            }
+            dimension => return Err(DdsError::UnsupportedDimension(dimension)),
+        };
+        let array_size = if is_cube {
+            array_size
+                .checked_mul(6)
+                .ok_or(DdsError::InvalidHeader("too many cubes"))?
+        } else {
+            array_size
+        };
+        (format, dimension, array_size, is_cube)
+    } else {
+        let format = pixel_format.to_dxgi()?;
+        if caps2 & DDSCAPS2_CUBEMAP != 0 {
+            // Cube maps with some faces left out have no D3D12
+            // equivalent.
+            if caps2 & DDSCAPS2_CUBEMAP_ALLFACES != DDSCAPS2_CUBEMAP_ALLFACES {
+                return Err(DdsError::InvalidHeader("cube map without all six faces"));
+            }
+            (format, TextureDimension::Texture2D, 6, true)
+        } else if flags & DDSD_DEPTH != 0 && caps2 & DDSCAPS2_VOLUME != 0 {
+            (format, TextureDimension::Texture3D, 1, false)
+        } else {
+            (format, TextureDimension::Texture2D, 1, false)
+        }
+    };
+
+    let layout = FormatLayout::of(format).ok_or(DdsError::UnsupportedFormat(format))?;
+    if width == 0 || height == 0 || depth == 0 {
+        return Err(DdsError::InvalidHeader("empty texture"));
+    }
+    let depth = if dimension == TextureDimension::Texture3D {
+        depth
+    } else {
+        1
+    };
+    let full_chain = u32::BITS - width.max(height).max(depth).leading_zeros();
+    if mip_count > full_chain {
+        return Err(DdsError::InvalidHeader("more mips than a full chain"));
+    }
+    let depth_or_array_size = if dimension == TextureDimension::Texture3D {
+        depth
+    } else {
+        array_size
+    };
+    let depth_or_array_size: u16 = depth_or_array_size
+        .try_into()
+        .map_err(|_| DdsError::InvalidHeader("too many array slices or depth slices"))?;
+
+    let mut subresources = Vec::new();
+    let mut offset = data_offset as u64;
+    for _ in 0..array_size {
+        for mip in 0..mip_count {
+            let row_pitch = layout.row_pitch(mip_size(width, mip));
+            let slice_pitch =
+                row_pitch.saturating_mul(layout.num_rows(mip_size(height, mip)) as u64);
+            let end = slice_pitch
+                .checked_mul(mip_size(depth, mip) as u64)
+                .and_then(|size| size.checked_add(offset))
+                .unwrap_or(u64::MAX);
+            let data = bytes
+                .get(offset as usize..end.try_into().unwrap_or(usize::MAX))
+                .ok_or(DdsError::Truncated {
+                    subresource: subresources.len(),
+                    expected: end,
+                    actual: bytes.len() as u64,
+                })?;
+            subresources.push(SubresourceData {
+                data: Cow::Borrowed(data),
+                row_pitch,
+                slice_pitch,
+            });
+            offset = end;
+        }
+    }
+
+    Ok(TextureData {
+        desc: TextureDesc {
+            dimension,
+            format,
+            width,
+            height,
+            depth_or_array_size,
+            mip_levels: mip_count as u16,
+        },
+        is_cube,
+        subresources,
+    })
+}
+
+struct PixelFormat {
+    flags: u32,
+    four_cc: [u8; 4],
+    bit_count: u32,
+    /// Red, green, blue and alpha.
+    masks: [u32; 4],
+}
+
+impl PixelFormat {
+    /// Maps the legacy formats that D3D10 and later can represent, following
+    /// the table in the DDS documentation.
+    fn to_dxgi(&self) -> Result<DXGI_FORMAT, DdsError> {
+        let masks = |r, g, b, a| self.masks == [r, g, b, a];
+        let format = if self.flags & DDPF_FOURCC != 0 {
+            match &self.four_cc {
+                b"DXT1" => DXGI_FORMAT_BC1_UNORM,
+                b"DXT2" | b"DXT3" => DXGI_FORMAT_BC2_UNORM,
+                b"DXT4" | b"DXT5" => DXGI_FORMAT_BC3_UNORM,
+                b"ATI1" | b"BC4U" => DXGI_FORMAT_BC4_UNORM,
+                b"BC4S" => DXGI_FORMAT_BC4_SNORM,
+                b"ATI2" | b"BC5U" => DXGI_FORMAT_BC5_UNORM,
+                b"BC5S" => DXGI_FORMAT_BC5_SNORM,
+                // Some writers store a D3DFORMAT number instead of a four CC.
+                four_cc => match u32::from_le_bytes(*four_cc) {
+                    36 => DXGI_FORMAT_R16G16B16A16_UNORM,
+                    110 => DXGI_FORMAT_R16G16B16A16_SNORM,
+                    111 => DXGI_FORMAT_R16_FLOAT,
+                    112 => DXGI_FORMAT_R16G16_FLOAT,
+                    113 => DXGI_FORMAT_R16G16B16A16_FLOAT,
+                    114 => DXGI_FORMAT_R32_FLOAT,
+                    115 => DXGI_FORMAT_R32G32_FLOAT,
+                    116 => DXGI_FORMAT_R32G32B32A32_FLOAT,
+                    _ => DXGI_FORMAT_UNKNOWN,
+                },
+            }
+        } else if self.flags & DDPF_RGB != 0 {
+            match self.bit_count {
+                32 if masks(0xff, 0xff00, 0xff0000, 0xff000000) => DXGI_FORMAT_R8G8B8A8_UNORM,
+                32 if masks(0xff0000, 0xff00, 0xff, 0xff000000) => DXGI_FORMAT_B8G8R8A8_UNORM,
+                32 if masks(0xff0000, 0xff00, 0xff, 0) => DXGI_FORMAT_B8G8R8X8_UNORM,
+                32 if masks(0x3ff, 0xffc00, 0x3ff00000, 0xc0000000) => {
+                    DXGI_FORMAT_R10G10B10A2_UNORM
+                }
+                32 if masks(0xffff, 0xffff0000, 0, 0) => DXGI_FORMAT_R16G16_UNORM,
+                32 if masks(0xffffffff, 0, 0, 0) => DXGI_FORMAT_R32_FLOAT,
+                16 if masks(0x7c00, 0x3e0, 0x1f, 0x8000) => DXGI_FORMAT_B5G5R5A1_UNORM,
+                16 if masks(0xf800, 0x7e0, 0x1f, 0) => DXGI_FORMAT_B5G6R5_UNORM,
+                16 if masks(0xf00, 0xf0, 0xf, 0xf000) => DXGI_FORMAT_B4G4R4A4_UNORM,
+                _ => DXGI_FORMAT_UNKNOWN,
+            }
+        } else if self.flags & DDPF_LUMINANCE != 0 {
+            match self.bit_count {
+                8 if masks(0xff, 0, 0, 0) => DXGI_FORMAT_R8_UNORM,
+                16 if masks(0xffff, 0, 0, 0) => DXGI_FORMAT_R16_UNORM,
+                16 if masks(0xff, 0, 0, 0xff00) => DXGI_FORMAT_R8G8_UNORM,
+                _ => DXGI_FORMAT_UNKNOWN,
+            }
+        } else if self.flags & DDPF_ALPHA != 0 && self.bit_count == 8 {
+            DXGI_FORMAT_A8_UNORM
+        } else if self.flags & DDPF_BUMPDUDV != 0 {
+            match self.bit_count {
+                16 if masks(0xff, 0xff00, 0, 0) => DXGI_FORMAT_R8G8_SNORM,
+                32 if masks(0xff, 0xff00, 0xff0000, 0xff000000) => DXGI_FORMAT_R8G8B8A8_SNORM,
+                32 if masks(0xffff, 0xffff0000, 0, 0) => DXGI_FORMAT_R16G16_SNORM,
+                _ => DXGI_FORMAT_UNKNOWN,
+            }
+        } else {
+            DXGI_FORMAT_UNKNOWN
+        };
+
+        if format == DXGI_FORMAT_UNKNOWN {
+            return Err(DdsError::UnsupportedPixelFormat {
+                flags: self.flags,
+                four_cc: self.four_cc,
+                bit_count: self.bit_count,
+            });
+        }
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `DDSD_CAPS` and `DDSD_PIXELFORMAT`, which writers set and `read`
+    /// ignores, and the width and height.
+    const REQUIRED_FLAGS: u32 = 0x1 | 0x1000 | DDSD_HEIGHT | DDSD_WIDTH;
+
+    /// A file with a legacy header and `data_size` bytes of data numbered so
+    /// that misplaced subresources show up. `four_cc` is left empty for
+    /// `bit_count` and `masks` to describe the format instead.
+    #[allow(clippy::too_many_arguments)]
+    fn legacy(
+        flags: u32,
+        (width, height, depth): (u32, u32, u32),
+        mip_count: u32,
+        four_cc: &[u8; 4],
+        bit_count: u32,
+        masks: [u32; 4],
+        caps2: u32,
+        data_size: usize,
+    ) -> Vec<u8> {
+        let mut fields = [0u32; HEADER_SIZE / 4];
+        fields[0] = HEADER_SIZE as u32;
+        fields[1] = REQUIRED_FLAGS | flags;
+        fields[2] = height;
+        fields[3] = width;
+        fields[5] = depth;
+        fields[6] = mip_count;
+        fields[18] = PIXEL_FORMAT_SIZE;
+        fields[19] = if four_cc == &[0; 4] {
+            DDPF_RGB
+        } else {
+            DDPF_FOURCC
+        };
+        fields[20] = u32::from_le_bytes(*four_cc);
+        fields[21] = bit_count;
+        fields[22..26].copy_from_slice(&masks);
+        fields[27] = caps2;
+
+        let mut file = DDS_MAGIC.to_vec();
+        file.extend(fields.iter().flat_map(|field| field.to_le_bytes()));
+        file.extend((0..data_size).map(|i| i as u8));
+        file
+    }
+
+    /// A file with a DX10 header and numbered data.
+    fn dx10(
+        format: DXGI_FORMAT,
+        dimension: u32,
+        (width, height): (u32, u32),
+        misc: u32,
+        array_size: u32,
+        data_size: usize,
+    ) -> Vec<u8> {
+        let mut file = legacy(0, (width, height, 1), 1, b"DX10", 0, [0; 4], 0, 0);
+        for field in [format.0 as u32, dimension, misc, array_size, 0] {
+            file.extend_from_slice(&field.to_le_bytes());
+        }
+        file.extend((0..data_size).map(|i| i as u8));
+        file
+    }
+
+    const DATA_OFFSET: usize = 4 + HEADER_SIZE;
+
+    fn pitches(texture: &TextureData) -> Vec<(u64, u64, usize)> {
+        texture
+            .subresources
+            .iter()
+            .map(|s| (s.row_pitch, s.slice_pitch, s.data.len()))
+            .collect()
+    }
+
+    #[test]
+    fn legacy_four_cc_with_mips() {
+        // 8x8 BC1 is 2x2 blocks; the smaller mips are one block each.
+        let file = legacy(DDSD_MIPMAPCOUNT, (8, 8, 0), 4, b"DXT1", 0, [0; 4], 0, 56);
+        let texture = read(&file).unwrap();
+        assert_eq!(
+            texture.desc,
+            TextureDesc {
+                dimension: TextureDimension::Texture2D,
+                format: DXGI_FORMAT_BC1_UNORM,
+                width: 8,
+                height: 8,
+                depth_or_array_size: 1,
+                mip_levels: 4,
+            }
+        );
+        assert!(!texture.is_cube);
+        assert_eq!(
+            pitches(&texture),
+            [(16, 32, 32), (8, 8, 8), (8, 8, 8), (8, 8, 8)]
+        );
+        assert_eq!(texture.subresource(1, 0).unwrap().data[0], 32);
+        assert_eq!(texture.subresource(3, 0).unwrap().data[0], 48);
+        assert!(texture.subresource(4, 0).is_none());
+
+        let file = legacy(0, (4, 4, 0), 4, b"DXT5", 0, [0; 4], 0, 16);
+        let texture = read(&file).unwrap();
+        assert_eq!(texture.desc.format, DXGI_FORMAT_BC3_UNORM);
+        // The count is ignored without its flag.
+        assert_eq!(texture.desc.mip_levels, 1);
+    }
+
+    #[test]
+    fn legacy_masks() {
+        let rgba = [0xff, 0xff00, 0xff0000, 0xff000000];
+        let file = legacy(0, (2, 1, 0), 1, &[0; 4], 32, rgba, 0, 8);
+        assert_eq!(read(&file).unwrap().desc.format, DXGI_FORMAT_R8G8B8A8_UNORM);
+
+        let bgrx = [0xff0000, 0xff00, 0xff, 0];
+        let file = legacy(0, (2, 1, 0), 1, &[0; 4], 32, bgrx, 0, 8);
+        assert_eq!(read(&file).unwrap().desc.format, DXGI_FORMAT_B8G8R8X8_UNORM);
+
+        let file = legacy(
+            0,
+            (2, 1, 0),
+            1,
+            &[0; 4],
+            24,
+            [0xff0000, 0xff00, 0xff, 0],
+            0,
+            6,
+        );
+        assert!(matches!(
+            read(&file),
+            Err(DdsError::UnsupportedPixelFormat { bit_count: 24, .. })
+        ));
+    }
+
+    #[test]
+    fn mip_counts() {
+        // A count of 0 means 1.
+        let file = legacy(DDSD_MIPMAPCOUNT, (4, 4, 0), 0, b"DXT1", 0, [0; 4], 0, 8);
+        assert_eq!(read(&file).unwrap().desc.mip_levels, 1);
+
+        // 4x4 has three mips at most.
+        let file = legacy(DDSD_MIPMAPCOUNT, (4, 4, 0), 3, b"DXT1", 0, [0; 4], 0, 24);
+        assert_eq!(read(&file).unwrap().desc.mip_levels, 3);
+        let file = legacy(DDSD_MIPMAPCOUNT, (4, 4, 0), 4, b"DXT1", 0, [0; 4], 0, 32);
+        assert!(matches!(read(&file), Err(DdsError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn dx10_header() {
+        let file = dx10(
+            DXGI_FORMAT_R8G8B8A8_UNORM,
+            DX10_DIMENSION_TEXTURE2D,
+            (4, 2),
+            0,
+            2,
+            64,
+        );
+        let texture = read(&file).unwrap();
+        assert_eq!(texture.desc.dimension, TextureDimension::Texture2D);
+        assert_eq!(texture.desc.format, DXGI_FORMAT_R8G8B8A8_UNORM);
+        assert_eq!((texture.array_size(), texture.depth()), (2, 1));
+        assert_eq!(pitches(&texture), [(16, 32, 32), (16, 32, 32)]);
+        assert_eq!(texture.subresource(0, 1).unwrap().data[0], 32);
+
+        let file = dx10(
+            DXGI_FORMAT_R16_FLOAT,
+            DX10_DIMENSION_TEXTURE1D,
+            (8, 1),
+            0,
+            1,
+            16,
+        );
+        assert_eq!(
+            read(&file).unwrap().desc.dimension,
+            TextureDimension::Texture1D
+        );
+
+        let file = dx10(DXGI_FORMAT_R8_UNORM, 5, (1, 1), 0, 1, 1);
+        assert!(matches!(
+            read(&file),
+            Err(DdsError::UnsupportedDimension(5))
+        ));
+    }
+
+    #[test]
+    fn cube_maps() {
+        // Two cubes of 1x1 faces.
+        let file = dx10(
+            DXGI_FORMAT_R8G8B8A8_UNORM,
+            DX10_DIMENSION_TEXTURE2D,
+            (1, 1),
+            DX10_MISC_TEXTURECUBE,
+            2,
+            48,
+        );
+        let texture = read(&file).unwrap();
+        assert!(texture.is_cube);
+        assert_eq!(texture.array_size(), 12);
+        assert_eq!(
+            texture.subresource(0, 11).unwrap().data[..],
+            [44, 45, 46, 47]
+        );
+
+        let all_faces = DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALLFACES;
+        let file = legacy(0, (4, 4, 0), 1, b"DXT1", 0, [0; 4], all_faces, 48);
+        let texture = read(&file).unwrap();
+        assert!(texture.is_cube);
+        assert_eq!(texture.desc.depth_or_array_size, 6);
+
+        // Only +X.
+        let file = legacy(
+            0,
+            (4, 4, 0),
+            1,
+            b"DXT1",
+            0,
+            [0; 4],
+            DDSCAPS2_CUBEMAP | 0x400,
+            8,
+        );
+        assert!(matches!(read(&file), Err(DdsError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn volume_textures() {
+        let rgba = [0xff, 0xff00, 0xff0000, 0xff000000];
+        let file = legacy(
+            DDSD_DEPTH | DDSD_MIPMAPCOUNT,
+            (4, 4, 2),
+            2,
+            &[0; 4],
+            32,
+            rgba,
+            DDSCAPS2_VOLUME,
+            144,
+        );
+        let texture = read(&file).unwrap();
+        assert_eq!(texture.desc.dimension, TextureDimension::Texture3D);
+        assert_eq!(texture.desc.depth_or_array_size, 2);
+        assert_eq!((texture.array_size(), texture.depth()), (1, 2));
+        // The second mip is 2x2x1.
+        assert_eq!(pitches(&texture), [(16, 64, 128), (8, 16, 16)]);
+
+        let file = dx10(
+            DXGI_FORMAT_R8_UNORM,
+            DX10_DIMENSION_TEXTURE3D,
+            (1, 1),
+            0,
+            2,
+            2,
+        );
+        assert!(matches!(read(&file), Err(DdsError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn bad_magic_and_truncated_files() {
+        let mut file = legacy(DDSD_MIPMAPCOUNT, (8, 8, 0), 4, b"DXT1", 0, [0; 4], 0, 56);
+        assert!(matches!(read(&file[..3]), Err(DdsError::NotDds)));
+        assert!(matches!(
+            read(&file[..DATA_OFFSET - 1]),
+            Err(DdsError::InvalidHeader(_))
+        ));
+
+        // The last mip is one byte short.
+        match read(&file[..file.len() - 1]) {
+            Err(DdsError::Truncated {
+                subresource: 3,
+                expected,
+                actual,
+            }) => assert_eq!((expected, actual), (file.len() as u64, expected - 1)),
+            result => panic!("{:?}", result.map(|texture| texture.desc)),
+        }
+
+        file[0] = b'X';
+        assert!(matches!(read(&file), Err(DdsError::NotDds)));
+    }
+}
@@ -0,0 +1,484 @@
+//! Parser for compiled shader containers (the `DXBC` blobs produced by FXC and
+//! stored in `.cso` files). DXC wraps SM6 DXIL programs in the same container
+//! format, with a different set of parts.
+//!
+//! Nothing in here calls into D3D, so the same code can inspect shaders on any
+//! platform.
+
+use std::fmt;
+
+mod checksum;
+pub use checksum::checksum;
+
+mod rdef;
+pub use rdef::*;
+
+mod signature;
+pub use signature::*;
+
+mod shex;
+pub use shex::*;
+
+mod dxil;
+pub use dxil::*;
+
+mod psv;
+pub use psv::*;
+
+mod root_signature;
+pub use root_signature::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The data ended before a structure could be read.
+    UnexpectedEof {
+        offset: usize,
+        wanted: usize,
+    },
+    BadMagic([u8; 4]),
+    UnsupportedVersion(u32),
+    /// The size in the header does not match the size of the data.
+    SizeMismatch {
+        header: u32,
+        actual: usize,
+    },
+    ChecksumMismatch {
+        stored: [u8; 16],
+        computed: [u8; 16],
+    },
+    ChunkOutOfBounds {
+        index: usize,
+        offset: u32,
+    },
+    InvalidChunk {
+        fourcc: FourCC,
+        reason: &'static str,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnexpectedEof { offset, wanted } => {
+                write!(
+                    f,
+                    "unexpected end of data reading {} bytes at {}",
+                    wanted, offset
+                )
+            }
+            Error::BadMagic(magic) => write!(f, "bad container magic {:?}", magic),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "unsupported container version {}", version)
+            }
+            Error::SizeMismatch { header, actual } => write!(
+                f,
+                "container header says {} bytes but {} were provided",
+                header, actual
+            ),
+            Error::ChecksumMismatch { stored, computed } => write!(
+                f,
+                "checksum mismatch: stored {:02x?}, computed {:02x?}",
+                stored, computed
+            ),
+            Error::ChunkOutOfBounds { index, offset } => {
+                write!(f, "chunk {} at offset {} is out of bounds", index, offset)
+            }
+            Error::InvalidChunk { fourcc, reason } => {
+                write!(f, "invalid {} chunk: {}", fourcc, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FourCC(pub [u8; 4]);
+
+impl FourCC {
+    pub const DXBC: FourCC = FourCC(*b"DXBC");
+    pub const DXIL: FourCC = FourCC(*b"DXIL");
+    pub const HASH: FourCC = FourCC(*b"HASH");
+    pub const ILDB: FourCC = FourCC(*b"ILDB");
+    pub const RDEF: FourCC = FourCC(*b"RDEF");
+    pub const ISGN: FourCC = FourCC(*b"ISGN");
+    pub const ISG1: FourCC = FourCC(*b"ISG1");
+    pub const OSGN: FourCC = FourCC(*b"OSGN");
+    pub const OSG1: FourCC = FourCC(*b"OSG1");
+    pub const OSG5: FourCC = FourCC(*b"OSG5");
+    pub const PCSG: FourCC = FourCC(*b"PCSG");
+    pub const PSG1: FourCC = FourCC(*b"PSG1");
+    pub const PSV0: FourCC = FourCC(*b"PSV0");
+    pub const RTS0: FourCC = FourCC(*b"RTS0");
+    pub const SFI0: FourCC = FourCC(*b"SFI0");
+    pub const SHDR: FourCC = FourCC(*b"SHDR");
+    pub const SHEX: FourCC = FourCC(*b"SHEX");
+    pub const STAT: FourCC = FourCC(*b"STAT");
+}
+
+impl fmt::Display for FourCC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+impl fmt::Debug for FourCC {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FourCC({})", self)
+    }
+}
+
+pub struct Chunk<'a> {
+    pub fourcc: FourCC,
+    pub data: &'a [u8],
+}
+
+/// A parsed shader container. Chunks borrow from the data that was parsed.
+pub struct Container<'a> {
+    pub checksum: [u8; 16],
+    pub chunks: Vec<Chunk<'a>>,
+    data: &'a [u8],
+}
+
+const HEADER_SIZE: usize = 32;
+
+impl<'a> Container<'a> {
+    /// Parses the container and verifies its checksum, unless the checksum
+    /// is all zeros, as DXC leaves it when it runs without a validator
+    /// (`-Vd`).
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let container = Self::parse_unverified(data)?;
+        if !container.is_unsigned() {
+            container.verify_checksum()?;
+        }
+        Ok(container)
+    }
+
+    /// Parses the container without checking the checksum. Useful for
+    /// containers that have been patched after compilation.
+    pub fn parse_unverified(data: &'a [u8]) -> Result<Self> {
+        let reader = Reader::new(data);
+
+        let magic = reader.fourcc(0)?;
+        if magic != FourCC::DXBC {
+            return Err(Error::BadMagic(magic.0));
+        }
+
+        let mut checksum = [0; 16];
+        checksum.copy_from_slice(reader.bytes(4, 16)?);
+
+        let version = reader.u32(20)?;
+        if version != 1 {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let total_size = reader.u32(24)?;
+        if total_size as usize != data.len() {
+            return Err(Error::SizeMismatch {
+                header: total_size,
+                actual: data.len(),
+            });
+        }
+
+        let chunk_count = reader.u32(28)? as usize;
+        let chunks = (0..chunk_count)
+            .map(|index| {
+                let offset = reader.u32(HEADER_SIZE + index * 4)?;
+                let out_of_bounds = Error::ChunkOutOfBounds { index, offset };
+
+                let fourcc = reader
+                    .fourcc(offset as usize)
+                    .map_err(|_| out_of_bounds.clone())?;
+                let size = reader
+                    .u32(offset as usize + 4)
+                    .map_err(|_| out_of_bounds.clone())?;
+                let data = reader
+                    .bytes(offset as usize + 8, size as usize)
+                    .map_err(|_| out_of_bounds)?;
+
+                Ok(Chunk { fourcc, data })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Container {
+            checksum,
+            chunks,
+            data,
+        })
+    }
+
+    /// Whether the checksum was left as zeros rather than filled in.
+    pub fn is_unsigned(&self) -> bool {
+        self.checksum == [0; 16]
+    }
+
+    pub fn verify_checksum(&self) -> Result<()> {
+        let computed = checksum(self.data);
+        if computed == self.checksum {
+            Ok(())
+        } else {
+            Err(Error::ChecksumMismatch {
+                stored: self.checksum,
+                computed,
+            })
+        }
+    }
+
+    /// The raw bytes of the whole container.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn chunk(&self, fourcc: FourCC) -> Option<&Chunk<'a>> {
+        self.chunks.iter().find(|c| c.fourcc == fourcc)
+    }
+
+    fn first_chunk(&self, fourccs: &[FourCC]) -> Option<&Chunk<'a>> {
+        self.chunks.iter().find(|c| fourccs.contains(&c.fourcc))
+    }
+
+    pub fn resource_definitions(&self) -> Result<Option<ResourceDefinitions>> {
+        self.chunk(FourCC::RDEF)
+            .map(|c| ResourceDefinitions::parse(c.data))
+            .transpose()
+    }
+
+    pub fn input_signature(&self) -> Result<Option<Signature>> {
+        self.first_chunk(&[FourCC::ISGN, FourCC::ISG1])
+            .map(|c| Signature::parse(c.fourcc, c.data))
+            .transpose()
+    }
+
+    pub fn output_signature(&self) -> Result<Option<Signature>> {
+        self.first_chunk(&[FourCC::OSGN, FourCC::OSG1, FourCC::OSG5])
+            .map(|c| Signature::parse(c.fourcc, c.data))
+            .transpose()
+    }
+
+    pub fn patch_constant_signature(&self) -> Result<Option<Signature>> {
+        self.first_chunk(&[FourCC::PCSG, FourCC::PSG1])
+            .map(|c| Signature::parse(c.fourcc, c.data))
+            .transpose()
+    }
+
+    pub fn shader_program(&self) -> Result<Option<ShaderProgram>> {
+        self.first_chunk(&[FourCC::SHEX, FourCC::SHDR])
+            .map(|c| ShaderProgram::parse(c.fourcc, c.data))
+            .transpose()
+    }
+
+    /// Whether this holds an SM6 DXIL program rather than SM4/5 bytecode.
+    pub fn is_dxil(&self) -> bool {
+        self.chunk(FourCC::DXIL).is_some()
+    }
+
+    /// The DXIL program header. Containers with debug info keep the full
+    /// program in ILDB as well as the stripped one in DXIL.
+    pub fn dxil_program(&self) -> Result<Option<DxilProgram>> {
+        self.first_chunk(&[FourCC::DXIL, FourCC::ILDB])
+            .map(|c| DxilProgram::parse(c.fourcc, c.data))
+            .transpose()
+    }
+
+    pub fn pipeline_state_validation(&self) -> Result<Option<PipelineStateValidation>> {
+        self.chunk(FourCC::PSV0)
+            .map(|c| PipelineStateValidation::parse(c.data))
+            .transpose()
+    }
+
+    /// The root signature embedded with `[RootSignature(...)]`, if any.
+    pub fn root_signature(&self) -> Result<Option<RootSignature>> {
+        self.chunk(FourCC::RTS0)
+            .map(|c| RootSignature::parse(c.data))
+            .transpose()
+    }
+
+    pub fn shader_hash(&self) -> Result<Option<ShaderHash>> {
+        self.chunk(FourCC::HASH)
+            .map(|c| ShaderHash::parse(c.data))
+            .transpose()
+    }
+
+    /// Gathers everything known about the shader's interface into one place.
+    pub fn reflect(&self) -> Result<ShaderReflection> {
+        if self.is_dxil() {
+            return self.reflect_dxil();
+        }
+
+        let program = self.shader_program()?.ok_or(Error::InvalidChunk {
+            fourcc: FourCC::SHEX,
+            reason: "container has no shader program",
+        })?;
+        let rdef = self.resource_definitions()?;
+
+        Ok(ShaderReflection {
+            kind: program.kind,
+            shader_model: program.shader_model,
+            input_signature: self.input_signature()?.unwrap_or_default(),
+            output_signature: self.output_signature()?.unwrap_or_default(),
+            constant_buffers: rdef
+                .as_ref()
+                .map(|r| r.constant_buffers.clone())
+                .unwrap_or_default(),
+            bound_resources: rdef.map(|r| r.bound_resources).unwrap_or_default(),
+            thread_group_size: program.thread_group_size,
+        })
+    }
+
+    // DXIL containers describe their bindings in PSV0, which has no names and
+    // no constant buffer layouts; those only exist in the bitcode.
+    fn reflect_dxil(&self) -> Result<ShaderReflection> {
+        let program = self.dxil_program()?.ok_or(Error::InvalidChunk {
+            fourcc: FourCC::DXIL,
+            reason: "container has no shader program",
+        })?;
+        let psv = self.pipeline_state_validation()?;
+
+        let bound_resources = psv
+            .as_ref()
+            .map(|psv| {
+                psv.resources
+                    .iter()
+                    .map(|resource| BoundResource {
+                        name: String::new(),
+                        input_type: resource.input_type,
+                        return_type: 0,
+                        dimension: 0,
+                        num_samples: 0,
+                        bind_point: resource.lower_bound,
+                        bind_count: resource.bind_count(),
+                        flags: 0,
+                        space: resource.space,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(ShaderReflection {
+            kind: program.kind,
+            shader_model: program.shader_model,
+            input_signature: self.input_signature()?.unwrap_or_default(),
+            output_signature: self.output_signature()?.unwrap_or_default(),
+            constant_buffers: Vec::new(),
+            bound_resources,
+            thread_group_size: psv.and_then(|psv| psv.thread_group_size),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderKind {
+    Pixel,
+    Vertex,
+    Geometry,
+    Hull,
+    Domain,
+    Compute,
+    Other(u32),
+}
+
+impl ShaderKind {
+    /// Maps the program type used in the version token of SHEX/SHDR chunks.
+    pub fn from_program_type(value: u32) -> Self {
+        match value {
+            0 => ShaderKind::Pixel,
+            1 => ShaderKind::Vertex,
+            2 => ShaderKind::Geometry,
+            3 => ShaderKind::Hull,
+            4 => ShaderKind::Domain,
+            5 => ShaderKind::Compute,
+            other => ShaderKind::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ShaderModel {
+    pub major: u8,
+    pub minor: u8,
+}
+
+/// The interface of a shader: signatures, constant buffers and the registers
+/// it binds.
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub kind: ShaderKind,
+    pub shader_model: ShaderModel,
+    pub input_signature: Signature,
+    pub output_signature: Signature,
+    pub constant_buffers: Vec<ConstantBuffer>,
+    pub bound_resources: Vec<BoundResource>,
+    pub thread_group_size: Option<[u32; 3]>,
+}
+
+impl ShaderReflection {
+    pub fn constant_buffer(&self, name: &str) -> Option<&ConstantBuffer> {
+        self.constant_buffers.iter().find(|cb| cb.name == name)
+    }
+
+    pub fn bound_resource(&self, name: &str) -> Option<&BoundResource> {
+        self.bound_resources.iter().find(|r| r.name == name)
+    }
+}
+
+/// Bounds-checked little-endian reads from a chunk.
+#[derive(Clone, Copy)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub(crate) fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Error::UnexpectedEof {
+                offset,
+                wanted: len,
+            })
+    }
+
+    pub(crate) fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    pub(crate) fn u16(&self, offset: usize) -> Result<u16> {
+        let b = self.bytes(offset, 2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub(crate) fn u32(&self, offset: usize) -> Result<u32> {
+        let b = self.bytes(offset, 4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub(crate) fn fourcc(&self, offset: usize) -> Result<FourCC> {
+        let b = self.bytes(offset, 4)?;
+        Ok(FourCC([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Reads a NUL-terminated string.
+    pub(crate) fn string(&self, offset: usize) -> Result<String> {
+        let tail = self
+            .data
+            .get(offset..)
+            .ok_or(Error::UnexpectedEof { offset, wanted: 1 })?;
+        let len = tail
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::UnexpectedEof {
+                offset,
+                wanted: tail.len() + 1,
+            })?;
+        Ok(String::from_utf8_lossy(&tail[..len]).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests;
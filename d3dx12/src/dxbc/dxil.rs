@@ -0,0 +1,75 @@
+use super::{Error, FourCC, Reader, Result, ShaderKind, ShaderModel};
+
+/// The header of a DXIL part. The LLVM bitcode that follows it is not parsed.
+#[derive(Debug, Clone)]
+pub struct DxilProgram {
+    pub kind: ShaderKind,
+    pub shader_model: ShaderModel,
+    /// The DXIL version, which can lag behind the shader model.
+    pub dxil_version: ShaderModel,
+    pub bitcode_size: u32,
+}
+
+const DXIL_MAGIC: FourCC = FourCC(*b"DXIL");
+
+impl DxilProgram {
+    pub fn parse(fourcc: FourCC, data: &[u8]) -> Result<Self> {
+        let r = Reader::new(data);
+        let invalid = |reason| Error::InvalidChunk { fourcc, reason };
+
+        let version = r.u32(0)?;
+        let size_in_u32 = r.u32(4)? as usize;
+        if size_in_u32 * 4 > data.len() {
+            return Err(invalid("program size does not match part size"));
+        }
+
+        if r.fourcc(8)? != DXIL_MAGIC {
+            return Err(invalid("missing bitcode header"));
+        }
+        let dxil_version = r.u32(12)?;
+        let bitcode_offset = r.u32(16)? as usize;
+        let bitcode_size = r.u32(20)?;
+
+        // The bitcode offset is relative to the bitcode header.
+        r.bytes(8 + bitcode_offset, bitcode_size as usize)?;
+
+        Ok(DxilProgram {
+            kind: ShaderKind::from_program_type(version >> 16),
+            shader_model: ShaderModel {
+                major: ((version >> 4) & 0xf) as u8,
+                minor: (version & 0xf) as u8,
+            },
+            dxil_version: ShaderModel {
+                major: (dxil_version >> 8) as u8,
+                minor: (dxil_version & 0xff) as u8,
+            },
+            bitcode_size,
+        })
+    }
+}
+
+/// The contents of a HASH part: an MD5 of the shader that identifies it
+/// independently of the container checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderHash {
+    pub flags: u32,
+    pub digest: [u8; 16],
+}
+
+impl ShaderHash {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let r = Reader::new(data);
+        let mut digest = [0; 16];
+        digest.copy_from_slice(r.bytes(4, 16)?);
+        Ok(ShaderHash {
+            flags: r.u32(0)?,
+            digest,
+        })
+    }
+
+    /// Whether the source (and so debug info) was included when hashing,
+    /// rather than just the compiled program.
+    pub fn includes_source(&self) -> bool {
+        self.flags & 1 != 0
+    }
+}
@@ -0,0 +1,140 @@
+use super::{Error, FourCC, Reader, Result, ShaderInputType, ShaderKind};
+
+/// The contents of a PSV0 (pipeline state validation) part. DXIL containers
+/// carry their bindings here instead of in an RDEF chunk.
+#[derive(Debug, Clone)]
+pub struct PipelineStateValidation {
+    /// Version of the runtime info structure, 0 to 3.
+    pub version: u32,
+    /// Only recorded from version 1 onwards.
+    pub kind: Option<ShaderKind>,
+    pub minimum_wave_lane_count: u32,
+    pub maximum_wave_lane_count: u32,
+    pub uses_view_id: bool,
+    /// Only recorded from version 2 onwards, for stages that have one.
+    pub thread_group_size: Option<[u32; 3]>,
+    /// Only recorded from version 3 onwards.
+    pub entry_function_name: Option<String>,
+    pub resources: Vec<PsvResource>,
+}
+
+/// A register range bound by the shader. PSV0 does not record resource names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PsvResource {
+    pub input_type: ShaderInputType,
+    pub space: u32,
+    pub lower_bound: u32,
+    /// `u32::MAX` for unbounded arrays.
+    pub upper_bound: u32,
+}
+
+impl PsvResource {
+    /// The number of registers in the range, or 0 if it is unbounded, as in
+    /// `BoundResource::bind_count`.
+    pub fn bind_count(&self) -> u32 {
+        if self.upper_bound == u32::MAX {
+            0
+        } else {
+            self.upper_bound
+                .wrapping_sub(self.lower_bound)
+                .wrapping_add(1)
+        }
+    }
+}
+
+// Sizes of PSVRuntimeInfo0 to PSVRuntimeInfo3.
+const RUNTIME_INFO_SIZES: [u32; 4] = [24, 36, 48, 52];
+
+fn invalid(reason: &'static str) -> Error {
+    Error::InvalidChunk {
+        fourcc: FourCC::PSV0,
+        reason,
+    }
+}
+
+impl PipelineStateValidation {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let r = Reader::new(data);
+
+        let info_size = r.u32(0)?;
+        let version = match RUNTIME_INFO_SIZES.iter().rposition(|&s| info_size >= s) {
+            Some(version) => version as u32,
+            None => return Err(invalid("runtime info is too small")),
+        };
+        let info = 4;
+
+        // Bytes 0..16 of the runtime info are a union of stage specific data.
+        let mut psv = PipelineStateValidation {
+            version,
+            kind: None,
+            minimum_wave_lane_count: r.u32(info + 16)?,
+            maximum_wave_lane_count: r.u32(info + 20)?,
+            uses_view_id: false,
+            thread_group_size: None,
+            entry_function_name: None,
+            resources: Vec::new(),
+        };
+
+        if version >= 1 {
+            psv.kind = Some(ShaderKind::from_program_type(r.u8(info + 24)? as u32));
+            psv.uses_view_id = r.u8(info + 25)? != 0;
+        }
+
+        // Stages without a thread group record zeros.
+        if version >= 2 {
+            let size = [r.u32(info + 36)?, r.u32(info + 40)?, r.u32(info + 44)?];
+            if size != [0; 3] {
+                psv.thread_group_size = Some(size);
+            }
+        }
+
+        let mut offset = info + info_size as usize;
+        let resource_count = r.u32(offset)? as usize;
+        offset += 4;
+
+        if resource_count > 0 {
+            let stride = r.u32(offset)? as usize;
+            offset += 4;
+            if stride < 16 {
+                return Err(invalid("resource bindings are too small"));
+            }
+
+            psv.resources = (0..resource_count)
+                .map(|i| {
+                    let base = offset + i * stride;
+                    Ok(PsvResource {
+                        input_type: resource_type(r.u32(base)?),
+                        space: r.u32(base + 4)?,
+                        lower_bound: r.u32(base + 8)?,
+                        upper_bound: r.u32(base + 12)?,
+                    })
+                })
+                .collect::<Result<_>>()?;
+            offset += resource_count * stride;
+        }
+
+        if version >= 3 {
+            let string_table_size = r.u32(offset)? as usize;
+            let string_table = Reader::new(r.bytes(offset + 4, string_table_size)?);
+            psv.entry_function_name = Some(string_table.string(r.u32(info + 48)? as usize)?);
+        }
+
+        Ok(psv)
+    }
+}
+
+/// Maps PSVResourceType onto the equivalent D3D_SHADER_INPUT_TYPE.
+fn resource_type(value: u32) -> ShaderInputType {
+    match value {
+        1 => ShaderInputType::Sampler,
+        2 => ShaderInputType::CBuffer,
+        3 => ShaderInputType::Texture,
+        4 => ShaderInputType::ByteAddress,
+        5 => ShaderInputType::Structured,
+        6 => ShaderInputType::UavRwTyped,
+        7 => ShaderInputType::UavRwByteAddress,
+        8 => ShaderInputType::UavRwStructured,
+        9 => ShaderInputType::UavRwStructuredWithCounter,
+        other => ShaderInputType::Other(other),
+    }
+}
@@ -0,0 +1,346 @@
+use super::{Error, FourCC, Reader, Result, ShaderKind, ShaderModel};
+use std::cell::Cell;
+
+/// The contents of an RDEF chunk: constant buffers and bound resources.
+#[derive(Debug, Clone)]
+pub struct ResourceDefinitions {
+    pub kind: ShaderKind,
+    pub shader_model: ShaderModel,
+    pub flags: u32,
+    pub creator: String,
+    pub constant_buffers: Vec<ConstantBuffer>,
+    pub bound_resources: Vec<BoundResource>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstantBufferType {
+    CBuffer,
+    TBuffer,
+    InterfacePointers,
+    ResourceBindInfo,
+    Other(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstantBuffer {
+    pub name: String,
+    pub buffer_type: ConstantBufferType,
+    pub size: u32,
+    pub flags: u32,
+    pub variables: Vec<Variable>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+    pub flags: u32,
+    pub variable_type: VariableType,
+}
+
+impl Variable {
+    /// D3D_SVF_USED
+    pub fn is_used(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+}
+
+/// D3D_SHADER_VARIABLE_CLASS, D3D_SHADER_VARIABLE_TYPE and the shape of a
+/// variable. Struct members are listed in `members`.
+#[derive(Debug, Clone)]
+pub struct VariableType {
+    /// Only SM5 containers record type names.
+    pub name: Option<String>,
+    pub class: u16,
+    pub base_type: u16,
+    pub rows: u16,
+    pub columns: u16,
+    pub elements: u16,
+    pub members: Vec<StructMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructMember {
+    pub name: String,
+    pub offset: u32,
+    pub member_type: VariableType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderInputType {
+    CBuffer,
+    TBuffer,
+    Texture,
+    Sampler,
+    UavRwTyped,
+    Structured,
+    UavRwStructured,
+    ByteAddress,
+    UavRwByteAddress,
+    UavAppendStructured,
+    UavConsumeStructured,
+    UavRwStructuredWithCounter,
+    RtAccelerationStructure,
+    UavFeedbackTexture,
+    Other(u32),
+}
+
+/// The kind of register a resource is bound to, and so the kind of root
+/// signature range that has to cover it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterClass {
+    /// b#
+    Cbv,
+    /// t#
+    Srv,
+    /// u#
+    Uav,
+    /// s#
+    Sampler,
+}
+
+impl ShaderInputType {
+    /// Maps D3D_SHADER_INPUT_TYPE.
+    pub fn from_raw(value: u32) -> Self {
+        match value {
+            0 => ShaderInputType::CBuffer,
+            1 => ShaderInputType::TBuffer,
+            2 => ShaderInputType::Texture,
+            3 => ShaderInputType::Sampler,
+            4 => ShaderInputType::UavRwTyped,
+            5 => ShaderInputType::Structured,
+            6 => ShaderInputType::UavRwStructured,
+            7 => ShaderInputType::ByteAddress,
+            8 => ShaderInputType::UavRwByteAddress,
+            9 => ShaderInputType::UavAppendStructured,
+            10 => ShaderInputType::UavConsumeStructured,
+            11 => ShaderInputType::UavRwStructuredWithCounter,
+            12 => ShaderInputType::RtAccelerationStructure,
+            13 => ShaderInputType::UavFeedbackTexture,
+            other => ShaderInputType::Other(other),
+        }
+    }
+
+    pub fn register_class(&self) -> Option<RegisterClass> {
+        match self {
+            ShaderInputType::CBuffer => Some(RegisterClass::Cbv),
+            ShaderInputType::TBuffer
+            | ShaderInputType::Texture
+            | ShaderInputType::Structured
+            | ShaderInputType::ByteAddress
+            | ShaderInputType::RtAccelerationStructure => Some(RegisterClass::Srv),
+            ShaderInputType::UavRwTyped
+            | ShaderInputType::UavRwStructured
+            | ShaderInputType::UavRwByteAddress
+            | ShaderInputType::UavAppendStructured
+            | ShaderInputType::UavConsumeStructured
+            | ShaderInputType::UavRwStructuredWithCounter
+            | ShaderInputType::UavFeedbackTexture => Some(RegisterClass::Uav),
+            ShaderInputType::Sampler => Some(RegisterClass::Sampler),
+            ShaderInputType::Other(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundResource {
+    pub name: String,
+    pub input_type: ShaderInputType,
+    /// D3D_RESOURCE_RETURN_TYPE
+    pub return_type: u32,
+    /// D3D_SRV_DIMENSION
+    pub dimension: u32,
+    pub num_samples: u32,
+    pub bind_point: u32,
+    pub bind_count: u32,
+    pub flags: u32,
+    pub space: u32,
+}
+
+impl BoundResource {
+    /// Unbounded arrays have a bind count of zero.
+    pub fn is_unbounded(&self) -> bool {
+        self.bind_count == 0
+    }
+}
+
+const RD11: FourCC = FourCC(*b"RD11");
+
+fn invalid(reason: &'static str) -> Error {
+    Error::InvalidChunk {
+        fourcc: FourCC::RDEF,
+        reason,
+    }
+}
+
+impl ResourceDefinitions {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let r = Reader::new(data);
+
+        let cbuffer_count = r.u32(0)? as usize;
+        let cbuffer_offset = r.u32(4)? as usize;
+        let binding_count = r.u32(8)? as usize;
+        let binding_offset = r.u32(12)? as usize;
+        let minor = r.u8(16)?;
+        let major = r.u8(17)?;
+        let program_type = r.u16(18)?;
+        let flags = r.u32(20)?;
+        let creator = r.string(r.u32(24)? as usize)?;
+
+        // SM5 containers carry the size of each structure so that they can
+        // grow; SM4 ones use the original sizes.
+        let (binding_stride, variable_stride, type_has_name) = if major >= 5 {
+            if r.fourcc(28)? != RD11 {
+                return Err(invalid("missing RD11 header"));
+            }
+            (r.u32(40)? as usize, r.u32(44)? as usize, true)
+        } else {
+            (32, 24, false)
+        };
+
+        if binding_stride < 32 || variable_stride < 24 {
+            return Err(invalid("structure sizes are too small"));
+        }
+
+        let types = TypeReader {
+            reader: r,
+            has_name: type_has_name,
+            remaining: Cell::new(MAX_TYPES),
+        };
+
+        let constant_buffers = (0..cbuffer_count)
+            .map(|i| {
+                let base = cbuffer_offset + i * 24;
+                let variable_count = r.u32(base + 4)? as usize;
+                let variable_offset = r.u32(base + 8)? as usize;
+
+                let variables = (0..variable_count)
+                    .map(|v| {
+                        let base = variable_offset + v * variable_stride;
+                        Ok(Variable {
+                            name: r.string(r.u32(base)? as usize)?,
+                            offset: r.u32(base + 4)?,
+                            size: r.u32(base + 8)?,
+                            flags: r.u32(base + 12)?,
+                            variable_type: types.read(r.u32(base + 16)? as usize, 0)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+
+                Ok(ConstantBuffer {
+                    name: r.string(r.u32(base)? as usize)?,
+                    variables,
+                    size: r.u32(base + 12)?,
+                    flags: r.u32(base + 16)?,
+                    buffer_type: match r.u32(base + 20)? {
+                        0 => ConstantBufferType::CBuffer,
+                        1 => ConstantBufferType::TBuffer,
+                        2 => ConstantBufferType::InterfacePointers,
+                        3 => ConstantBufferType::ResourceBindInfo,
+                        other => ConstantBufferType::Other(other),
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let bound_resources = (0..binding_count)
+            .map(|i| {
+                let base = binding_offset + i * binding_stride;
+                Ok(BoundResource {
+                    name: r.string(r.u32(base)? as usize)?,
+                    input_type: ShaderInputType::from_raw(r.u32(base + 4)?),
+                    return_type: r.u32(base + 8)?,
+                    dimension: r.u32(base + 12)?,
+                    num_samples: r.u32(base + 16)?,
+                    bind_point: r.u32(base + 20)?,
+                    bind_count: r.u32(base + 24)?,
+                    flags: r.u32(base + 28)?,
+                    // SM5.1 added register spaces
+                    space: if binding_stride >= 36 {
+                        r.u32(base + 32)?
+                    } else {
+                        0
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(ResourceDefinitions {
+            kind: match program_type {
+                0xffff => ShaderKind::Pixel,
+                0xfffe => ShaderKind::Vertex,
+                0x4753 => ShaderKind::Geometry,
+                0x4853 => ShaderKind::Hull,
+                0x4453 => ShaderKind::Domain,
+                0x4353 => ShaderKind::Compute,
+                other => ShaderKind::Other(other as u32),
+            },
+            shader_model: ShaderModel { major, minor },
+            flags,
+            creator,
+            constant_buffers,
+            bound_resources,
+        })
+    }
+}
+
+struct TypeReader<'a> {
+    reader: Reader<'a>,
+    has_name: bool,
+    /// How many more types can be read from the chunk.
+    remaining: Cell<u32>,
+}
+
+// Nesting deeper than this can only come from a malformed (or cyclic) chunk.
+const MAX_TYPE_DEPTH: u32 = 32;
+
+// Members can share a type, so a small chunk can describe a tree of types
+// that is exponentially large. No real shader comes close to this many.
+const MAX_TYPES: u32 = 1 << 16;
+
+impl TypeReader<'_> {
+    fn read(&self, offset: usize, depth: u32) -> Result<VariableType> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err(invalid("type nesting is too deep"));
+        }
+        match self.remaining.get().checked_sub(1) {
+            Some(remaining) => self.remaining.set(remaining),
+            None => return Err(invalid("too many types")),
+        }
+
+        let r = &self.reader;
+        let member_count = r.u16(offset + 10)? as usize;
+        let member_offset = r.u32(offset + 12)? as usize;
+
+        let members = (0..member_count)
+            .map(|i| {
+                let base = member_offset + i * 12;
+                Ok(StructMember {
+                    name: r.string(r.u32(base)? as usize)?,
+                    member_type: self.read(r.u32(base + 4)? as usize, depth + 1)?,
+                    offset: r.u32(base + 8)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let name = if self.has_name {
+            match r.u32(offset + 32)? {
+                0 => None,
+                name_offset => Some(r.string(name_offset as usize)?),
+            }
+        } else {
+            None
+        };
+
+        Ok(VariableType {
+            name,
+            class: r.u16(offset)?,
+            base_type: r.u16(offset + 2)?,
+            rows: r.u16(offset + 4)?,
+            columns: r.u16(offset + 6)?,
+            elements: r.u16(offset + 8)?,
+            members,
+        })
+    }
+}
@@ -0,0 +1,257 @@
+use super::{Error, FourCC, Reader, RegisterClass, Result};
+
+/// A serialized root signature, as embedded in an RTS0 part by
+/// `[RootSignature(...)]` or produced by `D3D12SerializeRootSignature`.
+#[derive(Debug, Clone)]
+pub struct RootSignature {
+    pub version: RootSignatureVersion,
+    /// D3D12_ROOT_SIGNATURE_FLAGS
+    pub flags: u32,
+    pub parameters: Vec<RootParameter>,
+    pub static_samplers: Vec<StaticSampler>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RootSignatureVersion {
+    V1_0,
+    V1_1,
+    V1_2,
+}
+
+/// D3D12_SHADER_VISIBILITY
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderVisibility {
+    All,
+    Vertex,
+    Hull,
+    Domain,
+    Geometry,
+    Pixel,
+    Amplification,
+    Mesh,
+    Other(u32),
+}
+
+impl ShaderVisibility {
+    pub fn from_raw(value: u32) -> Self {
+        match value {
+            0 => ShaderVisibility::All,
+            1 => ShaderVisibility::Vertex,
+            2 => ShaderVisibility::Hull,
+            3 => ShaderVisibility::Domain,
+            4 => ShaderVisibility::Geometry,
+            5 => ShaderVisibility::Pixel,
+            6 => ShaderVisibility::Amplification,
+            7 => ShaderVisibility::Mesh,
+            other => ShaderVisibility::Other(other),
+        }
+    }
+
+    pub fn to_raw(self) -> u32 {
+        match self {
+            ShaderVisibility::All => 0,
+            ShaderVisibility::Vertex => 1,
+            ShaderVisibility::Hull => 2,
+            ShaderVisibility::Domain => 3,
+            ShaderVisibility::Geometry => 4,
+            ShaderVisibility::Pixel => 5,
+            ShaderVisibility::Amplification => 6,
+            ShaderVisibility::Mesh => 7,
+            ShaderVisibility::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RootParameter {
+    pub visibility: ShaderVisibility,
+    pub kind: RootParameterKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum RootParameterKind {
+    DescriptorTable(Vec<DescriptorRange>),
+    Constants {
+        register: u32,
+        space: u32,
+        num_32bit_values: u32,
+    },
+    /// A root CBV, SRV or UAV.
+    Descriptor {
+        class: RegisterClass,
+        register: u32,
+        space: u32,
+        /// D3D12_ROOT_DESCRIPTOR_FLAGS; always 0 before version 1.1.
+        flags: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DescriptorRange {
+    pub class: RegisterClass,
+    /// `u32::MAX` for unbounded ranges.
+    pub num_descriptors: u32,
+    pub base_register: u32,
+    pub space: u32,
+    /// D3D12_DESCRIPTOR_RANGE_FLAGS; always 0 before version 1.1.
+    pub flags: u32,
+    pub offset_in_descriptors_from_table_start: u32,
+}
+
+/// The binding part of a static sampler. The sampler state itself is left
+/// as raw D3D12_STATIC_SAMPLER_DESC values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaticSampler {
+    pub filter: u32,
+    pub address: [u32; 3],
+    pub mip_lod_bias: f32,
+    pub max_anisotropy: u32,
+    pub comparison_func: u32,
+    pub border_color: u32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub register: u32,
+    pub space: u32,
+    pub visibility: ShaderVisibility,
+    /// D3D12_SAMPLER_FLAGS; only recorded by version 1.2.
+    pub flags: u32,
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::InvalidChunk {
+        fourcc: FourCC::RTS0,
+        reason,
+    }
+}
+
+const PARAMETER_TYPE_DESCRIPTOR_TABLE: u32 = 0;
+const PARAMETER_TYPE_32BIT_CONSTANTS: u32 = 1;
+const PARAMETER_TYPE_CBV: u32 = 2;
+const PARAMETER_TYPE_SRV: u32 = 3;
+const PARAMETER_TYPE_UAV: u32 = 4;
+
+impl RootSignature {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let r = Reader::new(data);
+
+        let version = match r.u32(0)? {
+            1 => RootSignatureVersion::V1_0,
+            2 => RootSignatureVersion::V1_1,
+            3 => RootSignatureVersion::V1_2,
+            _ => return Err(invalid("unknown root signature version")),
+        };
+        let has_flags = version >= RootSignatureVersion::V1_1;
+
+        let parameter_count = r.u32(4)? as usize;
+        let parameter_offset = r.u32(8)? as usize;
+        let sampler_count = r.u32(12)? as usize;
+        let sampler_offset = r.u32(16)? as usize;
+        let flags = r.u32(20)?;
+
+        let parameters = (0..parameter_count)
+            .map(|i| {
+                let base = parameter_offset + i * 12;
+                let parameter_type = r.u32(base)?;
+                let visibility = ShaderVisibility::from_raw(r.u32(base + 4)?);
+                let payload = r.u32(base + 8)? as usize;
+
+                let kind = match parameter_type {
+                    PARAMETER_TYPE_DESCRIPTOR_TABLE => {
+                        let range_count = r.u32(payload)? as usize;
+                        let range_offset = r.u32(payload + 4)? as usize;
+                        let stride = if has_flags { 24 } else { 20 };
+
+                        let ranges = (0..range_count)
+                            .map(|j| {
+                                let base = range_offset + j * stride;
+                                let (flags, table_offset) = if has_flags {
+                                    (r.u32(base + 16)?, r.u32(base + 20)?)
+                                } else {
+                                    (0, r.u32(base + 16)?)
+                                };
+                                Ok(DescriptorRange {
+                                    class: range_class(r.u32(base)?)?,
+                                    num_descriptors: r.u32(base + 4)?,
+                                    base_register: r.u32(base + 8)?,
+                                    space: r.u32(base + 12)?,
+                                    flags,
+                                    offset_in_descriptors_from_table_start: table_offset,
+                                })
+                            })
+                            .collect::<Result<_>>()?;
+                        RootParameterKind::DescriptorTable(ranges)
+                    }
+                    PARAMETER_TYPE_32BIT_CONSTANTS => RootParameterKind::Constants {
+                        register: r.u32(payload)?,
+                        space: r.u32(payload + 4)?,
+                        num_32bit_values: r.u32(payload + 8)?,
+                    },
+                    PARAMETER_TYPE_CBV | PARAMETER_TYPE_SRV | PARAMETER_TYPE_UAV => {
+                        RootParameterKind::Descriptor {
+                            class: match parameter_type {
+                                PARAMETER_TYPE_CBV => RegisterClass::Cbv,
+                                PARAMETER_TYPE_SRV => RegisterClass::Srv,
+                                _ => RegisterClass::Uav,
+                            },
+                            register: r.u32(payload)?,
+                            space: r.u32(payload + 4)?,
+                            flags: if has_flags { r.u32(payload + 8)? } else { 0 },
+                        }
+                    }
+                    _ => return Err(invalid("unknown root parameter type")),
+                };
+
+                Ok(RootParameter { visibility, kind })
+            })
+            .collect::<Result<_>>()?;
+
+        let sampler_stride = if version >= RootSignatureVersion::V1_2 {
+            56
+        } else {
+            52
+        };
+        let f32_at = |offset| r.u32(offset).map(f32::from_bits);
+
+        let static_samplers = (0..sampler_count)
+            .map(|i| {
+                let base = sampler_offset + i * sampler_stride;
+                Ok(StaticSampler {
+                    filter: r.u32(base)?,
+                    address: [r.u32(base + 4)?, r.u32(base + 8)?, r.u32(base + 12)?],
+                    mip_lod_bias: f32_at(base + 16)?,
+                    max_anisotropy: r.u32(base + 20)?,
+                    comparison_func: r.u32(base + 24)?,
+                    border_color: r.u32(base + 28)?,
+                    min_lod: f32_at(base + 32)?,
+                    max_lod: f32_at(base + 36)?,
+                    register: r.u32(base + 40)?,
+                    space: r.u32(base + 44)?,
+                    visibility: ShaderVisibility::from_raw(r.u32(base + 48)?),
+                    flags: if sampler_stride > 52 {
+                        r.u32(base + 52)?
+                    } else {
+                        0
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(RootSignature {
+            version,
+            flags,
+            parameters,
+            static_samplers,
+        })
+    }
+}
+
+/// Maps D3D12_DESCRIPTOR_RANGE_TYPE.
+fn range_class(value: u32) -> Result<RegisterClass> {
+    match value {
+        0 => Ok(RegisterClass::Srv),
+        1 => Ok(RegisterClass::Uav),
+        2 => Ok(RegisterClass::Cbv),
+        3 => Ok(RegisterClass::Sampler),
+        _ => Err(invalid("unknown descriptor range type")),
+    }
+}
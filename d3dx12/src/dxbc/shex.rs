@@ -0,0 +1,79 @@
+use super::{Error, FourCC, Reader, Result, ShaderKind, ShaderModel};
+
+/// Metadata about the tokenized program in a SHDR/SHEX chunk.
+#[derive(Debug, Clone)]
+pub struct ShaderProgram {
+    pub kind: ShaderKind,
+    pub shader_model: ShaderModel,
+    /// Length of the program, in 32-bit tokens.
+    pub token_count: u32,
+    pub instruction_count: u32,
+    /// The flags from `dcl_globalFlags`, if present.
+    pub global_flags: Option<u32>,
+    /// The size from `dcl_thread_group`, for compute shaders.
+    pub thread_group_size: Option<[u32; 3]>,
+}
+
+const OPCODE_CUSTOMDATA: u32 = 53;
+const OPCODE_DCL_GLOBAL_FLAGS: u32 = 106;
+const OPCODE_DCL_THREAD_GROUP: u32 = 155;
+
+impl ShaderProgram {
+    pub fn parse(fourcc: FourCC, data: &[u8]) -> Result<Self> {
+        let r = Reader::new(data);
+        let invalid = |reason| Error::InvalidChunk { fourcc, reason };
+
+        let version = r.u32(0)?;
+        let token_count = r.u32(4)?;
+        if token_count < 2 || token_count as usize * 4 > data.len() {
+            return Err(invalid("program length does not match chunk size"));
+        }
+
+        let mut program = ShaderProgram {
+            kind: ShaderKind::from_program_type(version >> 16),
+            shader_model: ShaderModel {
+                major: ((version >> 4) & 0xf) as u8,
+                minor: (version & 0xf) as u8,
+            },
+            token_count,
+            instruction_count: 0,
+            global_flags: None,
+            thread_group_size: None,
+        };
+
+        let mut token = 2;
+        while token < token_count as usize {
+            let opcode_token = r.u32(token * 4)?;
+            let opcode = opcode_token & 0x7ff;
+
+            let length = if opcode == OPCODE_CUSTOMDATA {
+                r.u32(token * 4 + 4)? as usize
+            } else {
+                ((opcode_token >> 24) & 0x7f) as usize
+            };
+
+            if length == 0 || token + length > token_count as usize {
+                return Err(invalid("malformed instruction"));
+            }
+
+            match opcode {
+                OPCODE_DCL_GLOBAL_FLAGS => {
+                    program.global_flags = Some((opcode_token >> 11) & 0x1fff);
+                }
+                OPCODE_DCL_THREAD_GROUP => {
+                    program.thread_group_size = Some([
+                        r.u32(token * 4 + 4)?,
+                        r.u32(token * 4 + 8)?,
+                        r.u32(token * 4 + 12)?,
+                    ]);
+                }
+                _ => (),
+            }
+
+            program.instruction_count += 1;
+            token += length;
+        }
+
+        Ok(program)
+    }
+}
@@ -0,0 +1,148 @@
+use super::{Error, FourCC, Reader, Result};
+
+/// An input, output or patch constant signature.
+#[derive(Debug, Clone, Default)]
+pub struct Signature {
+    pub elements: Vec<SignatureElement>,
+}
+
+/// D3D_REGISTER_COMPONENT_TYPE, extended with the 16 and 64-bit types that
+/// DXIL signatures can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentType {
+    Unknown,
+    UInt32,
+    SInt32,
+    Float32,
+    UInt16,
+    SInt16,
+    Float16,
+    UInt64,
+    SInt64,
+    Float64,
+    Other(u32),
+}
+
+impl ComponentType {
+    pub fn from_raw(value: u32) -> Self {
+        match value {
+            0 => ComponentType::Unknown,
+            1 => ComponentType::UInt32,
+            2 => ComponentType::SInt32,
+            3 => ComponentType::Float32,
+            4 => ComponentType::UInt16,
+            5 => ComponentType::SInt16,
+            6 => ComponentType::Float16,
+            7 => ComponentType::UInt64,
+            8 => ComponentType::SInt64,
+            9 => ComponentType::Float64,
+            other => ComponentType::Other(other),
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            ComponentType::Float16 | ComponentType::Float32 | ComponentType::Float64
+        )
+    }
+
+    pub fn is_unsigned_int(&self) -> bool {
+        matches!(
+            self,
+            ComponentType::UInt16 | ComponentType::UInt32 | ComponentType::UInt64
+        )
+    }
+
+    pub fn is_signed_int(&self) -> bool {
+        matches!(
+            self,
+            ComponentType::SInt16 | ComponentType::SInt32 | ComponentType::SInt64
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SignatureElement {
+    pub stream: u32,
+    pub semantic_name: String,
+    pub semantic_index: u32,
+    /// D3D_NAME; 0 for anything that is not a system value.
+    pub system_value: u32,
+    pub component_type: ComponentType,
+    pub register: u32,
+    pub mask: u8,
+    pub read_write_mask: u8,
+    /// D3D_MIN_PRECISION; only recorded by the `*1` chunk variants.
+    pub min_precision: u32,
+}
+
+impl SignatureElement {
+    /// Number of components declared in the mask (e.g. 3 for `xyz`).
+    pub fn component_count(&self) -> u32 {
+        (self.mask & 0xf).count_ones()
+    }
+
+    pub fn is_system_value(&self) -> bool {
+        self.system_value != 0
+    }
+}
+
+impl Signature {
+    /// Parses a signature chunk. The layout of each element depends on which
+    /// flavour of chunk it came from.
+    pub fn parse(fourcc: FourCC, data: &[u8]) -> Result<Self> {
+        let (stride, has_stream, has_min_precision) = match fourcc {
+            FourCC::ISGN | FourCC::OSGN | FourCC::PCSG => (24, false, false),
+            FourCC::OSG5 => (28, true, false),
+            FourCC::ISG1 | FourCC::OSG1 | FourCC::PSG1 => (32, true, true),
+            _ => {
+                return Err(Error::InvalidChunk {
+                    fourcc,
+                    reason: "not a signature chunk",
+                })
+            }
+        };
+
+        let r = Reader::new(data);
+        let count = r.u32(0)? as usize;
+
+        let elements = (0..count)
+            .map(|i| {
+                let element = 8 + i * stride;
+                let (stream, base) = if has_stream {
+                    (r.u32(element)?, element + 4)
+                } else {
+                    (0, element)
+                };
+
+                Ok(SignatureElement {
+                    stream,
+                    semantic_name: r.string(r.u32(base)? as usize)?,
+                    semantic_index: r.u32(base + 4)?,
+                    system_value: r.u32(base + 8)?,
+                    component_type: ComponentType::from_raw(r.u32(base + 12)?),
+                    register: r.u32(base + 16)?,
+                    mask: r.u8(base + 20)?,
+                    read_write_mask: r.u8(base + 21)?,
+                    min_precision: if has_min_precision {
+                        r.u32(base + 24)?
+                    } else {
+                        0
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Signature { elements })
+    }
+
+    /// Finds an element by semantic, ignoring the case of the name as the
+    /// compiler does.
+    pub fn find(&self, semantic_name: &str, semantic_index: u32) -> Option<&SignatureElement> {
+        self.elements.iter().find(|e| {
+            e.semantic_index == semantic_index
+                && e.semantic_name.eq_ignore_ascii_case(semantic_name)
+        })
+    }
+}
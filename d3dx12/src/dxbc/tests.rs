@@ -0,0 +1,508 @@
+use super::*;
+
+const FXC_VS: &[u8] = include_bytes!("../../tests/fixtures/dxbc/fxc_vs_5_0.cso");
+const FXC_PS: &[u8] = include_bytes!("../../tests/fixtures/dxbc/fxc_ps_5_0.cso");
+const DXIL_CS: &[u8] = include_bytes!("../../tests/fixtures/dxbc/dxil_compute.cso");
+const DXIL_VS_UNSIGNED: &[u8] =
+    include_bytes!("../../tests/fixtures/dxbc/dxil_vertex_unsigned.cso");
+
+fn semantics(signature: &Signature) -> Vec<(&str, u32, u32, u8)> {
+    signature
+        .elements
+        .iter()
+        .map(|e| {
+            (
+                e.semantic_name.as_str(),
+                e.semantic_index,
+                e.register,
+                e.mask,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn checksum_matches_fxc_and_signed_dxil() {
+    for data in [FXC_VS, FXC_PS, DXIL_CS] {
+        assert_eq!(checksum(data)[..], data[4..20]);
+        assert!(!Container::parse(data).unwrap().is_unsigned());
+    }
+}
+
+#[test]
+fn checksum_mismatch_is_reported() {
+    let mut data = FXC_PS.to_vec();
+    let last = data.len() - 1;
+    data[last] ^= 1;
+
+    let mut stored = [0; 16];
+    stored.copy_from_slice(&data[4..20]);
+    assert_eq!(
+        Container::parse(&data).err(),
+        Some(Error::ChecksumMismatch {
+            stored,
+            computed: checksum(&data),
+        })
+    );
+    assert!(Container::parse_unverified(&data).is_ok());
+}
+
+#[test]
+fn unsigned_containers_skip_verification() {
+    let container = Container::parse(DXIL_VS_UNSIGNED).unwrap();
+    assert!(container.is_unsigned());
+    assert!(container.verify_checksum().is_err());
+}
+
+#[test]
+fn header_errors() {
+    assert_eq!(
+        Container::parse(&[]).err(),
+        Some(Error::UnexpectedEof {
+            offset: 0,
+            wanted: 4
+        })
+    );
+
+    let mut data = FXC_VS.to_vec();
+    data[0] = b'X';
+    assert_eq!(
+        Container::parse(&data).err(),
+        Some(Error::BadMagic(*b"XXBC"))
+    );
+
+    let mut data = FXC_VS.to_vec();
+    data[20] = 2;
+    assert_eq!(
+        Container::parse(&data).err(),
+        Some(Error::UnsupportedVersion(2))
+    );
+
+    assert_eq!(
+        Container::parse(&FXC_VS[..FXC_VS.len() - 1]).err(),
+        Some(Error::SizeMismatch {
+            header: FXC_VS.len() as u32,
+            actual: FXC_VS.len() - 1,
+        })
+    );
+
+    // Point the first chunk past the end.
+    let mut data = FXC_VS.to_vec();
+    data[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(
+        Container::parse_unverified(&data).err(),
+        Some(Error::ChunkOutOfBounds {
+            index: 0,
+            offset: u32::MAX
+        })
+    );
+}
+
+#[test]
+fn fxc_vertex_shader_signatures() {
+    let container = Container::parse(FXC_VS).unwrap();
+    assert!(!container.is_dxil());
+    let fourccs: Vec<_> = container.chunks.iter().map(|c| c.fourcc).collect();
+    assert_eq!(
+        fourccs,
+        [
+            FourCC::RDEF,
+            FourCC::ISGN,
+            FourCC::OSGN,
+            FourCC::SHEX,
+            FourCC::STAT
+        ]
+    );
+
+    let reflection = container.reflect().unwrap();
+    assert_eq!(reflection.kind, ShaderKind::Vertex);
+    assert_eq!(reflection.shader_model, ShaderModel { major: 5, minor: 0 });
+    assert_eq!(
+        semantics(&reflection.input_signature),
+        [
+            ("POSITION", 0, 0, 0x3),
+            ("TEXCOORD", 0, 1, 0x3),
+            ("COLOR", 0, 2, 0xf)
+        ]
+    );
+    assert_eq!(
+        semantics(&reflection.output_signature),
+        [
+            ("SV_POSITION", 0, 0, 0xf),
+            ("TEXCOORD", 0, 1, 0x3),
+            ("COLOR", 0, 2, 0xf)
+        ]
+    );
+
+    let outputs = &reflection.output_signature.elements;
+    assert!(outputs[0].is_system_value());
+    assert!(!outputs[1].is_system_value());
+    assert!(outputs
+        .iter()
+        .all(|e| e.component_type == ComponentType::Float32));
+    assert!(reflection.bound_resources.is_empty());
+}
+
+#[test]
+fn fxc_pixel_shader_resource_definitions() {
+    let container = Container::parse(FXC_PS).unwrap();
+    let rdef = container.resource_definitions().unwrap().unwrap();
+    assert_eq!(rdef.kind, ShaderKind::Pixel);
+    assert_eq!(rdef.shader_model, ShaderModel { major: 5, minor: 0 });
+    assert_eq!(rdef.creator, "Microsoft (R) HLSL Shader Compiler 10.1");
+    assert!(rdef.constant_buffers.is_empty());
+
+    let bindings: Vec<_> = rdef
+        .bound_resources
+        .iter()
+        .map(|r| {
+            (
+                r.name.as_str(),
+                r.input_type.register_class(),
+                r.bind_point,
+                r.bind_count,
+                r.space,
+            )
+        })
+        .collect();
+    assert_eq!(
+        bindings,
+        [
+            ("g_sampler", Some(RegisterClass::Sampler), 0, 1, 0),
+            ("g_texture", Some(RegisterClass::Srv), 0, 1, 0)
+        ]
+    );
+    // D3D_SRV_DIMENSION_TEXTURE2D, D3D_RETURN_TYPE_FLOAT
+    let texture = &rdef.bound_resources[1];
+    assert_eq!((texture.dimension, texture.return_type), (4, 5));
+
+    assert_eq!(
+        semantics(&container.output_signature().unwrap().unwrap()),
+        [("SV_TARGET", 0, 0, 0xf)]
+    );
+}
+
+#[test]
+fn dxil_compute_shader_validation_info() {
+    let container = Container::parse(DXIL_CS).unwrap();
+    assert!(container.is_dxil());
+
+    let program = container.dxil_program().unwrap().unwrap();
+    assert_eq!(program.kind, ShaderKind::Compute);
+    assert_eq!(program.shader_model, ShaderModel { major: 6, minor: 0 });
+    assert_eq!(program.dxil_version, ShaderModel { major: 1, minor: 0 });
+
+    let psv = container.pipeline_state_validation().unwrap().unwrap();
+    assert_eq!(psv.version, 2);
+    assert_eq!(psv.kind, Some(ShaderKind::Compute));
+    assert_eq!(psv.thread_group_size, Some([8, 8, 1]));
+    assert_eq!(
+        psv.resources,
+        [
+            PsvResource {
+                input_type: ShaderInputType::CBuffer,
+                space: 0,
+                lower_bound: 0,
+                upper_bound: 0
+            },
+            PsvResource {
+                input_type: ShaderInputType::Texture,
+                space: 0,
+                lower_bound: 1,
+                upper_bound: 1
+            },
+            PsvResource {
+                input_type: ShaderInputType::UavRwByteAddress,
+                space: 0,
+                lower_bound: 2,
+                upper_bound: 2
+            },
+        ]
+    );
+
+    let reflection = container.reflect().unwrap();
+    assert_eq!(reflection.thread_group_size, Some([8, 8, 1]));
+    let classes: Vec<_> = reflection
+        .bound_resources
+        .iter()
+        .map(|r| (r.input_type.register_class(), r.bind_point, r.bind_count))
+        .collect();
+    assert_eq!(
+        classes,
+        [
+            (Some(RegisterClass::Cbv), 0, 1),
+            (Some(RegisterClass::Srv), 1, 1),
+            (Some(RegisterClass::Uav), 2, 1)
+        ]
+    );
+}
+
+#[test]
+fn dxil_vertex_shader_signatures() {
+    let reflection = Container::parse(DXIL_VS_UNSIGNED)
+        .unwrap()
+        .reflect()
+        .unwrap();
+    assert_eq!(reflection.kind, ShaderKind::Vertex);
+    assert_eq!(
+        semantics(&reflection.input_signature),
+        [
+            ("TEXCOORD", 0, 0, 0x7),
+            ("TEXCOORD", 1, 1, 0x3),
+            ("TEXCOORD", 2, 2, 0xf)
+        ]
+    );
+    let position = reflection
+        .output_signature
+        .elements
+        .iter()
+        .find(|e| e.semantic_name == "SV_Position")
+        .unwrap();
+    assert_eq!((position.system_value, position.mask), (1, 0xf));
+    assert_eq!(reflection.thread_group_size, None);
+}
+
+/// Little-endian u32s, as chunks are laid out.
+fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[test]
+fn root_signature_1_1() {
+    let data = words(&[
+        2,   // version 1.1
+        3,   // parameters
+        24,  // parameter offset
+        1,   // static samplers
+        116, // static sampler offset
+        1,   // ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT
+        // The parameters: a table, constants and a root CBV.
+        0,
+        5,
+        60, //
+        1,
+        0,
+        68, //
+        2,
+        1,
+        80, //
+        // The table at 60 has one range, at 92.
+        1,
+        92, //
+        // The constants at 68 are 4 values in b0.
+        0,
+        0,
+        4, //
+        // The root CBV at 80 is b1, with DATA_STATIC.
+        1,
+        0,
+        8, //
+        // The range at 92 is unbounded SRVs from t2 in space 1.
+        0,
+        u32::MAX,
+        2,
+        1,
+        0,
+        0, //
+        // The static sampler at 116 is s3, for pixel shaders.
+        0x15,
+        1,
+        1,
+        1,
+        0,
+        16,
+        4,
+        0,
+        0,
+        0x7f7f_ffff,
+        3,
+        0,
+        5,
+    ]);
+    let signature = RootSignature::parse(&data).unwrap();
+    assert_eq!(signature.version, RootSignatureVersion::V1_1);
+    assert_eq!(signature.flags, 1);
+    assert_eq!(signature.parameters.len(), 3);
+
+    assert_eq!(signature.parameters[0].visibility, ShaderVisibility::Pixel);
+    match &signature.parameters[0].kind {
+        RootParameterKind::DescriptorTable(ranges) => assert_eq!(
+            ranges[..],
+            [DescriptorRange {
+                class: RegisterClass::Srv,
+                num_descriptors: u32::MAX,
+                base_register: 2,
+                space: 1,
+                flags: 0,
+                offset_in_descriptors_from_table_start: 0,
+            }]
+        ),
+        other => panic!("expected a descriptor table, got {:?}", other),
+    }
+    assert!(matches!(
+        signature.parameters[1].kind,
+        RootParameterKind::Constants {
+            register: 0,
+            space: 0,
+            num_32bit_values: 4
+        }
+    ));
+    assert!(matches!(
+        signature.parameters[2].kind,
+        RootParameterKind::Descriptor {
+            class: RegisterClass::Cbv,
+            register: 1,
+            space: 0,
+            flags: 8
+        }
+    ));
+    assert_eq!(signature.parameters[2].visibility, ShaderVisibility::Vertex);
+
+    let sampler = &signature.static_samplers[0];
+    assert_eq!((sampler.filter, sampler.address), (0x15, [1, 1, 1]));
+    assert_eq!((sampler.max_anisotropy, sampler.max_lod), (16, f32::MAX));
+    assert_eq!((sampler.register, sampler.space), (3, 0));
+    assert_eq!(sampler.visibility, ShaderVisibility::Pixel);
+}
+
+#[test]
+fn root_signature_errors() {
+    assert_eq!(
+        RootSignature::parse(&words(&[4, 0, 24, 0, 24, 0])).err(),
+        Some(Error::InvalidChunk {
+            fourcc: FourCC::RTS0,
+            reason: "unknown root signature version"
+        })
+    );
+    // A parameter past the end of the part.
+    assert_eq!(
+        RootSignature::parse(&words(&[1, 1, 24, 0, 24, 0])).err(),
+        Some(Error::UnexpectedEof {
+            offset: 24,
+            wanted: 4
+        })
+    );
+}
+
+/// An SM4 RDEF part with the given types and members. Offsets in `types`
+/// and `members` are relative to the start of the part.
+fn rdef(types: &[u32], members: &[u32]) -> Vec<u8> {
+    const TYPES: u32 = 76;
+    let members_offset = TYPES + types.len() as u32 * 4;
+    let strings = members_offset + members.len() as u32 * 4;
+    let mut data = words(&[
+        1,           // constant buffers
+        28,          // constant buffer offset
+        0,           // bindings
+        0,           // binding offset
+        0xfffe_0400, // vs_4_0
+        0,           // flags
+        strings,     // creator
+        // The constant buffer at 28.
+        strings,
+        1,
+        52,
+        16,
+        0,
+        0, //
+        // Its variable at 52, of the first type.
+        strings,
+        0,
+        16,
+        2,
+        TYPES,
+        0,
+    ]);
+    data.extend(words(types));
+    data.extend(words(members));
+    data.extend(b"Constants\0");
+    data
+}
+
+#[test]
+fn resource_definitions_structs() {
+    // A struct with a float3 at 0 and a uint at 12, with the members at 124
+    // and their types at 92 and 108.
+    let types = [
+        5,
+        1 | 4 << 16,
+        2 << 16,
+        124, // struct
+        1 | 3 << 16,
+        1 | 3 << 16,
+        0,
+        0, // float3
+        19 << 16,
+        1 | 1 << 16,
+        0,
+        0, // uint
+    ];
+    let strings = 76 + 4 * 12 + 4 * 6;
+    let members = [strings, 92, 0, strings, 108, 12];
+    let rdef = ResourceDefinitions::parse(&rdef(&types, &members)).unwrap();
+
+    assert_eq!(rdef.kind, ShaderKind::Vertex);
+    assert_eq!(rdef.shader_model, ShaderModel { major: 4, minor: 0 });
+    let buffer = &rdef.constant_buffers[0];
+    assert_eq!((buffer.name.as_str(), buffer.size), ("Constants", 16));
+    assert_eq!(buffer.buffer_type, ConstantBufferType::CBuffer);
+
+    let variable = &buffer.variables[0];
+    assert!(variable.is_used());
+    let struct_type = &variable.variable_type;
+    assert_eq!((struct_type.class, struct_type.columns), (5, 4));
+    assert_eq!(struct_type.name, None);
+    let members: Vec<_> = struct_type
+        .members
+        .iter()
+        .map(|m| (m.offset, m.member_type.base_type, m.member_type.columns))
+        .collect();
+    assert_eq!(members, [(0, 3, 3), (12, 19, 1)]);
+}
+
+#[test]
+fn resource_definitions_reject_cyclic_types() {
+    // A struct whose only member is the struct itself.
+    let types = [5, 0, 1 << 16, 92];
+    let members = [76 + 16 + 12, 76, 0];
+    assert_eq!(
+        ResourceDefinitions::parse(&rdef(&types, &members)).err(),
+        Some(Error::InvalidChunk {
+            fourcc: FourCC::RDEF,
+            reason: "type nesting is too deep"
+        })
+    );
+}
+
+#[test]
+fn resource_definitions_limit_shared_types() {
+    // A chain of structs that each have 16 members of the next, which makes
+    // a tree of 16^16 types out of a few hundred bytes.
+    const LEVELS: u32 = 16;
+    const FAN_OUT: u32 = 16;
+    let types_offset = 76;
+    let members_offset = types_offset + LEVELS * 16;
+    let strings = members_offset + LEVELS * FAN_OUT * 12;
+
+    let mut types = Vec::new();
+    let mut members = Vec::new();
+    for level in 0..LEVELS {
+        let member_count = if level + 1 < LEVELS { FAN_OUT } else { 0 };
+        types.extend([
+            5,
+            0,
+            member_count << 16,
+            members_offset + level * FAN_OUT * 12,
+        ]);
+        for _ in 0..FAN_OUT {
+            members.extend([strings, types_offset + (level + 1) * 16, 0]);
+        }
+    }
+    assert_eq!(
+        ResourceDefinitions::parse(&rdef(&types, &members)).err(),
+        Some(Error::InvalidChunk {
+            fourcc: FourCC::RDEF,
+            reason: "too many types"
+        })
+    );
+}
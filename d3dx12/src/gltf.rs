@@ -0,0 +1,1254 @@
+//! Imports glTF 2.0 files, both `.gltf` with its buffers in separate files or
+//! inline, and binary `.glb`, into a `Scene`.
+//!
+//! Every mesh that the default scene's nodes reach is added once per node,
+//! with the node's world transform applied to its vertices, so the scene
+//! needs no transforms of its own. Each triangle primitive becomes a draw:
+//! strips and fans are turned into lists, and points and lines are skipped.
+//! Normals and tangents are generated where a primitive has none. Only
+//! `TEXCOORD_0` is used, and the handedness in the `w` of a tangent is
+//! dropped because the standard vertex layout has no room for it.
+//!
+//! glTF's front faces are counter-clockwise, so the winding is reversed to
+//! the clockwise one that the samples' pipelines cull with.
+
+mod json;
+
+use crate::format::*;
+use crate::scene::{
+    standard_vertex_layout, Draw, Material, Scene, SceneError, STANDARD_VERTEX_STRIDE,
+};
+use crate::texture::Texture;
+use json::Value;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::TryInto,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Extensions a file may require that the importer understands.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "KHR_materials_pbrSpecularGlossiness",
+    "KHR_mesh_quantization",
+    "MSFT_texture_dds",
+];
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: usize = 0x4e4f534a;
+const GLB_CHUNK_BIN: usize = 0x004e4942;
+
+const BYTE: usize = 5120;
+const UNSIGNED_BYTE: usize = 5121;
+const SHORT: usize = 5122;
+const UNSIGNED_SHORT: usize = 5123;
+const UNSIGNED_INT: usize = 5125;
+const FLOAT: usize = 5126;
+
+/// An image that a material uses, passed to the `decode_image` callback.
+#[derive(Debug, Clone, Copy)]
+pub struct Image<'a> {
+    /// Index into the document's `images`.
+    pub index: usize,
+    pub name: Option<&'a str>,
+    /// As the file gives it or, failing that, guessed from the extension of
+    /// the image's URI.
+    pub mime_type: Option<&'a str>,
+    /// The encoded file, such as a PNG.
+    pub data: &'a [u8],
+    /// Base color images hold sRGB values; the others are linear.
+    pub srgb: bool,
+}
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io(io::Error),
+    Json {
+        offset: usize,
+        message: &'static str,
+    },
+    Glb(&'static str),
+    UnsupportedVersion(String),
+    UnsupportedExtension(String),
+    /// Properties are named by their path in the document, such as
+    /// `meshes[0].primitives[1].attributes.POSITION`.
+    Missing(String),
+    /// A property has the wrong type or a value out of range.
+    Invalid(String),
+    Unsupported(String),
+    /// An accessor, buffer view or buffer reaches past the end of the data
+    /// it is in.
+    OutOfBounds(String),
+    Image {
+        index: usize,
+        error: io::Error,
+    },
+    Scene(SceneError),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Io(e) => write!(f, "{}", e),
+            GltfError::Json { offset, message } => {
+                write!(f, "invalid JSON: {} at byte {}", message, offset)
+            }
+            GltfError::Glb(message) => write!(f, "invalid GLB: {}", message),
+            GltfError::UnsupportedVersion(version) => {
+                write!(f, "unsupported glTF version {}", version)
+            }
+            GltfError::UnsupportedExtension(name) => {
+                write!(f, "requires unsupported extension {}", name)
+            }
+            GltfError::Missing(path) => write!(f, "{} is missing", path),
+            GltfError::Invalid(path) => write!(f, "{} is invalid", path),
+            GltfError::Unsupported(path) => write!(f, "{} is not supported", path),
+            GltfError::OutOfBounds(path) => write!(f, "{} is out of bounds", path),
+            GltfError::Image { index, error } => write!(f, "images[{}]: {}", index, error),
+            GltfError::Scene(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GltfError::Io(e) | GltfError::Image { error: e, .. } => Some(e),
+            GltfError::Scene(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GltfError {
+    fn from(error: io::Error) -> Self {
+        GltfError::Io(error)
+    }
+}
+
+impl From<json::ParseError> for GltfError {
+    fn from(error: json::ParseError) -> Self {
+        GltfError::Json {
+            offset: error.offset,
+            message: error.message,
+        }
+    }
+}
+
+impl From<SceneError> for GltfError {
+    fn from(error: SceneError) -> Self {
+        GltfError::Scene(error)
+    }
+}
+
+/// Loads a `.gltf` or `.glb` file. `decode_image` turns the images that
+/// materials use into textures; a material leaves out any texture whose
+/// image it returns `None` for.
+pub fn load<P, F>(path: P, decode_image: F) -> Result<Scene, GltfError>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Image) -> io::Result<Option<Texture>>,
+{
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    read(
+        &bytes,
+        path.parent().unwrap_or_else(|| Path::new("")),
+        decode_image,
+    )
+}
+
+/// As `load`, for a file that is already in memory. URIs of other files are
+/// relative to `dir`.
+pub fn read<F>(bytes: &[u8], dir: &Path, decode_image: F) -> Result<Scene, GltfError>
+where
+    F: FnMut(&Image) -> io::Result<Option<Texture>>,
+{
+    let (text, bin) = if bytes.starts_with(GLB_MAGIC) {
+        split_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let text = std::str::from_utf8(text).map_err(|e| GltfError::Json {
+        offset: e.valid_up_to(),
+        message: "invalid UTF-8",
+    })?;
+    let document = json::parse(text.trim_start_matches('\u{feff}'))?;
+
+    Importer::new(Property::root(&document), bin, dir)?.import(decode_image)
+}
+
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfError> {
+    let u32_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    };
+
+    let version = u32_at(4).ok_or(GltfError::Glb("truncated header"))?;
+    if version != 2 {
+        return Err(GltfError::UnsupportedVersion(version.to_string()));
+    }
+    let length = u32_at(8).ok_or(GltfError::Glb("truncated header"))?;
+    if length > bytes.len() {
+        return Err(GltfError::Glb("the file is shorter than its header says"));
+    }
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset < length {
+        let (chunk_length, chunk_type) = u32_at(offset)
+            .zip(u32_at(offset + 4))
+            .ok_or(GltfError::Glb("truncated chunk header"))?;
+        let data = bytes[..length]
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or(GltfError::Glb("truncated chunk"))?;
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(data),
+            GLB_CHUNK_BIN if bin.is_none() && json.is_some() => bin = Some(data),
+            // Other chunks are for extensions, and are to be ignored.
+            _ => (),
+        }
+        offset += 8 + chunk_length;
+    }
+
+    Ok((json.ok_or(GltfError::Glb("no JSON chunk"))?, bin))
+}
+
+/// A place in the document that may or may not exist, along with its path
+/// for error messages.
+#[derive(Debug, Clone)]
+struct Property<'a> {
+    path: String,
+    value: Option<&'a Value>,
+}
+
+impl<'a> Property<'a> {
+    fn root(value: &'a Value) -> Self {
+        Property {
+            path: String::new(),
+            value: Some(value),
+        }
+    }
+
+    fn get(&self, key: &str) -> Property<'a> {
+        Property {
+            path: if self.path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", self.path, key)
+            },
+            value: self.value.and_then(|value| value.get(key)),
+        }
+    }
+
+    fn at(&self, index: usize) -> Property<'a> {
+        Property {
+            path: format!("{}[{}]", self.path, index),
+            value: self
+                .value
+                .and_then(Value::as_array)
+                .and_then(|values| values.get(index)),
+        }
+    }
+
+    fn missing(&self) -> GltfError {
+        GltfError::Missing(self.path.clone())
+    }
+
+    fn invalid(&self) -> GltfError {
+        GltfError::Invalid(self.path.clone())
+    }
+
+    fn exists(&self) -> bool {
+        self.value.is_some()
+    }
+
+    fn required(&self) -> Result<&'a Value, GltfError> {
+        self.value.ok_or_else(|| self.missing())
+    }
+
+    /// The elements of an array that may be left out.
+    fn items(&self) -> Result<Vec<Property<'a>>, GltfError> {
+        match self.value {
+            None => Ok(Vec::new()),
+            Some(value) => {
+                let count = value.as_array().ok_or_else(|| self.invalid())?.len();
+                Ok((0..count).map(|index| self.at(index)).collect())
+            }
+        }
+    }
+
+    fn number(&self) -> Result<Option<f64>, GltfError> {
+        self.value
+            .map(|value| value.as_f64().ok_or_else(|| self.invalid()))
+            .transpose()
+    }
+
+    fn index(&self) -> Result<Option<usize>, GltfError> {
+        match self.number()? {
+            None => Ok(None),
+            Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= u32::MAX as f64 => Ok(Some(n as usize)),
+            Some(_) => Err(self.invalid()),
+        }
+    }
+
+    fn required_index(&self) -> Result<usize, GltfError> {
+        self.index()?.ok_or_else(|| self.missing())
+    }
+
+    fn bool(&self) -> Result<Option<bool>, GltfError> {
+        self.value
+            .map(|value| value.as_bool().ok_or_else(|| self.invalid()))
+            .transpose()
+    }
+
+    fn string(&self) -> Result<Option<&'a str>, GltfError> {
+        self.value
+            .map(|value| value.as_str().ok_or_else(|| self.invalid()))
+            .transpose()
+    }
+
+    fn floats<const N: usize>(&self) -> Result<Option<[f32; N]>, GltfError> {
+        let items = match self.value {
+            None => return Ok(None),
+            Some(value) => value.as_array().ok_or_else(|| self.invalid())?,
+        };
+        if items.len() != N {
+            return Err(self.invalid());
+        }
+        let mut floats = [0.0; N];
+        for (float, item) in floats.iter_mut().zip(items) {
+            *float = item.as_f64().ok_or_else(|| self.invalid())? as f32;
+        }
+        Ok(Some(floats))
+    }
+}
+
+/// A typed view of an accessor's elements.
+struct Accessor<'a> {
+    path: String,
+    /// Starts at the first element. `None` for an accessor with no buffer
+    /// view, whose elements are all zero.
+    data: Option<Cow<'a, [u8]>>,
+    stride: usize,
+    count: usize,
+    component_type: usize,
+    component_size: usize,
+    components: usize,
+    normalized: bool,
+}
+
+impl<'a> Accessor<'a> {
+    fn bytes(&self, element: usize, component: usize) -> Option<&[u8]> {
+        let offset = element * self.stride + component * self.component_size;
+        self.data
+            .as_ref()
+            .map(|data| &data[offset..offset + self.component_size])
+    }
+
+    fn float(&self, element: usize, component: usize) -> f32 {
+        let bytes = match self.bytes(element, component) {
+            Some(bytes) => bytes,
+            None => return 0.0,
+        };
+        let normalized = self.normalized;
+        match self.component_type {
+            BYTE if normalized => (bytes[0] as i8 as f32 / 127.0).max(-1.0),
+            BYTE => bytes[0] as i8 as f32,
+            UNSIGNED_BYTE if normalized => bytes[0] as f32 / 255.0,
+            UNSIGNED_BYTE => bytes[0] as f32,
+            SHORT if normalized => {
+                (i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32767.0).max(-1.0)
+            }
+            SHORT => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            UNSIGNED_SHORT if normalized => {
+                u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0
+            }
+            UNSIGNED_SHORT => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            UNSIGNED_INT => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            _ => f32::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+
+    fn vectors<const N: usize>(&self) -> Result<Vec<[f32; N]>, GltfError> {
+        if self.components != N {
+            return Err(GltfError::Invalid(format!("{}.type", self.path)));
+        }
+        Ok((0..self.count)
+            .map(|element| {
+                let mut vector = [0.0; N];
+                for (component, value) in vector.iter_mut().enumerate() {
+                    *value = self.float(element, component);
+                }
+                vector
+            })
+            .collect())
+    }
+
+    fn indices(&self) -> Result<Vec<u32>, GltfError> {
+        if self.components != 1 || self.normalized {
+            return Err(GltfError::Invalid(self.path.clone()));
+        }
+        (0..self.count)
+            .map(|element| {
+                Ok(match self.bytes(element, 0) {
+                    None => 0,
+                    Some(bytes) => match self.component_type {
+                        UNSIGNED_BYTE => bytes[0] as u32,
+                        UNSIGNED_SHORT => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+                        UNSIGNED_INT => u32::from_le_bytes(bytes.try_into().unwrap()),
+                        _ => {
+                            return Err(GltfError::Invalid(format!("{}.componentType", self.path)))
+                        }
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+/// One triangle list, in the mesh's space.
+struct Primitive {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    tangents: Vec<[f32; 3]>,
+    /// Counter-clockwise, as in the file.
+    indices: Vec<u32>,
+    material: Option<usize>,
+}
+
+struct Importer<'a> {
+    root: Property<'a>,
+    dir: &'a Path,
+    buffers: Vec<Cow<'a, [u8]>>,
+    node_count: usize,
+    meshes: HashMap<usize, Rc<Vec<Primitive>>>,
+    scene: Scene,
+    /// The scene material for each glTF material.
+    materials: Vec<u32>,
+    /// For primitives with no material, added when the first one is.
+    default_material: Option<u32>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(root: Property<'a>, bin: Option<&'a [u8]>, dir: &'a Path) -> Result<Self, GltfError> {
+        let version = root.get("asset").get("version");
+        let version = version.string()?.ok_or_else(|| version.missing())?;
+        if version.split('.').next() != Some("2") {
+            return Err(GltfError::UnsupportedVersion(version.to_string()));
+        }
+
+        for extension in root.get("extensionsRequired").items()? {
+            let name = extension.string()?.ok_or_else(|| extension.invalid())?;
+            if !SUPPORTED_EXTENSIONS.contains(&name) {
+                return Err(GltfError::UnsupportedExtension(name.to_string()));
+            }
+        }
+
+        let mut buffers = Vec::new();
+        for (index, buffer) in root.get("buffers").items()?.into_iter().enumerate() {
+            let length = buffer.get("byteLength").required_index()?;
+            let uri = buffer.get("uri");
+            let data = match uri.string()? {
+                Some(text) => Cow::Owned(read_uri(&uri, text, dir)?),
+                None if index == 0 => Cow::Borrowed(bin.ok_or_else(|| uri.missing())?),
+                None => return Err(uri.missing()),
+            };
+            if data.len() < length {
+                return Err(GltfError::OutOfBounds(buffer.path));
+            }
+            buffers.push(data);
+        }
+
+        Ok(Importer {
+            node_count: root.get("nodes").items()?.len(),
+            root,
+            dir,
+            buffers,
+            meshes: HashMap::new(),
+            scene: Scene {
+                textures: Vec::new(),
+                vertex_layout: standard_vertex_layout(),
+                vertex_stride: STANDARD_VERTEX_STRIDE,
+                index_format: DXGI_FORMAT_R32_UINT,
+                vertices: Vec::new(),
+                indices: Vec::new(),
+                materials: Vec::new(),
+                draws: Vec::new(),
+            },
+            materials: Vec::new(),
+            default_material: None,
+        })
+    }
+
+    fn import<F>(mut self, mut decode_image: F) -> Result<Scene, GltfError>
+    where
+        F: FnMut(&Image) -> io::Result<Option<Texture>>,
+    {
+        self.import_materials(&mut decode_image)?;
+
+        let nodes = self.root.get("nodes").items()?;
+        let scenes = self.root.get("scenes");
+        let roots = if scenes.exists() {
+            let scene = self.root.get("scene").index()?.unwrap_or(0);
+            let scene = scenes.at(scene);
+            scene.required()?;
+            scene
+                .get("nodes")
+                .items()?
+                .iter()
+                .map(Property::required_index)
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            // With no scenes, draw everything that isn't a child of another
+            // node.
+            let mut is_child = vec![false; nodes.len()];
+            for node in &nodes {
+                for child in node.get("children").items()? {
+                    if let Some(is_child) = is_child.get_mut(child.required_index()?) {
+                        *is_child = true;
+                    }
+                }
+            }
+            (0..nodes.len()).filter(|&node| !is_child[node]).collect()
+        };
+
+        for node in roots {
+            self.import_node(node, &IDENTITY, 0)?;
+        }
+
+        self.scene.validate()?;
+        Ok(self.scene)
+    }
+
+    fn import_materials<F>(&mut self, decode_image: &mut F) -> Result<(), GltfError>
+    where
+        F: FnMut(&Image) -> io::Result<Option<Texture>>,
+    {
+        let mut decoded = HashMap::new();
+        for material in self.root.get("materials").items()? {
+            let pbr = material.get("pbrMetallicRoughness");
+            let spec_gloss = material
+                .get("extensions")
+                .get("KHR_materials_pbrSpecularGlossiness");
+
+            let mut texture = |info: Property<'a>, srgb| {
+                self.import_texture(&info, srgb, decode_image, &mut decoded)
+            };
+            let diffuse = match texture(pbr.get("baseColorTexture"), true)? {
+                Some(texture) => Some(texture),
+                None => texture(spec_gloss.get("diffuseTexture"), true)?,
+            };
+            let normal = texture(material.get("normalTexture"), false)?;
+            let specular = texture(spec_gloss.get("specularGlossinessTexture"), true)?;
+
+            self.scene.materials.push(Material {
+                diffuse,
+                normal,
+                specular,
+            });
+            self.materials.push(self.scene.materials.len() as u32 - 1);
+        }
+        Ok(())
+    }
+
+    /// Returns the scene texture for a texture info property, decoding its
+    /// image if no other material has used it the same way.
+    fn import_texture<F>(
+        &mut self,
+        info: &Property<'a>,
+        srgb: bool,
+        decode_image: &mut F,
+        decoded: &mut HashMap<(usize, bool), Option<u32>>,
+    ) -> Result<Option<u32>, GltfError>
+    where
+        F: FnMut(&Image) -> io::Result<Option<Texture>>,
+    {
+        let index = match info.get("index").index()? {
+            Some(index) => index,
+            None if info.exists() => return Err(info.get("index").missing()),
+            None => return Ok(None),
+        };
+        let texture = self.root.get("textures").at(index);
+        texture.required()?;
+
+        // A DDS version of the image is preferred if the decoder can read it.
+        let sources = [
+            texture
+                .get("extensions")
+                .get("MSFT_texture_dds")
+                .get("source"),
+            texture.get("source"),
+        ];
+        for source in &sources {
+            let image = match source.index()? {
+                Some(image) => image,
+                None => continue,
+            };
+            if let Some(&texture) = decoded.get(&(image, srgb)) {
+                if texture.is_some() {
+                    return Ok(texture);
+                }
+                continue;
+            }
+
+            let texture = self
+                .decode_image(image, srgb, decode_image)?
+                .map(|texture| {
+                    self.scene.textures.push(texture);
+                    self.scene.textures.len() as u32 - 1
+                });
+            decoded.insert((image, srgb), texture);
+            if texture.is_some() {
+                return Ok(texture);
+            }
+        }
+        Ok(None)
+    }
+
+    fn decode_image<F>(
+        &self,
+        index: usize,
+        srgb: bool,
+        decode_image: &mut F,
+    ) -> Result<Option<Texture>, GltfError>
+    where
+        F: FnMut(&Image) -> io::Result<Option<Texture>>,
+    {
+        let image = self.root.get("images").at(index);
+        image.required()?;
+
+        let mut mime_type = image.get("mimeType").string()?;
+        let uri = image.get("uri");
+        let data = match uri.string()? {
+            Some(text) => {
+                mime_type = mime_type.or_else(|| guess_mime_type(text));
+                Cow::Owned(read_uri(&uri, text, self.dir)?)
+            }
+            None => {
+                let view = image.get("bufferView");
+                Cow::Borrowed(self.buffer_view(view.required_index()?)?.0)
+            }
+        };
+
+        decode_image(&Image {
+            index,
+            name: image.get("name").string()?,
+            mime_type,
+            data: &data,
+            srgb,
+        })
+        .map_err(|error| GltfError::Image { index, error })
+    }
+
+    fn import_node(
+        &mut self,
+        index: usize,
+        parent: &Matrix,
+        depth: usize,
+    ) -> Result<(), GltfError> {
+        let node = self.root.get("nodes").at(index);
+        node.required()?;
+        // The nodes form a forest, so a path longer than the number of nodes
+        // has gone round a cycle.
+        if depth > self.node_count {
+            return Err(node.get("children").invalid());
+        }
+
+        let local = match node.get("matrix").floats::<16>()? {
+            Some(matrix) => matrix,
+            None => from_trs(
+                node.get("translation").floats()?.unwrap_or([0.0; 3]),
+                node.get("rotation")
+                    .floats()?
+                    .unwrap_or([0.0, 0.0, 0.0, 1.0]),
+                node.get("scale").floats()?.unwrap_or([1.0; 3]),
+            ),
+        };
+        let world = multiply(parent, &local);
+
+        if let Some(mesh) = node.get("mesh").index()? {
+            let primitives = match self.meshes.get(&mesh) {
+                Some(primitives) => primitives.clone(),
+                None => {
+                    let primitives = Rc::new(self.read_mesh(mesh)?);
+                    self.meshes.insert(mesh, primitives.clone());
+                    primitives
+                }
+            };
+            for primitive in primitives.iter() {
+                self.add_primitive(primitive, &world)?;
+            }
+        }
+
+        for child in node.get("children").items()? {
+            self.import_node(child.required_index()?, &world, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn read_mesh(&self, index: usize) -> Result<Vec<Primitive>, GltfError> {
+        let mesh = self.root.get("meshes").at(index);
+        mesh.required()?;
+
+        let mut primitives = Vec::new();
+        for primitive in mesh.get("primitives").items()? {
+            let mode = primitive.get("mode").index()?.unwrap_or(4);
+            if mode < 4 {
+                continue;
+            }
+            if mode > 6 {
+                return Err(primitive.get("mode").invalid());
+            }
+
+            let attributes = primitive.get("attributes");
+            let position = attributes.get("POSITION");
+            let positions: Vec<[f32; 3]> = self.accessor(position.required_index()?)?.vectors()?;
+            let count = positions.len();
+
+            let attribute = |name: &str| -> Result<Option<Accessor<'_>>, GltfError> {
+                let attribute = attributes.get(name);
+                match attribute.index()? {
+                    None => Ok(None),
+                    Some(index) => {
+                        let accessor = self.accessor(index)?;
+                        if accessor.count != count {
+                            return Err(attribute.invalid());
+                        }
+                        Ok(Some(accessor))
+                    }
+                }
+            };
+            let normals = attribute("NORMAL")?.map(|a| a.vectors()).transpose()?;
+            let texcoords = attribute("TEXCOORD_0")?.map(|a| a.vectors()).transpose()?;
+            let tangents: Option<Vec<[f32; 4]>> =
+                attribute("TANGENT")?.map(|a| a.vectors()).transpose()?;
+
+            let indices = match primitive.get("indices").index()? {
+                Some(index) => self.accessor(index)?.indices()?,
+                None => (0..count as u32).collect(),
+            };
+            if indices.iter().any(|&index| index as usize >= count) {
+                return Err(primitive.get("indices").invalid());
+            }
+            let indices = triangle_list(&indices, mode);
+
+            let normals = normals.unwrap_or_else(|| generate_normals(&positions, &indices));
+            let texcoords = texcoords.unwrap_or_else(|| vec![[0.0; 2]; count]);
+            let tangents = match tangents {
+                Some(tangents) => tangents.iter().map(|t| [t[0], t[1], t[2]]).collect(),
+                None => generate_tangents(&positions, &normals, &texcoords, &indices),
+            };
+
+            primitives.push(Primitive {
+                positions,
+                normals,
+                texcoords,
+                tangents,
+                indices,
+                material: primitive.get("material").index()?,
+            });
+        }
+        Ok(primitives)
+    }
+
+    fn add_primitive(&mut self, primitive: &Primitive, world: &Matrix) -> Result<(), GltfError> {
+        let material = match primitive.material {
+            Some(index) => *self
+                .materials
+                .get(index)
+                .ok_or_else(|| GltfError::Invalid(format!("materials[{}]", index)))?,
+            None => match self.default_material {
+                Some(material) => material,
+                None => {
+                    self.scene.materials.push(Material::default());
+                    let material = self.scene.materials.len() as u32 - 1;
+                    self.default_material = Some(material);
+                    material
+                }
+            },
+        };
+
+        let normal_matrix = normal_matrix(world);
+        let vertex_base = self.scene.vertices.len() / STANDARD_VERTEX_STRIDE as usize;
+        for i in 0..primitive.positions.len() {
+            let position = transform_point(world, primitive.positions[i]);
+            let normal = normalize(transform_vector(&normal_matrix, primitive.normals[i]));
+            let tangent = normalize(transform_vector(world, primitive.tangents[i]));
+            let vertex = position
+                .iter()
+                .chain(&normal)
+                .chain(&primitive.texcoords[i])
+                .chain(&tangent);
+            for value in vertex {
+                self.scene.vertices.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        // Reversing the winding makes counter-clockwise triangles clockwise,
+        // unless the transform is a mirror image and has already done so.
+        let mirrored = determinant(world) < 0.0;
+        let index_start = self.scene.indices.len() / 4;
+        for triangle in primitive.indices.chunks_exact(3) {
+            let triangle = if mirrored {
+                [triangle[0], triangle[1], triangle[2]]
+            } else {
+                [triangle[0], triangle[2], triangle[1]]
+            };
+            for index in triangle {
+                self.scene.indices.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+
+        let too_big =
+            || GltfError::Unsupported("more than 2^31 vertices or 2^32 indices".to_string());
+        self.scene.draws.push(Draw {
+            material,
+            index_start: index_start.try_into().map_err(|_| too_big())?,
+            index_count: primitive.indices.len().try_into().map_err(|_| too_big())?,
+            vertex_base: vertex_base.try_into().map_err(|_| too_big())?,
+        });
+        Ok(())
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let view = self.root.get("bufferViews").at(index);
+        view.required()?;
+
+        let buffer = self
+            .buffers
+            .get(view.get("buffer").required_index()?)
+            .ok_or_else(|| view.get("buffer").invalid())?;
+        let offset = view.get("byteOffset").index()?.unwrap_or(0);
+        let length = view.get("byteLength").required_index()?;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| GltfError::OutOfBounds(view.path.clone()))?;
+        Ok((data, view.get("byteStride").index()?))
+    }
+
+    fn accessor(&self, index: usize) -> Result<Accessor<'_>, GltfError> {
+        let accessor = self.root.get("accessors").at(index);
+        accessor.required()?;
+
+        let component_type = accessor.get("componentType").required_index()?;
+        let component_size = match component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            _ => return Err(accessor.get("componentType").invalid()),
+        };
+        let element_type = accessor.get("type");
+        let components = match element_type
+            .string()?
+            .ok_or_else(|| element_type.missing())?
+        {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" | "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            _ => return Err(element_type.invalid()),
+        };
+        let count = accessor.get("count").required_index()?;
+        let normalized = accessor.get("normalized").bool()?.unwrap_or(false);
+        let element_size = component_size * components;
+
+        let mut stride = element_size;
+        let data = match accessor.get("bufferView").index()? {
+            None => None,
+            Some(view) => {
+                let (view, view_stride) = self.buffer_view(view)?;
+                stride = view_stride.unwrap_or(element_size);
+                let offset = accessor.get("byteOffset").index()?.unwrap_or(0);
+                let end = match count {
+                    0 => Some(offset),
+                    count => stride
+                        .checked_mul(count - 1)
+                        .and_then(|size| size.checked_add(element_size + offset)),
+                };
+                let data = end
+                    .and_then(|end| view.get(offset..end))
+                    .ok_or_else(|| GltfError::OutOfBounds(accessor.path.clone()))?;
+                Some(data)
+            }
+        };
+
+        let sparse = accessor.get("sparse");
+        let data = if sparse.exists() {
+            let dense = self.apply_sparse(&sparse, data, stride, element_size, count)?;
+            stride = element_size;
+            Some(Cow::Owned(dense))
+        } else {
+            data.map(Cow::Borrowed)
+        };
+
+        Ok(Accessor {
+            path: accessor.path,
+            data,
+            stride,
+            count,
+            component_type,
+            component_size,
+            components,
+            normalized,
+        })
+    }
+
+    /// Copies an accessor's elements out, packed together, with the ones that
+    /// its sparse property lists replaced.
+    fn apply_sparse(
+        &self,
+        sparse: &Property,
+        data: Option<&[u8]>,
+        stride: usize,
+        element_size: usize,
+        count: usize,
+    ) -> Result<Vec<u8>, GltfError> {
+        let mut dense = vec![0; element_size * count];
+        if let Some(data) = data {
+            for (element, bytes) in dense.chunks_exact_mut(element_size).enumerate() {
+                bytes.copy_from_slice(&data[element * stride..element * stride + element_size]);
+            }
+        }
+
+        let sparse_count = sparse.get("count");
+        let sparse_count = match sparse_count.required_index()? {
+            n if n > 0 && n <= count => n,
+            _ => return Err(sparse_count.invalid()),
+        };
+        let indices = sparse.get("indices");
+        let index_size = match indices.get("componentType").required_index()? {
+            UNSIGNED_BYTE => 1,
+            UNSIGNED_SHORT => 2,
+            UNSIGNED_INT => 4,
+            _ => return Err(indices.get("componentType").invalid()),
+        };
+        let index_data = self.sparse_data(&indices, index_size * sparse_count)?;
+        let values = sparse.get("values");
+        let value_data = self.sparse_data(&values, element_size * sparse_count)?;
+
+        for (index, value) in index_data
+            .chunks_exact(index_size)
+            .zip(value_data.chunks_exact(element_size))
+        {
+            let element = match *index {
+                [byte] => byte as usize,
+                [a, b] => u16::from_le_bytes([a, b]) as usize,
+                _ => u32::from_le_bytes(index.try_into().unwrap()) as usize,
+            };
+            if element >= count {
+                return Err(indices.invalid());
+            }
+            dense[element * element_size..(element + 1) * element_size].copy_from_slice(value);
+        }
+        Ok(dense)
+    }
+
+    /// The `size` bytes that a sparse accessor's indices or values start at.
+    fn sparse_data(&self, property: &Property, size: usize) -> Result<&[u8], GltfError> {
+        let (view, _) = self.buffer_view(property.get("bufferView").required_index()?)?;
+        let offset = property.get("byteOffset").index()?.unwrap_or(0);
+        offset
+            .checked_add(size)
+            .and_then(|end| view.get(offset..end))
+            .ok_or_else(|| GltfError::OutOfBounds(property.path.clone()))
+    }
+}
+
+/// Reads a `data:` URI, or a file relative to `dir`.
+fn read_uri(property: &Property, uri: &str, dir: &Path) -> Result<Vec<u8>, GltfError> {
+    let invalid = || property.invalid();
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (header, payload) = data.split_once(',').ok_or_else(invalid)?;
+        return if header.ends_with(";base64") {
+            decode_base64(payload).ok_or_else(invalid)
+        } else {
+            Ok(percent_decode(payload).ok_or_else(invalid)?)
+        };
+    }
+
+    let path = PathBuf::from(
+        String::from_utf8(percent_decode(uri).ok_or_else(invalid)?).map_err(|_| invalid())?,
+    );
+    Ok(fs::read(dir.join(path))?)
+}
+
+fn guess_mime_type(uri: &str) -> Option<&str> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        return data
+            .split([';', ','])
+            .next()
+            .filter(|mime| !mime.is_empty());
+    }
+    let extension = uri.rsplit('.').next()?;
+    [
+        ("png", "image/png"),
+        ("jpg", "image/jpeg"),
+        ("jpeg", "image/jpeg"),
+        ("dds", "image/vnd-ms.dds"),
+        ("ktx2", "image/ktx2"),
+    ]
+    .iter()
+    .find(|(e, _)| e.eq_ignore_ascii_case(extension))
+    .map(|(_, mime)| *mime)
+}
+
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Some(bytes)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for c in text.bytes().take_while(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Turns strips (mode 5) and fans (mode 6) into lists, dropping any
+/// incomplete triangle.
+fn triangle_list(indices: &[u32], mode: usize) -> Vec<u32> {
+    match mode {
+        5 => (0..indices.len().saturating_sub(2))
+            .flat_map(|i| {
+                // Every other triangle in a strip is wound the other way.
+                if i % 2 == 0 {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i + 1], indices[i], indices[i + 2]]
+                }
+            })
+            .collect(),
+        6 => (1..indices.len().saturating_sub(1))
+            .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+            .collect(),
+        _ => indices[..indices.len() / 3 * 3].to_vec(),
+    }
+}
+
+fn generate_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![[0.0; 3]; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+        // Weighted by area, as the cross product isn't normalized.
+        let normal = cross(sub(b, a), sub(c, a));
+        for &index in triangle {
+            normals[index as usize] = add(normals[index as usize], normal);
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| {
+            if length(normal) > 0.0 {
+                normalize(normal)
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
+fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    texcoords: &[[f32; 2]],
+    indices: &[u32],
+) -> Vec<[f32; 3]> {
+    let mut tangents = vec![[0.0; 3]; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let (edge1, edge2) = (
+            sub(positions[b], positions[a]),
+            sub(positions[c], positions[a]),
+        );
+        let (du1, dv1) = (
+            texcoords[b][0] - texcoords[a][0],
+            texcoords[b][1] - texcoords[a][1],
+        );
+        let (du2, dv2) = (
+            texcoords[c][0] - texcoords[a][0],
+            texcoords[c][1] - texcoords[a][1],
+        );
+        let r = du1 * dv2 - du2 * dv1;
+        if r.abs() <= f32::EPSILON {
+            continue;
+        }
+        let tangent = scale(sub(scale(edge1, dv2), scale(edge2, dv1)), 1.0 / r);
+        for index in [a, b, c] {
+            tangents[index] = add(tangents[index], tangent);
+        }
+    }
+
+    // Gram-Schmidt against the normal, falling back to any perpendicular
+    // direction where the texture coordinates don't give one.
+    tangents
+        .into_iter()
+        .zip(normals)
+        .map(|(tangent, &normal)| {
+            let tangent = sub(tangent, scale(normal, dot(normal, tangent)));
+            if length(tangent) > 1e-6 {
+                normalize(tangent)
+            } else {
+                let axis = if normal[0].abs() < 0.9 {
+                    [1.0, 0.0, 0.0]
+                } else {
+                    [0.0, 1.0, 0.0]
+                };
+                normalize(cross(axis, normal))
+            }
+        })
+        .collect()
+}
+
+/// Column-major, as glTF stores them.
+type Matrix = [f32; 16];
+
+const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0, //
+    0.0, 1.0, 0.0, 0.0, //
+    0.0, 0.0, 1.0, 0.0, //
+    0.0, 0.0, 0.0, 1.0,
+];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [0.0; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            m[column * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[column * 4 + k]).sum();
+        }
+    }
+    m
+}
+
+fn from_trs(t: [f32; 3], [x, y, z, w]: [f32; 4], s: [f32; 3]) -> Matrix {
+    [
+        (1.0 - 2.0 * (y * y + z * z)) * s[0],
+        2.0 * (x * y + z * w) * s[0],
+        2.0 * (x * z - y * w) * s[0],
+        0.0,
+        2.0 * (x * y - z * w) * s[1],
+        (1.0 - 2.0 * (x * x + z * z)) * s[1],
+        2.0 * (y * z + x * w) * s[1],
+        0.0,
+        2.0 * (x * z + y * w) * s[2],
+        2.0 * (y * z - x * w) * s[2],
+        (1.0 - 2.0 * (x * x + y * y)) * s[2],
+        0.0,
+        t[0],
+        t[1],
+        t[2],
+        1.0,
+    ]
+}
+
+fn transform_point(m: &Matrix, p: [f32; 3]) -> [f32; 3] {
+    add(transform_vector(m, p), [m[12], m[13], m[14]])
+}
+
+/// Ignores the translation.
+fn transform_vector(m: &Matrix, v: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|row| m[row] * v[0] + m[4 + row] * v[1] + m[8 + row] * v[2])
+}
+
+/// The determinant of the upper 3x3.
+fn determinant(m: &Matrix) -> f32 {
+    dot(
+        [m[0], m[1], m[2]],
+        cross([m[4], m[5], m[6]], [m[8], m[9], m[10]]),
+    )
+}
+
+/// The inverse transpose of the upper 3x3, up to a positive scale, which is
+/// enough for directions that are normalized afterwards.
+fn normal_matrix(m: &Matrix) -> Matrix {
+    let columns = [[m[0], m[1], m[2]], [m[4], m[5], m[6]], [m[8], m[9], m[10]]];
+    // The columns of the cofactor matrix are the cross products of the other
+    // two columns, and it is the inverse transpose times the determinant.
+    let sign = determinant(m).signum();
+    let c = [
+        scale(cross(columns[1], columns[2]), sign),
+        scale(cross(columns[2], columns[0]), sign),
+        scale(cross(columns[0], columns[1]), sign),
+    ];
+    [
+        c[0][0], c[0][1], c[0][2], 0.0, //
+        c[1][0], c[1][1], c[1][2], 0.0, //
+        c[2][0], c[2][1], c[2][2], 0.0, //
+        0.0, 0.0, 0.0, 1.0,
+    ]
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = length(a);
+    if length > 0.0 {
+        scale(a, 1.0 / length)
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests;
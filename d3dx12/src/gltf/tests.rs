@@ -0,0 +1,319 @@
+use super::*;
+
+const TRIANGLE: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+fn bytes<T: Copy, const N: usize>(values: &[T], to_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+    values.iter().flat_map(|&value| to_bytes(value)).collect()
+}
+
+fn data_uri(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut uri = String::from("data:application/octet-stream;base64,");
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            uri.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+        }
+        for _ in chunk.len()..3 {
+            uri.push('=');
+        }
+    }
+    uri
+}
+
+/// A document with `buffer` inline as its only buffer, and `rest` for the
+/// other top-level properties.
+fn document(buffer: &[u8], rest: &str) -> String {
+    format!(
+        r#"{{ "asset": {{ "version": "2.0" }}, "buffers": [{{ "byteLength": {}, "uri": "{}" }}], {} }}"#,
+        buffer.len(),
+        data_uri(buffer),
+        rest
+    )
+}
+
+fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+    let chunk = |kind: usize, data: &[u8], padding: u8| {
+        let mut chunk = Vec::new();
+        let length = data.len().div_ceil(4) * 4;
+        chunk.extend_from_slice(&(length as u32).to_le_bytes());
+        chunk.extend_from_slice(&(kind as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        chunk.resize(8 + length, padding);
+        chunk
+    };
+    let chunks = [
+        chunk(GLB_CHUNK_JSON, json.as_bytes(), b' '),
+        chunk(GLB_CHUNK_BIN, bin, 0),
+    ]
+    .concat();
+
+    let mut file = GLB_MAGIC.to_vec();
+    file.extend_from_slice(&2u32.to_le_bytes());
+    file.extend_from_slice(&(12 + chunks.len() as u32).to_le_bytes());
+    file.extend_from_slice(&chunks);
+    file
+}
+
+fn import(file: &[u8]) -> Result<Scene, GltfError> {
+    read(file, Path::new(""), |_| Ok(None))
+}
+
+/// Position, normal, texture coordinate and tangent.
+fn vertex(scene: &Scene, index: usize) -> [f32; 11] {
+    let stride = STANDARD_VERTEX_STRIDE as usize;
+    let mut vertex = [0.0; 11];
+    for (i, value) in vertex.iter_mut().enumerate() {
+        let offset = index * stride + i * 4;
+        *value = f32::from_le_bytes(scene.vertices[offset..offset + 4].try_into().unwrap());
+    }
+    vertex
+}
+
+fn indices(scene: &Scene) -> Vec<u32> {
+    scene
+        .indices
+        .chunks_exact(4)
+        .map(|index| u32::from_le_bytes(index.try_into().unwrap()))
+        .collect()
+}
+
+fn assert_close(actual: &[f32], expected: &[f32]) {
+    assert!(
+        actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (a - e).abs() < 1e-6),
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn glb_and_data_uri_buffers() {
+    let mut buffer = bytes(&TRIANGLE, f32::to_le_bytes);
+    buffer.extend(bytes(&[0u16, 1, 2], u16::to_le_bytes));
+    let rest = r#"
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3 },
+            { "bufferView": 1, "componentType": 5123, "type": "SCALAR", "count": 3 }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }],
+        "nodes": [{ "mesh": 0 }]
+    "#;
+
+    let inline = import(document(&buffer, rest).as_bytes()).unwrap();
+    for (index, position) in TRIANGLE.chunks_exact(3).enumerate() {
+        assert_close(&vertex(&inline, index)[..3], position);
+    }
+    // The winding is reversed.
+    assert_eq!(indices(&inline), [0, 2, 1]);
+    assert_eq!(inline.draws.len(), 1);
+
+    let json = format!(
+        r#"{{ "asset": {{ "version": "2.0" }}, "buffers": [{{ "byteLength": {} }}], {} }}"#,
+        buffer.len(),
+        rest
+    );
+    let binary = glb(&json, &buffer);
+    assert_eq!(import(&binary).unwrap(), inline);
+
+    match import(&binary[..20]) {
+        Err(GltfError::Glb(_)) => (),
+        result => panic!("{:?}", result),
+    }
+    // The BIN chunk is shorter than the buffer says.
+    let short = glb(&json, &buffer[..40]);
+    match import(&short) {
+        Err(GltfError::OutOfBounds(path)) => assert_eq!(path, "buffers[0]"),
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn sparse_accessors() {
+    // The base positions, the sparse indices 2 and 1, and the values that
+    // replace position 2 and texture coordinate 1.
+    let mut buffer = bytes(&TRIANGLE, f32::to_le_bytes);
+    buffer.extend([2, 1, 0, 0]);
+    buffer.extend(bytes(&[0.0f32, 2.0, 0.0, 0.5, 0.25], f32::to_le_bytes));
+    let rest = r#"
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 2 },
+            { "buffer": 0, "byteOffset": 40, "byteLength": 20 }
+        ],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3,
+                "sparse": {
+                    "count": 1,
+                    "indices": { "bufferView": 1, "componentType": 5121 },
+                    "values": { "bufferView": 2 }
+                }
+            },
+            {
+                "componentType": 5126, "type": "VEC2", "count": 3,
+                "sparse": {
+                    "count": 1,
+                    "indices": { "bufferView": 1, "byteOffset": 1, "componentType": 5121 },
+                    "values": { "bufferView": 2, "byteOffset": 12 }
+                }
+            }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1 } }] }],
+        "nodes": [{ "mesh": 0 }]
+    "#;
+
+    let scene = import(document(&buffer, rest).as_bytes()).unwrap();
+    assert_close(&vertex(&scene, 0)[..3], &[0.0, 0.0, 0.0]);
+    assert_close(&vertex(&scene, 1)[..3], &[1.0, 0.0, 0.0]);
+    assert_close(&vertex(&scene, 2)[..3], &[0.0, 2.0, 0.0]);
+    // With no buffer view, the elements that aren't replaced are zero.
+    assert_close(&vertex(&scene, 0)[6..8], &[0.0, 0.0]);
+    assert_close(&vertex(&scene, 1)[6..8], &[0.5, 0.25]);
+    assert_close(&vertex(&scene, 2)[6..8], &[0.0, 0.0]);
+
+    buffer[36] = 3;
+    match import(document(&buffer, rest).as_bytes()) {
+        Err(GltfError::Invalid(path)) => assert_eq!(path, "accessors[0].sparse.indices"),
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn normalized_integer_attributes() {
+    // Signed bytes for the normals, one of them -128, and unsigned bytes for
+    // the texture coordinates.
+    let mut buffer = bytes(&TRIANGLE, f32::to_le_bytes);
+    buffer.extend(bytes(
+        &[0i8, 0, 127, 0, 0, -128, 0, -127, 0],
+        i8::to_le_bytes,
+    ));
+    buffer.extend([0, 0, 0]);
+    buffer.extend([0, 255, 51, 0, 255, 255]);
+    let rest = r#"
+        "extensionsRequired": ["KHR_mesh_quantization"],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 9 },
+            { "buffer": 0, "byteOffset": 48, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3 },
+            { "bufferView": 1, "componentType": 5120, "normalized": true, "type": "VEC3", "count": 3 },
+            { "bufferView": 2, "componentType": 5121, "normalized": true, "type": "VEC2", "count": 3 }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 } }] }],
+        "nodes": [{ "mesh": 0 }]
+    "#;
+
+    let scene = import(document(&buffer, rest).as_bytes()).unwrap();
+    let expected_normals = [[0.0, 0.0, 1.0], [0.0, 0.0, -1.0], [0.0, -1.0, 0.0]];
+    let expected_texcoords = [[0.0, 1.0], [0.2, 0.0], [1.0, 1.0]];
+    for index in 0..3 {
+        let vertex = vertex(&scene, index);
+        assert_close(&vertex[3..6], &expected_normals[index]);
+        assert_close(&vertex[6..8], &expected_texcoords[index]);
+    }
+}
+
+#[test]
+fn missing_normals_and_tangents_are_generated() {
+    // The same triangle twice, with and then without texture coordinates.
+    let mut buffer = bytes(&TRIANGLE, f32::to_le_bytes);
+    buffer.extend(bytes(&[0.0f32, 0.0, 1.0, 0.0, 0.0, 1.0], f32::to_le_bytes));
+    let rest = r#"
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 24 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3 },
+            { "bufferView": 1, "componentType": 5126, "type": "VEC2", "count": 3 }
+        ],
+        "meshes": [{ "primitives": [
+            { "attributes": { "POSITION": 0, "TEXCOORD_0": 1 } },
+            { "attributes": { "POSITION": 0 } }
+        ] }],
+        "nodes": [{ "mesh": 0 }]
+    "#;
+
+    let scene = import(document(&buffer, rest).as_bytes()).unwrap();
+    for index in 0..3 {
+        let vertex = vertex(&scene, index);
+        // Facing the side the triangle is counter-clockwise from, with the
+        // tangent along increasing u.
+        assert_close(&vertex[3..6], &[0.0, 0.0, 1.0]);
+        assert_close(&vertex[8..11], &[1.0, 0.0, 0.0]);
+    }
+    for index in 3..6 {
+        let vertex = vertex(&scene, index);
+        assert_close(&vertex[3..6], &[0.0, 0.0, 1.0]);
+        // Any unit vector at right angles to the normal will do.
+        let normal = [vertex[3], vertex[4], vertex[5]];
+        let tangent = [vertex[8], vertex[9], vertex[10]];
+        assert_close(&[length(tangent), dot(normal, tangent)], &[1.0, 0.0]);
+    }
+}
+
+#[test]
+fn node_transforms_are_flattened() {
+    // A translated parent with a scaled child, and a node whose matrix
+    // mirrors x and moves the triangle along z.
+    let buffer = bytes(&TRIANGLE, f32::to_le_bytes);
+    let rest = r#"
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 3 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+        "nodes": [
+            { "translation": [1, 0, 0], "children": [1] },
+            { "scale": [2, 2, 2], "mesh": 0 },
+            { "matrix": [-1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 5, 1], "mesh": 0 }
+        ],
+        "scenes": [{ "nodes": [0, 2] }]
+    "#;
+
+    let scene = import(document(&buffer, rest).as_bytes()).unwrap();
+    let expected = [
+        [1.0, 0.0, 0.0],
+        [3.0, 0.0, 0.0],
+        [1.0, 2.0, 0.0],
+        [0.0, 0.0, 5.0],
+        [-1.0, 0.0, 5.0],
+        [0.0, 1.0, 5.0],
+    ];
+    for (index, position) in expected.iter().enumerate() {
+        let vertex = vertex(&scene, index);
+        assert_close(&vertex[..3], position);
+        assert_close(&vertex[3..6], &[0.0, 0.0, 1.0]);
+    }
+
+    // The mirror image already has the winding the samples want.
+    assert_eq!(indices(&scene), [0, 2, 1, 0, 1, 2]);
+    let vertex_bases: Vec<i32> = scene.draws.iter().map(|draw| draw.vertex_base).collect();
+    assert_eq!(vertex_bases, [0, 3]);
+}
+
+#[test]
+fn buffer_views_past_the_end_of_the_buffer() {
+    // The largest offset and length an index can hold, whose sum
+    // overflows a 32-bit usize.
+    let rest = r#"
+        "bufferViews": [{ "buffer": 0, "byteOffset": 4294967295, "byteLength": 4294967295 }],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "type": "VEC3", "count": 1 }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+        "nodes": [{ "mesh": 0 }]
+    "#;
+    match import(document(&[0; 12], rest).as_bytes()) {
+        Err(GltfError::OutOfBounds(path)) => assert_eq!(path, "bufferViews[0]"),
+        result => panic!("{:?}", result),
+    }
+}
@@ -0,0 +1,115 @@
+//! Decodes PNG and TGA files into RGBA8 textures, for debug textures that
+//! haven't been through an offline conversion to DDS.
+
+use crate::format::*;
+use crate::texture::{SubresourceData, TextureData, TextureDesc};
+use std::{borrow::Cow, fmt, fs, io, path::Path};
+
+mod inflate;
+mod png;
+mod tga;
+
+pub use png::PNG_SIGNATURE;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// Neither a PNG nor a TGA file.
+    UnknownFormat,
+    InvalidPng(&'static str),
+    InvalidTga(&'static str),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "{}", e),
+            ImageError::UnknownFormat => write!(f, "not a PNG or TGA file"),
+            ImageError::InvalidPng(message) => write!(f, "invalid PNG file: {}", message),
+            ImageError::InvalidTga(message) => write!(f, "invalid TGA file: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(error: io::Error) -> Self {
+        ImageError::Io(error)
+    }
+}
+
+impl From<ImageError> for io::Error {
+    fn from(error: ImageError) -> Self {
+        match error {
+            ImageError::Io(e) => e,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+/// Loads a PNG or TGA file as a single mip of `DXGI_FORMAT_R8G8B8A8_UNORM`,
+/// or of `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB` if `srgb` is set.
+pub fn load<P: AsRef<Path>>(path: P, srgb: bool) -> Result<TextureData<'static>, ImageError> {
+    let bytes = fs::read(path)?;
+    read(&bytes, srgb)
+}
+
+/// Decodes a PNG or TGA file that is already in memory, as `load` does.
+pub fn read(bytes: &[u8], srgb: bool) -> Result<TextureData<'static>, ImageError> {
+    let image = if bytes.starts_with(PNG_SIGNATURE) {
+        png::decode(bytes).map_err(ImageError::InvalidPng)?
+    } else if tga::is_tga(bytes) {
+        tga::decode(bytes).map_err(ImageError::InvalidTga)?
+    } else {
+        return Err(ImageError::UnknownFormat);
+    };
+
+    let format = if srgb {
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+    } else {
+        DXGI_FORMAT_R8G8B8A8_UNORM
+    };
+    let row_pitch = image.width as u64 * 4;
+    Ok(TextureData {
+        desc: TextureDesc::tex2d(format, image.width, image.height),
+        is_cube: false,
+        subresources: vec![SubresourceData {
+            data: Cow::Owned(image.pixels),
+            row_pitch,
+            slice_pitch: row_pitch * image.height as u64,
+        }],
+    })
+}
+
+/// Decoded pixels, four bytes to a texel with rows packed tightly.
+struct Rgba8 {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// `D3D12_REQ_TEXTURE2D_U_OR_V_DIMENSION`.
+const MAX_TEXTURE2D_DIMENSION: u32 = 16384;
+
+/// Rejects images that are too big to be a 2D texture before anything is
+/// allocated for them.
+fn check_size(width: u32, height: u32) -> Result<(), &'static str> {
+    if width == 0 || height == 0 {
+        Err("empty image")
+    } else if width > MAX_TEXTURE2D_DIMENSION || height > MAX_TEXTURE2D_DIMENSION {
+        Err("larger than a 2D texture can be")
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
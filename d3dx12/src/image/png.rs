@@ -0,0 +1,343 @@
+//! Decodes PNG files of every color type and bit depth, interlaced or not.
+//! Sixteen bit samples are cut down to eight.
+
+use super::{check_size, inflate, Rgba8};
+use std::convert::TryInto;
+
+pub type Result<T> = std::result::Result<T, &'static str>;
+
+pub const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+const COLOR_GRAY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GRAY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+/// The first pixel, and the spacing between pixels, of each Adam7 pass.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            COLOR_RGB => 3,
+            COLOR_GRAY_ALPHA => 2,
+            COLOR_RGBA => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    /// The size of a row of `width` pixels, without its filter byte.
+    fn row_size(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Rgba8> {
+    let mut rest = bytes.strip_prefix(PNG_SIGNATURE).ok_or("not a PNG file")?;
+
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+    loop {
+        if rest.len() < 12 {
+            return Err("truncated chunk");
+        }
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let data = rest.get(8..8 + length).ok_or("truncated chunk")?;
+        let crc = rest.get(8 + length..12 + length).ok_or("truncated chunk")?;
+        if u32::from_be_bytes(crc.try_into().unwrap()) != crc32(&rest[4..8 + length]) {
+            return Err("chunk CRC doesn't match");
+        }
+        rest = &rest[12 + length..];
+
+        if header.is_none() && &kind != b"IHDR" {
+            return Err("first chunk isn't IHDR");
+        }
+        match &kind {
+            b"IHDR" => header = Some(read_header(data)?),
+            b"PLTE" => palette = data,
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Ancillary chunks, with a lowercase first letter, can be
+            // skipped; critical ones can't.
+            kind if kind[0].is_ascii_uppercase() => return Err("unknown critical chunk"),
+            _ => {}
+        }
+    }
+    let header = header.ok_or("no IHDR chunk")?;
+
+    if header.color_type == COLOR_PALETTE
+        && (palette.is_empty() || palette.len() % 3 != 0 || palette.len() / 3 > 256)
+    {
+        return Err("invalid palette");
+    }
+
+    // Each pass is stored as rows with a filter byte at the start of each.
+    let passes: Vec<_> = if header.interlaced {
+        ADAM7
+            .iter()
+            .map(|&(x, y, dx, dy)| {
+                (
+                    (header.width + dx - 1 - x) / dx,
+                    (header.height + dy - 1 - y) / dy,
+                )
+            })
+            .collect()
+    } else {
+        vec![(header.width, header.height)]
+    };
+    let size = passes
+        .iter()
+        .filter(|&&(width, height)| width > 0 && height > 0)
+        .map(|&(width, height)| (header.row_size(width) + 1) * height)
+        .sum();
+    let mut filtered = inflate::decompress_zlib(&compressed, size)?;
+    if filtered.len() != size {
+        return Err("image data is the wrong size");
+    }
+
+    let mut image = Rgba8 {
+        width: header.width as u32,
+        height: header.height as u32,
+        pixels: vec![0; header.width * header.height * 4],
+    };
+    let mut offset = 0;
+    for (pass, &(width, height)) in passes.iter().enumerate() {
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let row_size = header.row_size(width);
+        let data = &mut filtered[offset..offset + (row_size + 1) * height];
+        offset += data.len();
+        unfilter(data, row_size, header.bits_per_pixel().div_ceil(8))?;
+
+        let (x0, y0, dx, dy) = if header.interlaced {
+            ADAM7[pass]
+        } else {
+            (0, 0, 1, 1)
+        };
+        for (row, line) in data.chunks_exact(row_size + 1).enumerate() {
+            let y = y0 + row * dy;
+            for column in 0..width {
+                let x = x0 + column * dx;
+                let texel = (y * header.width + x) * 4;
+                image.pixels[texel..texel + 4].copy_from_slice(&to_rgba(
+                    &header,
+                    &line[1..],
+                    column,
+                    palette,
+                    transparency,
+                )?);
+            }
+        }
+    }
+    Ok(image)
+}
+
+fn read_header(data: &[u8]) -> Result<Header> {
+    if data.len() != 13 {
+        return Err("IHDR is the wrong size");
+    }
+    let width = u32::from_be_bytes(data[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    let header = Header {
+        width: width as usize,
+        height: height as usize,
+        bit_depth: data[8],
+        color_type: data[9],
+        interlaced: match data[12] {
+            0 => false,
+            1 => true,
+            _ => return Err("unknown interlace method"),
+        },
+    };
+    check_size(width, height)?;
+    if data[10] != 0 || data[11] != 0 {
+        return Err("unknown compression or filter method");
+    }
+    let valid_depth = match header.color_type {
+        COLOR_GRAY => [1, 2, 4, 8, 16].contains(&header.bit_depth),
+        COLOR_PALETTE => [1, 2, 4, 8].contains(&header.bit_depth),
+        COLOR_RGB | COLOR_GRAY_ALPHA | COLOR_RGBA => [8, 16].contains(&header.bit_depth),
+        _ => return Err("unknown color type"),
+    };
+    if !valid_depth {
+        return Err("invalid bit depth for the color type");
+    }
+    Ok(header)
+}
+
+/// Undoes the filter on each row in place. `data` holds rows of `row_size`
+/// bytes, each after a byte saying which filter it uses.
+fn unfilter(data: &mut [u8], row_size: usize, bytes_per_pixel: usize) -> Result<()> {
+    let stride = row_size + 1;
+    for row in 0..data.len() / stride {
+        let (before, line) = data.split_at_mut(row * stride);
+        let previous = if row > 0 {
+            Some(&before[before.len() - row_size..])
+        } else {
+            None
+        };
+        let (filter, line) = line[..stride].split_first_mut().unwrap();
+        let up = |i: usize| previous.map_or(0, |p| p[i]);
+
+        for i in 0..row_size {
+            let left = if i >= bytes_per_pixel {
+                line[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up_left = if i >= bytes_per_pixel {
+                previous.map_or(0, |p| p[i - bytes_per_pixel])
+            } else {
+                0
+            };
+            let predictor = match *filter {
+                0 => 0,
+                1 => left,
+                2 => up(i),
+                3 => ((left as u16 + up(i) as u16) / 2) as u8,
+                4 => paeth(left, up(i), up_left),
+                _ => return Err("unknown filter type"),
+            };
+            line[i] = line[i].wrapping_add(predictor);
+        }
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Converts pixel `column` of an unfiltered row.
+fn to_rgba(
+    header: &Header,
+    line: &[u8],
+    column: usize,
+    palette: &[u8],
+    transparency: &[u8],
+) -> Result<[u8; 4]> {
+    let depth = header.bit_depth as usize;
+    // Samples as they are stored, for comparing against tRNS, and cut down
+    // to eight bits.
+    let sample = |index: usize| -> u16 {
+        match depth {
+            16 => u16::from_be_bytes([line[index * 2], line[index * 2 + 1]]),
+            8 => line[index] as u16,
+            _ => {
+                let bit = index * depth;
+                let shift = 8 - depth - bit % 8;
+                ((line[bit / 8] >> shift) & ((1 << depth) - 1) as u8) as u16
+            }
+        }
+    };
+    let to_8 = |value: u16| -> u8 {
+        match depth {
+            16 => (value >> 8) as u8,
+            8 => value as u8,
+            _ => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+        }
+    };
+    let transparent = |values: &[u16]| {
+        transparency.len() == values.len() * 2
+            && values.iter().enumerate().all(|(i, &v)| {
+                u16::from_be_bytes([transparency[i * 2], transparency[i * 2 + 1]]) == v
+            })
+    };
+
+    let channels = header.channels();
+    let base = column * channels;
+    Ok(match header.color_type {
+        COLOR_GRAY => {
+            let gray = sample(base);
+            let alpha = if transparent(&[gray]) { 0 } else { 255 };
+            let gray = to_8(gray);
+            [gray, gray, gray, alpha]
+        }
+        COLOR_RGB => {
+            let rgb = [sample(base), sample(base + 1), sample(base + 2)];
+            let alpha = if transparent(&rgb) { 0 } else { 255 };
+            [to_8(rgb[0]), to_8(rgb[1]), to_8(rgb[2]), alpha]
+        }
+        COLOR_PALETTE => {
+            let index = sample(base) as usize;
+            let color = palette
+                .get(index * 3..index * 3 + 3)
+                .ok_or("palette index out of range")?;
+            let alpha = transparency.get(index).copied().unwrap_or(255);
+            [color[0], color[1], color[2], alpha]
+        }
+        COLOR_GRAY_ALPHA => {
+            let gray = to_8(sample(base));
+            [gray, gray, gray, to_8(sample(base + 1))]
+        }
+        _ => [
+            to_8(sample(base)),
+            to_8(sample(base + 1)),
+            to_8(sample(base + 2)),
+            to_8(sample(base + 3)),
+        ],
+    })
+}
+
+pub(super) fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut value = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                value = if value & 1 != 0 {
+                    0xedb88320 ^ (value >> 1)
+                } else {
+                    value >> 1
+                };
+                bit += 1;
+            }
+            table[i] = value;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
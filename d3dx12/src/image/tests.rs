@@ -0,0 +1,460 @@
+use super::*;
+use inflate::{adler32, decompress_zlib};
+
+const FIXED: &[u8] = include_bytes!("../../tests/fixtures/image/fixed.zlib");
+const DYNAMIC: &[u8] = include_bytes!("../../tests/fixtures/image/dynamic.zlib");
+
+/// What the zlib fixtures hold.
+fn fixture_text() -> Vec<u8> {
+    (0..200)
+        .map(|i| format!("line {} of the inflate fixture\n", i))
+        .collect::<String>()
+        .into_bytes()
+}
+
+/// A zlib stream of stored blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks: Vec<_> = data.chunks(0xffff).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        stream.push((i + 1 == blocks.len()) as u8);
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn all_prefixes_fail<T>(bytes: &[u8], decode: impl Fn(&[u8]) -> Result<T, &'static str>) {
+    for size in 0..bytes.len() {
+        assert!(
+            decode(&bytes[..size]).is_err(),
+            "{} of {} bytes",
+            size,
+            bytes.len()
+        );
+    }
+}
+
+#[test]
+fn inflate_stored_blocks() {
+    // Two blocks.
+    let data: Vec<u8> = (0..70_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    assert_eq!(
+        decompress_zlib(&zlib_stored(&data), data.len()).unwrap(),
+        data
+    );
+    assert_eq!(decompress_zlib(&zlib_stored(&[]), 0).unwrap(), []);
+}
+
+#[test]
+fn inflate_fixed_and_dynamic_blocks() {
+    let text = fixture_text();
+    assert_eq!(FIXED[2] >> 1 & 3, 1);
+    assert_eq!(decompress_zlib(FIXED, text.len()).unwrap(), text);
+    assert_eq!(DYNAMIC[2] >> 1 & 3, 2);
+    assert_eq!(decompress_zlib(DYNAMIC, text.len()).unwrap(), text);
+}
+
+#[test]
+fn inflate_errors() {
+    let corrupt = |offset: usize| {
+        let mut stream = zlib_stored(b"abc");
+        stream[offset] ^= 1;
+        decompress_zlib(&stream, 3)
+    };
+    assert_eq!(corrupt(1), Err("zlib header check failed"));
+    assert_eq!(corrupt(5), Err("stored block length check failed"));
+    assert_eq!(corrupt(11), Err("zlib checksum doesn't match"));
+    // FDICT, with the check bits left at 0 because 0x7820 is a multiple of
+    // 31.
+    assert_eq!(
+        decompress_zlib(&[0x78, 0x20], 0),
+        Err("preset dictionaries are not supported")
+    );
+    // A final block of type 3.
+    assert_eq!(
+        decompress_zlib(&[0x78, 0x01, 0x07], 0),
+        Err("reserved block type")
+    );
+    assert_eq!(
+        decompress_zlib(&zlib_stored(b"abc"), 2),
+        Err("more data than expected")
+    );
+    assert_eq!(
+        decompress_zlib(DYNAMIC, 100),
+        Err("more data than expected")
+    );
+
+    for stream in [FIXED, DYNAMIC, &zlib_stored(b"abc")] {
+        all_prefixes_fail(stream, |stream| decompress_zlib(stream, usize::MAX));
+    }
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = png::crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// A PNG file of one IDAT chunk holding the `filtered` rows, after the
+/// header and `chunks`.
+fn png(
+    (width, height): (u32, u32),
+    bit_depth: u8,
+    color_type: u8,
+    chunks: &[(&[u8; 4], &[u8])],
+    filtered: &[u8],
+) -> Vec<u8> {
+    let mut png = PNG_SIGNATURE.to_vec();
+    let mut header = width.to_be_bytes().to_vec();
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+    for (kind, data) in chunks {
+        chunk(&mut png, kind, data);
+    }
+    chunk(&mut png, b"IDAT", &zlib_stored(filtered));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Rows that use no filter.
+fn unfiltered(rows: &[&[u8]]) -> Vec<u8> {
+    rows.iter()
+        .flat_map(|row| [&[0][..], row].concat())
+        .collect()
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Vec<u8>, &'static str> {
+    png::decode(bytes).map(|image| image.pixels)
+}
+
+/// RGBA8 texels from gray values.
+fn gray(values: &[u8]) -> Vec<u8> {
+    values.iter().flat_map(|&v| [v, v, v, 255]).collect()
+}
+
+#[test]
+fn png_filter_types() {
+    // Three RGB pixels to a row, so that "left" is three bytes back.
+    let raw: Vec<Vec<u8>> = (0..5)
+        .map(|row| {
+            (0..9)
+                .map(|i| (row * 50 + i * 23 + i * i % 7) as u8)
+                .collect()
+        })
+        .collect();
+    let paeth = |a: u8, b: u8, c: u8| {
+        let p = a as i16 + b as i16 - c as i16;
+        let distance = |x: u8| (p - x as i16).abs();
+        if distance(a) <= distance(b) && distance(a) <= distance(c) {
+            a
+        } else if distance(b) <= distance(c) {
+            b
+        } else {
+            c
+        }
+    };
+    let mut filtered = Vec::new();
+    for (row, filter) in (0..5).zip(0..5u8) {
+        filtered.push(filter);
+        for i in 0..9 {
+            let left = if i >= 3 { raw[row][i - 3] } else { 0 };
+            let up = if row > 0 { raw[row - 1][i] } else { 0 };
+            let up_left = if row > 0 && i >= 3 {
+                raw[row - 1][i - 3]
+            } else {
+                0
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                _ => paeth(left, up, up_left),
+            };
+            filtered.push(raw[row][i].wrapping_sub(predictor));
+        }
+    }
+
+    let expected: Vec<u8> = raw
+        .iter()
+        .flat_map(|row| row.chunks(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]))
+        .collect();
+    assert_eq!(
+        decode_png(&png((3, 5), 8, 2, &[], &filtered)).unwrap(),
+        expected
+    );
+
+    filtered[10] = 5;
+    assert_eq!(
+        decode_png(&png((3, 5), 8, 2, &[], &filtered)),
+        Err("unknown filter type")
+    );
+}
+
+#[test]
+fn png_palette_and_transparency() {
+    let palette = [10, 11, 12, 20, 21, 22, 30, 31, 32, 40, 41, 42];
+    // Entries past the end of tRNS are opaque.
+    let transparency = [0, 128];
+    // Two bits to an index: 0 1 2 3 0, then 3 2 1 0 1.
+    let rows = unfiltered(&[&[0b00_01_10_11, 0b00_000000], &[0b11_10_01_00, 0b01_000000]]);
+    let chunks: [(&[u8; 4], &[u8]); 2] = [(b"PLTE", &palette), (b"tRNS", &transparency)];
+    let color = |index: usize| {
+        let alpha = [0, 128, 255, 255][index];
+        [
+            palette[index * 3],
+            palette[index * 3 + 1],
+            palette[index * 3 + 2],
+            alpha,
+        ]
+    };
+    let expected: Vec<u8> = [0, 1, 2, 3, 0, 3, 2, 1, 0, 1]
+        .iter()
+        .flat_map(|&index| color(index))
+        .collect();
+    assert_eq!(
+        decode_png(&png((5, 2), 2, 3, &chunks, &rows)).unwrap(),
+        expected
+    );
+
+    // Three entries, with index 3 used.
+    let chunks: [(&[u8; 4], &[u8]); 1] = [(b"PLTE", &palette[..9])];
+    assert_eq!(
+        decode_png(&png((5, 2), 2, 3, &chunks, &rows)),
+        Err("palette index out of range")
+    );
+    let chunks: [(&[u8; 4], &[u8]); 1] = [(b"PLTE", &palette[..10])];
+    assert_eq!(
+        decode_png(&png((5, 2), 2, 3, &chunks, &rows)),
+        Err("invalid palette")
+    );
+    assert_eq!(
+        decode_png(&png((5, 2), 2, 3, &[], &rows)),
+        Err("invalid palette")
+    );
+}
+
+#[test]
+fn png_bit_depths() {
+    // Sixteen bit samples keep their high byte.
+    let rows = unfiltered(&[&[0x12, 0x34, 0xab, 0xcd, 0x00, 0xff, 0xff, 0x00]]);
+    assert_eq!(
+        decode_png(&png((1, 1), 16, 6, &[], &rows)).unwrap(),
+        [0x12, 0xab, 0x00, 0xff]
+    );
+
+    // tRNS compares the whole sixteen bits.
+    let rows = unfiltered(&[&[0x12, 0x34, 0x12, 0x35]]);
+    let chunks: [(&[u8; 4], &[u8]); 1] = [(b"tRNS", &[0x12, 0x34])];
+    assert_eq!(
+        decode_png(&png((2, 1), 16, 0, &chunks, &rows)).unwrap(),
+        [0x12, 0x12, 0x12, 0, 0x12, 0x12, 0x12, 255]
+    );
+
+    // Gray and alpha.
+    let rows = unfiltered(&[&[0x40, 0x80, 0x00, 0x00, 0xc0, 0x00, 0xff, 0xff]]);
+    assert_eq!(
+        decode_png(&png((2, 1), 16, 4, &[], &rows)).unwrap(),
+        [0x40, 0x40, 0x40, 0x00, 0xc0, 0xc0, 0xc0, 0xff]
+    );
+
+    // Samples of fewer than eight bits are scaled up to the full range.
+    let rows = unfiltered(&[&[0b1010_0000, 0b1000_0000]]);
+    assert_eq!(
+        decode_png(&png((9, 1), 1, 0, &[], &rows)).unwrap(),
+        gray(&[255, 0, 255, 0, 0, 0, 0, 0, 255])
+    );
+    let rows = unfiltered(&[&[0x5f]]);
+    assert_eq!(
+        decode_png(&png((2, 1), 4, 0, &[], &rows)).unwrap(),
+        gray(&[85, 255])
+    );
+
+    assert_eq!(
+        decode_png(&png((1, 1), 16, 3, &[], &[0, 0, 0])),
+        Err("invalid bit depth for the color type")
+    );
+}
+
+#[test]
+fn png_chunk_errors() {
+    let rows = unfiltered(&[&[1, 2], &[3, 4]]);
+    let file = png((2, 2), 8, 0, &[], &rows);
+    assert_eq!(decode_png(&file).unwrap(), gray(&[1, 2, 3, 4]));
+
+    // A byte of the width.
+    let mut corrupt = file.clone();
+    corrupt[19] ^= 1;
+    assert_eq!(decode_png(&corrupt), Err("chunk CRC doesn't match"));
+    all_prefixes_fail(&file, decode_png);
+
+    let ancillary: [(&[u8; 4], &[u8]); 1] = [(b"tEXt", b"Comment\0hi")];
+    assert!(decode_png(&png((2, 2), 8, 0, &ancillary, &rows)).is_ok());
+    let critical: [(&[u8; 4], &[u8]); 1] = [(b"CRIT", b"")];
+    assert_eq!(
+        decode_png(&png((2, 2), 8, 0, &critical, &rows)),
+        Err("unknown critical chunk")
+    );
+
+    let mut no_header = PNG_SIGNATURE.to_vec();
+    chunk(&mut no_header, b"IEND", &[]);
+    assert_eq!(decode_png(&no_header), Err("first chunk isn't IHDR"));
+
+    assert_eq!(
+        decode_png(&png((2, 2), 8, 0, &[], &rows[..5])),
+        Err("image data is the wrong size")
+    );
+}
+
+/// A TGA file with a two byte image ID.
+fn tga(
+    image_type: u8,
+    pixel_depth: u8,
+    descriptor: u8,
+    (width, height): (u16, u16),
+    color_map: Option<(u16, u8, &[u8])>,
+    data: &[u8],
+) -> Vec<u8> {
+    let (first, depth, entries) = color_map.unwrap_or((0, 0, &[]));
+    let entry_size = (depth as usize).div_ceil(8).max(1);
+    let mut tga = vec![2, color_map.is_some() as u8, image_type];
+    tga.extend_from_slice(&first.to_le_bytes());
+    tga.extend_from_slice(&((entries.len() / entry_size) as u16).to_le_bytes());
+    tga.extend_from_slice(&[depth, 0, 0, 0, 0]);
+    tga.extend_from_slice(&width.to_le_bytes());
+    tga.extend_from_slice(&height.to_le_bytes());
+    tga.extend_from_slice(&[pixel_depth, descriptor]);
+    tga.extend_from_slice(b"id");
+    tga.extend_from_slice(entries);
+    tga.extend_from_slice(data);
+    tga
+}
+
+fn decode_tga(bytes: &[u8]) -> Result<Vec<u8>, &'static str> {
+    tga::decode(bytes).map(|image| image.pixels)
+}
+
+#[test]
+fn tga_origin_flags() {
+    // BGR pixels with red 1, 2, 3 and 4, in the order they are stored.
+    let data = [0, 0, 1, 0, 0, 2, 0, 0, 3, 0, 0, 4];
+    let texels =
+        |order: [u8; 4]| -> Vec<u8> { order.iter().flat_map(|&r| [r, 0, 0, 255]).collect() };
+
+    // Bottom to top by default.
+    assert_eq!(
+        decode_tga(&tga(2, 24, 0, (2, 2), None, &data)).unwrap(),
+        texels([3, 4, 1, 2])
+    );
+    assert_eq!(
+        decode_tga(&tga(2, 24, 0x20, (2, 2), None, &data)).unwrap(),
+        texels([1, 2, 3, 4])
+    );
+    assert_eq!(
+        decode_tga(&tga(2, 24, 0x10, (2, 2), None, &data)).unwrap(),
+        texels([4, 3, 2, 1])
+    );
+    assert_eq!(
+        decode_tga(&tga(2, 24, 0x30, (2, 2), None, &data)).unwrap(),
+        texels([2, 1, 4, 3])
+    );
+}
+
+#[test]
+fn tga_run_length_encoding() {
+    // BGRA with eight alpha bits, top to bottom. A run of four pixels goes on
+    // into the second row, then two pixels are stored as they are.
+    let data = [0x83, 1, 2, 3, 4, 0x01, 5, 6, 7, 8, 9, 10, 11, 12];
+    let file = tga(10, 32, 0x28, (3, 2), None, &data);
+    assert_eq!(
+        decode_tga(&file).unwrap(),
+        [
+            [3, 2, 1, 4],
+            [3, 2, 1, 4],
+            [3, 2, 1, 4],
+            [3, 2, 1, 4],
+            [7, 6, 5, 8],
+            [11, 10, 9, 12]
+        ]
+        .concat()
+    );
+    all_prefixes_fail(&file, decode_tga);
+
+    // Without alpha bits in the descriptor, the fourth byte is ignored.
+    let file = tga(10, 32, 0x20, (3, 2), None, &data);
+    assert_eq!(decode_tga(&file).unwrap()[..4], [3, 2, 1, 255]);
+}
+
+#[test]
+fn tga_color_maps_and_grayscale() {
+    // Entries 2 to 4 of a BGR color map.
+    let color_map = [0, 0, 10, 0, 0, 20, 0, 0, 30];
+    let file = tga(1, 8, 0x20, (2, 2), Some((2, 24, &color_map)), &[2, 3, 4, 2]);
+    assert_eq!(
+        decode_tga(&file).unwrap(),
+        [
+            [10, 0, 0, 255],
+            [20, 0, 0, 255],
+            [30, 0, 0, 255],
+            [10, 0, 0, 255]
+        ]
+        .concat()
+    );
+    let file = tga(1, 8, 0x20, (2, 2), Some((2, 24, &color_map)), &[2, 3, 1, 2]);
+    assert_eq!(decode_tga(&file), Err("color map index out of range"));
+    all_prefixes_fail(&file, decode_tga);
+
+    // Gray and alpha.
+    let file = tga(3, 16, 0x28, (2, 1), None, &[50, 100, 200, 255]);
+    assert_eq!(
+        decode_tga(&file).unwrap(),
+        [50, 50, 50, 100, 200, 200, 200, 255]
+    );
+
+    // ARRRRRGG GGGBBBBB, with the top bit clear making the pixel transparent
+    // when the descriptor has an alpha bit.
+    let file = tga(2, 16, 0x21, (2, 1), None, &[0x00, 0x7c, 0x1f, 0x80]);
+    assert_eq!(decode_tga(&file).unwrap(), [255, 0, 0, 0, 0, 0, 255, 255]);
+}
+
+#[test]
+fn read_makes_a_texture() {
+    let file = png((2, 1), 8, 0, &[], &unfiltered(&[&[1, 2]]));
+    let texture = read(&file, true).unwrap();
+    assert_eq!(
+        texture.desc,
+        TextureDesc::tex2d(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, 2, 1)
+    );
+    assert_eq!(texture.subresources.len(), 1);
+    assert_eq!(texture.subresources[0].row_pitch, 8);
+    assert_eq!(texture.subresources[0].slice_pitch, 8);
+    assert_eq!(texture.subresources[0].data, gray(&[1, 2]));
+
+    let file = tga(3, 8, 0, (2, 1), None, &[1, 2]);
+    let texture = read(&file, false).unwrap();
+    assert_eq!(texture.desc.format, DXGI_FORMAT_R8G8B8A8_UNORM);
+    assert_eq!(texture.subresources[0].data, gray(&[1, 2]));
+
+    assert!(matches!(
+        read(&[0; 32], false),
+        Err(ImageError::UnknownFormat)
+    ));
+    assert!(matches!(
+        read(&png((0, 1), 8, 0, &[], &[]), false),
+        Err(ImageError::InvalidPng("empty image"))
+    ));
+    assert!(matches!(
+        read(&tga(3, 8, 0, (16385, 1), None, &[]), false),
+        Err(ImageError::InvalidTga("larger than a 2D texture can be"))
+    ));
+}
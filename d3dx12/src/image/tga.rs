@@ -0,0 +1,176 @@
+//! Decodes TGA files: color-mapped, true-color and grayscale images, either
+//! stored as they are or run-length encoded.
+
+use super::{check_size, Rgba8};
+
+pub type Result<T> = std::result::Result<T, &'static str>;
+
+const HEADER_SIZE: usize = 18;
+
+const TYPE_COLOR_MAPPED: u8 = 1;
+const TYPE_TRUE_COLOR: u8 = 2;
+const TYPE_GRAYSCALE: u8 = 3;
+const TYPE_RLE: u8 = 8;
+
+const DESCRIPTOR_ALPHA_BITS: u8 = 0x0f;
+const DESCRIPTOR_RIGHT_TO_LEFT: u8 = 0x10;
+const DESCRIPTOR_TOP_TO_BOTTOM: u8 = 0x20;
+
+/// Whether `bytes` starts with a header that `decode` could read. TGA files
+/// have no signature, so this only rules out what can't be one.
+pub fn is_tga(bytes: &[u8]) -> bool {
+    let header = match bytes.get(..HEADER_SIZE) {
+        Some(header) => header,
+        None => return false,
+    };
+    let color_map_type = header[1];
+    let image_type = header[2] & !TYPE_RLE;
+    let pixel_depth = header[16];
+    match image_type {
+        TYPE_COLOR_MAPPED => color_map_type == 1 && [8, 16].contains(&pixel_depth),
+        TYPE_TRUE_COLOR => color_map_type <= 1 && [15, 16, 24, 32].contains(&pixel_depth),
+        TYPE_GRAYSCALE => color_map_type <= 1 && [8, 16].contains(&pixel_depth),
+        _ => false,
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Rgba8> {
+    if !is_tga(bytes) {
+        return Err("not a TGA file");
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+    let id_length = bytes[0] as usize;
+    let color_map_type = bytes[1];
+    let image_type = bytes[2];
+    let color_map_first = u16_at(3) as usize;
+    let color_map_length = u16_at(5) as usize;
+    let color_map_depth = bytes[7];
+    let width = u16_at(12) as usize;
+    let height = u16_at(14) as usize;
+    let pixel_depth = bytes[16];
+    let descriptor = bytes[17];
+    check_size(width as u32, height as u32)?;
+
+    let has_alpha = descriptor & DESCRIPTOR_ALPHA_BITS != 0;
+    let mut offset = HEADER_SIZE + id_length;
+
+    // The color map is there whenever the header says it is, even for images
+    // that don't use it.
+    let mut palette = Vec::new();
+    if color_map_type == 1 {
+        let entry_size = (color_map_depth as usize).div_ceil(8);
+        if ![15, 16, 24, 32].contains(&color_map_depth) {
+            return Err("unsupported color map entry size");
+        }
+        let data = bytes
+            .get(offset..offset + color_map_length * entry_size)
+            .ok_or("truncated color map")?;
+        palette = data
+            .chunks_exact(entry_size)
+            .map(|entry| to_rgba(entry, color_map_depth, has_alpha))
+            .collect();
+        offset += data.len();
+    }
+
+    let pixel_size = (pixel_depth as usize).div_ceil(8);
+    let count = width * height;
+    let mut raw = Vec::with_capacity(count * pixel_size);
+    let mut data = bytes.get(offset..).unwrap_or_default();
+    if image_type & TYPE_RLE != 0 {
+        // Packets may run on from one row into the next.
+        while raw.len() < count * pixel_size {
+            let (&packet, rest) = data.split_first().ok_or("truncated image data")?;
+            let length = (packet & 0x7f) as usize + 1;
+            if packet & 0x80 != 0 {
+                let pixel = rest.get(..pixel_size).ok_or("truncated image data")?;
+                for _ in 0..length {
+                    raw.extend_from_slice(pixel);
+                }
+                data = &rest[pixel_size..];
+            } else {
+                let pixels = rest
+                    .get(..length * pixel_size)
+                    .ok_or("truncated image data")?;
+                raw.extend_from_slice(pixels);
+                data = &rest[pixels.len()..];
+            }
+        }
+        raw.truncate(count * pixel_size);
+    } else {
+        raw.extend_from_slice(
+            data.get(..count * pixel_size)
+                .ok_or("truncated image data")?,
+        );
+    }
+
+    let mut image = Rgba8 {
+        width: width as u32,
+        height: height as u32,
+        pixels: vec![0; count * 4],
+    };
+    for (index, pixel) in raw.chunks_exact(pixel_size).enumerate() {
+        let color = match image_type & !TYPE_RLE {
+            TYPE_COLOR_MAPPED => {
+                let index = if pixel_size == 2 {
+                    u16::from_le_bytes([pixel[0], pixel[1]]) as usize
+                } else {
+                    pixel[0] as usize
+                };
+                *index
+                    .checked_sub(color_map_first)
+                    .and_then(|index| palette.get(index))
+                    .ok_or("color map index out of range")?
+            }
+            TYPE_GRAYSCALE => {
+                let alpha = if pixel_size == 2 { pixel[1] } else { 255 };
+                [pixel[0], pixel[0], pixel[0], alpha]
+            }
+            _ => to_rgba(pixel, pixel_depth, has_alpha),
+        };
+
+        // Rows go from the bottom up unless the descriptor says otherwise.
+        let (x, y) = (index % width, index / width);
+        let x = if descriptor & DESCRIPTOR_RIGHT_TO_LEFT != 0 {
+            width - 1 - x
+        } else {
+            x
+        };
+        let y = if descriptor & DESCRIPTOR_TOP_TO_BOTTOM != 0 {
+            y
+        } else {
+            height - 1 - y
+        };
+        let texel = (y * width + x) * 4;
+        image.pixels[texel..texel + 4].copy_from_slice(&color);
+    }
+    Ok(image)
+}
+
+/// Converts a true-color pixel or color map entry, which is stored as BGR or
+/// BGRA, or packed into 16 bits as ARRRRRGG GGGBBBBB.
+fn to_rgba(pixel: &[u8], depth: u8, has_alpha: bool) -> [u8; 4] {
+    match depth {
+        15 | 16 => {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+            let expand = |bits: u16| ((bits & 0x1f) * 255 / 31) as u8;
+            let alpha = if depth == 16 && has_alpha && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [
+                expand(value >> 10),
+                expand(value >> 5),
+                expand(value),
+                alpha,
+            ]
+        }
+        24 => [pixel[2], pixel[1], pixel[0], 255],
+        _ => [
+            pixel[2],
+            pixel[1],
+            pixel[0],
+            if has_alpha { pixel[3] } else { 255 },
+        ],
+    }
+}
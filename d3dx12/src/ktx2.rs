@@ -0,0 +1,452 @@
+//! Reads KTX2 files: the level index, the basic Data Format Descriptor, and
+//! levels that are stored as they are or supercompressed with Zstandard.
+//!
+//! KTX2 stores each mip level as one block holding every array layer, face
+//! and depth slice, with the rows tightly packed. `read` splits those blocks
+//! up into subresources in the order D3D12 numbers them.
+
+use crate::format::*;
+use crate::texture::{
+    mip_size, FormatLayout, SubresourceData, TextureData, TextureDesc, TextureDimension,
+};
+use std::{borrow::Cow, convert::TryInto, fmt, fs, io, path::Path};
+
+mod zstd;
+
+pub const KTX2_MAGIC: &[u8; 12] = b"\xabKTX 20\xbb\r\n\x1a\n";
+
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+const DFD_BASIC_BLOCK_SIZE: usize = 24;
+const KHR_DF_VENDORID_KHRONOS: u32 = 0;
+const KHR_DF_KHR_DESCRIPTORTYPE_BASICFORMAT: u32 = 0;
+const KHR_DF_VERSIONNUMBER_1_3: u32 = 2;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+
+#[derive(Debug)]
+pub enum Ktx2Error {
+    Io(io::Error),
+    NotKtx2,
+    InvalidHeader(&'static str),
+    InvalidDfd(&'static str),
+    /// A `vkFormat` with no DXGI equivalent. 0 is `VK_FORMAT_UNDEFINED`,
+    /// which Basis Universal textures use.
+    UnsupportedVkFormat(u32),
+    UnsupportedFormat(DXGI_FORMAT),
+    UnsupportedSupercompression(u32),
+    /// The level index puts `level` outside the file.
+    Truncated {
+        level: usize,
+        expected: u64,
+        actual: u64,
+    },
+    /// `level` doesn't hold the data that the header describes.
+    LevelSize {
+        level: usize,
+        expected: u64,
+        actual: u64,
+    },
+    Zstd {
+        level: usize,
+        message: &'static str,
+    },
+}
+
+impl fmt::Display for Ktx2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ktx2Error::Io(e) => write!(f, "{}", e),
+            Ktx2Error::NotKtx2 => write!(f, "not a KTX2 file"),
+            Ktx2Error::InvalidHeader(message) => write!(f, "invalid KTX2 header: {}", message),
+            Ktx2Error::InvalidDfd(message) => {
+                write!(f, "invalid data format descriptor: {}", message)
+            }
+            Ktx2Error::UnsupportedVkFormat(format) => {
+                write!(f, "unsupported vkFormat {}", format)
+            }
+            Ktx2Error::UnsupportedFormat(format) => {
+                write!(f, "unsupported DXGI format {}", format.0)
+            }
+            Ktx2Error::UnsupportedSupercompression(scheme) => {
+                write!(f, "unsupported supercompression scheme {}", scheme)
+            }
+            Ktx2Error::Truncated {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "level {} needs the file to be {} bytes but it is {}",
+                level, expected, actual
+            ),
+            Ktx2Error::LevelSize {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "level {} should be {} bytes but it is {}",
+                level, expected, actual
+            ),
+            Ktx2Error::Zstd { level, message } => {
+                write!(f, "level {} doesn't decompress: {}", level, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Ktx2Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Ktx2Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Ktx2Error {
+    fn from(error: io::Error) -> Self {
+        Ktx2Error::Io(error)
+    }
+}
+
+impl From<Ktx2Error> for io::Error {
+    fn from(error: Ktx2Error) -> Self {
+        match error {
+            Ktx2Error::Io(e) => e,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<TextureData<'static>, Ktx2Error> {
+    let bytes = fs::read(path)?;
+    Ok(read(&bytes)?.into_owned())
+}
+
+/// Reads a KTX2 file that is already in memory. Subresources of levels that
+/// aren't supercompressed borrow from `bytes`.
+pub fn read(bytes: &[u8]) -> Result<TextureData<'_>, Ktx2Error> {
+    if !bytes.starts_with(KTX2_MAGIC) {
+        return Err(Ktx2Error::NotKtx2);
+    }
+    let header = bytes
+        .get(..HEADER_SIZE)
+        .ok_or(Ktx2Error::InvalidHeader("truncated header"))?;
+    let field = |index: usize| {
+        let offset = KTX2_MAGIC.len() + index * 4;
+        u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap())
+    };
+
+    let vk_format = field(0);
+    let width = field(2);
+    let height = field(3);
+    let depth = field(4);
+    let layer_count = field(5);
+    let face_count = field(6);
+    let level_count = field(7);
+    let supercompression = field(8);
+    let dfd_offset = field(9) as usize;
+    let dfd_length = field(10) as usize;
+
+    let format = vk_format_to_dxgi(vk_format).ok_or(Ktx2Error::UnsupportedVkFormat(vk_format))?;
+    let layout = FormatLayout::of(format).ok_or(Ktx2Error::UnsupportedFormat(format))?;
+    if supercompression != SUPERCOMPRESSION_NONE && supercompression != SUPERCOMPRESSION_ZSTD {
+        return Err(Ktx2Error::UnsupportedSupercompression(supercompression));
+    }
+
+    // Heights and depths of 0 mark 1D and 2D textures, and a layer count of
+    // 0 a texture that isn't an array.
+    if width == 0 {
+        return Err(Ktx2Error::InvalidHeader("empty texture"));
+    }
+    let dimension = if depth > 0 {
+        if height == 0 {
+            return Err(Ktx2Error::InvalidHeader("volume texture with no height"));
+        }
+        if layer_count > 1 || face_count != 1 {
+            return Err(Ktx2Error::InvalidHeader(
+                "arrays of volume textures and volume cube maps",
+            ));
+        }
+        TextureDimension::Texture3D
+    } else if height > 0 {
+        TextureDimension::Texture2D
+    } else {
+        TextureDimension::Texture1D
+    };
+    let height = height.max(1);
+    let depth = depth.max(1);
+    let is_cube = match face_count {
+        1 => false,
+        6 if dimension == TextureDimension::Texture2D && width == height => true,
+        6 => {
+            return Err(Ktx2Error::InvalidHeader(
+                "cube map faces that aren't square",
+            ))
+        }
+        _ => return Err(Ktx2Error::InvalidHeader("face count that isn't 1 or 6")),
+    };
+    let array_size = layer_count
+        .max(1)
+        .checked_mul(face_count)
+        .ok_or(Ktx2Error::InvalidHeader("too many array layers"))?;
+
+    // A level count of 0 asks for the mips to be generated when the texture
+    // is loaded; that is left to the caller.
+    let mip_count = level_count.max(1);
+    let full_chain = u32::BITS - width.max(height).max(depth).leading_zeros();
+    if mip_count > full_chain {
+        return Err(Ktx2Error::InvalidHeader("more levels than a full chain"));
+    }
+    let depth_or_array_size = if dimension == TextureDimension::Texture3D {
+        depth
+    } else {
+        array_size
+    };
+    let depth_or_array_size: u16 = depth_or_array_size
+        .try_into()
+        .map_err(|_| Ktx2Error::InvalidHeader("too many array layers or depth slices"))?;
+
+    let dfd = dfd_offset
+        .checked_add(dfd_length)
+        .and_then(|end| bytes.get(dfd_offset..end))
+        .ok_or(Ktx2Error::InvalidDfd("outside the file"))?;
+    check_dfd(dfd, format, &layout, supercompression)?;
+
+    let level_index = bytes
+        .get(HEADER_SIZE..HEADER_SIZE + mip_count as usize * LEVEL_INDEX_ENTRY_SIZE)
+        .ok_or(Ktx2Error::InvalidHeader("truncated level index"))?;
+    let mut levels = Vec::with_capacity(mip_count as usize);
+    for (mip, entry) in level_index.chunks_exact(LEVEL_INDEX_ENTRY_SIZE).enumerate() {
+        let field =
+            |index: usize| u64::from_le_bytes(entry[index * 8..index * 8 + 8].try_into().unwrap());
+        let (offset, length, uncompressed_length) = (field(0), field(1), field(2));
+
+        let end = offset.saturating_add(length);
+        let data = bytes
+            .get(offset.try_into().unwrap_or(usize::MAX)..end.try_into().unwrap_or(usize::MAX))
+            .ok_or(Ktx2Error::Truncated {
+                level: mip,
+                expected: end,
+                actual: bytes.len() as u64,
+            })?;
+
+        let mip = mip as u32;
+        let row_pitch = layout.row_pitch(mip_size(width, mip));
+        let slice_pitch = row_pitch.saturating_mul(layout.num_rows(mip_size(height, mip)) as u64);
+        let expected = slice_pitch
+            .saturating_mul(mip_size(depth, mip) as u64)
+            .saturating_mul(array_size as u64);
+        if uncompressed_length != expected {
+            return Err(Ktx2Error::LevelSize {
+                level: mip as usize,
+                expected,
+                actual: uncompressed_length,
+            });
+        }
+
+        let data = if supercompression == SUPERCOMPRESSION_ZSTD {
+            let data = zstd::decompress(data, expected.try_into().unwrap_or(usize::MAX)).map_err(
+                |message| Ktx2Error::Zstd {
+                    level: mip as usize,
+                    message,
+                },
+            )?;
+            Cow::Owned(data)
+        } else {
+            Cow::Borrowed(data)
+        };
+        if data.len() as u64 != expected {
+            return Err(Ktx2Error::LevelSize {
+                level: mip as usize,
+                expected,
+                actual: data.len() as u64,
+            });
+        }
+        levels.push((data, row_pitch, slice_pitch));
+    }
+
+    // Within a level the layers come first, then the faces of each layer,
+    // which matches D3D12's numbering of array slices.
+    let mut subresources = Vec::with_capacity(array_size as usize * levels.len());
+    for slice in 0..array_size as usize {
+        for (mip, (data, row_pitch, slice_pitch)) in levels.iter().enumerate() {
+            let size = (*slice_pitch * mip_size(depth, mip as u32) as u64) as usize;
+            let range = slice * size..(slice + 1) * size;
+            subresources.push(SubresourceData {
+                data: match data {
+                    Cow::Borrowed(data) => Cow::Borrowed(&data[range]),
+                    Cow::Owned(data) => Cow::Owned(data[range].to_vec()),
+                },
+                row_pitch: *row_pitch,
+                slice_pitch: *slice_pitch,
+            });
+        }
+    }
+
+    Ok(TextureData {
+        desc: TextureDesc {
+            dimension,
+            format,
+            width,
+            height,
+            depth_or_array_size,
+            mip_levels: mip_count as u16,
+        },
+        is_cube,
+        subresources,
+    })
+}
+
+/// Checks that the basic descriptor block agrees with the `vkFormat`: the
+/// same block size, the same number of bytes in a block, and an sRGB
+/// transfer function only for sRGB formats.
+fn check_dfd(
+    dfd: &[u8],
+    format: DXGI_FORMAT,
+    layout: &FormatLayout,
+    supercompression: u32,
+) -> Result<(), Ktx2Error> {
+    let word = |offset: usize| {
+        dfd.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or(Ktx2Error::InvalidDfd("truncated"))
+    };
+    if word(0)? as usize != dfd.len() {
+        return Err(Ktx2Error::InvalidDfd("total size doesn't match the index"));
+    }
+
+    let block = dfd.get(4..).unwrap_or_default();
+    let header = word(4)?;
+    if header & 0x1ffff != KHR_DF_VENDORID_KHRONOS
+        || header >> 17 != KHR_DF_KHR_DESCRIPTORTYPE_BASICFORMAT
+    {
+        return Err(Ktx2Error::InvalidDfd(
+            "first block isn't a basic descriptor",
+        ));
+    }
+    let version_and_size = word(8)?;
+    let block_size = (version_and_size >> 16) as usize;
+    if version_and_size & 0xffff != KHR_DF_VERSIONNUMBER_1_3 {
+        return Err(Ktx2Error::InvalidDfd("unsupported descriptor version"));
+    }
+    if block_size < DFD_BASIC_BLOCK_SIZE || block_size > block.len() {
+        return Err(Ktx2Error::InvalidDfd("basic descriptor is the wrong size"));
+    }
+
+    let transfer_function = block[10];
+    let block_width = block[12] as u32 + 1;
+    let block_height = block[13] as u32 + 1;
+    let bytes_plane0 = block[16] as u32;
+    if block_width != layout.block_width || block_height != layout.block_height {
+        return Err(Ktx2Error::InvalidDfd(
+            "texel block size doesn't match vkFormat",
+        ));
+    }
+    // Supercompressed files may leave the plane sizes as 0.
+    if (supercompression == SUPERCOMPRESSION_NONE || bytes_plane0 != 0)
+        && bytes_plane0 != layout.bytes_per_block
+    {
+        return Err(Ktx2Error::InvalidDfd(
+            "bytes per block doesn't match vkFormat",
+        ));
+    }
+    if (transfer_function == KHR_DF_TRANSFER_SRGB) != is_srgb(format) {
+        return Err(Ktx2Error::InvalidDfd(
+            "transfer function doesn't match vkFormat",
+        ));
+    }
+    Ok(())
+}
+
+fn is_srgb(format: DXGI_FORMAT) -> bool {
+    matches!(
+        format,
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+            | DXGI_FORMAT_BC1_UNORM_SRGB
+            | DXGI_FORMAT_BC2_UNORM_SRGB
+            | DXGI_FORMAT_BC3_UNORM_SRGB
+            | DXGI_FORMAT_BC7_UNORM_SRGB
+    )
+}
+
+/// Maps the `VkFormat` values that have a DXGI equivalent with the same
+/// memory layout.
+fn vk_format_to_dxgi(vk_format: u32) -> Option<DXGI_FORMAT> {
+    Some(match vk_format {
+        4 => DXGI_FORMAT_B5G6R5_UNORM,   // VK_FORMAT_R5G6B5_UNORM_PACK16
+        8 => DXGI_FORMAT_B5G5R5A1_UNORM, // VK_FORMAT_A1R5G5B5_UNORM_PACK16
+        9 => DXGI_FORMAT_R8_UNORM,
+        10 => DXGI_FORMAT_R8_SNORM,
+        13 => DXGI_FORMAT_R8_UINT,
+        14 => DXGI_FORMAT_R8_SINT,
+        16 => DXGI_FORMAT_R8G8_UNORM,
+        17 => DXGI_FORMAT_R8G8_SNORM,
+        20 => DXGI_FORMAT_R8G8_UINT,
+        21 => DXGI_FORMAT_R8G8_SINT,
+        37 => DXGI_FORMAT_R8G8B8A8_UNORM,
+        38 => DXGI_FORMAT_R8G8B8A8_SNORM,
+        41 => DXGI_FORMAT_R8G8B8A8_UINT,
+        42 => DXGI_FORMAT_R8G8B8A8_SINT,
+        43 => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        44 => DXGI_FORMAT_B8G8R8A8_UNORM,
+        50 => DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        64 => DXGI_FORMAT_R10G10B10A2_UNORM, // VK_FORMAT_A2B10G10R10_UNORM_PACK32
+        68 => DXGI_FORMAT_R10G10B10A2_UINT,  // VK_FORMAT_A2B10G10R10_UINT_PACK32
+        70 => DXGI_FORMAT_R16_UNORM,
+        71 => DXGI_FORMAT_R16_SNORM,
+        74 => DXGI_FORMAT_R16_UINT,
+        75 => DXGI_FORMAT_R16_SINT,
+        76 => DXGI_FORMAT_R16_FLOAT,
+        77 => DXGI_FORMAT_R16G16_UNORM,
+        78 => DXGI_FORMAT_R16G16_SNORM,
+        81 => DXGI_FORMAT_R16G16_UINT,
+        82 => DXGI_FORMAT_R16G16_SINT,
+        83 => DXGI_FORMAT_R16G16_FLOAT,
+        91 => DXGI_FORMAT_R16G16B16A16_UNORM,
+        92 => DXGI_FORMAT_R16G16B16A16_SNORM,
+        95 => DXGI_FORMAT_R16G16B16A16_UINT,
+        96 => DXGI_FORMAT_R16G16B16A16_SINT,
+        97 => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        98 => DXGI_FORMAT_R32_UINT,
+        99 => DXGI_FORMAT_R32_SINT,
+        100 => DXGI_FORMAT_R32_FLOAT,
+        101 => DXGI_FORMAT_R32G32_UINT,
+        102 => DXGI_FORMAT_R32G32_SINT,
+        103 => DXGI_FORMAT_R32G32_FLOAT,
+        104 => DXGI_FORMAT_R32G32B32_UINT,
+        105 => DXGI_FORMAT_R32G32B32_SINT,
+        106 => DXGI_FORMAT_R32G32B32_FLOAT,
+        107 => DXGI_FORMAT_R32G32B32A32_UINT,
+        108 => DXGI_FORMAT_R32G32B32A32_SINT,
+        109 => DXGI_FORMAT_R32G32B32A32_FLOAT,
+        122 => DXGI_FORMAT_R11G11B10_FLOAT, // VK_FORMAT_B10G11R11_UFLOAT_PACK32
+        123 => DXGI_FORMAT_R9G9B9E5_SHAREDEXP, // VK_FORMAT_E5B9G9R9_UFLOAT_PACK32
+        124 => DXGI_FORMAT_D16_UNORM,
+        126 => DXGI_FORMAT_D32_FLOAT,
+        131 | 133 => DXGI_FORMAT_BC1_UNORM,
+        132 | 134 => DXGI_FORMAT_BC1_UNORM_SRGB,
+        135 => DXGI_FORMAT_BC2_UNORM,
+        136 => DXGI_FORMAT_BC2_UNORM_SRGB,
+        137 => DXGI_FORMAT_BC3_UNORM,
+        138 => DXGI_FORMAT_BC3_UNORM_SRGB,
+        139 => DXGI_FORMAT_BC4_UNORM,
+        140 => DXGI_FORMAT_BC4_SNORM,
+        141 => DXGI_FORMAT_BC5_UNORM,
+        142 => DXGI_FORMAT_BC5_SNORM,
+        143 => DXGI_FORMAT_BC6H_UF16,
+        144 => DXGI_FORMAT_BC6H_SF16,
+        145 => DXGI_FORMAT_BC7_UNORM,
+        146 => DXGI_FORMAT_BC7_UNORM_SRGB,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests;
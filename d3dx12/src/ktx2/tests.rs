@@ -0,0 +1,238 @@
+use super::*;
+
+const RGBA8: &[u8] = include_bytes!("../../tests/fixtures/ktx2/rgba8.ktx2");
+const RGBA8_ZSTD: &[u8] = include_bytes!("../../tests/fixtures/ktx2/rgba8_zstd.ktx2");
+
+/// Where the first level index entry and the basic descriptor block are in
+/// both fixtures.
+const LEVEL_INDEX: usize = HEADER_SIZE;
+const DFD_BLOCK: usize = HEADER_SIZE + 4 * LEVEL_INDEX_ENTRY_SIZE + 4;
+
+/// The texels that the fixtures hold, as the README describes them.
+fn level(layer: u32, mip: u32) -> Vec<u8> {
+    let size = 8 >> mip;
+    (0..size)
+        .flat_map(|y| {
+            (0..size).flat_map(move |x| {
+                [
+                    x as u8 * 30,
+                    y as u8 * 30,
+                    (layer * 100 + mip * 40) as u8,
+                    255,
+                ]
+            })
+        })
+        .collect()
+}
+
+fn set_u32(file: &mut [u8], offset: usize, value: u32) {
+    file[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn set_u64(file: &mut [u8], offset: usize, value: u64) {
+    file[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn header_field(index: usize) -> usize {
+    KTX2_MAGIC.len() + index * 4
+}
+
+fn read_modified(
+    file: &[u8],
+    modify: impl FnOnce(&mut Vec<u8>),
+) -> Result<TextureData<'static>, Ktx2Error> {
+    let mut file = file.to_vec();
+    modify(&mut file);
+    read(&file).map(TextureData::into_owned)
+}
+
+#[test]
+fn uncompressed_levels() {
+    let texture = read(RGBA8).unwrap();
+    assert_eq!(
+        texture.desc,
+        TextureDesc {
+            dimension: TextureDimension::Texture2D,
+            format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            width: 8,
+            height: 8,
+            depth_or_array_size: 2,
+            mip_levels: 4,
+        }
+    );
+    assert!(!texture.is_cube);
+    assert_eq!(texture.subresources.len(), 8);
+    for layer in 0..2 {
+        for mip in 0..4 {
+            let subresource = texture.subresource(mip, layer).unwrap();
+            assert!(matches!(subresource.data, Cow::Borrowed(_)));
+            assert_eq!(subresource.data[..], level(layer, mip)[..]);
+            assert_eq!(subresource.row_pitch, 32 >> mip);
+            assert_eq!(subresource.slice_pitch, 256 >> (2 * mip));
+        }
+    }
+}
+
+#[test]
+fn zstd_supercompressed_levels() {
+    let compressed = read(RGBA8_ZSTD).unwrap();
+    let uncompressed = read(RGBA8).unwrap();
+    assert_eq!(compressed.desc, uncompressed.desc);
+    for (compressed, uncompressed) in compressed
+        .subresources
+        .iter()
+        .zip(&uncompressed.subresources)
+    {
+        assert!(matches!(compressed.data, Cow::Owned(_)));
+        assert_eq!(compressed.data, uncompressed.data);
+        assert_eq!(compressed.row_pitch, uncompressed.row_pitch);
+        assert_eq!(compressed.slice_pitch, uncompressed.slice_pitch);
+    }
+}
+
+#[test]
+fn header_errors() {
+    assert!(matches!(read(&RGBA8[..11]), Err(Ktx2Error::NotKtx2)));
+    assert!(matches!(
+        read(&RGBA8[..HEADER_SIZE - 1]),
+        Err(Ktx2Error::InvalidHeader(_))
+    ));
+
+    let result = read_modified(RGBA8, |file| set_u32(file, header_field(0), 0));
+    assert!(matches!(result, Err(Ktx2Error::UnsupportedVkFormat(0))));
+    // BasisLZ.
+    let result = read_modified(RGBA8, |file| set_u32(file, header_field(8), 1));
+    assert!(matches!(
+        result,
+        Err(Ktx2Error::UnsupportedSupercompression(1))
+    ));
+    let result = read_modified(RGBA8, |file| set_u32(file, header_field(6), 3));
+    assert!(matches!(result, Err(Ktx2Error::InvalidHeader(_))));
+    let result = read_modified(RGBA8, |file| {
+        set_u32(file, header_field(3), 4);
+        set_u32(file, header_field(6), 6);
+    });
+    assert!(matches!(result, Err(Ktx2Error::InvalidHeader(_))));
+}
+
+#[test]
+fn level_index() {
+    // 8x8 has four levels at most.
+    let result = read_modified(RGBA8, |file| set_u32(file, header_field(7), 5));
+    assert!(matches!(result, Err(Ktx2Error::InvalidHeader(_))));
+
+    // A level count of 0 leaves the mips to the caller, so only the first
+    // entry is read.
+    let texture = read_modified(RGBA8, |file| set_u32(file, header_field(7), 0)).unwrap();
+    assert_eq!(texture.desc.mip_levels, 1);
+    assert_eq!(texture.subresource(0, 1).unwrap().data, level(1, 0));
+
+    // Level 0 is stored last.
+    match read(&RGBA8[..RGBA8.len() - 1]) {
+        Err(Ktx2Error::Truncated {
+            level: 0,
+            expected,
+            actual,
+        }) => {
+            assert_eq!(
+                (expected, actual),
+                (RGBA8.len() as u64, RGBA8.len() as u64 - 1)
+            )
+        }
+        result => panic!("{:?}", result.map(|texture| texture.desc)),
+    }
+
+    let result = read_modified(RGBA8, |file| set_u64(file, LEVEL_INDEX + 16, 511));
+    assert!(matches!(
+        result,
+        Err(Ktx2Error::LevelSize {
+            level: 0,
+            expected: 512,
+            actual: 511
+        })
+    ));
+    // The stored size disagreeing with the uncompressed size.
+    let result = read_modified(RGBA8, |file| set_u64(file, LEVEL_INDEX + 8, 508));
+    assert!(matches!(
+        result,
+        Err(Ktx2Error::LevelSize {
+            level: 0,
+            expected: 512,
+            actual: 508
+        })
+    ));
+}
+
+#[test]
+fn data_format_descriptor() {
+    let invalid_dfd = |file: &[u8], modify: fn(&mut Vec<u8>)| {
+        matches!(read_modified(file, modify), Err(Ktx2Error::InvalidDfd(_)))
+    };
+    // Offset and length in the index.
+    assert!(invalid_dfd(RGBA8, |file| set_u32(
+        file,
+        header_field(9),
+        4096
+    )));
+    assert!(invalid_dfd(RGBA8, |file| set_u32(
+        file,
+        header_field(10),
+        80
+    )));
+    // Vendor, version, transfer function, texel block size and bytes per
+    // block.
+    assert!(invalid_dfd(RGBA8, |file| file[DFD_BLOCK] = 1));
+    assert!(invalid_dfd(RGBA8, |file| file[DFD_BLOCK + 4] = 0));
+    assert!(invalid_dfd(RGBA8, |file| file[DFD_BLOCK + 10] = KHR_DF_TRANSFER_SRGB));
+    assert!(invalid_dfd(RGBA8, |file| file[DFD_BLOCK + 12] = 3));
+    assert!(invalid_dfd(RGBA8, |file| file[DFD_BLOCK + 16] = 8));
+
+    // Supercompressed files leave the bytes per block out.
+    assert_eq!(RGBA8_ZSTD[DFD_BLOCK + 16], 0);
+    assert!(invalid_dfd(RGBA8_ZSTD, |file| file[DFD_BLOCK + 16] = 8));
+
+    // An sRGB format needs the sRGB transfer function.
+    let result = read_modified(RGBA8, |file| {
+        set_u32(file, header_field(0), 43);
+        file[DFD_BLOCK + 10] = KHR_DF_TRANSFER_SRGB;
+    });
+    assert_eq!(result.unwrap().desc.format, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB);
+    assert!(invalid_dfd(RGBA8, |file| set_u32(
+        file,
+        header_field(0),
+        43
+    )));
+}
+
+#[test]
+fn corrupt_and_truncated_zstd_levels() {
+    let offset = |file: &[u8]| {
+        u64::from_le_bytes(file[LEVEL_INDEX..LEVEL_INDEX + 8].try_into().unwrap()) as usize
+    };
+
+    let result = read_modified(RGBA8_ZSTD, |file| {
+        let offset = offset(file);
+        file[offset] ^= 1;
+    });
+    assert!(matches!(
+        result,
+        Err(Ktx2Error::Zstd {
+            level: 0,
+            message: "not a Zstandard frame"
+        })
+    ));
+
+    // Level 0 is stored last, so cutting it short in the index only loses its
+    // checksum, and then part of its last block.
+    let length = u64::from_le_bytes(
+        RGBA8_ZSTD[LEVEL_INDEX + 8..LEVEL_INDEX + 16]
+            .try_into()
+            .unwrap(),
+    );
+    for cut in [1, 8] {
+        let result = read_modified(RGBA8_ZSTD, |file| {
+            set_u64(file, LEVEL_INDEX + 8, length - cut)
+        });
+        assert!(matches!(result, Err(Ktx2Error::Zstd { level: 0, .. })));
+    }
+}
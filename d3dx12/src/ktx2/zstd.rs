@@ -0,0 +1,980 @@
+//! A Zstandard decoder, as RFC 8878 describes the format, for the
+//! supercompressed levels of KTX2 files. Dictionaries aren't supported, and
+//! checksums are skipped rather than checked.
+
+use std::convert::TryInto;
+
+pub type Result<T> = std::result::Result<T, &'static str>;
+
+const FRAME_MAGIC: u32 = 0xfd2fb528;
+const SKIPPABLE_MAGIC_MASK: u32 = 0xfffffff0;
+const SKIPPABLE_MAGIC: u32 = 0x184d2a50;
+
+const BLOCK_RAW: u32 = 0;
+const BLOCK_RLE: u32 = 1;
+const BLOCK_COMPRESSED: u32 = 2;
+
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+
+/// Decompresses every frame in `input`, one after the other, giving up if
+/// the output grows past `limit` bytes.
+pub fn decompress(input: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut input = input;
+    while !input.is_empty() {
+        let magic = read_u32(input, 0)?;
+        if magic & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+            let size = read_u32(input, 4)? as usize;
+            input = input.get(8 + size..).ok_or("truncated skippable frame")?;
+        } else if magic == FRAME_MAGIC {
+            input = decompress_frame(&input[4..], &mut output, limit)?;
+        } else {
+            return Err("not a Zstandard frame");
+        }
+    }
+    Ok(output)
+}
+
+fn read_u32(input: &[u8], offset: usize) -> Result<u32> {
+    input
+        .get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or("truncated frame header")
+}
+
+fn read_le(input: &[u8], size: usize) -> Result<u64> {
+    let bytes = input.get(..size).ok_or("truncated frame header")?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as u64))
+}
+
+/// Decodes one frame after its magic number, and returns what follows it.
+fn decompress_frame<'a>(input: &'a [u8], output: &mut Vec<u8>, limit: usize) -> Result<&'a [u8]> {
+    let descriptor = *input.first().ok_or("truncated frame header")?;
+    let content_size_flag = descriptor >> 6;
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+    let dictionary_id_flag = descriptor & 0x03;
+    if descriptor & 0x08 != 0 {
+        return Err("reserved frame header bit is set");
+    }
+
+    let mut offset = 1;
+    if !single_segment {
+        // The window size only tells a streaming decoder how much history to
+        // keep; this one keeps all of it.
+        offset += 1;
+    }
+    let dictionary_id_size = [0, 1, 2, 4][dictionary_id_flag as usize];
+    let dictionary_id = read_le(&input[offset.min(input.len())..], dictionary_id_size)?;
+    if dictionary_id != 0 {
+        return Err("dictionaries are not supported");
+    }
+    offset += dictionary_id_size;
+    let content_size_size = match content_size_flag {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let content_size = read_le(&input[offset.min(input.len())..], content_size_size)?;
+    let content_size = if content_size_size == 2 {
+        Some(content_size + 256)
+    } else if content_size_size > 0 {
+        Some(content_size)
+    } else {
+        None
+    };
+    offset += content_size_size;
+
+    let frame_start = output.len();
+    if let Some(size) = content_size {
+        output.reserve((size as usize).min(limit.saturating_sub(output.len())));
+    }
+
+    let mut state = FrameState::default();
+    loop {
+        let header = read_le(&input[offset.min(input.len())..], 3)? as u32;
+        offset += 3;
+        let last = header & 1 != 0;
+        let block_type = (header >> 1) & 3;
+        let size = (header >> 3) as usize;
+        if size > MAX_BLOCK_SIZE {
+            return Err("block is too large");
+        }
+
+        match block_type {
+            BLOCK_RAW => {
+                let block = input.get(offset..offset + size).ok_or("truncated block")?;
+                output.extend_from_slice(block);
+                offset += size;
+            }
+            BLOCK_RLE => {
+                let byte = *input.get(offset).ok_or("truncated block")?;
+                output.resize(output.len() + size, byte);
+                offset += 1;
+            }
+            BLOCK_COMPRESSED => {
+                let block = input.get(offset..offset + size).ok_or("truncated block")?;
+                decompress_block(block, &mut state, output, frame_start)?;
+                offset += size;
+            }
+            _ => return Err("reserved block type"),
+        }
+        if output.len() > limit {
+            return Err("more data than expected");
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    if let Some(size) = content_size {
+        if (output.len() - frame_start) as u64 != size {
+            return Err("frame is not the size its header says");
+        }
+    }
+    if has_checksum {
+        offset += 4;
+    }
+    input.get(offset..).ok_or("truncated checksum")
+}
+
+/// What later blocks in a frame may reuse from earlier ones.
+struct FrameState {
+    huffman: Option<HuffmanTable>,
+    literal_lengths: Option<FseTable>,
+    offsets: Option<FseTable>,
+    match_lengths: Option<FseTable>,
+    repeat_offsets: [usize; 3],
+}
+
+impl Default for FrameState {
+    fn default() -> Self {
+        FrameState {
+            huffman: None,
+            literal_lengths: None,
+            offsets: None,
+            match_lengths: None,
+            repeat_offsets: [1, 4, 8],
+        }
+    }
+}
+
+fn decompress_block(
+    block: &[u8],
+    state: &mut FrameState,
+    output: &mut Vec<u8>,
+    frame_start: usize,
+) -> Result<()> {
+    let (literals, rest) = decode_literals(block, state)?;
+    let sequences = decode_sequences(rest, state)?;
+
+    let mut literals = &literals[..];
+    for sequence in sequences {
+        let (copied, rest) = literals
+            .split_at_checked(sequence.literal_length)
+            .ok_or("sequence uses more literals than there are")?;
+        output.extend_from_slice(copied);
+        literals = rest;
+
+        if sequence.offset == 0 || sequence.offset > output.len() - frame_start {
+            return Err("match offset is before the start of the frame");
+        }
+        let start = output.len() - sequence.offset;
+        if sequence.offset >= sequence.match_length {
+            output.extend_from_within(start..start + sequence.match_length);
+        } else {
+            // The match overlaps what it is writing, so it repeats.
+            for i in 0..sequence.match_length {
+                output.push(output[start + i]);
+            }
+        }
+    }
+    output.extend_from_slice(literals);
+    Ok(())
+}
+
+fn decode_literals<'a>(block: &'a [u8], state: &mut FrameState) -> Result<(Vec<u8>, &'a [u8])> {
+    let byte0 = *block.first().ok_or("truncated literals")? as usize;
+    let block_type = byte0 & 3;
+    let size_format = (byte0 >> 2) & 3;
+    let byte = |i: usize| {
+        block
+            .get(i)
+            .map(|&b| b as usize)
+            .ok_or("truncated literals")
+    };
+
+    if block_type < 2 {
+        let (header_size, size) = match size_format {
+            0 | 2 => (1, byte0 >> 3),
+            1 => (2, (byte0 >> 4) + (byte(1)? << 4)),
+            _ => (3, (byte0 >> 4) + (byte(1)? << 4) + (byte(2)? << 12)),
+        };
+        return if block_type == 0 {
+            let literals = block
+                .get(header_size..header_size + size)
+                .ok_or("truncated literals")?;
+            Ok((literals.to_vec(), &block[header_size + size..]))
+        } else {
+            Ok((
+                vec![byte(header_size)? as u8; size],
+                &block[header_size + 1..],
+            ))
+        };
+    }
+
+    let (header_size, streams, size_bits) = match size_format {
+        0 => (3, 1, 10),
+        1 => (3, 4, 10),
+        2 => (4, 4, 14),
+        _ => (5, 4, 18),
+    };
+    let header = read_le(block, header_size).map_err(|_| "truncated literals")? >> 4;
+    let mask = (1 << size_bits) - 1;
+    let regenerated_size = (header & mask) as usize;
+    let compressed_size = ((header >> size_bits) & mask) as usize;
+    let mut data = block
+        .get(header_size..header_size + compressed_size)
+        .ok_or("truncated literals")?;
+    let rest = &block[header_size + compressed_size..];
+
+    if block_type == 2 {
+        let (table, size) = HuffmanTable::read(data)?;
+        state.huffman = Some(table);
+        data = &data[size..];
+    }
+    let table = state
+        .huffman
+        .as_ref()
+        .ok_or("literals reuse a Huffman table that doesn't exist")?;
+
+    let mut literals = Vec::with_capacity(regenerated_size);
+    if streams == 1 {
+        table.decode(data, regenerated_size, &mut literals)?;
+    } else {
+        let jump = |i: usize| {
+            data.get(i * 2..i * 2 + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                .ok_or("truncated literals")
+        };
+        let sizes = [jump(0)?, jump(1)?, jump(2)?];
+        let mut stream_data = &data[6..];
+        let stream_size = regenerated_size.div_ceil(4);
+        for (i, &size) in sizes.iter().enumerate() {
+            let (stream, rest) = stream_data
+                .split_at_checked(size)
+                .ok_or("truncated literals")?;
+            table.decode(stream, stream_size, &mut literals)?;
+            stream_data = rest;
+            debug_assert_eq!(literals.len(), stream_size * (i + 1));
+        }
+        let last_size = regenerated_size
+            .checked_sub(stream_size * 3)
+            .ok_or("literal streams are the wrong size")?;
+        table.decode(stream_data, last_size, &mut literals)?;
+    }
+    Ok((literals, rest))
+}
+
+struct HuffmanTable {
+    max_bits: u32,
+    /// Indexed by the next `max_bits` bits: the symbol and its length.
+    entries: Vec<(u8, u8)>,
+}
+
+impl HuffmanTable {
+    /// Reads a tree description, and returns the table and the size of the
+    /// description.
+    fn read(data: &[u8]) -> Result<(Self, usize)> {
+        let header = *data.first().ok_or("truncated Huffman tree")? as usize;
+        let (mut weights, size) = if header < 128 {
+            let compressed = data.get(1..1 + header).ok_or("truncated Huffman tree")?;
+            let (table, table_size) = FseTable::read(compressed, 6)?;
+            let weights = table.decode_interleaved(&compressed[table_size..], 255)?;
+            (weights, 1 + header)
+        } else {
+            let count = header - 127;
+            let bytes = data
+                .get(1..1 + count.div_ceil(2))
+                .ok_or("truncated Huffman tree")?;
+            let weights = (0..count)
+                .map(|i| {
+                    let byte = bytes[i / 2];
+                    if i % 2 == 0 {
+                        byte >> 4
+                    } else {
+                        byte & 0xf
+                    }
+                })
+                .collect();
+            (weights, 1 + count.div_ceil(2))
+        };
+
+        // The last symbol's weight is whatever makes the total a power of
+        // two.
+        let total: u32 = weights
+            .iter()
+            .filter(|&&w| w > 0)
+            .map(|&w| 1u32.checked_shl(w as u32 - 1).unwrap_or(u32::MAX))
+            .fold(0, u32::saturating_add);
+        if total == 0 || weights.iter().any(|&w| w > 11) {
+            return Err("invalid Huffman weights");
+        }
+        let max_bits = 32 - total.leading_zeros();
+        let remainder = (1 << max_bits) - total;
+        if !remainder.is_power_of_two() || max_bits > 11 {
+            return Err("invalid Huffman weights");
+        }
+        weights.push(remainder.trailing_zeros() as u8 + 1);
+
+        let mut entries = vec![(0, 0); 1 << max_bits];
+        let mut position = 0;
+        for weight in 1..=max_bits as u8 {
+            for (symbol, _) in weights.iter().enumerate().filter(|(_, &w)| w == weight) {
+                let count = 1 << (weight - 1);
+                let bits = (max_bits + 1 - weight as u32) as u8;
+                entries[position..position + count].fill((symbol as u8, bits));
+                position += count;
+            }
+        }
+
+        Ok((HuffmanTable { max_bits, entries }, size))
+    }
+
+    fn decode(&self, stream: &[u8], count: usize, output: &mut Vec<u8>) -> Result<()> {
+        let mut bits = BackwardBits::new(stream)?;
+        for _ in 0..count {
+            let (symbol, length) = self.entries[bits.peek(self.max_bits) as usize];
+            bits.consume(length as u32);
+            output.push(symbol);
+        }
+        if bits.position != 0 {
+            return Err("Huffman stream is the wrong size");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct FseTable {
+    accuracy_log: u32,
+    /// Per state: the symbol, the bits to read for the next state, and the
+    /// value they are added to.
+    entries: Vec<(u8, u8, u16)>,
+}
+
+impl FseTable {
+    /// Reads a table description, and returns the table and the size of the
+    /// description.
+    fn read(data: &[u8], max_accuracy_log: u32) -> Result<(Self, usize)> {
+        let mut bits = ForwardBits { data, position: 0 };
+        let accuracy_log = bits.read(4) as u32 + 5;
+        if accuracy_log > max_accuracy_log {
+            return Err("FSE table accuracy is too high");
+        }
+
+        let mut probabilities = Vec::new();
+        let mut remaining = (1i32 << accuracy_log) + 1;
+        let mut threshold = 1i32 << accuracy_log;
+        let mut bit_count = accuracy_log + 1;
+        while remaining > 1 {
+            if probabilities.len() > 255 {
+                return Err("FSE table has too many symbols");
+            }
+            let max = 2 * threshold - 1 - remaining;
+            let value = bits.peek(bit_count) as i32;
+            let mut count = if value & (threshold - 1) < max {
+                bits.consume(bit_count - 1);
+                value & (threshold - 1)
+            } else {
+                bits.consume(bit_count);
+                let count = value & (2 * threshold - 1);
+                if count >= threshold {
+                    count - max
+                } else {
+                    count
+                }
+            };
+            count -= 1;
+            remaining -= count.abs();
+            probabilities.push(count);
+
+            if count == 0 {
+                // A run of zeros follows, in repeated 2 bit counts that go on
+                // while they are 3.
+                loop {
+                    let repeat = bits.read(2);
+                    probabilities.extend(std::iter::repeat_n(0, repeat as usize));
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+            while remaining < threshold {
+                bit_count -= 1;
+                threshold >>= 1;
+            }
+        }
+        if remaining != 1 || bits.position > data.len() * 8 {
+            return Err("invalid FSE table");
+        }
+
+        Ok((
+            Self::from_probabilities(&probabilities, accuracy_log)?,
+            bits.position.div_ceil(8),
+        ))
+    }
+
+    fn from_probabilities(probabilities: &[i32], accuracy_log: u32) -> Result<Self> {
+        let size = 1usize << accuracy_log;
+        let mut symbols = vec![0u8; size];
+        let mut next = vec![0u32; probabilities.len()];
+
+        // Symbols with a probability of "less than one" get one state each
+        // at the end of the table.
+        let mut high = size;
+        for (symbol, &probability) in probabilities.iter().enumerate() {
+            if probability == -1 {
+                high = high.checked_sub(1).ok_or("invalid FSE table")?;
+                symbols[high] = symbol as u8;
+                next[symbol] = 1;
+            }
+        }
+
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut position = 0;
+        for (symbol, &probability) in probabilities.iter().enumerate() {
+            if probability <= 0 {
+                continue;
+            }
+            next[symbol] = probability as u32;
+            for _ in 0..probability {
+                symbols[position] = symbol as u8;
+                loop {
+                    position = (position + step) & (size - 1);
+                    if position < high {
+                        break;
+                    }
+                }
+            }
+        }
+        if position != 0 {
+            return Err("invalid FSE table");
+        }
+
+        let entries = symbols
+            .iter()
+            .map(|&symbol| {
+                let state = next[symbol as usize];
+                next[symbol as usize] += 1;
+                let bits = accuracy_log - (31 - state.leading_zeros());
+                let baseline = (state << bits) as usize - size;
+                (symbol, bits as u8, baseline as u16)
+            })
+            .collect();
+        Ok(FseTable {
+            accuracy_log,
+            entries,
+        })
+    }
+
+    /// A table that always decodes `symbol` and reads no bits.
+    fn rle(symbol: u8) -> Self {
+        FseTable {
+            accuracy_log: 0,
+            entries: vec![(symbol, 0, 0)],
+        }
+    }
+
+    fn symbol(&self, state: usize) -> u8 {
+        self.entries[state].0
+    }
+
+    fn update(&self, state: &mut usize, bits: &mut BackwardBits) {
+        let (_, count, baseline) = self.entries[*state];
+        *state = baseline as usize + bits.read(count as u32) as usize;
+    }
+
+    /// Decodes with two states taking turns, as Huffman weights are.
+    fn decode_interleaved(&self, stream: &[u8], max: usize) -> Result<Vec<u8>> {
+        let mut bits = BackwardBits::new(stream)?;
+        let mut states = [
+            bits.read(self.accuracy_log) as usize,
+            bits.read(self.accuracy_log) as usize,
+        ];
+        let mut output = Vec::new();
+        for turn in (0..2).cycle() {
+            output.push(self.symbol(states[turn]));
+            self.update(&mut states[turn], &mut bits);
+            if bits.position < 0 {
+                output.push(self.symbol(states[1 - turn]));
+                break;
+            }
+            if output.len() >= max {
+                return Err("too many Huffman weights");
+            }
+        }
+        Ok(output)
+    }
+}
+
+struct Sequence {
+    literal_length: usize,
+    match_length: usize,
+    offset: usize,
+}
+
+const LITERAL_LENGTH_CODES: [(u32, u8); 36] = [
+    (0, 0),
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 0),
+    (12, 0),
+    (13, 0),
+    (14, 0),
+    (15, 0),
+    (16, 1),
+    (18, 1),
+    (20, 1),
+    (22, 1),
+    (24, 2),
+    (28, 2),
+    (32, 3),
+    (40, 3),
+    (48, 4),
+    (64, 6),
+    (128, 7),
+    (256, 8),
+    (512, 9),
+    (1024, 10),
+    (2048, 11),
+    (4096, 12),
+    (8192, 13),
+    (16384, 14),
+    (32768, 15),
+    (65536, 16),
+];
+
+const MATCH_LENGTH_CODES: [(u32, u8); 53] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 0),
+    (12, 0),
+    (13, 0),
+    (14, 0),
+    (15, 0),
+    (16, 0),
+    (17, 0),
+    (18, 0),
+    (19, 0),
+    (20, 0),
+    (21, 0),
+    (22, 0),
+    (23, 0),
+    (24, 0),
+    (25, 0),
+    (26, 0),
+    (27, 0),
+    (28, 0),
+    (29, 0),
+    (30, 0),
+    (31, 0),
+    (32, 0),
+    (33, 0),
+    (34, 0),
+    (35, 1),
+    (37, 1),
+    (39, 1),
+    (41, 1),
+    (43, 2),
+    (47, 2),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 5),
+    (131, 7),
+    (259, 8),
+    (515, 9),
+    (1027, 10),
+    (2051, 11),
+    (4099, 12),
+    (8195, 13),
+    (16387, 14),
+    (32771, 15),
+    (65539, 16),
+];
+
+const LITERAL_LENGTH_DEFAULT: [i32; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+];
+
+const MATCH_LENGTH_DEFAULT: [i32; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+
+const OFFSET_DEFAULT: [i32; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+fn decode_sequences(data: &[u8], state: &mut FrameState) -> Result<Vec<Sequence>> {
+    let byte = |i: usize| {
+        data.get(i)
+            .map(|&b| b as usize)
+            .ok_or("truncated sequences")
+    };
+    let (count, mut offset) = match byte(0)? {
+        0 => return Ok(Vec::new()),
+        count @ 1..=127 => (count, 1),
+        count @ 128..=254 => (((count - 128) << 8) + byte(1)?, 2),
+        _ => (byte(1)? + (byte(2)? << 8) + 0x7f00, 3),
+    };
+
+    let modes = byte(offset)?;
+    offset += 1;
+    if modes & 3 != 0 {
+        return Err("reserved sequence mode bits are set");
+    }
+    let mut read_table = |mode: usize,
+                          table: &mut Option<FseTable>,
+                          default: &[i32],
+                          default_log: u32,
+                          max_log: u32,
+                          max_symbol: usize|
+     -> Result<()> {
+        match mode {
+            0 => *table = Some(FseTable::from_probabilities(default, default_log)?),
+            1 => {
+                let symbol = byte(offset)?;
+                if symbol > max_symbol {
+                    return Err("invalid sequence code");
+                }
+                *table = Some(FseTable::rle(symbol as u8));
+                offset += 1;
+            }
+            2 => {
+                let (fse, size) = FseTable::read(&data[offset..], max_log)?;
+                if fse
+                    .entries
+                    .iter()
+                    .any(|&(symbol, ..)| symbol as usize > max_symbol)
+                {
+                    return Err("invalid sequence code");
+                }
+                *table = Some(fse);
+                offset += size;
+            }
+            _ => {
+                if table.is_none() {
+                    return Err("sequences reuse a table that doesn't exist");
+                }
+            }
+        }
+        Ok(())
+    };
+    read_table(
+        modes >> 6,
+        &mut state.literal_lengths,
+        &LITERAL_LENGTH_DEFAULT,
+        6,
+        9,
+        35,
+    )?;
+    read_table(
+        (modes >> 4) & 3,
+        &mut state.offsets,
+        &OFFSET_DEFAULT,
+        5,
+        8,
+        31,
+    )?;
+    read_table(
+        (modes >> 2) & 3,
+        &mut state.match_lengths,
+        &MATCH_LENGTH_DEFAULT,
+        6,
+        9,
+        52,
+    )?;
+
+    let literal_lengths = state.literal_lengths.as_ref().unwrap();
+    let offsets = state.offsets.as_ref().unwrap();
+    let match_lengths = state.match_lengths.as_ref().unwrap();
+
+    let mut bits = BackwardBits::new(data.get(offset..).ok_or("truncated sequences")?)?;
+    let mut literal_length_state = bits.read(literal_lengths.accuracy_log) as usize;
+    let mut offset_state = bits.read(offsets.accuracy_log) as usize;
+    let mut match_length_state = bits.read(match_lengths.accuracy_log) as usize;
+
+    let mut sequences = Vec::with_capacity(count);
+    for i in 0..count {
+        let offset_code = offsets.symbol(offset_state) as u32;
+        let (match_base, match_bits) =
+            MATCH_LENGTH_CODES[match_lengths.symbol(match_length_state) as usize];
+        let (literal_base, literal_bits) =
+            LITERAL_LENGTH_CODES[literal_lengths.symbol(literal_length_state) as usize];
+
+        let offset_value = (1u64 << offset_code) + bits.read(offset_code);
+        let match_length = (match_base as u64 + bits.read(match_bits as u32)) as usize;
+        let literal_length = (literal_base as u64 + bits.read(literal_bits as u32)) as usize;
+
+        let repeat = &mut state.repeat_offsets;
+        let offset = if offset_value > 3 {
+            let offset = (offset_value - 3) as usize;
+            *repeat = [offset, repeat[0], repeat[1]];
+            offset
+        } else {
+            // Repeat offsets shift by one when there are no literals.
+            let index = offset_value as usize - 1 + (literal_length == 0) as usize;
+            match index {
+                0 => repeat[0],
+                _ => {
+                    let offset = if index == 3 {
+                        repeat[0].wrapping_sub(1)
+                    } else {
+                        repeat[index]
+                    };
+                    if index != 1 {
+                        repeat[2] = repeat[1];
+                    }
+                    repeat[1] = repeat[0];
+                    repeat[0] = offset;
+                    offset
+                }
+            }
+        };
+
+        sequences.push(Sequence {
+            literal_length,
+            match_length,
+            offset,
+        });
+
+        if i + 1 < count {
+            literal_lengths.update(&mut literal_length_state, &mut bits);
+            match_lengths.update(&mut match_length_state, &mut bits);
+            offsets.update(&mut offset_state, &mut bits);
+        }
+    }
+    if bits.position != 0 {
+        return Err("sequence stream is the wrong size");
+    }
+    Ok(sequences)
+}
+
+/// Reads bits from the least significant end of the first byte onwards.
+struct ForwardBits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ForwardBits<'a> {
+    fn peek(&self, count: u32) -> u64 {
+        let mut value = 0u64;
+        for i in 0..count as usize {
+            let position = self.position + i;
+            let bit = self
+                .data
+                .get(position / 8)
+                .map_or(0, |byte| (byte >> (position % 8)) & 1);
+            value |= (bit as u64) << i;
+        }
+        value
+    }
+
+    fn consume(&mut self, count: u32) {
+        self.position += count as usize;
+    }
+
+    fn read(&mut self, count: u32) -> u64 {
+        let value = self.peek(count);
+        self.consume(count);
+        value
+    }
+}
+
+/// Reads a stream written backwards: from the bit below the highest set bit
+/// of the last byte, towards the start. Reading past the start gives zeros,
+/// and leaves `position` negative.
+struct BackwardBits<'a> {
+    data: &'a [u8],
+    position: isize,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let last = *data.last().ok_or("empty bit stream")?;
+        if last == 0 {
+            return Err("bit stream has no start marker");
+        }
+        let position = (data.len() - 1) * 8 + 7 - last.leading_zeros() as usize;
+        Ok(BackwardBits {
+            data,
+            position: position as isize,
+        })
+    }
+
+    /// The `count` bits below `position`, with the first of them the most
+    /// significant.
+    fn peek(&self, count: u32) -> u64 {
+        let end = self.position;
+        if count == 0 || end <= 0 {
+            return 0;
+        }
+        let start = end - count as isize;
+        let low = start.max(0) as usize;
+        let mut bytes = [0u8; 8];
+        let first = low / 8;
+        let available = self.data.len().saturating_sub(first).min(8);
+        bytes[..available].copy_from_slice(&self.data[first..first + available]);
+        let value = u64::from_le_bytes(bytes) >> (low % 8);
+        let width = (end - low as isize).max(0) as u32;
+        let value = value & ((1u64 << width) - 1);
+        // Bits from before the start of the stream are zeros.
+        value << (low as isize - start)
+    }
+
+    fn consume(&mut self, count: u32) {
+        self.position -= count as isize;
+    }
+
+    fn read(&mut self, count: u32) -> u64 {
+        let value = self.peek(count);
+        self.consume(count);
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &[u8] = include_bytes!("../../tests/fixtures/ktx2/words.txt");
+    const WORDS_ZST: &[u8] = include_bytes!("../../tests/fixtures/ktx2/words.zst");
+    const WORDS_SPLIT_ZST: &[u8] = include_bytes!("../../tests/fixtures/ktx2/words_split.zst");
+    const NIBBLES: &[u8] = include_bytes!("../../tests/fixtures/ktx2/nibbles.bin");
+    const NIBBLES_ZST: &[u8] = include_bytes!("../../tests/fixtures/ktx2/nibbles.zst");
+    const RANDOM: &[u8] = include_bytes!("../../tests/fixtures/ktx2/random.bin");
+    const RANDOM_ZST: &[u8] = include_bytes!("../../tests/fixtures/ktx2/random.zst");
+    const RLE_ZST: &[u8] = include_bytes!("../../tests/fixtures/ktx2/rle.zst");
+
+    fn frames() -> [(&'static [u8], Vec<u8>); 5] {
+        [
+            (WORDS_ZST, WORDS.to_vec()),
+            (WORDS_SPLIT_ZST, WORDS.to_vec()),
+            (NIBBLES_ZST, NIBBLES.to_vec()),
+            (RANDOM_ZST, RANDOM.to_vec()),
+            (RLE_ZST, vec![b'a'; 200_000]),
+        ]
+    }
+
+    #[test]
+    fn block_and_table_types() {
+        for (frame, expected) in frames().iter() {
+            assert_eq!(decompress(frame, usize::MAX).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn concatenated_and_skippable_frames() {
+        let mut input = RANDOM_ZST.to_vec();
+        input.extend_from_slice(&(SKIPPABLE_MAGIC | 7).to_le_bytes());
+        input.extend_from_slice(&3u32.to_le_bytes());
+        input.extend_from_slice(b"abc");
+        input.extend_from_slice(NIBBLES_ZST);
+        assert_eq!(
+            decompress(&input, usize::MAX).unwrap(),
+            [RANDOM, NIBBLES].concat()
+        );
+
+        input.truncate(RANDOM_ZST.len() + 10);
+        assert_eq!(
+            decompress(&input, usize::MAX),
+            Err("truncated skippable frame")
+        );
+    }
+
+    #[test]
+    fn output_limit() {
+        assert_eq!(decompress(RLE_ZST, 199_999), Err("more data than expected"));
+        assert_eq!(decompress(RLE_ZST, 200_000).unwrap().len(), 200_000);
+    }
+
+    #[test]
+    fn truncated_frames() {
+        for (frame, _) in frames().iter() {
+            for size in 1..frame.len() {
+                assert!(
+                    decompress(&frame[..size], usize::MAX).is_err(),
+                    "{} of {} bytes",
+                    size,
+                    frame.len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt_frames() {
+        let corrupt = |offset: usize, f: fn(u8) -> u8| {
+            let mut frame = WORDS_ZST.to_vec();
+            frame[offset] = f(frame[offset]);
+            decompress(&frame, usize::MAX)
+        };
+        assert_eq!(corrupt(0, |b| b ^ 1), Err("not a Zstandard frame"));
+        // The frame header descriptor, then the block header that follows the
+        // window descriptor.
+        assert_eq!(
+            corrupt(4, |b| b | 0x08),
+            Err("reserved frame header bit is set")
+        );
+        assert_eq!(
+            corrupt(4, |b| b | 0x01),
+            Err("dictionaries are not supported")
+        );
+        assert_eq!(corrupt(6, |b| b | 0x06), Err("reserved block type"));
+
+        // A single segment with a 1-byte content size of 5, and an RLE block
+        // of 4.
+        let mut frame = FRAME_MAGIC.to_le_bytes().to_vec();
+        frame.extend_from_slice(&[0x20, 5, 4 << 3 | 2 | 1, 0, 0, b'x']);
+        assert_eq!(
+            decompress(&frame, usize::MAX),
+            Err("frame is not the size its header says")
+        );
+        frame[5] = 4;
+        assert_eq!(decompress(&frame, usize::MAX).unwrap(), b"xxxx");
+
+        // Whatever happens to the data in the blocks, it must be reported
+        // rather than panic.
+        for (frame, _) in frames().iter() {
+            for offset in 0..frame.len() {
+                let mut frame = frame.to_vec();
+                frame[offset] ^= 1 << (offset % 8);
+                let _ = decompress(&frame, 1 << 20);
+            }
+        }
+    }
+}
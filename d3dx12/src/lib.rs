@@ -1,13 +1,57 @@
+// Everything that touches D3D is Windows-only; the parsers and build helpers
+// are plain Rust and work anywhere.
+#[cfg(windows)]
 use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
 
+#[cfg(windows)]
 mod descriptor_heaps;
+#[cfg(windows)]
 pub use descriptor_heaps::*;
 
+#[cfg(windows)]
 mod pipeline_states;
+#[cfg(windows)]
 pub use pipeline_states::*;
 
+#[cfg(windows)]
+mod pipeline_validation;
+#[cfg(windows)]
+pub use pipeline_validation::*;
+
+#[cfg(windows)]
+mod pipeline_cache;
+#[cfg(windows)]
+pub use pipeline_cache::*;
+
+#[cfg(windows)]
+mod mip_generator;
+#[cfg(windows)]
+pub use mip_generator::*;
+
+#[cfg(windows)]
+mod upload_heap;
+#[cfg(windows)]
+pub use upload_heap::*;
+
+pub mod bc;
 pub mod build;
+pub mod cache;
+pub mod convert;
+pub mod dds;
+pub mod dxbc;
+pub mod format;
+pub mod gltf;
+pub mod image;
+pub mod ktx2;
+pub mod mips;
+pub mod procedural;
+pub mod raw_scene;
+pub mod ring_allocator;
+pub mod scene;
+pub mod shader;
+pub mod texture;
 
+#[cfg(windows)]
 pub fn transition_barrier(
     resource: &ID3D12Resource,
     state_before: D3D12_RESOURCE_STATES,
@@ -27,12 +71,29 @@ pub fn transition_barrier(
     }
 }
 
+/// Makes UAV accesses to `resource` after the barrier wait for those before
+/// it.
+#[cfg(windows)]
+pub fn uav_barrier(resource: &ID3D12Resource) -> D3D12_RESOURCE_BARRIER {
+    D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_UAV,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            UAV: std::mem::ManuallyDrop::new(D3D12_RESOURCE_UAV_BARRIER {
+                pResource: unsafe { std::mem::transmute_copy(resource) },
+            }),
+        },
+    }
+}
+
+#[cfg(windows)]
 pub trait ResourceDesc {
     fn default() -> Self;
     fn buffer(size: usize) -> Self;
     fn tex2d(format: DXGI_FORMAT, width: u64, height: u32) -> Self;
 }
 
+#[cfg(windows)]
 impl ResourceDesc for D3D12_RESOURCE_DESC {
     fn buffer(size: usize) -> Self {
         D3D12_RESOURCE_DESC {
@@ -72,11 +133,13 @@ impl ResourceDesc for D3D12_RESOURCE_DESC {
     }
 }
 
+#[cfg(windows)]
 pub trait HeapProperties {
     fn default() -> Self;
     fn standard(heap_type: D3D12_HEAP_TYPE) -> Self;
 }
 
+#[cfg(windows)]
 impl HeapProperties for D3D12_HEAP_PROPERTIES {
     fn default() -> Self {
         D3D12_HEAP_PROPERTIES {
@@ -0,0 +1,90 @@
+// Generates one mip from the one above it with a box filter. Each texel
+// averages the part of the source that it covers: 2x2 texels when both
+// dimensions halve exactly, and up to 3x3 with fractional weights when one
+// is odd, so that non-power-of-two textures don't lose their last row or
+// column.
+
+#define RootSig \
+    "RootConstants(num32BitConstants = 5, b0), " \
+    "DescriptorTable(UAV(u0, numDescriptors = 2))"
+
+cbuffer Constants : register(b0)
+{
+    uint2 SourceSize;
+    uint2 DestinationSize;
+    uint IsSrgb;
+};
+
+RWTexture2D<float4> Source : register(u0);
+RWTexture2D<float4> Destination : register(u1);
+
+float3 SrgbToLinear(float3 color)
+{
+    return color <= 0.04045 ? color / 12.92 : pow((color + 0.055) / 1.055, 2.4);
+}
+
+float3 LinearToSrgb(float3 color)
+{
+    return color <= 0.0031308 ? color * 12.92 : 1.055 * pow(color, 1.0 / 2.4) - 0.055;
+}
+
+float4 LoadLinear(uint2 texel)
+{
+    float4 value = Source[min(texel, SourceSize - 1)];
+    if (IsSrgb)
+    {
+        value.rgb = SrgbToLinear(value.rgb);
+    }
+    return value;
+}
+
+// The weights of the three source texels from `first` along one axis, which
+// are how much of each the destination texel covers.
+float3 Weights(uint destination, uint sourceSize, uint destinationSize, out uint first)
+{
+    float scale = (float)sourceSize / destinationSize;
+    float start = destination * scale;
+    float end = start + scale;
+    first = (uint)start;
+
+    float3 weights;
+    [unroll] for (uint i = 0; i < 3; ++i)
+    {
+        float texel = first + i;
+        weights[i] = max(0, min(end, texel + 1) - max(start, texel));
+    }
+    return weights / scale;
+}
+
+[RootSignature(RootSig)]
+[numthreads(8, 8, 1)]
+void CSMain(uint3 id : SV_DispatchThreadID)
+{
+    if (any(id.xy >= DestinationSize))
+    {
+        return;
+    }
+
+    uint2 first;
+    float3 weightsX = Weights(id.x, SourceSize.x, DestinationSize.x, first.x);
+    float3 weightsY = Weights(id.y, SourceSize.y, DestinationSize.y, first.y);
+
+    float4 sum = 0;
+    [unroll] for (uint y = 0; y < 3; ++y)
+    {
+        [unroll] for (uint x = 0; x < 3; ++x)
+        {
+            float weight = weightsX[x] * weightsY[y];
+            if (weight > 0)
+            {
+                sum += LoadLinear(first + uint2(x, y)) * weight;
+            }
+        }
+    }
+
+    if (IsSrgb)
+    {
+        sum.rgb = LinearToSrgb(saturate(sum.rgb));
+    }
+    Destination[id.xy] = sum;
+}
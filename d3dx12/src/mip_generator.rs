@@ -0,0 +1,224 @@
+use crate::descriptor_heaps::{CbvSrvUavDescriptorHeap, DescriptorHeap};
+use crate::mips::{GpuMipPlan, GpuMipStep, MipFormats};
+use crate::shader::{CompileOptions, ShaderCompiler, ShaderSource};
+use crate::{transition_barrier, uav_barrier, ShaderBytecode};
+use windows::core::*;
+use windows::Win32::{
+    Foundation::E_INVALIDARG,
+    Graphics::{Direct3D12::*, Dxgi::Common::*},
+};
+
+const SHADER: &str = include_str!("mip_generator.hlsl");
+
+/// Generates mips on the GPU with a compute shader, as `GpuMipPlan`
+/// describes. The texture needs `D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS`,
+/// and the device has to support typed UAV loads of its format, since each
+/// mip is read back through a UAV.
+///
+/// sRGB textures need a typeless resource, so that they can have UNORM UAVs;
+/// `MipGenerator::resource_desc` gives a suitable one for any supported
+/// format. The supported formats are `R8G8B8A8_UNORM`, `R8G8B8A8_UNORM_SRGB`,
+/// `R16G16B16A16_FLOAT` and `R32G32B32A32_FLOAT`.
+pub struct MipGenerator {
+    root_signature: ID3D12RootSignature,
+    pipeline_state: ID3D12PipelineState,
+}
+
+impl MipGenerator {
+    pub fn new(device: &ID3D12Device, compiler: &dyn ShaderCompiler) -> Result<Self> {
+        let shader = compiler.compile(
+            &ShaderSource::Text {
+                name: "mip_generator.hlsl",
+                text: SHADER,
+            },
+            &CompileOptions::new("CSMain", "cs_5_0"),
+        )?;
+
+        // The root signature is declared in the shader.
+        let root_signature: ID3D12RootSignature =
+            unsafe { device.CreateRootSignature(0, &shader.bytecode) }?;
+        let pipeline_state = unsafe {
+            device.CreateComputePipelineState(&D3D12_COMPUTE_PIPELINE_STATE_DESC {
+                pRootSignature: std::mem::transmute_copy(&root_signature),
+                CS: D3D12_SHADER_BYTECODE::from_bytes(&shader.bytecode),
+                ..Default::default()
+            })
+        }?;
+
+        Ok(MipGenerator {
+            root_signature,
+            pipeline_state,
+        })
+    }
+
+    /// A description of a 2D texture with a full chain of mips that
+    /// `generate` can fill in, or `None` if it can't handle `format`.
+    pub fn resource_desc(
+        format: DXGI_FORMAT,
+        width: u32,
+        height: u32,
+    ) -> Option<D3D12_RESOURCE_DESC> {
+        let formats = MipFormats::of(format)?;
+        Some(D3D12_RESOURCE_DESC {
+            Format: formats.typeless,
+            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            Width: width as u64,
+            Height: height,
+            DepthOrArraySize: 1,
+            MipLevels: crate::mips::mip_count(width, height) as u16,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+            Alignment: 0,
+        })
+    }
+
+    /// Records the commands that fill in every mip of `texture` after the
+    /// first, which is read as `format`. The texture is in `state` before
+    /// and after; on a compute command list that has to be a state compute
+    /// queues can use.
+    ///
+    /// `descriptors` is where the UAVs go, starting at its first descriptor,
+    /// and needs room for the plan's `descriptor_count`. It has to be shader
+    /// visible, and kept alive until the commands have run. The command list
+    /// is left with this generator's pipeline state, root signature and
+    /// descriptor heap set.
+    ///
+    /// # Safety
+    /// `descriptors` must have room for the UAVs, and `state` must be the
+    /// state that every mip of `texture` is in.
+    pub unsafe fn generate(
+        &self,
+        device: &ID3D12Device,
+        command_list: &ID3D12GraphicsCommandList,
+        texture: &ID3D12Resource,
+        format: DXGI_FORMAT,
+        state: D3D12_RESOURCE_STATES,
+        descriptors: &CbvSrvUavDescriptorHeap,
+    ) -> Result<GpuMipPlan> {
+        let desc = texture.GetDesc();
+        let formats = MipFormats::of(format).ok_or_else(|| {
+            Error::new(
+                E_INVALIDARG,
+                format!("can't generate mips of format {}", format.0),
+            )
+        })?;
+        if desc.Dimension != D3D12_RESOURCE_DIMENSION_TEXTURE2D || desc.DepthOrArraySize != 1 {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "mips can only be generated for 2D textures with one array slice",
+            ));
+        }
+        if (desc.Flags & D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS).0 == 0 {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "the texture doesn't allow unordered access",
+            ));
+        }
+        if !formats.allows_resource_format(desc.Format) {
+            return Err(Error::new(
+                E_INVALIDARG,
+                format!(
+                    "the texture's format is {}, but needs to be {}",
+                    desc.Format.0, formats.typeless.0
+                ),
+            ));
+        }
+        check_typed_uav_support(device, formats.uav)?;
+
+        let plan = GpuMipPlan::new(
+            desc.Width as u32,
+            desc.Height,
+            desc.MipLevels as u32,
+            formats.srgb,
+            state == D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+        );
+
+        for mip in 0..plan.descriptor_count {
+            device.CreateUnorderedAccessView(
+                texture,
+                None,
+                Some(&D3D12_UNORDERED_ACCESS_VIEW_DESC {
+                    Format: formats.uav,
+                    ViewDimension: D3D12_UAV_DIMENSION_TEXTURE2D,
+                    Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+                        Texture2D: D3D12_TEX2D_UAV {
+                            MipSlice: mip,
+                            PlaneSlice: 0,
+                        },
+                    },
+                }),
+                descriptors.get_cpu_descriptor_handle(mip as usize),
+            );
+        }
+
+        command_list.SetComputeRootSignature(&self.root_signature);
+        command_list.SetPipelineState(&self.pipeline_state);
+        command_list.SetDescriptorHeaps(&[Some(descriptors.heap.clone())]);
+        for step in &plan.steps {
+            match step {
+                GpuMipStep::ToUnorderedAccess => {
+                    command_list.ResourceBarrier(&[transition_barrier(
+                        texture,
+                        state,
+                        D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                    )]);
+                }
+                GpuMipStep::FromUnorderedAccess => {
+                    command_list.ResourceBarrier(&[transition_barrier(
+                        texture,
+                        D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                        state,
+                    )]);
+                }
+                GpuMipStep::UavBarrier => {
+                    command_list.ResourceBarrier(&[uav_barrier(texture)]);
+                }
+                GpuMipStep::Dispatch(dispatch) => {
+                    let constants = dispatch.constants.to_root_constants();
+                    command_list.SetComputeRoot32BitConstants(
+                        0,
+                        constants.len() as u32,
+                        constants.as_ptr() as _,
+                        0,
+                    );
+                    command_list.SetComputeRootDescriptorTable(
+                        1,
+                        descriptors.get_gpu_descriptor_handle(dispatch.source_mip as usize),
+                    );
+                    let [x, y, z] = dispatch.thread_groups;
+                    command_list.Dispatch(x, y, z);
+                }
+            }
+        }
+        Ok(plan)
+    }
+}
+
+fn check_typed_uav_support(device: &ID3D12Device, format: DXGI_FORMAT) -> Result<()> {
+    let mut support = D3D12_FEATURE_DATA_FORMAT_SUPPORT {
+        Format: format,
+        ..Default::default()
+    };
+    unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_FORMAT_SUPPORT,
+            &mut support as *mut D3D12_FEATURE_DATA_FORMAT_SUPPORT as _,
+            std::mem::size_of_val(&support) as u32,
+        )
+    }?;
+    let needed = D3D12_FORMAT_SUPPORT2_UAV_TYPED_LOAD | D3D12_FORMAT_SUPPORT2_UAV_TYPED_STORE;
+    if support.Support2 & needed != needed {
+        return Err(Error::new(
+            E_INVALIDARG,
+            format!(
+                "the device can't load and store format {} through UAVs",
+                format.0
+            ),
+        ));
+    }
+    Ok(())
+}
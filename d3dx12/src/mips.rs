@@ -0,0 +1,632 @@
+//! Generates mip chains on the CPU.
+//!
+//! Each level is filtered from the one above it, first along rows and then
+//! along columns, at full precision. Levels are sized as D3D sizes them, so a
+//! dimension of 5 goes to 2 and then 1; each texel of the smaller level covers
+//! a fractional footprint of the larger one, 2.5 texels in that case, rather
+//! than the filter dropping the last row or column.
+//!
+//! sRGB formats are filtered in linear space. Block-compressed formats are
+//! decoded, filtered and encoded again with `bc`. To compress a texture that
+//! doesn't have mips yet, generate them first and then call `bc::compress`,
+//! which keeps the top level from being compressed twice.
+//!
+//! `GpuMipPlan` describes generating mips with a box filter on the GPU
+//! instead, which `MipGenerator` records into a command list.
+
+use crate::format::*;
+use crate::texture::{mip_size, FormatLayout, Texture};
+use crate::{bc, convert};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MipFilter {
+    /// Averages the texels each smaller texel covers. Fast, but a little
+    /// blurry, and it lets some aliasing through.
+    Box,
+    /// A windowed sinc, which keeps more detail. It can overshoot at hard
+    /// edges, so UNORM results are clamped.
+    #[default]
+    Kaiser,
+}
+
+/// Half the width of the Kaiser filter, in texels of the smaller level.
+const KAISER_RADIUS: f32 = 3.0;
+/// How sharply the Kaiser window falls off.
+const KAISER_ALPHA: f32 = 4.0;
+
+/// Replaces the mips of `texture` with a full chain down to 1x1, each level
+/// filtered from the one above it, starting from the top level. The top level
+/// is kept exactly as it is. Returns `None` if the texture has no subresources
+/// or its format isn't one that `convert::convert` supports, or a UNORM or
+/// sRGB format that `bc::decode_surface` supports.
+pub fn generate_mips(texture: &Texture, filter: MipFilter) -> Option<Texture> {
+    let top = texture.subresources.first()?;
+    if FormatLayout::of(texture.format)?.is_block_compressed() {
+        return generate_compressed_mips(texture, filter);
+    }
+    let mut level = Level {
+        width: top.width as usize,
+        height: top.height as usize,
+        texels: convert::read_texels(texture.format, top)?,
+    };
+    let mut subresources = vec![top.clone()];
+    let levels = mip_count(texture.width, texture.height);
+    for mip in 1..levels {
+        level = level.resample(
+            mip_size(texture.width, mip),
+            mip_size(texture.height, mip),
+            filter,
+        );
+        subresources.push(convert::write_texels(
+            texture.format,
+            level.width as u32,
+            level.height as u32,
+            &level.texels,
+        )?);
+    }
+
+    Some(Texture {
+        subresources,
+        ..texture.clone()
+    })
+}
+
+/// The number of mips in a full chain for a `width` by `height` texture.
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Threads along each side of a thread group in `MipGenerator`'s shader.
+pub const GPU_MIP_GROUP_SIZE: u32 = 8;
+
+/// What `MipGenerator` records to generate the mips of a 2D texture on the
+/// GPU, worked out without a device.
+///
+/// Each dispatch reads one mip through a UAV and writes the next through
+/// another, so every mip stays in `D3D12_RESOURCE_STATE_UNORDERED_ACCESS`
+/// throughout, which a compute queue can use as well as a direct one. A UAV
+/// barrier between dispatches makes each one wait for the mip it reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuMipPlan {
+    /// One UAV for each mip, in order, in consecutive descriptors.
+    pub descriptor_count: u32,
+    pub steps: Vec<GpuMipStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpuMipStep {
+    /// Transitions every mip from the texture's state to
+    /// `D3D12_RESOURCE_STATE_UNORDERED_ACCESS`.
+    ToUnorderedAccess,
+    /// Transitions every mip back to the texture's state.
+    FromUnorderedAccess,
+    UavBarrier,
+    Dispatch(GpuMipDispatch),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuMipDispatch {
+    /// The mip read. The one after it is written, and the descriptor table
+    /// starts at its UAV.
+    pub source_mip: u32,
+    pub thread_groups: [u32; 3],
+    pub constants: GpuMipConstants,
+}
+
+/// The shader's root constants, in the order it declares them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuMipConstants {
+    pub source_size: [u32; 2],
+    pub destination_size: [u32; 2],
+    /// Whether the texels are sRGB, which the shader has to convert itself
+    /// since UAVs can't have sRGB formats.
+    pub srgb: bool,
+}
+
+impl GpuMipConstants {
+    pub fn to_root_constants(&self) -> [u32; 5] {
+        [
+            self.source_size[0],
+            self.source_size[1],
+            self.destination_size[0],
+            self.destination_size[1],
+            self.srgb as u32,
+        ]
+    }
+}
+
+impl GpuMipPlan {
+    /// Plans generating mips 1 to `mip_levels - 1` of a `width` by `height`
+    /// texture from mip 0. The texture is left in the state it was in, which
+    /// needs no transitions if it's already `in_unordered_access`.
+    pub fn new(
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        srgb: bool,
+        in_unordered_access: bool,
+    ) -> Self {
+        let mut steps = Vec::new();
+        let needs_transition = mip_levels > 1 && !in_unordered_access;
+        if needs_transition {
+            steps.push(GpuMipStep::ToUnorderedAccess);
+        }
+        for source_mip in 0..mip_levels.saturating_sub(1) {
+            if source_mip > 0 {
+                steps.push(GpuMipStep::UavBarrier);
+            }
+            let destination_size = [
+                mip_size(width, source_mip + 1),
+                mip_size(height, source_mip + 1),
+            ];
+            steps.push(GpuMipStep::Dispatch(GpuMipDispatch {
+                source_mip,
+                thread_groups: [
+                    destination_size[0].div_ceil(GPU_MIP_GROUP_SIZE),
+                    destination_size[1].div_ceil(GPU_MIP_GROUP_SIZE),
+                    1,
+                ],
+                constants: GpuMipConstants {
+                    source_size: [mip_size(width, source_mip), mip_size(height, source_mip)],
+                    destination_size,
+                    srgb,
+                },
+            }));
+        }
+        if needs_transition {
+            steps.push(GpuMipStep::FromUnorderedAccess);
+        }
+
+        GpuMipPlan {
+            descriptor_count: mip_levels,
+            steps,
+        }
+    }
+}
+
+/// The formats of a texture that `MipGenerator` works on: the resource's,
+/// and its UAVs', which can't be sRGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MipFormats {
+    pub typeless: DXGI_FORMAT,
+    pub uav: DXGI_FORMAT,
+    pub srgb: bool,
+}
+
+impl MipFormats {
+    /// Returns `None` for formats `MipGenerator` doesn't support.
+    pub fn of(format: DXGI_FORMAT) -> Option<Self> {
+        let (typeless, uav, srgb) = match format {
+            DXGI_FORMAT_R8G8B8A8_UNORM => (DXGI_FORMAT_R8G8B8A8_TYPELESS, format, false),
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => (
+                DXGI_FORMAT_R8G8B8A8_TYPELESS,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+                true,
+            ),
+            DXGI_FORMAT_R16G16B16A16_FLOAT => (DXGI_FORMAT_R16G16B16A16_TYPELESS, format, false),
+            DXGI_FORMAT_R32G32B32A32_FLOAT => (DXGI_FORMAT_R32G32B32A32_TYPELESS, format, false),
+            _ => return None,
+        };
+        Some(MipFormats {
+            typeless,
+            uav,
+            srgb,
+        })
+    }
+
+    /// Whether a resource of `format` can have the UAVs. sRGB textures have
+    /// to be typeless; the others can also be the UAV format itself.
+    pub fn allows_resource_format(&self, format: DXGI_FORMAT) -> bool {
+        format == self.typeless || (!self.srgb && format == self.uav)
+    }
+}
+
+/// Generates the mips of a block-compressed texture from its decoded top
+/// level, and compresses them back into its format.
+fn generate_compressed_mips(texture: &Texture, filter: MipFilter) -> Option<Texture> {
+    let top = Texture {
+        subresources: texture.subresources[..1].to_vec(),
+        ..texture.clone()
+    };
+    let mut decoded = generate_mips(&bc::decompress(&top)?, filter)?;
+    decoded.subresources.remove(0);
+    let mips = bc::compress(&decoded, texture.format)?;
+
+    let mut subresources = top.subresources;
+    subresources.extend(mips.subresources);
+    Some(Texture {
+        subresources,
+        ..texture.clone()
+    })
+}
+
+/// One mip level, as linear RGBA.
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<[f32; 4]>,
+}
+
+impl Level {
+    fn resample(&self, width: u32, height: u32, filter: MipFilter) -> Level {
+        let (width, height) = (width as usize, height as usize);
+        let add = |sum: &mut [f32; 4], texel: &[f32; 4], weight: f32| {
+            for (sum, value) in sum.iter_mut().zip(texel) {
+                *sum += value * weight;
+            }
+        };
+
+        // Rows first, into a level that is already the new width.
+        let columns = taps(self.width, width, filter);
+        let mut wide = vec![[0.0; 4]; width * self.height];
+        for (source, destination) in self
+            .texels
+            .chunks_exact(self.width)
+            .zip(wide.chunks_exact_mut(width))
+        {
+            for (texel, taps) in destination.iter_mut().zip(&columns) {
+                for &(x, weight) in taps {
+                    add(texel, &source[x], weight);
+                }
+            }
+        }
+
+        let rows = taps(self.height, height, filter);
+        let mut texels = vec![[0.0; 4]; width * height];
+        for (row, taps) in texels.chunks_exact_mut(width).zip(&rows) {
+            for &(y, weight) in taps {
+                let source = &wide[y * width..][..width];
+                for (texel, source) in row.iter_mut().zip(source) {
+                    add(texel, source, weight);
+                }
+            }
+        }
+
+        Level {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+/// For each texel along one axis of the smaller level, the texels of the
+/// larger level that go into it and their weights, which sum to 1. Taps that
+/// fall off the edge are clamped to it.
+fn taps(source: usize, destination: usize, filter: MipFilter) -> Vec<Vec<(usize, f32)>> {
+    let scale = source as f32 / destination as f32;
+    (0..destination)
+        .map(|x| {
+            let (start, end) = (x as f32 * scale, (x + 1) as f32 * scale);
+            let center = (start + end) / 2.0;
+            let mut taps: Vec<(usize, f32)> = match filter {
+                // The overlap of each source texel with the footprint.
+                MipFilter::Box => (start.floor() as usize..end.ceil() as usize)
+                    .map(|i| {
+                        let overlap = end.min(i as f32 + 1.0) - start.max(i as f32);
+                        (i, overlap)
+                    })
+                    .collect(),
+                MipFilter::Kaiser => {
+                    let radius = KAISER_RADIUS * scale;
+                    let first = (center - radius).floor() as isize;
+                    let last = (center + radius).ceil() as isize;
+                    (first..last)
+                        .map(|i| {
+                            let distance = (i as f32 + 0.5 - center) / scale;
+                            let clamped = i.clamp(0, source as isize - 1) as usize;
+                            (clamped, kaiser(distance))
+                        })
+                        .collect()
+                }
+            };
+            taps.retain(|&(_, weight)| weight != 0.0);
+            let total: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+            for (_, weight) in &mut taps {
+                *weight /= total;
+            }
+            taps
+        })
+        .collect()
+}
+
+/// The Kaiser-windowed sinc, `distance` texels of the smaller level from its
+/// center.
+fn kaiser(distance: f32) -> f32 {
+    let ratio = distance / KAISER_RADIUS;
+    if ratio.abs() >= 1.0 {
+        return 0.0;
+    }
+    let sinc = if distance == 0.0 {
+        1.0
+    } else {
+        let x = std::f32::consts::PI * distance;
+        x.sin() / x
+    };
+    sinc * bessel_i0(KAISER_ALPHA * (1.0 - ratio * ratio).sqrt()) / bessel_i0(KAISER_ALPHA)
+}
+
+/// The zeroth order modified Bessel function of the first kind, from its
+/// power series.
+fn bessel_i0(x: f32) -> f32 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    let quarter_square = x * x / 4.0;
+    for k in 1..20 {
+        term *= quarter_square / (k * k) as f32;
+        sum += term;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::Subresource;
+
+    /// A texture with only its top level, its rows packed together.
+    fn texture(format: DXGI_FORMAT, width: u32, height: u32, data: Vec<u8>) -> Texture {
+        Texture {
+            format,
+            width,
+            height,
+            subresources: vec![Subresource {
+                width,
+                height,
+                row_pitch: data.len() as u64 / height as u64,
+                data,
+            }],
+        }
+    }
+
+    fn sizes(texture: &Texture) -> Vec<(u32, u32)> {
+        texture
+            .subresources
+            .iter()
+            .map(|subresource| (subresource.width, subresource.height))
+            .collect()
+    }
+
+    #[test]
+    fn box_filter_averages_each_footprint() {
+        let two = texture(DXGI_FORMAT_R8_UNORM, 2, 2, vec![0, 100, 200, 60]);
+        let mipped = generate_mips(&two, MipFilter::Box).unwrap();
+        assert_eq!(mipped.subresources[0], two.subresources[0]);
+        assert_eq!(mipped.subresources[1].data, [90]);
+
+        #[rustfmt::skip]
+        let four = texture(DXGI_FORMAT_R8_UNORM, 4, 4, vec![
+            0, 20, 40, 60,
+            100, 120, 140, 160,
+            4, 8, 12, 16,
+            36, 40, 44, 48,
+        ]);
+        let mipped = generate_mips(&four, MipFilter::Box).unwrap();
+        assert_eq!(sizes(&mipped), [(4, 4), (2, 2), (1, 1)]);
+        assert_eq!(mipped.subresources[1].data, [60, 100, 22, 30]);
+        assert_eq!(mipped.subresources[2].data, [53]);
+    }
+
+    #[test]
+    fn filter_weights_sum_to_one() {
+        for &(source, destination) in &[(2, 1), (3, 1), (4, 2), (5, 2), (7, 3), (16, 8)] {
+            for &filter in &[MipFilter::Box, MipFilter::Kaiser] {
+                let taps = taps(source, destination, filter);
+                assert_eq!(taps.len(), destination);
+                for taps in taps {
+                    let total: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+                    assert!((total - 1.0).abs() < 1e-5, "{:?}: {}", filter, total);
+                    assert!(taps.iter().all(|&(i, _)| i < source));
+                }
+            }
+        }
+
+        assert_eq!(kaiser(0.0), 1.0);
+        assert_eq!(kaiser(KAISER_RADIUS), 0.0);
+        assert_eq!(kaiser(-0.5), kaiser(0.5));
+    }
+
+    #[test]
+    fn odd_sizes_go_down_to_one_by_one() {
+        assert_eq!(mip_count(1, 1), 1);
+        assert_eq!(mip_count(5, 3), 3);
+        assert_eq!(mip_count(7, 1), 3);
+        assert_eq!(mip_count(1, 256), 9);
+
+        for &(width, height) in &[(5, 3), (7, 1), (1, 6), (3, 3)] {
+            let color = [10, 20, 30, 40];
+            let data = color.repeat((width * height) as usize);
+            let top = texture(DXGI_FORMAT_R8G8B8A8_UNORM, width, height, data);
+            for &filter in &[MipFilter::Box, MipFilter::Kaiser] {
+                let mipped = generate_mips(&top, filter).unwrap();
+                assert_eq!(mipped.subresources.len() as u32, mip_count(width, height));
+                for (mip, subresource) in mipped.subresources.iter().enumerate() {
+                    let mip = mip as u32;
+                    let size = (mip_size(width, mip), mip_size(height, mip));
+                    assert_eq!((subresource.width, subresource.height), size);
+                    assert_eq!(subresource.row_pitch, size.0 as u64 * 4);
+                    // An even color stays the same at every level.
+                    assert!(subresource.data.chunks_exact(4).all(|texel| texel == color));
+                }
+                assert_eq!(sizes(&mipped).last(), Some(&(1, 1)));
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_is_filtered_in_linear_space() {
+        let data = vec![0, 0, 0, 0, 255, 255, 255, 255];
+        let srgb = texture(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, 2, 1, data.clone());
+        let mipped = generate_mips(&srgb, MipFilter::Box).unwrap();
+        // Half of white is 0.5 in linear space, which is 188 in sRGB. Alpha
+        // is linear either way.
+        assert_eq!(mipped.subresources[1].data, [188, 188, 188, 128]);
+
+        let unorm = texture(DXGI_FORMAT_R8G8B8A8_UNORM, 2, 1, data);
+        let mipped = generate_mips(&unorm, MipFilter::Box).unwrap();
+        assert_eq!(mipped.subresources[1].data, [128; 4]);
+    }
+
+    #[test]
+    fn block_compressed_mips_are_compressed_again() {
+        // Red on the left and blue on the right, which stay apart, and exact
+        // in BC1, down to the 2x1 level.
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let data: Vec<u8> = (0..8 * 4)
+            .flat_map(|i| if i % 8 < 4 { red } else { blue })
+            .collect();
+        let bc1 = bc::compress(
+            &texture(DXGI_FORMAT_R8G8B8A8_UNORM, 8, 4, data),
+            DXGI_FORMAT_BC1_UNORM,
+        )
+        .unwrap();
+
+        let mipped = generate_mips(&bc1, MipFilter::Box).unwrap();
+        assert_eq!(mipped.format, DXGI_FORMAT_BC1_UNORM);
+        assert_eq!(sizes(&mipped), [(8, 4), (4, 2), (2, 1), (1, 1)]);
+        assert_eq!(mipped.subresources[0], bc1.subresources[0]);
+        // Two blocks, and then one for each level smaller than a block.
+        let lengths: Vec<_> = mipped.subresources.iter().map(|s| s.data.len()).collect();
+        assert_eq!(lengths, [16, 8, 8, 8]);
+
+        let decoded = bc::decompress(&mipped).unwrap();
+        for subresource in &decoded.subresources[..3] {
+            let width = subresource.width as usize;
+            for (i, texel) in subresource.data.chunks_exact(4).enumerate() {
+                let expected = if i % width < width / 2 { red } else { blue };
+                assert_eq!(
+                    texel, expected,
+                    "{}x{} texel {}",
+                    width, subresource.height, i
+                );
+            }
+        }
+
+        let unsupported = Texture {
+            format: DXGI_FORMAT_R10G10B10A2_UNORM,
+            ..bc1
+        };
+        assert_eq!(generate_mips(&unsupported, MipFilter::Box), None);
+    }
+
+    fn dispatches(plan: &GpuMipPlan) -> Vec<GpuMipDispatch> {
+        plan.steps
+            .iter()
+            .filter_map(|step| match step {
+                GpuMipStep::Dispatch(dispatch) => Some(*dispatch),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gpu_plan_sizes_for_odd_dimensions() {
+        let plan = GpuMipPlan::new(100, 37, mip_count(100, 37), false, true);
+        assert_eq!(plan.descriptor_count, 7);
+
+        let expected = [
+            ([100, 37], [50, 18], [7, 3]),
+            ([50, 18], [25, 9], [4, 2]),
+            ([25, 9], [12, 4], [2, 1]),
+            ([12, 4], [6, 2], [1, 1]),
+            ([6, 2], [3, 1], [1, 1]),
+            ([3, 1], [1, 1], [1, 1]),
+        ];
+        let dispatches = dispatches(&plan);
+        assert_eq!(dispatches.len(), expected.len());
+        for (mip, (dispatch, &(source, destination, [x, y]))) in
+            dispatches.iter().zip(&expected).enumerate()
+        {
+            assert_eq!(
+                *dispatch,
+                GpuMipDispatch {
+                    source_mip: mip as u32,
+                    thread_groups: [x, y, 1],
+                    constants: GpuMipConstants {
+                        source_size: source,
+                        destination_size: destination,
+                        srgb: false,
+                    },
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn gpu_plan_groups_cover_every_texel() {
+        for &(width, height) in &[(1, 1), (7, 9), (17, 16), (1000, 3), (4096, 2049)] {
+            let plan = GpuMipPlan::new(width, height, mip_count(width, height), false, true);
+            for dispatch in dispatches(&plan) {
+                let [x, y, z] = dispatch.thread_groups;
+                let [w, h] = dispatch.constants.destination_size;
+                assert!(x * GPU_MIP_GROUP_SIZE >= w && (x - 1) * GPU_MIP_GROUP_SIZE < w);
+                assert!(y * GPU_MIP_GROUP_SIZE >= h && (y - 1) * GPU_MIP_GROUP_SIZE < h);
+                assert_eq!(z, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn gpu_plan_barriers() {
+        let plan = GpuMipPlan::new(8, 8, 4, false, false);
+        assert_eq!(
+            plan.steps
+                .iter()
+                .map(|step| match step {
+                    GpuMipStep::ToUnorderedAccess => "to unordered access",
+                    GpuMipStep::FromUnorderedAccess => "from unordered access",
+                    GpuMipStep::UavBarrier => "uav barrier",
+                    GpuMipStep::Dispatch(_) => "dispatch",
+                })
+                .collect::<Vec<_>>(),
+            [
+                "to unordered access",
+                "dispatch",
+                "uav barrier",
+                "dispatch",
+                "uav barrier",
+                "dispatch",
+                "from unordered access"
+            ]
+        );
+
+        // Textures already in the UAV state, or with nothing to generate,
+        // need no transitions.
+        let plan = GpuMipPlan::new(8, 8, 4, false, true);
+        assert_eq!(plan.steps.len(), 5);
+        assert!(matches!(plan.steps[0], GpuMipStep::Dispatch(_)));
+        let plan = GpuMipPlan::new(8, 8, 1, false, false);
+        assert_eq!(plan.steps, []);
+    }
+
+    #[test]
+    fn gpu_plan_root_constants() {
+        let plan = GpuMipPlan::new(640, 480, 3, true, true);
+        let constants: Vec<[u32; 5]> = dispatches(&plan)
+            .iter()
+            .map(|dispatch| dispatch.constants.to_root_constants())
+            .collect();
+        assert_eq!(
+            constants,
+            [[640, 480, 320, 240, 1], [320, 240, 160, 120, 1]]
+        );
+    }
+
+    #[test]
+    fn srgb_textures_need_typeless_resources() {
+        let formats = MipFormats::of(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB).unwrap();
+        assert_eq!(formats.typeless, DXGI_FORMAT_R8G8B8A8_TYPELESS);
+        assert_eq!(formats.uav, DXGI_FORMAT_R8G8B8A8_UNORM);
+        assert!(formats.srgb);
+        assert!(formats.allows_resource_format(DXGI_FORMAT_R8G8B8A8_TYPELESS));
+        assert!(!formats.allows_resource_format(DXGI_FORMAT_R8G8B8A8_UNORM));
+        assert!(!formats.allows_resource_format(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB));
+
+        let formats = MipFormats::of(DXGI_FORMAT_R16G16B16A16_FLOAT).unwrap();
+        assert_eq!(formats.uav, DXGI_FORMAT_R16G16B16A16_FLOAT);
+        assert!(!formats.srgb);
+        assert!(formats.allows_resource_format(DXGI_FORMAT_R16G16B16A16_TYPELESS));
+        assert!(formats.allows_resource_format(DXGI_FORMAT_R16G16B16A16_FLOAT));
+
+        assert!(MipFormats::of(DXGI_FORMAT_BC1_UNORM).is_none());
+    }
+}
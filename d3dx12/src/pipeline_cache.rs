@@ -0,0 +1,264 @@
+use crate::blob_bytes;
+use crate::cache::{CacheDir, ContentHash, ContentHasher};
+use std::convert::TryInto;
+use windows::core::*;
+use windows::Win32::{Foundation::BOOL, Graphics::Direct3D12::*};
+
+/// Hashes everything in `desc` that affects the pipeline state it creates,
+/// following its pointers to shader bytecode, input elements and stream output
+/// declarations. The root signature is an opaque interface, so the blob it was
+/// created from is hashed in its place. `CachedPSO` is ignored.
+///
+/// # Safety
+/// The pointers in `desc` must be valid, as for `CreateGraphicsPipelineState`.
+pub unsafe fn hash_graphics_pipeline(
+    desc: &D3D12_GRAPHICS_PIPELINE_STATE_DESC,
+    root_signature_blob: &[u8],
+) -> ContentHash {
+    let mut h = ContentHasher::new();
+    h.write_str("graphics pipeline");
+    h.write_bytes(root_signature_blob);
+
+    for shader in [&desc.VS, &desc.PS, &desc.DS, &desc.HS, &desc.GS] {
+        h.write_bytes(bytecode(shader));
+    }
+
+    let so = &desc.StreamOutput;
+    h.write_u32(so.NumEntries);
+    for entry in slice(so.pSODeclaration, so.NumEntries) {
+        h.write_u32(entry.Stream);
+        h.write_bytes(name(entry.SemanticName));
+        h.write_u32(entry.SemanticIndex);
+        h.write(&[entry.StartComponent, entry.ComponentCount, entry.OutputSlot]);
+    }
+    h.write_u32(so.NumStrides);
+    for stride in slice(so.pBufferStrides, so.NumStrides) {
+        h.write_u32(*stride);
+    }
+    h.write_u32(so.RasterizedStream);
+
+    let blend = &desc.BlendState;
+    write_bool(&mut h, blend.AlphaToCoverageEnable);
+    write_bool(&mut h, blend.IndependentBlendEnable);
+    for target in &blend.RenderTarget {
+        write_bool(&mut h, target.BlendEnable);
+        write_bool(&mut h, target.LogicOpEnable);
+        for value in [
+            target.SrcBlend.0,
+            target.DestBlend.0,
+            target.BlendOp.0,
+            target.SrcBlendAlpha.0,
+            target.DestBlendAlpha.0,
+            target.BlendOpAlpha.0,
+            target.LogicOp.0,
+        ] {
+            h.write_u32(value as u32);
+        }
+        h.write(&[target.RenderTargetWriteMask]);
+    }
+
+    h.write_u32(desc.SampleMask);
+
+    let raster = &desc.RasterizerState;
+    h.write_u32(raster.FillMode.0 as u32);
+    h.write_u32(raster.CullMode.0 as u32);
+    write_bool(&mut h, raster.FrontCounterClockwise);
+    h.write_u32(raster.DepthBias as u32);
+    h.write_u32(raster.DepthBiasClamp.to_bits());
+    h.write_u32(raster.SlopeScaledDepthBias.to_bits());
+    write_bool(&mut h, raster.DepthClipEnable);
+    write_bool(&mut h, raster.MultisampleEnable);
+    write_bool(&mut h, raster.AntialiasedLineEnable);
+    h.write_u32(raster.ForcedSampleCount);
+    h.write_u32(raster.ConservativeRaster.0 as u32);
+
+    let depth = &desc.DepthStencilState;
+    write_bool(&mut h, depth.DepthEnable);
+    h.write_u32(depth.DepthWriteMask.0 as u32);
+    h.write_u32(depth.DepthFunc.0 as u32);
+    write_bool(&mut h, depth.StencilEnable);
+    h.write(&[depth.StencilReadMask, depth.StencilWriteMask]);
+    for face in [&depth.FrontFace, &depth.BackFace] {
+        h.write_u32(face.StencilFailOp.0 as u32);
+        h.write_u32(face.StencilDepthFailOp.0 as u32);
+        h.write_u32(face.StencilPassOp.0 as u32);
+        h.write_u32(face.StencilFunc.0 as u32);
+    }
+
+    let layout = &desc.InputLayout;
+    h.write_u32(layout.NumElements);
+    for element in slice(layout.pInputElementDescs, layout.NumElements) {
+        h.write_bytes(name(element.SemanticName));
+        h.write_u32(element.SemanticIndex);
+        h.write_u32(element.Format.0 as u32);
+        h.write_u32(element.InputSlot);
+        h.write_u32(element.AlignedByteOffset);
+        h.write_u32(element.InputSlotClass.0 as u32);
+        h.write_u32(element.InstanceDataStepRate);
+    }
+
+    h.write_u32(desc.IBStripCutValue.0 as u32);
+    h.write_u32(desc.PrimitiveTopologyType.0 as u32);
+    h.write_u32(desc.NumRenderTargets);
+    for format in &desc.RTVFormats {
+        h.write_u32(format.0 as u32);
+    }
+    h.write_u32(desc.DSVFormat.0 as u32);
+    h.write_u32(desc.SampleDesc.Count);
+    h.write_u32(desc.SampleDesc.Quality);
+    h.write_u32(desc.NodeMask);
+    h.write_u32(desc.Flags.0 as u32);
+
+    h.finish()
+}
+
+fn write_bool(h: &mut ContentHasher, value: BOOL) {
+    h.write_bool(value.as_bool());
+}
+
+unsafe fn bytecode(shader: &D3D12_SHADER_BYTECODE) -> &[u8] {
+    slice(shader.pShaderBytecode as *const u8, shader.BytecodeLength)
+}
+
+unsafe fn name<'a>(name: PCSTR) -> &'a [u8] {
+    if name.is_null() {
+        &[]
+    } else {
+        std::ffi::CStr::from_ptr(name.0 as _).to_bytes()
+    }
+}
+
+unsafe fn slice<'a, T, N: TryInto<usize>>(data: *const T, count: N) -> &'a [T] {
+    let count = count.try_into().unwrap_or(0);
+    if data.is_null() || count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, count)
+    }
+}
+
+/// Creates pipeline states through a cache that persists across runs, so the
+/// driver does not have to compile them again.
+///
+/// Pipelines are stored in an `ID3D12PipelineLibrary` where the device supports
+/// it, and otherwise each one's `GetCachedBlob` is stored separately and
+/// handed back through `CachedPSO`. Anything the driver rejects, for example
+/// after a driver update, is rebuilt.
+pub struct PipelineCache {
+    device: ID3D12Device,
+    cache: CacheDir,
+    library: Option<PipelineLibrary>,
+}
+
+struct PipelineLibrary {
+    // Declared before `_data`, which must outlive it.
+    library: ID3D12PipelineLibrary,
+    _data: Vec<u8>,
+    dirty: bool,
+}
+
+fn library_key() -> ContentHash {
+    let mut h = ContentHasher::new();
+    h.write_str("pipeline library");
+    h.finish()
+}
+
+impl PipelineCache {
+    pub fn new(device: &ID3D12Device, cache: CacheDir) -> Self {
+        let library = device.cast::<ID3D12Device1>().ok().and_then(|device| {
+            let data = cache.load(&library_key()).unwrap_or_default();
+            match unsafe { device.CreatePipelineLibrary(&data) } {
+                Ok(library) => Some(PipelineLibrary {
+                    library,
+                    _data: data,
+                    dirty: false,
+                }),
+                // A library from another driver or adapter is rejected;
+                // start a new one, which replaces it when saved.
+                Err(_) => unsafe { device.CreatePipelineLibrary(&[]) }
+                    .ok()
+                    .map(|library| PipelineLibrary {
+                        library,
+                        _data: Vec::new(),
+                        dirty: true,
+                    }),
+            }
+        });
+
+        PipelineCache {
+            device: device.clone(),
+            cache,
+            library,
+        }
+    }
+
+    /// Caches in `pipelines` under `CacheDir::default_root()`.
+    pub fn with_default_cache(device: &ID3D12Device) -> Self {
+        Self::new(device, CacheDir::new(CacheDir::default_root(), "pipelines"))
+    }
+
+    pub fn uses_pipeline_library(&self) -> bool {
+        self.library.is_some()
+    }
+
+    /// # Safety
+    /// The pointers in `desc` must be valid, as for
+    /// `CreateGraphicsPipelineState`.
+    pub unsafe fn create_graphics_pipeline(
+        &mut self,
+        desc: &D3D12_GRAPHICS_PIPELINE_STATE_DESC,
+        root_signature_blob: &[u8],
+    ) -> Result<ID3D12PipelineState> {
+        let key = hash_graphics_pipeline(desc, root_signature_blob);
+
+        match &mut self.library {
+            Some(library) => {
+                let name = HSTRING::from(key.to_string());
+                if let Ok(pso) = library.library.LoadGraphicsPipeline(&name, desc) {
+                    return Ok(pso);
+                }
+
+                let pso: ID3D12PipelineState = self.device.CreateGraphicsPipelineState(desc)?;
+                if library.library.StorePipeline(&name, &pso).is_ok() {
+                    library.dirty = true;
+                }
+                Ok(pso)
+            }
+            None => {
+                if let Some(blob) = self.cache.load(&key) {
+                    // The copy shares the original's root signature pointer
+                    // without adding a reference, which is fine since it is
+                    // never dropped.
+                    let mut cached_desc = std::ptr::read(desc);
+                    cached_desc.CachedPSO = D3D12_CACHED_PIPELINE_STATE {
+                        pCachedBlob: blob.as_ptr() as _,
+                        CachedBlobSizeInBytes: blob.len(),
+                    };
+                    if let Ok(pso) = self.device.CreateGraphicsPipelineState(&cached_desc) {
+                        return Ok(pso);
+                    }
+                }
+
+                let pso: ID3D12PipelineState = self.device.CreateGraphicsPipelineState(desc)?;
+                if let Ok(blob) = pso.GetCachedBlob() {
+                    let _ = self.cache.store(&key, blob_bytes(&blob));
+                }
+                Ok(pso)
+            }
+        }
+    }
+
+    /// Writes out the pipeline library, if anything was added to it. Pipelines
+    /// cached through `GetCachedBlob` are written as they are created.
+    pub fn save(&mut self) -> Result<()> {
+        if let Some(library) = &mut self.library {
+            if library.dirty {
+                let mut data = vec![0; unsafe { library.library.GetSerializedSize() }];
+                unsafe { library.library.Serialize(&mut data) }?;
+                self.cache.store(&library_key(), &data)?;
+                library.dirty = false;
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use windows::Win32::Graphics::{Direct3D::ID3DBlob, Direct3D12::*};
 
 pub trait ShaderBytecode {
     fn from_blob(blob: &ID3DBlob) -> Self;
+    fn from_bytes(bytes: &[u8]) -> Self;
 }
 
 impl ShaderBytecode for D3D12_SHADER_BYTECODE {
@@ -11,6 +12,20 @@ impl ShaderBytecode for D3D12_SHADER_BYTECODE {
             BytecodeLength: unsafe { blob.GetBufferSize() },
         }
     }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        D3D12_SHADER_BYTECODE {
+            pShaderBytecode: bytes.as_ptr() as _,
+            BytecodeLength: bytes.len(),
+        }
+    }
+}
+
+/// The contents of a blob, for example compiled shader bytecode.
+pub fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize())
+    }
 }
 
 pub trait RasterizerDesc {
@@ -0,0 +1,527 @@
+use crate::dxbc::{
+    ComponentType, RegisterClass, RootParameterKind, RootSignature, ShaderKind, ShaderReflection,
+};
+use std::fmt;
+use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::*};
+
+mod root_signature_desc;
+
+/// A register range that a root signature makes visible to one or more shader
+/// stages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootRange {
+    pub class: RegisterClass,
+    pub space: u32,
+    pub base_register: u32,
+    /// `None` for unbounded descriptor table ranges.
+    pub count: Option<u32>,
+    pub visibility: D3D12_SHADER_VISIBILITY,
+    /// For root constants, the number of 32-bit values.
+    pub root_constants: Option<u32>,
+}
+
+impl RootRange {
+    fn contains(&self, register: u32, count: Option<u32>) -> bool {
+        if register < self.base_register {
+            return false;
+        }
+        match (self.count, count) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(available), Some(wanted)) => {
+                register as u64 + wanted as u64 <= self.base_register as u64 + available as u64
+            }
+        }
+    }
+
+    fn is_visible_to(&self, kind: ShaderKind) -> bool {
+        self.visibility == D3D12_SHADER_VISIBILITY_ALL
+            || Some(self.visibility) == visibility_for(kind)
+    }
+}
+
+/// The registers bound by a root signature, flattened out of its parameters
+/// and static samplers.
+#[derive(Debug, Clone, Default)]
+pub struct RootSignatureLayout {
+    pub ranges: Vec<RootRange>,
+}
+
+impl RootSignatureLayout {
+    /// Builds the layout from a root signature embedded in a shader container.
+    pub fn from_root_signature(root_signature: &RootSignature) -> Self {
+        let mut layout = RootSignatureLayout::default();
+
+        for parameter in &root_signature.parameters {
+            let visibility = D3D12_SHADER_VISIBILITY(parameter.visibility.to_raw() as i32);
+            match &parameter.kind {
+                RootParameterKind::DescriptorTable(ranges) => {
+                    for range in ranges {
+                        layout.ranges.push(RootRange {
+                            class: range.class,
+                            space: range.space,
+                            base_register: range.base_register,
+                            count: if range.num_descriptors == u32::MAX {
+                                None
+                            } else {
+                                Some(range.num_descriptors)
+                            },
+                            visibility,
+                            root_constants: None,
+                        });
+                    }
+                }
+                RootParameterKind::Constants {
+                    register,
+                    space,
+                    num_32bit_values,
+                } => layout.ranges.push(RootRange {
+                    class: RegisterClass::Cbv,
+                    space: *space,
+                    base_register: *register,
+                    count: Some(1),
+                    visibility,
+                    root_constants: Some(*num_32bit_values),
+                }),
+                RootParameterKind::Descriptor {
+                    class,
+                    register,
+                    space,
+                    ..
+                } => layout.ranges.push(RootRange {
+                    class: *class,
+                    space: *space,
+                    base_register: *register,
+                    count: Some(1),
+                    visibility,
+                    root_constants: None,
+                }),
+            }
+        }
+
+        for sampler in &root_signature.static_samplers {
+            layout.ranges.push(RootRange {
+                class: RegisterClass::Sampler,
+                space: sampler.space,
+                base_register: sampler.register,
+                count: Some(1),
+                visibility: D3D12_SHADER_VISIBILITY(sampler.visibility.to_raw() as i32),
+                root_constants: None,
+            });
+        }
+
+        layout
+    }
+
+    /// Finds the range that makes `count` registers starting at `register`
+    /// visible to a shader of the given kind. A count of `None` asks for an
+    /// unbounded array.
+    pub fn find(
+        &self,
+        kind: ShaderKind,
+        class: RegisterClass,
+        space: u32,
+        register: u32,
+        count: Option<u32>,
+    ) -> Option<&RootRange> {
+        self.ranges.iter().find(|range| {
+            range.class == class
+                && range.space == space
+                && range.is_visible_to(kind)
+                && range.contains(register, count)
+        })
+    }
+}
+
+fn visibility_for(kind: ShaderKind) -> Option<D3D12_SHADER_VISIBILITY> {
+    match kind {
+        ShaderKind::Vertex => Some(D3D12_SHADER_VISIBILITY_VERTEX),
+        ShaderKind::Pixel => Some(D3D12_SHADER_VISIBILITY_PIXEL),
+        ShaderKind::Geometry => Some(D3D12_SHADER_VISIBILITY_GEOMETRY),
+        ShaderKind::Hull => Some(D3D12_SHADER_VISIBILITY_HULL),
+        ShaderKind::Domain => Some(D3D12_SHADER_VISIBILITY_DOMAIN),
+        _ => None,
+    }
+}
+
+/// The size of a constant buffer view that the application binds at a given
+/// register. The root signature only describes where CBVs go, not how big they
+/// are, so this has to come from the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstantBufferBinding {
+    pub space: u32,
+    pub register: u32,
+    pub size: u32,
+}
+
+/// Everything about a graphics pipeline that has to agree with its shaders.
+pub struct GraphicsPipelineLayout<'a> {
+    pub input_layout: &'a [D3D12_INPUT_ELEMENT_DESC],
+    pub root_signature: &'a RootSignatureLayout,
+    pub constant_buffers: &'a [ConstantBufferBinding],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The vertex shader reads a semantic that the input layout does not
+    /// provide.
+    MissingInputElement {
+        semantic_name: String,
+        semantic_index: u32,
+    },
+    /// The input layout provides the semantic in a format the vertex shader
+    /// cannot read it as.
+    IncompatibleInputFormat {
+        semantic_name: String,
+        semantic_index: u32,
+        format: DXGI_FORMAT,
+        component_type: ComponentType,
+    },
+    /// A shader reads a value that the previous stage does not write.
+    UnlinkedStageInput {
+        stage: ShaderKind,
+        semantic_name: String,
+        semantic_index: u32,
+    },
+    /// A shader binds a register that no root signature range covers.
+    UncoveredRegister {
+        stage: ShaderKind,
+        name: String,
+        class: RegisterClass,
+        space: u32,
+        register: u32,
+    },
+    /// A cbuffer is bigger than the view bound to its register.
+    ConstantBufferTooSmall {
+        stage: ShaderKind,
+        name: String,
+        space: u32,
+        register: u32,
+        required: u32,
+        bound: u32,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::MissingInputElement {
+                semantic_name,
+                semantic_index,
+            } => write!(
+                f,
+                "input layout has no element for {}{}",
+                semantic_name, semantic_index
+            ),
+            ValidationError::IncompatibleInputFormat {
+                semantic_name,
+                semantic_index,
+                format,
+                component_type,
+            } => write!(
+                f,
+                "input layout provides {}{} as format {}, which cannot be read as {:?}",
+                semantic_name, semantic_index, format.0, component_type
+            ),
+            ValidationError::UnlinkedStageInput {
+                stage,
+                semantic_name,
+                semantic_index,
+            } => write!(
+                f,
+                "{:?} shader reads {}{}, which the previous stage does not write",
+                stage, semantic_name, semantic_index
+            ),
+            ValidationError::UncoveredRegister {
+                stage,
+                name,
+                class,
+                space,
+                register,
+            } => {
+                write!(f, "{:?} shader binds ", stage)?;
+                // DXIL reflection does not know resource names.
+                if !name.is_empty() {
+                    write!(f, "'{}' to ", name)?;
+                }
+                write!(
+                    f,
+                    "{}{} (space {}), which the root signature does not cover",
+                    register_prefix(*class),
+                    register,
+                    space
+                )
+            }
+            ValidationError::ConstantBufferTooSmall {
+                stage,
+                name,
+                space,
+                register,
+                required,
+                bound,
+            } => write!(
+                f,
+                "{:?} shader needs {} bytes for cbuffer '{}' at b{} (space {}), but only {} are bound",
+                stage, required, name, register, space, bound
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+fn register_prefix(class: RegisterClass) -> char {
+    match class {
+        RegisterClass::Cbv => 'b',
+        RegisterClass::Srv => 't',
+        RegisterClass::Uav => 'u',
+        RegisterClass::Sampler => 's',
+    }
+}
+
+/// Checks the shaders of a graphics pipeline, in pipeline order, against the
+/// rest of the pipeline's description. Returns every problem found rather than
+/// stopping at the first.
+pub fn validate_graphics_pipeline(
+    shaders: &[&ShaderReflection],
+    layout: &GraphicsPipelineLayout,
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    if let Some(vs) = shaders.iter().find(|s| s.kind == ShaderKind::Vertex) {
+        validate_input_layout(vs, layout.input_layout, &mut errors);
+    }
+
+    for pair in shaders.windows(2) {
+        validate_linkage(pair[0], pair[1], &mut errors);
+    }
+
+    for shader in shaders {
+        validate_bindings(shader, layout, &mut errors);
+    }
+
+    errors
+}
+
+fn validate_input_layout(
+    vs: &ShaderReflection,
+    input_layout: &[D3D12_INPUT_ELEMENT_DESC],
+    errors: &mut Vec<ValidationError>,
+) {
+    for input in &vs.input_signature.elements {
+        // System values such as SV_VertexID are generated, not fetched.
+        if input.is_system_value() {
+            continue;
+        }
+
+        let element = input_layout.iter().find(|element| {
+            element.SemanticIndex == input.semantic_index
+                && unsafe { element.SemanticName.as_bytes() }
+                    .eq_ignore_ascii_case(input.semantic_name.as_bytes())
+        });
+
+        match element {
+            None => errors.push(ValidationError::MissingInputElement {
+                semantic_name: input.semantic_name.clone(),
+                semantic_index: input.semantic_index,
+            }),
+            Some(element) => {
+                if !format_matches(element.Format, input.component_type) {
+                    errors.push(ValidationError::IncompatibleInputFormat {
+                        semantic_name: input.semantic_name.clone(),
+                        semantic_index: input.semantic_index,
+                        format: element.Format,
+                        component_type: input.component_type,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn validate_linkage(
+    producer: &ShaderReflection,
+    consumer: &ShaderReflection,
+    errors: &mut Vec<ValidationError>,
+) {
+    for input in &consumer.input_signature.elements {
+        // Only read components need to be written; system values like
+        // SV_IsFrontFace come from the rasterizer.
+        if input.read_write_mask == 0 {
+            continue;
+        }
+
+        let written = producer
+            .output_signature
+            .find(&input.semantic_name, input.semantic_index)
+            .map(|output| input.read_write_mask & !output.mask == 0)
+            .unwrap_or(false);
+
+        if !written && !is_generated_input(consumer.kind, &input.semantic_name) {
+            errors.push(ValidationError::UnlinkedStageInput {
+                stage: consumer.kind,
+                semantic_name: input.semantic_name.clone(),
+                semantic_index: input.semantic_index,
+            });
+        }
+    }
+}
+
+fn is_generated_input(kind: ShaderKind, semantic_name: &str) -> bool {
+    const GENERATED: &[&str] = &[
+        "SV_PrimitiveID",
+        "SV_IsFrontFace",
+        "SV_SampleIndex",
+        "SV_Coverage",
+        "SV_InnerCoverage",
+        "SV_InstanceID",
+    ];
+    kind == ShaderKind::Pixel
+        && GENERATED
+            .iter()
+            .any(|g| g.eq_ignore_ascii_case(semantic_name))
+}
+
+fn validate_bindings(
+    shader: &ShaderReflection,
+    layout: &GraphicsPipelineLayout,
+    errors: &mut Vec<ValidationError>,
+) {
+    for resource in &shader.bound_resources {
+        let class = match resource.input_type.register_class() {
+            Some(class) => class,
+            None => continue,
+        };
+
+        let count = if resource.is_unbounded() {
+            None
+        } else {
+            Some(resource.bind_count)
+        };
+
+        let range = match layout.root_signature.find(
+            shader.kind,
+            class,
+            resource.space,
+            resource.bind_point,
+            count,
+        ) {
+            Some(range) => range,
+            None => {
+                errors.push(ValidationError::UncoveredRegister {
+                    stage: shader.kind,
+                    name: resource.name.clone(),
+                    class,
+                    space: resource.space,
+                    register: resource.bind_point,
+                });
+                continue;
+            }
+        };
+
+        if class != RegisterClass::Cbv {
+            continue;
+        }
+
+        let required = match shader.constant_buffer(&resource.name) {
+            Some(cb) => cb.size,
+            None => continue,
+        };
+
+        let bound = range.root_constants.map(|count| count * 4).or_else(|| {
+            layout
+                .constant_buffers
+                .iter()
+                .find(|cb| cb.space == resource.space && cb.register == resource.bind_point)
+                .map(|cb| cb.size)
+        });
+
+        if let Some(bound) = bound {
+            if bound < required {
+                errors.push(ValidationError::ConstantBufferTooSmall {
+                    stage: shader.kind,
+                    name: resource.name.clone(),
+                    space: resource.space,
+                    register: resource.bind_point,
+                    required,
+                    bound,
+                });
+            }
+        }
+    }
+}
+
+/// Whether a vertex attribute in `format` can be read by a shader input of the
+/// given component type. Normalized formats are read as floats, and the width
+/// of the shader's type does not matter.
+fn format_matches(format: DXGI_FORMAT, component_type: ComponentType) -> bool {
+    match format_component_type(format) {
+        Some(format_type) => {
+            if component_type.is_float() {
+                format_type == ComponentType::Float32
+            } else if component_type.is_unsigned_int() {
+                format_type == ComponentType::UInt32
+            } else if component_type.is_signed_int() {
+                format_type == ComponentType::SInt32
+            } else {
+                true
+            }
+        }
+        None => false,
+    }
+}
+
+fn format_component_type(format: DXGI_FORMAT) -> Option<ComponentType> {
+    match format {
+        DXGI_FORMAT_R32G32B32A32_FLOAT
+        | DXGI_FORMAT_R32G32B32_FLOAT
+        | DXGI_FORMAT_R32G32_FLOAT
+        | DXGI_FORMAT_R32_FLOAT
+        | DXGI_FORMAT_R16G16B16A16_FLOAT
+        | DXGI_FORMAT_R16G16_FLOAT
+        | DXGI_FORMAT_R16_FLOAT
+        | DXGI_FORMAT_R16G16B16A16_UNORM
+        | DXGI_FORMAT_R16G16_UNORM
+        | DXGI_FORMAT_R16_UNORM
+        | DXGI_FORMAT_R16G16B16A16_SNORM
+        | DXGI_FORMAT_R16G16_SNORM
+        | DXGI_FORMAT_R16_SNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM
+        | DXGI_FORMAT_R8G8_UNORM
+        | DXGI_FORMAT_R8_UNORM
+        | DXGI_FORMAT_R8G8B8A8_SNORM
+        | DXGI_FORMAT_R8G8_SNORM
+        | DXGI_FORMAT_R8_SNORM
+        | DXGI_FORMAT_B8G8R8A8_UNORM
+        | DXGI_FORMAT_R10G10B10A2_UNORM
+        | DXGI_FORMAT_R11G11B10_FLOAT => Some(ComponentType::Float32),
+
+        DXGI_FORMAT_R32G32B32A32_UINT
+        | DXGI_FORMAT_R32G32B32_UINT
+        | DXGI_FORMAT_R32G32_UINT
+        | DXGI_FORMAT_R32_UINT
+        | DXGI_FORMAT_R16G16B16A16_UINT
+        | DXGI_FORMAT_R16G16_UINT
+        | DXGI_FORMAT_R16_UINT
+        | DXGI_FORMAT_R8G8B8A8_UINT
+        | DXGI_FORMAT_R8G8_UINT
+        | DXGI_FORMAT_R8_UINT
+        | DXGI_FORMAT_R10G10B10A2_UINT => Some(ComponentType::UInt32),
+
+        DXGI_FORMAT_R32G32B32A32_SINT
+        | DXGI_FORMAT_R32G32B32_SINT
+        | DXGI_FORMAT_R32G32_SINT
+        | DXGI_FORMAT_R32_SINT
+        | DXGI_FORMAT_R16G16B16A16_SINT
+        | DXGI_FORMAT_R16G16_SINT
+        | DXGI_FORMAT_R16_SINT
+        | DXGI_FORMAT_R8G8B8A8_SINT
+        | DXGI_FORMAT_R8G8_SINT
+        | DXGI_FORMAT_R8_SINT => Some(ComponentType::SInt32),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,181 @@
+use super::{RootRange, RootSignatureLayout};
+use crate::dxbc::RegisterClass;
+use windows::Win32::Graphics::Direct3D12::*;
+
+impl RootSignatureLayout {
+    /// # Safety
+    ///
+    /// The parameter, range and static sampler pointers in `desc` must be
+    /// valid.
+    pub unsafe fn from_versioned_desc(desc: &D3D12_VERSIONED_ROOT_SIGNATURE_DESC) -> Self {
+        if desc.Version == D3D_ROOT_SIGNATURE_VERSION_1_0 {
+            Self::from_desc(&desc.Anonymous.Desc_1_0)
+        } else {
+            Self::from_desc1(&desc.Anonymous.Desc_1_1)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The parameter, range and static sampler pointers in `desc` must be
+    /// valid.
+    pub unsafe fn from_desc(desc: &D3D12_ROOT_SIGNATURE_DESC) -> Self {
+        Self::from_parameters(
+            raw_slice(desc.pParameters, desc.NumParameters),
+            raw_slice(desc.pStaticSamplers, desc.NumStaticSamplers),
+        )
+    }
+
+    /// # Safety
+    ///
+    /// The parameter, range and static sampler pointers in `desc` must be
+    /// valid.
+    pub unsafe fn from_desc1(desc: &D3D12_ROOT_SIGNATURE_DESC1) -> Self {
+        Self::from_parameters(
+            raw_slice(desc.pParameters, desc.NumParameters),
+            raw_slice(desc.pStaticSamplers, desc.NumStaticSamplers),
+        )
+    }
+
+    unsafe fn from_parameters<P: RootParameterDesc>(
+        parameters: &[P],
+        static_samplers: &[D3D12_STATIC_SAMPLER_DESC],
+    ) -> Self {
+        let mut layout = RootSignatureLayout::default();
+
+        for parameter in parameters {
+            parameter.push_ranges(&mut layout);
+        }
+
+        for sampler in static_samplers {
+            layout.ranges.push(RootRange {
+                class: RegisterClass::Sampler,
+                space: sampler.RegisterSpace,
+                base_register: sampler.ShaderRegister,
+                count: Some(1),
+                visibility: sampler.ShaderVisibility,
+                root_constants: None,
+            });
+        }
+
+        layout
+    }
+
+    fn push_table_range(
+        &mut self,
+        range_type: D3D12_DESCRIPTOR_RANGE_TYPE,
+        space: u32,
+        base_register: u32,
+        count: u32,
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) {
+        let class = match range_type {
+            D3D12_DESCRIPTOR_RANGE_TYPE_CBV => RegisterClass::Cbv,
+            D3D12_DESCRIPTOR_RANGE_TYPE_SRV => RegisterClass::Srv,
+            D3D12_DESCRIPTOR_RANGE_TYPE_UAV => RegisterClass::Uav,
+            _ => RegisterClass::Sampler,
+        };
+        self.ranges.push(RootRange {
+            class,
+            space,
+            base_register,
+            count: if count == u32::MAX { None } else { Some(count) },
+            visibility,
+            root_constants: None,
+        });
+    }
+
+    fn push_root_constants(
+        &mut self,
+        constants: &D3D12_ROOT_CONSTANTS,
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) {
+        self.ranges.push(RootRange {
+            class: RegisterClass::Cbv,
+            space: constants.RegisterSpace,
+            base_register: constants.ShaderRegister,
+            count: Some(1),
+            visibility,
+            root_constants: Some(constants.Num32BitValues),
+        });
+    }
+
+    fn push_root_descriptor(
+        &mut self,
+        parameter_type: D3D12_ROOT_PARAMETER_TYPE,
+        space: u32,
+        register: u32,
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) {
+        let class = match parameter_type {
+            D3D12_ROOT_PARAMETER_TYPE_SRV => RegisterClass::Srv,
+            D3D12_ROOT_PARAMETER_TYPE_UAV => RegisterClass::Uav,
+            _ => RegisterClass::Cbv,
+        };
+        self.ranges.push(RootRange {
+            class,
+            space,
+            base_register: register,
+            count: Some(1),
+            visibility,
+            root_constants: None,
+        });
+    }
+}
+
+/// `D3D12_ROOT_PARAMETER` and `D3D12_ROOT_PARAMETER1` only differ in the
+/// flags on their ranges and descriptors, which the layout doesn't keep, so
+/// both are read by the same code.
+trait RootParameterDesc {
+    /// # Safety
+    ///
+    /// The range pointers of descriptor tables must be valid.
+    unsafe fn push_ranges(&self, layout: &mut RootSignatureLayout);
+}
+
+macro_rules! impl_root_parameter_desc {
+    ($parameter:ty) => {
+        impl RootParameterDesc for $parameter {
+            unsafe fn push_ranges(&self, layout: &mut RootSignatureLayout) {
+                let visibility = self.ShaderVisibility;
+                match self.ParameterType {
+                    D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE => {
+                        let table = &self.Anonymous.DescriptorTable;
+                        for range in raw_slice(table.pDescriptorRanges, table.NumDescriptorRanges) {
+                            layout.push_table_range(
+                                range.RangeType,
+                                range.RegisterSpace,
+                                range.BaseShaderRegister,
+                                range.NumDescriptors,
+                                visibility,
+                            );
+                        }
+                    }
+                    D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS => {
+                        layout.push_root_constants(&self.Anonymous.Constants, visibility);
+                    }
+                    parameter_type => {
+                        let descriptor = &self.Anonymous.Descriptor;
+                        layout.push_root_descriptor(
+                            parameter_type,
+                            descriptor.RegisterSpace,
+                            descriptor.ShaderRegister,
+                            visibility,
+                        );
+                    }
+                }
+            }
+        }
+    };
+}
+
+impl_root_parameter_desc!(D3D12_ROOT_PARAMETER);
+impl_root_parameter_desc!(D3D12_ROOT_PARAMETER1);
+
+unsafe fn raw_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() || count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, count as usize)
+    }
+}
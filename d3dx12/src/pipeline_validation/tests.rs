@@ -0,0 +1,344 @@
+use super::*;
+use crate::dxbc::{
+    BoundResource, ConstantBuffer, ConstantBufferType, Container, RootParameter, ShaderInputType,
+    ShaderVisibility, StaticSampler,
+};
+use windows::core::{s, PCSTR};
+
+fn reflect(data: &[u8]) -> ShaderReflection {
+    Container::parse(data).unwrap().reflect().unwrap()
+}
+
+// The vertex shader reads float2 POSITION, float2 TEXCOORD and float4 COLOR,
+// and writes them on with SV_POSITION. The pixel shader samples t0 with s0.
+fn vertex_shader() -> ShaderReflection {
+    reflect(include_bytes!("../../tests/fixtures/dxbc/fxc_vs_5_0.cso"))
+}
+
+fn pixel_shader() -> ShaderReflection {
+    reflect(include_bytes!("../../tests/fixtures/dxbc/fxc_ps_5_0.cso"))
+}
+
+fn element(name: PCSTR, format: DXGI_FORMAT) -> D3D12_INPUT_ELEMENT_DESC {
+    D3D12_INPUT_ELEMENT_DESC {
+        SemanticName: name,
+        Format: format,
+        ..Default::default()
+    }
+}
+
+fn input_layout() -> [D3D12_INPUT_ELEMENT_DESC; 3] {
+    [
+        element(s!("POSITION"), DXGI_FORMAT_R32G32_FLOAT),
+        element(s!("TEXCOORD"), DXGI_FORMAT_R32G32_FLOAT),
+        element(s!("COLOR"), DXGI_FORMAT_R8G8B8A8_UNORM),
+    ]
+}
+
+fn range(class: RegisterClass, base_register: u32, count: Option<u32>) -> RootRange {
+    RootRange {
+        class,
+        space: 0,
+        base_register,
+        count,
+        visibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        root_constants: None,
+    }
+}
+
+fn pixel_root_signature() -> RootSignatureLayout {
+    RootSignatureLayout {
+        ranges: vec![
+            range(RegisterClass::Srv, 0, Some(1)),
+            range(RegisterClass::Sampler, 0, Some(1)),
+        ],
+    }
+}
+
+fn validate(
+    shaders: &[&ShaderReflection],
+    input_layout: &[D3D12_INPUT_ELEMENT_DESC],
+    root_signature: &RootSignatureLayout,
+    constant_buffers: &[ConstantBufferBinding],
+) -> Vec<ValidationError> {
+    validate_graphics_pipeline(
+        shaders,
+        &GraphicsPipelineLayout {
+            input_layout,
+            root_signature,
+            constant_buffers,
+        },
+    )
+}
+
+#[test]
+fn matching_pipeline_has_no_errors() {
+    let errors = validate(
+        &[&vertex_shader(), &pixel_shader()],
+        &input_layout(),
+        &pixel_root_signature(),
+        &[],
+    );
+    assert_eq!(errors, []);
+}
+
+#[test]
+fn input_layout_must_provide_every_input() {
+    let mut input_layout = input_layout();
+    input_layout[1].SemanticIndex = 1;
+    input_layout[2].Format = DXGI_FORMAT_R8G8B8A8_UINT;
+
+    let errors = validate(
+        &[&vertex_shader()],
+        &input_layout,
+        &RootSignatureLayout::default(),
+        &[],
+    );
+    assert_eq!(
+        errors,
+        [
+            ValidationError::MissingInputElement {
+                semantic_name: "TEXCOORD".into(),
+                semantic_index: 0,
+            },
+            ValidationError::IncompatibleInputFormat {
+                semantic_name: "COLOR".into(),
+                semantic_index: 0,
+                format: DXGI_FORMAT_R8G8B8A8_UINT,
+                component_type: ComponentType::Float32,
+            },
+        ]
+    );
+}
+
+#[test]
+fn semantic_names_match_case_insensitively() {
+    let mut input_layout = input_layout();
+    input_layout[0].SemanticName = s!("position");
+    let errors = validate(
+        &[&vertex_shader()],
+        &input_layout,
+        &RootSignatureLayout::default(),
+        &[],
+    );
+    assert_eq!(errors, []);
+}
+
+#[test]
+fn stages_must_link() {
+    let mut vertex_shader = vertex_shader();
+    vertex_shader
+        .output_signature
+        .elements
+        .retain(|e| e.semantic_name != "TEXCOORD");
+    // Writing fewer components than the pixel shader reads is not enough
+    // either.
+    vertex_shader.output_signature.elements[1].mask = 0x7;
+
+    let errors = validate(
+        &[&vertex_shader, &pixel_shader()],
+        &input_layout(),
+        &pixel_root_signature(),
+        &[],
+    );
+    let unlinked = |semantic_name: &str| ValidationError::UnlinkedStageInput {
+        stage: ShaderKind::Pixel,
+        semantic_name: semantic_name.into(),
+        semantic_index: 0,
+    };
+    assert_eq!(errors, [unlinked("TEXCOORD"), unlinked("COLOR")]);
+}
+
+#[test]
+fn root_signature_must_cover_bindings() {
+    let mut root_signature = pixel_root_signature();
+    // The texture is only visible to the vertex shader, and the sampler is in
+    // the wrong space.
+    root_signature.ranges[0].visibility = D3D12_SHADER_VISIBILITY_VERTEX;
+    root_signature.ranges[1].space = 1;
+
+    let errors = validate(
+        &[&vertex_shader(), &pixel_shader()],
+        &input_layout(),
+        &root_signature,
+        &[],
+    );
+    let uncovered = |name: &str, class| ValidationError::UncoveredRegister {
+        stage: ShaderKind::Pixel,
+        name: name.into(),
+        class,
+        space: 0,
+        register: 0,
+    };
+    assert_eq!(
+        errors,
+        [
+            uncovered("g_sampler", RegisterClass::Sampler),
+            uncovered("g_texture", RegisterClass::Srv)
+        ]
+    );
+    assert_eq!(
+        errors[1].to_string(),
+        "Pixel shader binds 'g_texture' to t0 (space 0), which the root signature does not cover"
+    );
+}
+
+#[test]
+fn ranges_cover_registers_within_them() {
+    let layout = RootSignatureLayout {
+        ranges: vec![
+            range(RegisterClass::Srv, 2, Some(3)),
+            range(RegisterClass::Uav, 4, None),
+        ],
+    };
+    let find = |class, register, count| {
+        layout
+            .find(ShaderKind::Pixel, class, 0, register, count)
+            .is_some()
+    };
+
+    assert!(find(RegisterClass::Srv, 2, Some(3)));
+    assert!(find(RegisterClass::Srv, 4, Some(1)));
+    assert!(!find(RegisterClass::Srv, 1, Some(1)));
+    assert!(!find(RegisterClass::Srv, 3, Some(3)));
+    // Only an unbounded range can hold an unbounded array.
+    assert!(!find(RegisterClass::Srv, 2, None));
+    assert!(find(RegisterClass::Uav, 4, None));
+    assert!(find(RegisterClass::Uav, u32::MAX, Some(1)));
+    assert!(!find(RegisterClass::Uav, 3, Some(1)));
+    assert!(!find(RegisterClass::Cbv, 2, Some(1)));
+}
+
+/// A vertex shader with a 64 byte cbuffer at b0.
+fn shader_with_constants() -> ShaderReflection {
+    let mut shader = vertex_shader();
+    shader.constant_buffers.push(ConstantBuffer {
+        name: "Constants".into(),
+        buffer_type: ConstantBufferType::CBuffer,
+        size: 64,
+        flags: 0,
+        variables: Vec::new(),
+    });
+    shader.bound_resources.push(BoundResource {
+        name: "Constants".into(),
+        input_type: ShaderInputType::CBuffer,
+        return_type: 0,
+        dimension: 0,
+        num_samples: 0,
+        bind_point: 0,
+        bind_count: 1,
+        flags: 0,
+        space: 0,
+    });
+    shader
+}
+
+#[test]
+fn constant_buffers_must_be_big_enough() {
+    let shader = shader_with_constants();
+    let mut root_signature = RootSignatureLayout {
+        ranges: vec![RootRange {
+            visibility: D3D12_SHADER_VISIBILITY_ALL,
+            ..range(RegisterClass::Cbv, 0, Some(1))
+        }],
+    };
+    let too_small = |bound| ValidationError::ConstantBufferTooSmall {
+        stage: ShaderKind::Vertex,
+        name: "Constants".into(),
+        space: 0,
+        register: 0,
+        required: 64,
+        bound,
+    };
+    let binding = |size| ConstantBufferBinding {
+        space: 0,
+        register: 0,
+        size,
+    };
+
+    let check = |root_signature: &RootSignatureLayout, bindings: &[ConstantBufferBinding]| {
+        validate(&[&shader], &input_layout(), root_signature, bindings)
+    };
+
+    // Without a binding there is no size to check against.
+    assert_eq!(check(&root_signature, &[]), []);
+    assert_eq!(check(&root_signature, &[binding(256)]), []);
+    assert_eq!(check(&root_signature, &[binding(48)]), [too_small(48)]);
+
+    // Root constants are sized by the root signature itself.
+    root_signature.ranges[0].root_constants = Some(8);
+    assert_eq!(check(&root_signature, &[binding(256)]), [too_small(32)]);
+}
+
+#[test]
+fn layout_from_embedded_root_signature() {
+    let root_signature = RootSignature {
+        version: crate::dxbc::RootSignatureVersion::V1_1,
+        flags: 0,
+        parameters: vec![
+            RootParameter {
+                visibility: ShaderVisibility::Pixel,
+                kind: RootParameterKind::DescriptorTable(vec![crate::dxbc::DescriptorRange {
+                    class: RegisterClass::Srv,
+                    num_descriptors: u32::MAX,
+                    base_register: 1,
+                    space: 2,
+                    flags: 0,
+                    offset_in_descriptors_from_table_start: 0,
+                }]),
+            },
+            RootParameter {
+                visibility: ShaderVisibility::All,
+                kind: RootParameterKind::Constants {
+                    register: 0,
+                    space: 0,
+                    num_32bit_values: 4,
+                },
+            },
+            RootParameter {
+                visibility: ShaderVisibility::Vertex,
+                kind: RootParameterKind::Descriptor {
+                    class: RegisterClass::Uav,
+                    register: 3,
+                    space: 0,
+                    flags: 0,
+                },
+            },
+        ],
+        static_samplers: vec![StaticSampler {
+            filter: 0,
+            address: [1; 3],
+            mip_lod_bias: 0.0,
+            max_anisotropy: 1,
+            comparison_func: 0,
+            border_color: 0,
+            min_lod: 0.0,
+            max_lod: f32::MAX,
+            register: 5,
+            space: 0,
+            visibility: ShaderVisibility::Pixel,
+            flags: 0,
+        }],
+    };
+
+    let layout = RootSignatureLayout::from_root_signature(&root_signature);
+    assert_eq!(
+        layout.ranges,
+        [
+            RootRange {
+                space: 2,
+                ..range(RegisterClass::Srv, 1, None)
+            },
+            RootRange {
+                visibility: D3D12_SHADER_VISIBILITY_ALL,
+                root_constants: Some(4),
+                ..range(RegisterClass::Cbv, 0, Some(1))
+            },
+            RootRange {
+                visibility: D3D12_SHADER_VISIBILITY_VERTEX,
+                ..range(RegisterClass::Uav, 3, Some(1))
+            },
+            range(RegisterClass::Sampler, 5, Some(1)),
+        ]
+    );
+}
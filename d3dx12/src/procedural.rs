@@ -0,0 +1,427 @@
+//! Textures generated from patterns, for placeholders and for scenes that
+//! have to render the same way every time.
+//!
+//! Colors are linear RGBA, written through `convert`, so a pattern can be
+//! generated in any format that `convert::convert` supports. Noise is hashed
+//! from its seed rather than drawn from a random number generator, and it
+//! tiles, as do the other patterns when their cells divide the texture evenly.
+
+use crate::convert;
+use crate::format::DXGI_FORMAT;
+use crate::texture::{FormatLayout, Texture};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    Solid([f32; 4]),
+    /// `cells` squares across and down, alternating between the two colors,
+    /// starting with the first at the top left.
+    Checker {
+        cells: [u32; 2],
+        colors: [[f32; 4]; 2],
+    },
+    /// Blends from the first color to the second, in linear space.
+    Gradient {
+        colors: [[f32; 4]; 2],
+        direction: GradientDirection,
+    },
+    /// Random values at the corners of each cell, blended smoothly.
+    ValueNoise(Noise),
+    /// Random gradients at the corners of each cell, which gives softer,
+    /// less blocky noise than `ValueNoise`.
+    PerlinNoise(Noise),
+    /// U in red and V in green, with `cells` squares across and down
+    /// alternately dimmed, and a white line along the top and left edge of
+    /// each, so that stretched or flipped texture coordinates show.
+    UvGrid {
+        cells: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientDirection {
+    /// Left to right.
+    Horizontal,
+    /// Top to bottom.
+    Vertical,
+    /// Top left to bottom right.
+    Diagonal,
+}
+
+/// The most cells across and down that noise has in any octave. Finer
+/// octaves would be much smaller than a texel of the largest texture.
+pub const MAX_NOISE_CELLS: u32 = 1 << 16;
+
+/// Noise that blends between two colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Noise {
+    /// Cells across and down in the coarsest octave, from 1 to
+    /// `MAX_NOISE_CELLS`. Values outside that are clamped to it.
+    pub cells: u32,
+    /// Each octave after the first has twice as many cells and half the
+    /// strength of the one before it. Octaves that would have more than
+    /// `MAX_NOISE_CELLS` are left out.
+    pub octaves: u32,
+    pub seed: u32,
+    pub colors: [[f32; 4]; 2],
+}
+
+impl Pattern {
+    /// The texels of a `width` by `height` texture, in rows from the top
+    /// left.
+    pub fn texels(&self, width: u32, height: u32) -> Vec<[f32; 4]> {
+        let mut texels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                texels.push(self.texel(x, y, width, height));
+            }
+        }
+        texels
+    }
+
+    /// A texture with only its top level. Returns `None` if `convert` can't
+    /// write `format`.
+    pub fn to_texture(&self, format: DXGI_FORMAT, width: u32, height: u32) -> Option<Texture> {
+        let subresource =
+            convert::write_texels(format, width, height, &self.texels(width, height))?;
+        Some(Texture {
+            format,
+            width,
+            height,
+            subresources: vec![subresource],
+        })
+    }
+
+    /// Writes the texels into `destination` with rows `row_pitch` bytes
+    /// apart, such as the part of a mapped upload buffer that a placed
+    /// footprint describes. Returns `None` if `convert` can't write `format`,
+    /// the rows don't fit in `row_pitch`, or `destination` is too small.
+    pub fn write(
+        &self,
+        format: DXGI_FORMAT,
+        width: u32,
+        height: u32,
+        row_pitch: u64,
+        destination: &mut [u8],
+    ) -> Option<()> {
+        let row_size = FormatLayout::of(format)?.row_pitch(width) as usize;
+        let row_pitch = row_pitch as usize;
+        let size = row_pitch * (height.max(1) as usize - 1) + row_size;
+        if row_pitch < row_size || destination.len() < size {
+            return None;
+        }
+
+        let subresource =
+            convert::write_texels(format, width, height, &self.texels(width, height))?;
+        for (source, destination) in subresource
+            .data
+            .chunks_exact(row_size)
+            .zip(destination.chunks_mut(row_pitch))
+        {
+            destination[..row_size].copy_from_slice(source);
+        }
+        Some(())
+    }
+
+    fn texel(&self, x: u32, y: u32, width: u32, height: u32) -> [f32; 4] {
+        // Texture coordinates of the texel's center.
+        let u = (x as f32 + 0.5) / width as f32;
+        let v = (y as f32 + 0.5) / height as f32;
+        match *self {
+            Pattern::Solid(color) => color,
+            Pattern::Checker { cells, colors } => {
+                let cell_x = x as u64 * cells[0] as u64 / width as u64;
+                let cell_y = y as u64 * cells[1] as u64 / height as u64;
+                colors[((cell_x + cell_y) % 2) as usize]
+            }
+            Pattern::Gradient { colors, direction } => {
+                let t = match direction {
+                    GradientDirection::Horizontal => u,
+                    GradientDirection::Vertical => v,
+                    GradientDirection::Diagonal => (u + v) / 2.0,
+                };
+                lerp(colors, t)
+            }
+            Pattern::ValueNoise(noise) => noise.color(u, v, value_noise),
+            Pattern::PerlinNoise(noise) => noise.color(u, v, perlin_noise),
+            Pattern::UvGrid { cells } => {
+                let cell = |x: u32, size: u32| x as u64 * cells as u64 / size as u64;
+                let on_line = |x: u32, size: u32| x == 0 || cell(x, size) != cell(x - 1, size);
+                if on_line(x, width) || on_line(y, height) {
+                    return [1.0; 4];
+                }
+                let shade = if (cell(x, width) + cell(y, height)) % 2 == 0 {
+                    1.0
+                } else {
+                    0.6
+                };
+                [u * shade, v * shade, 0.0, 1.0]
+            }
+        }
+    }
+}
+
+impl Noise {
+    fn color(&self, u: f32, v: f32, noise: fn(f32, f32, u32, u32) -> f32) -> [f32; 4] {
+        let (mut sum, mut total, mut strength) = (0.0, 0.0, 1.0);
+        let first = self.cells.clamp(1, MAX_NOISE_CELLS);
+        for octave in 0..self.octaves.max(1) {
+            let cells = match 1u32
+                .checked_shl(octave)
+                .and_then(|scale| first.checked_mul(scale))
+            {
+                Some(cells) if cells <= MAX_NOISE_CELLS => cells,
+                _ => break,
+            };
+            let seed = self.seed.wrapping_add(octave);
+            sum += noise(u * cells as f32, v * cells as f32, cells, seed) * strength;
+            total += strength;
+            strength /= 2.0;
+        }
+        lerp(self.colors, sum / total)
+    }
+}
+
+/// Noise from 0 to 1 at `(x, y)` in cells, repeating every `period` cells.
+fn value_noise(x: f32, y: f32, period: u32, seed: u32) -> f32 {
+    let corner = |x: u32, y: u32| hash(x % period, y % period, seed) as f32 / u32::MAX as f32;
+    cell_noise(x, y, corner)
+}
+
+/// As `value_noise`, from gradients rather than values.
+fn perlin_noise(x: f32, y: f32, period: u32, seed: u32) -> f32 {
+    let (cell_x, cell_y) = (x.floor(), y.floor());
+    let (fraction_x, fraction_y) = (x - cell_x, y - cell_y);
+    let gradient = |corner_x: u32, corner_y: u32| {
+        let angle = hash(corner_x % period, corner_y % period, seed) as f32 / u32::MAX as f32
+            * std::f32::consts::TAU;
+        // From the corner to the point.
+        let dx = fraction_x - (corner_x - cell_x as u32) as f32;
+        let dy = fraction_y - (corner_y - cell_y as u32) as f32;
+        angle.cos() * dx + angle.sin() * dy
+    };
+    // Dot products with unit gradients stay within plus or minus the square
+    // root of a half.
+    cell_noise(x, y, gradient) * std::f32::consts::FRAC_1_SQRT_2 + 0.5
+}
+
+/// Blends the values at the corners of the cell that `(x, y)` is in, with a
+/// curve that is smooth across cell edges.
+fn cell_noise(x: f32, y: f32, corner: impl Fn(u32, u32) -> f32) -> f32 {
+    let (cell_x, cell_y) = (x.floor() as u32, y.floor() as u32);
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (tx, ty) = (fade(x.fract()), fade(y.fract()));
+    let top = mix(corner(cell_x, cell_y), corner(cell_x + 1, cell_y), tx);
+    let bottom = mix(
+        corner(cell_x, cell_y + 1),
+        corner(cell_x + 1, cell_y + 1),
+        tx,
+    );
+    mix(top, bottom, ty)
+}
+
+fn hash(x: u32, y: u32, seed: u32) -> u32 {
+    let mut hash =
+        x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb_352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846c_a68b);
+    hash ^ hash >> 16
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp(colors: [[f32; 4]; 2], t: f32) -> [f32; 4] {
+    std::array::from_fn(|channel| mix(colors[0][channel], colors[1][channel], t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    /// The pattern in `DXGI_FORMAT_R8_UNORM`, which is enough to tell
+    /// patterns between black and white apart.
+    fn red(pattern: Pattern, width: u32, height: u32) -> Vec<u8> {
+        let texture = pattern
+            .to_texture(DXGI_FORMAT_R8_UNORM, width, height)
+            .unwrap();
+        texture.subresources[0].data.clone()
+    }
+
+    fn noise(cells: u32, octaves: u32, seed: u32) -> Noise {
+        Noise {
+            cells,
+            octaves,
+            seed,
+            colors: [BLACK, WHITE],
+        }
+    }
+
+    #[test]
+    fn solid() {
+        let color = [0.25, 0.5, 0.75, 1.0];
+        assert_eq!(Pattern::Solid(color).texels(3, 2), [color; 6]);
+    }
+
+    #[test]
+    fn checker() {
+        let checker = |cells| Pattern::Checker {
+            cells,
+            colors: [BLACK, WHITE],
+        };
+        #[rustfmt::skip]
+        assert_eq!(red(checker([2, 2]), 4, 4), [
+            0, 0, 255, 255,
+            0, 0, 255, 255,
+            255, 255, 0, 0,
+            255, 255, 0, 0,
+        ]);
+        // Cells that don't divide the texture evenly are as even as they can
+        // be.
+        assert_eq!(red(checker([2, 1]), 5, 1), [0, 0, 0, 255, 255]);
+        assert_eq!(red(checker([0, 0]), 2, 2), [0; 4]);
+    }
+
+    #[test]
+    fn gradient() {
+        let gradient = |direction| Pattern::Gradient {
+            colors: [BLACK, WHITE],
+            direction,
+        };
+        assert_eq!(
+            red(gradient(GradientDirection::Horizontal), 4, 2),
+            [32, 96, 159, 223, 32, 96, 159, 223]
+        );
+        assert_eq!(
+            red(gradient(GradientDirection::Vertical), 2, 4),
+            [32, 32, 96, 96, 159, 159, 223, 223]
+        );
+        assert_eq!(
+            red(gradient(GradientDirection::Diagonal), 2, 2),
+            [64, 128, 128, 191]
+        );
+    }
+
+    #[test]
+    fn value_noise() {
+        let texels = red(Pattern::ValueNoise(noise(2, 3, 7)), 4, 4);
+        assert_eq!(texels, VALUE_NOISE);
+        assert_eq!(red(Pattern::ValueNoise(noise(2, 3, 7)), 4, 4), texels);
+        assert_ne!(red(Pattern::ValueNoise(noise(2, 3, 8)), 4, 4), texels);
+    }
+
+    #[test]
+    fn perlin_noise() {
+        let texels = red(Pattern::PerlinNoise(noise(2, 3, 7)), 4, 4);
+        assert_eq!(texels, PERLIN_NOISE);
+        assert_eq!(red(Pattern::PerlinNoise(noise(2, 3, 7)), 4, 4), texels);
+        assert_ne!(red(Pattern::PerlinNoise(noise(2, 3, 8)), 4, 4), texels);
+    }
+
+    #[test]
+    fn noise_tiles() {
+        // With four cells across a 16x16 texture, the texels past its right
+        // and bottom edges would be the ones at its left and top.
+        for pattern in [
+            Pattern::ValueNoise(noise(4, 2, 1)),
+            Pattern::PerlinNoise(noise(4, 2, 1)),
+        ] {
+            let texels = pattern.texels(16, 16);
+            for y in 0..16 {
+                let [left, right] = [0, 16].map(|x| pattern.texel(x, y, 16, 16));
+                assert_eq!(left, texels[y as usize * 16]);
+                assert!(left.iter().zip(right).all(|(a, b)| (a - b).abs() < 1e-4));
+            }
+        }
+    }
+
+    #[test]
+    fn noise_cells_and_octaves_out_of_range() {
+        for &(cells, octaves) in &[(0, 0), (u32::MAX, 1), (1, 40), (u32::MAX, u32::MAX)] {
+            for pattern in [
+                Pattern::ValueNoise(noise(cells, octaves, 3)),
+                Pattern::PerlinNoise(noise(cells, octaves, 3)),
+            ] {
+                assert!(pattern
+                    .texels(8, 8)
+                    .iter()
+                    .all(|texel| texel.iter().all(|c| (0.0..=1.0).contains(c))));
+            }
+        }
+        // Out of range cells are clamped, and octaves past the finest are
+        // left out.
+        assert_eq!(
+            Pattern::ValueNoise(noise(0, 1, 3)).texels(4, 4),
+            Pattern::ValueNoise(noise(1, 1, 3)).texels(4, 4)
+        );
+        assert_eq!(
+            Pattern::ValueNoise(noise(MAX_NOISE_CELLS / 2, 100, 3)).texels(4, 4),
+            Pattern::ValueNoise(noise(MAX_NOISE_CELLS / 2, 2, 3)).texels(4, 4)
+        );
+    }
+
+    #[test]
+    fn uv_grid() {
+        let texels = Pattern::UvGrid { cells: 2 }.texels(4, 4);
+        let line = |x: usize, y: usize| x % 2 == 0 || y % 2 == 0;
+        for (i, &texel) in texels.iter().enumerate() {
+            let (x, y) = (i % 4, i / 4);
+            if line(x, y) {
+                assert_eq!(texel, WHITE);
+            } else {
+                let (u, v) = ((x as f32 + 0.5) / 4.0, (y as f32 + 0.5) / 4.0);
+                let shade = if (x / 2 + y / 2) % 2 == 0 { 1.0 } else { 0.6 };
+                assert_eq!(texel, [u * shade, v * shade, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn write_leaves_row_padding() {
+        let checker = Pattern::Checker {
+            cells: [2, 1],
+            colors: [BLACK, WHITE],
+        };
+        let mut destination = [7; 7];
+        checker
+            .write(DXGI_FORMAT_R8_UNORM, 2, 2, 4, &mut destination)
+            .unwrap();
+        assert_eq!(destination, [0, 255, 7, 7, 0, 255, 7]);
+
+        assert_eq!(
+            checker.write(DXGI_FORMAT_R8_UNORM, 2, 2, 4, &mut [0; 5]),
+            None
+        );
+        assert_eq!(
+            checker.write(DXGI_FORMAT_R8_UNORM, 2, 2, 1, &mut [0; 8]),
+            None
+        );
+        assert_eq!(
+            checker.write(DXGI_FORMAT_BC1_UNORM, 4, 4, 8, &mut [0; 8]),
+            None
+        );
+    }
+
+    /// What the noise tests' 4x4 textures come out as. Noise is hashed, so
+    /// these only change if the noise functions do.
+    #[rustfmt::skip]
+    const VALUE_NOISE: [u8; 16] = [
+        99, 174, 151, 74,
+        157, 181, 144, 133,
+        155, 160, 148, 136,
+        87, 181, 162, 94,
+    ];
+    #[rustfmt::skip]
+    const PERLIN_NOISE: [u8; 16] = [
+        148, 117, 133, 141,
+        137, 146, 163, 193,
+        77, 90, 103, 131,
+        108, 138, 133, 82,
+    ];
+}
@@ -0,0 +1,348 @@
+//! Scenes in files with no header of their own, such as the original samples'
+//! SquidRoom.bin, where tables compiled into the program say where everything
+//! is. Reading one gives the same `Scene` as a scene file, so that it can be
+//! converted to one or drawn directly.
+
+use crate::format::DXGI_FORMAT;
+use crate::scene::{
+    check_range, check_stored_surface, read_range, Draw, Material, Scene, SceneEntry, SceneError,
+    VertexElement,
+};
+use crate::texture::Texture;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Where a texture's single mip is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawTexture {
+    pub format: DXGI_FORMAT,
+    pub width: u32,
+    pub height: u32,
+    pub offset: u64,
+    /// Bytes from the start of one row of blocks to the next.
+    pub pitch: u64,
+}
+
+/// A draw that names its textures directly; draws that name the same three
+/// share a material once read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawDraw {
+    pub diffuse: Option<u32>,
+    pub normal: Option<u32>,
+    pub specular: Option<u32>,
+    pub index_start: u32,
+    pub index_count: u32,
+    pub vertex_base: i32,
+}
+
+/// Everything there is to know about a file that can't describe itself.
+#[derive(Debug, Clone)]
+pub struct RawSceneLayout<'a> {
+    pub textures: &'a [RawTexture],
+    pub draws: &'a [RawDraw],
+    pub vertex_layout: Vec<VertexElement>,
+    pub vertex_stride: u32,
+    pub index_format: DXGI_FORMAT,
+    /// Offset and size in bytes.
+    pub vertices: (u64, u64),
+    pub indices: (u64, u64),
+}
+
+impl RawSceneLayout<'_> {
+    /// Reads the file the layout describes, checking first that everything
+    /// it points at is in the file, and then the scene with
+    /// `Scene::validate`.
+    pub fn read<R: Read + Seek>(&self, reader: &mut R) -> Result<Scene, SceneError> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        let expected_size = [self.vertices, self.indices]
+            .iter()
+            .map(|&(offset, size)| offset.saturating_add(size))
+            .max()
+            .unwrap_or(0);
+        if file_size < expected_size {
+            return Err(SceneError::FileSize {
+                expected: expected_size,
+                actual: file_size,
+            });
+        }
+
+        for (index, texture) in self.textures.iter().enumerate() {
+            check_stored_surface(
+                SceneEntry::Mip {
+                    texture: index,
+                    mip: 0,
+                },
+                texture.format,
+                texture.width,
+                texture.height,
+                texture.offset,
+                texture.pitch,
+                file_size,
+            )?;
+        }
+        check_range(
+            SceneEntry::Vertices,
+            self.vertices.0,
+            self.vertices.1,
+            file_size,
+        )?;
+        check_range(
+            SceneEntry::Indices,
+            self.indices.0,
+            self.indices.1,
+            file_size,
+        )?;
+
+        let textures = self
+            .textures
+            .iter()
+            .map(|texture| {
+                Texture::read_2d(
+                    reader,
+                    texture.offset,
+                    texture.format,
+                    texture.width,
+                    texture.height,
+                    texture.pitch,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut materials = Vec::new();
+        let mut material_indices = HashMap::new();
+        let draws = self
+            .draws
+            .iter()
+            .map(|draw| {
+                let material = Material {
+                    diffuse: draw.diffuse,
+                    normal: draw.normal,
+                    specular: draw.specular,
+                };
+                let material = *material_indices.entry(material).or_insert_with(|| {
+                    materials.push(material);
+                    materials.len() as u32 - 1
+                });
+                Draw {
+                    material,
+                    index_start: draw.index_start,
+                    index_count: draw.index_count,
+                    vertex_base: draw.vertex_base,
+                }
+            })
+            .collect();
+
+        let scene = Scene {
+            textures,
+            vertex_layout: self.vertex_layout.clone(),
+            vertex_stride: self.vertex_stride,
+            index_format: self.index_format,
+            vertices: read_range(reader, self.vertices)?,
+            indices: read_range(reader, self.indices)?,
+            materials,
+            draws,
+        };
+        scene.validate()?;
+        Ok(scene)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::*;
+    use crate::texture::Subresource;
+    use std::io::Cursor;
+
+    const TEXTURES: [RawTexture; 2] = [
+        // Rows padded from 16 to 20 bytes.
+        RawTexture {
+            format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            width: 4,
+            height: 2,
+            offset: 0,
+            pitch: 20,
+        },
+        // 2x2 blocks.
+        RawTexture {
+            format: DXGI_FORMAT_BC1_UNORM,
+            width: 8,
+            height: 8,
+            offset: 40,
+            pitch: 16,
+        },
+    ];
+
+    const fn draw(diffuse: u32, normal: Option<u32>, index_start: u32) -> RawDraw {
+        RawDraw {
+            diffuse: Some(diffuse),
+            normal,
+            specular: None,
+            index_start,
+            index_count: 3,
+            vertex_base: 0,
+        }
+    }
+
+    const DRAWS: [RawDraw; 3] = [draw(0, Some(1), 0), draw(1, None, 3), draw(0, Some(1), 3)];
+
+    fn layout() -> RawSceneLayout<'static> {
+        RawSceneLayout {
+            textures: &TEXTURES,
+            draws: &DRAWS,
+            vertex_layout: vec![VertexElement::new(
+                "POSITION",
+                DXGI_FORMAT_R32G32B32_FLOAT,
+                0,
+            )],
+            vertex_stride: 12,
+            index_format: DXGI_FORMAT_R16_UINT,
+            vertices: (72, 36),
+            indices: (108, 12),
+        }
+    }
+
+    /// The file `layout` describes, with each byte of the data numbered so
+    /// that misplaced reads show up.
+    fn file() -> Vec<u8> {
+        let mut file: Vec<u8> = (0..72).collect();
+        file.extend((0..9u32).flat_map(|i| (i as f32).to_le_bytes()));
+        file.extend([0u16, 1, 2, 2, 1, 0].iter().flat_map(|i| i.to_le_bytes()));
+        file
+    }
+
+    #[test]
+    fn reads_a_synthetic_file() {
+        let scene = layout().read(&mut Cursor::new(file())).unwrap();
+
+        assert_eq!(
+            scene.textures,
+            [
+                Texture {
+                    format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                    width: 4,
+                    height: 2,
+                    subresources: vec![Subresource {
+                        width: 4,
+                        height: 2,
+                        row_pitch: 16,
+                        data: (0..16).chain(20..36).collect(),
+                    }],
+                },
+                Texture {
+                    format: DXGI_FORMAT_BC1_UNORM,
+                    width: 8,
+                    height: 8,
+                    subresources: vec![Subresource {
+                        width: 8,
+                        height: 8,
+                        row_pitch: 16,
+                        data: (40..72).collect(),
+                    }],
+                },
+            ]
+        );
+        assert_eq!(scene.vertices, &file()[72..108]);
+        assert_eq!(scene.indices, &file()[108..120]);
+        assert_eq!(scene.vertex_stride, 12);
+        assert_eq!(scene.index_format, DXGI_FORMAT_R16_UINT);
+
+        // The first and last draws use the same textures.
+        assert_eq!(
+            scene.materials,
+            [
+                Material {
+                    diffuse: Some(0),
+                    normal: Some(1),
+                    specular: None,
+                },
+                Material {
+                    diffuse: Some(1),
+                    normal: None,
+                    specular: None,
+                },
+            ]
+        );
+        let materials: Vec<u32> = scene.draws.iter().map(|draw| draw.material).collect();
+        assert_eq!(materials, [0, 1, 0]);
+        assert_eq!(scene.draws[1].index_start, 3);
+    }
+
+    #[test]
+    fn short_files_are_rejected() {
+        let mut file = file();
+        file.pop();
+        match layout().read(&mut Cursor::new(file)) {
+            Err(SceneError::FileSize { expected, actual }) => {
+                assert_eq!((expected, actual), (120, 119))
+            }
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn textures_must_be_in_the_file() {
+        let mut past_the_end = TEXTURES;
+        past_the_end[1].offset = 112;
+        let layout = RawSceneLayout {
+            textures: &past_the_end,
+            ..layout()
+        };
+        match layout.read(&mut Cursor::new(file())) {
+            Err(SceneError::OutOfBounds {
+                entry: SceneEntry::Mip { texture: 1, mip: 0 },
+                offset: 112,
+                size: 32,
+                file_size: 120,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+
+        let mut small_pitch = TEXTURES;
+        small_pitch[1].pitch = 8;
+        let layout = RawSceneLayout {
+            textures: &small_pitch,
+            ..layout
+        };
+        match layout.read(&mut Cursor::new(file())) {
+            Err(SceneError::PitchTooSmall {
+                entry: SceneEntry::Mip { texture: 1, mip: 0 },
+                pitch: 8,
+                row_pitch: 16,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn draws_are_validated() {
+        let draws = [draw(2, None, 0)];
+        let layout = RawSceneLayout {
+            draws: &draws,
+            ..layout()
+        };
+        match layout.read(&mut Cursor::new(file())) {
+            Err(SceneError::MissingTexture {
+                entry: SceneEntry::Material(0),
+                texture: 2,
+                count: 2,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+
+        let draws = [draw(0, None, 4)];
+        let layout = RawSceneLayout {
+            draws: &draws,
+            ..layout
+        };
+        match layout.read(&mut Cursor::new(file())) {
+            Err(SceneError::IndexRange {
+                entry: SceneEntry::Draw(0),
+                start: 4,
+                count: 3,
+                total: 6,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+    }
+}
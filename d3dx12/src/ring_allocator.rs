@@ -0,0 +1,237 @@
+//! Hands out space in a buffer that the GPU reads from a frame or two behind
+//! the CPU, such as constants in an upload heap.
+//!
+//! Allocations are made one after another around the buffer, and freed a
+//! frame at a time: `finish_frame` tags everything allocated since the last
+//! call with the fence value that the frame signals, and `retire` frees the
+//! frames whose values the fence has reached. This only deals in offsets;
+//! `UploadHeap` puts it over a mapped buffer.
+
+use std::collections::VecDeque;
+
+/// Where constant buffer views have to start, and a multiple of their size:
+/// `D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT`.
+pub const CONSTANT_BUFFER_ALIGNMENT: u64 = 256;
+/// Where texture data that is copied from a buffer has to start:
+/// `D3D12_TEXTURE_DATA_PLACEMENT_ALIGNMENT`.
+pub const TEXTURE_DATA_ALIGNMENT: u64 = 512;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingAllocator {
+    capacity: u64,
+    /// Where the next allocation goes, unless it has to be aligned or wrap.
+    head: u64,
+    /// Bytes from the oldest allocation still in use round to `head`,
+    /// including the padding skipped to align or wrap allocations.
+    used: u64,
+    /// The part of `used` allocated since the last `finish_frame`.
+    pending: u64,
+    frames: VecDeque<RetiringFrame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RetiringFrame {
+    fence_value: u64,
+    size: u64,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: u64) -> Self {
+        RingAllocator {
+            capacity,
+            head: 0,
+            used: 0,
+            pending: 0,
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Bytes that haven't been retired yet, counting any padding.
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Returns the offset of `size` bytes aligned to `alignment`, which has to
+    /// be a power of two. An allocation never wraps around the end of the
+    /// buffer; the space left at the end is skipped instead. Returns `None`
+    /// if there isn't room until more frames are retired.
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        if !alignment.is_power_of_two() || size > self.capacity {
+            return None;
+        }
+        let mut offset = self.head.checked_next_multiple_of(alignment)?;
+        if offset + size > self.capacity {
+            offset = 0;
+        }
+        // The padding before the allocation, counting the end of the buffer
+        // when it wraps.
+        let padding = if offset >= self.head {
+            offset - self.head
+        } else {
+            self.capacity - self.head
+        };
+        if self.used + padding + size > self.capacity {
+            return None;
+        }
+
+        self.used += padding + size;
+        self.pending += padding + size;
+        self.head = offset + size;
+        if self.head == self.capacity {
+            self.head = 0;
+        }
+        Some(offset)
+    }
+
+    /// Tags everything allocated since the last call with `fence_value`, which
+    /// has to be at least the value given before.
+    pub fn finish_frame(&mut self, fence_value: u64) {
+        if self.pending == 0 {
+            return;
+        }
+        self.frames.push_back(RetiringFrame {
+            fence_value,
+            size: self.pending,
+        });
+        self.pending = 0;
+    }
+
+    /// Frees the frames whose fence values are at most `completed_fence_value`.
+    pub fn retire(&mut self, completed_fence_value: u64) {
+        while let Some(frame) = self.frames.front() {
+            if frame.fence_value > completed_fence_value {
+                break;
+            }
+            self.used -= frame.size;
+            self.frames.pop_front();
+        }
+        // With nothing in use, start again at the beginning, so that the
+        // next allocations don't have to wrap.
+        if self.used == 0 {
+            self.head = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_allocations() {
+        let mut ring = RingAllocator::new(4096);
+        assert_eq!(ring.allocate(10, CONSTANT_BUFFER_ALIGNMENT), Some(0));
+        assert_eq!(ring.allocate(10, CONSTANT_BUFFER_ALIGNMENT), Some(256));
+        assert_eq!(ring.allocate(10, TEXTURE_DATA_ALIGNMENT), Some(512));
+        assert_eq!(ring.allocate(1, 64), Some(576));
+        assert_eq!(ring.allocate(1, 1), Some(577));
+        // The padding skipped to align counts as used.
+        assert_eq!(ring.used(), 578);
+
+        assert_eq!(ring.allocate(1, 3), None);
+        assert_eq!(ring.allocate(1, 0), None);
+        assert_eq!(ring.used(), 578);
+    }
+
+    #[test]
+    fn wrapping_skips_the_end() {
+        let mut ring = RingAllocator::new(1024);
+        assert_eq!(ring.allocate(600, 1), Some(0));
+        ring.finish_frame(1);
+        assert_eq!(ring.allocate(300, 1), Some(600));
+        ring.finish_frame(2);
+        ring.retire(1);
+        assert_eq!(ring.used(), 300);
+
+        // 200 bytes don't fit after 900, so the last 124 are skipped.
+        assert_eq!(ring.allocate(200, 1), Some(0));
+        assert_eq!(ring.used(), 624);
+        ring.finish_frame(3);
+
+        // The skipped bytes belong to the frame that skipped them.
+        ring.retire(2);
+        assert_eq!(ring.used(), 324);
+        ring.retire(3);
+        assert_eq!(ring.used(), 0);
+
+        // With nothing in use the next allocation starts at the beginning.
+        assert_eq!(ring.allocate(1024, 1), Some(0));
+    }
+
+    #[test]
+    fn wrapping_needs_room_for_the_skipped_end() {
+        let mut ring = RingAllocator::new(1024);
+        assert_eq!(ring.allocate(100, 1), Some(0));
+        ring.finish_frame(1);
+        assert_eq!(ring.allocate(800, 1), Some(100));
+        ring.finish_frame(2);
+        ring.retire(1);
+
+        // 150 bytes would fit at the start, but only by skipping 124 at the
+        // end as well.
+        assert_eq!(ring.allocate(150, 1), None);
+        // Filling the end exactly wraps with no padding.
+        assert_eq!(ring.allocate(124, 1), Some(900));
+        assert_eq!(ring.allocate(100, 1), Some(0));
+        assert_eq!(ring.used(), 1024);
+    }
+
+    #[test]
+    fn full_until_retired() {
+        let mut ring = RingAllocator::new(1024);
+        assert_eq!(ring.allocate(1025, 1), None);
+        assert_eq!(ring.allocate(1024, 1), Some(0));
+        assert_eq!(ring.allocate(1, 1), None);
+        ring.finish_frame(1);
+        assert_eq!(ring.allocate(1, 1), None);
+        ring.retire(0);
+        assert_eq!(ring.allocate(1, 1), None);
+        ring.retire(1);
+        assert_eq!(ring.allocate(1, 1), Some(0));
+    }
+
+    #[test]
+    fn frames_retire_by_fence_value() {
+        let mut ring = RingAllocator::new(1024);
+        for (fence_value, size) in [(1, 100), (2, 200), (3, 300)] {
+            ring.allocate(size, 1).unwrap();
+            ring.finish_frame(fence_value);
+        }
+        // A frame that allocated nothing has nothing to retire.
+        ring.finish_frame(4);
+        assert_eq!(ring.used(), 600);
+
+        ring.retire(1);
+        assert_eq!(ring.used(), 500);
+        ring.retire(2);
+        assert_eq!(ring.used(), 300);
+        ring.retire(4);
+        assert_eq!(ring.used(), 0);
+    }
+
+    #[test]
+    fn retiring_out_of_order() {
+        let mut ring = RingAllocator::new(1024);
+        for fence_value in 1..=4 {
+            ring.allocate(100, 1).unwrap();
+            ring.finish_frame(fence_value);
+        }
+
+        // A fence that has moved on several frames frees them all at once.
+        ring.retire(3);
+        assert_eq!(ring.used(), 100);
+        // An older value, read before the newer one, frees nothing more.
+        ring.retire(2);
+        ring.retire(0);
+        assert_eq!(ring.used(), 100);
+        // Nor does one that hasn't reached the last frame.
+        ring.retire(3);
+        assert_eq!(ring.used(), 100);
+        ring.retire(4);
+        assert_eq!(ring.used(), 0);
+    }
+}
@@ -0,0 +1,1111 @@
+//! A self-describing scene file: textures, one vertex and one index stream,
+//! the layout of the vertices, and a list of draws with their materials.
+//!
+//! Everything is little-endian. The file starts with a fixed size header,
+//! followed by the tables and then the data they point at:
+//!
+//! ```text
+//! header      magic, version, file size, vertex stride, index format,
+//!             table counts, vertex and index stream ranges
+//! elements    per vertex element: name length, name, index, format, offset
+//! textures    per texture: format, width, height, mip count,
+//!             then per mip: offset, pitch
+//! materials   per material: diffuse, normal, specular texture (-1 if none)
+//! draws       per draw: material, index start, index count, vertex base
+//! data        texture mips, vertices and indices
+//! ```
+//!
+//! Reading checks the header against the file, that everything the tables
+//! point at lies inside it, and that the tables agree with each other. Any
+//! problem is reported as a `SceneError` naming the entry at fault.
+
+use crate::format::*;
+use crate::texture::{mip_size, FormatLayout, Subresource, Texture};
+use std::{
+    convert::TryInto,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+pub const SCENE_MAGIC: &[u8; 8] = b"D3DXSCNE";
+
+/// Bump this whenever the layout changes.
+pub const SCENE_VERSION: u32 = 1;
+
+const HEADER_SIZE: u64 = 8 + 4 + 8 + 4 + 4 + 4 * 4 + 2 * 16;
+
+/// Offsets of data in the file are aligned to this.
+const DATA_ALIGNMENT: u64 = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexElement {
+    pub semantic_name: String,
+    pub semantic_index: u32,
+    pub format: DXGI_FORMAT,
+    /// From the start of the vertex.
+    pub offset: u32,
+}
+
+impl VertexElement {
+    pub fn new(semantic_name: &str, format: DXGI_FORMAT, offset: u32) -> Self {
+        VertexElement {
+            semantic_name: semantic_name.to_string(),
+            semantic_index: 0,
+            format,
+            offset,
+        }
+    }
+}
+
+/// Position, normal, texture coordinate and tangent: the layout the samples'
+/// shaders expect.
+pub fn standard_vertex_layout() -> Vec<VertexElement> {
+    vec![
+        VertexElement::new("POSITION", DXGI_FORMAT_R32G32B32_FLOAT, 0),
+        VertexElement::new("NORMAL", DXGI_FORMAT_R32G32B32_FLOAT, 12),
+        VertexElement::new("TEXCOORD", DXGI_FORMAT_R32G32_FLOAT, 24),
+        VertexElement::new("TANGENT", DXGI_FORMAT_R32G32B32_FLOAT, 32),
+    ]
+}
+
+pub const STANDARD_VERTEX_STRIDE: u32 = 44;
+
+/// Indices into `Scene::textures`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Material {
+    pub diffuse: Option<u32>,
+    pub normal: Option<u32>,
+    pub specular: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Draw {
+    /// Index into `Scene::materials`.
+    pub material: u32,
+    pub index_start: u32,
+    pub index_count: u32,
+    pub vertex_base: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scene {
+    pub textures: Vec<Texture>,
+    pub vertex_layout: Vec<VertexElement>,
+    pub vertex_stride: u32,
+    pub index_format: DXGI_FORMAT,
+    pub vertices: Vec<u8>,
+    pub indices: Vec<u8>,
+    pub materials: Vec<Material>,
+    pub draws: Vec<Draw>,
+}
+
+/// The part of a scene that a `SceneError` is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneEntry {
+    Header,
+    Element(usize),
+    Texture(usize),
+    Mip { texture: usize, mip: usize },
+    Material(usize),
+    Draw(usize),
+    Vertices,
+    Indices,
+}
+
+impl fmt::Display for SceneEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneEntry::Header => write!(f, "header"),
+            SceneEntry::Element(index) => write!(f, "vertex element {}", index),
+            SceneEntry::Texture(index) => write!(f, "texture {}", index),
+            SceneEntry::Mip { texture, mip } => write!(f, "texture {} mip {}", texture, mip),
+            SceneEntry::Material(index) => write!(f, "material {}", index),
+            SceneEntry::Draw(index) => write!(f, "draw {}", index),
+            SceneEntry::Vertices => write!(f, "vertex data"),
+            SceneEntry::Indices => write!(f, "index data"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    NotAScene,
+    UnsupportedVersion(u32),
+    /// The file is not the size its header, or the layout it was read with,
+    /// expects.
+    FileSize {
+        expected: u64,
+        actual: u64,
+    },
+    OutOfBounds {
+        entry: SceneEntry,
+        offset: u64,
+        size: u64,
+        file_size: u64,
+    },
+    SemanticName {
+        entry: SceneEntry,
+    },
+    UnsupportedFormat {
+        entry: SceneEntry,
+        format: DXGI_FORMAT,
+    },
+    ElementOutsideVertex {
+        entry: SceneEntry,
+        offset: u32,
+        size: u32,
+        stride: u32,
+    },
+    /// A stream that is not a whole number of vertices or indices.
+    StreamSize {
+        entry: SceneEntry,
+        size: u64,
+        element_size: u32,
+    },
+    EmptyTexture {
+        entry: SceneEntry,
+        width: u32,
+        height: u32,
+    },
+    MipCount {
+        entry: SceneEntry,
+        count: usize,
+        max: usize,
+    },
+    PitchTooSmall {
+        entry: SceneEntry,
+        pitch: u64,
+        row_pitch: u64,
+    },
+    /// A mip whose dimensions or data don't match its format's block layout.
+    SurfaceLayout {
+        entry: SceneEntry,
+        width: u32,
+        height: u32,
+        row_pitch: u64,
+        size: u64,
+        expected_width: u32,
+        expected_height: u32,
+        expected_row_pitch: u64,
+        expected_size: u64,
+    },
+    MissingTexture {
+        entry: SceneEntry,
+        texture: u32,
+        count: usize,
+    },
+    MissingMaterial {
+        entry: SceneEntry,
+        material: u32,
+        count: usize,
+    },
+    IndexRange {
+        entry: SceneEntry,
+        start: u32,
+        count: u32,
+        total: u64,
+    },
+    VertexRange {
+        entry: SceneEntry,
+        vertex: i64,
+        count: u64,
+    },
+}
+
+impl SceneError {
+    pub fn entry(&self) -> Option<SceneEntry> {
+        match self {
+            SceneError::Io(_)
+            | SceneError::NotAScene
+            | SceneError::UnsupportedVersion(_)
+            | SceneError::FileSize { .. } => None,
+            SceneError::OutOfBounds { entry, .. }
+            | SceneError::SemanticName { entry }
+            | SceneError::UnsupportedFormat { entry, .. }
+            | SceneError::ElementOutsideVertex { entry, .. }
+            | SceneError::StreamSize { entry, .. }
+            | SceneError::EmptyTexture { entry, .. }
+            | SceneError::MipCount { entry, .. }
+            | SceneError::PitchTooSmall { entry, .. }
+            | SceneError::SurfaceLayout { entry, .. }
+            | SceneError::MissingTexture { entry, .. }
+            | SceneError::MissingMaterial { entry, .. }
+            | SceneError::IndexRange { entry, .. }
+            | SceneError::VertexRange { entry, .. } => Some(*entry),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(entry) = self.entry() {
+            write!(f, "{}: ", entry)?;
+        }
+
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::NotAScene => write!(f, "not a scene file"),
+            SceneError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported scene version {} (expected {})",
+                version, SCENE_VERSION
+            ),
+            SceneError::FileSize { expected, actual } => {
+                write!(f, "the file is {} bytes but should be {}", actual, expected)
+            }
+            SceneError::OutOfBounds {
+                offset,
+                size,
+                file_size,
+                ..
+            } => write!(
+                f,
+                "{} bytes at offset {} run past the end of the {} byte file",
+                size, offset, file_size
+            ),
+            SceneError::SemanticName { .. } => write!(f, "semantic name is not UTF-8"),
+            SceneError::UnsupportedFormat { format, .. } => {
+                write!(f, "unsupported format {}", format.0)
+            }
+            SceneError::ElementOutsideVertex {
+                offset,
+                size,
+                stride,
+                ..
+            } => write!(
+                f,
+                "{} bytes at offset {} don't fit in a {} byte vertex",
+                size, offset, stride
+            ),
+            SceneError::StreamSize {
+                size, element_size, ..
+            } => write!(
+                f,
+                "{} bytes is not a whole number of {} byte elements",
+                size, element_size
+            ),
+            SceneError::EmptyTexture { width, height, .. } => {
+                write!(f, "{}x{} has no texels", width, height)
+            }
+            SceneError::MipCount { count, max, .. } => {
+                write!(f, "{} mips, expected 1 to {}", count, max)
+            }
+            SceneError::PitchTooSmall {
+                pitch, row_pitch, ..
+            } => write!(
+                f,
+                "pitch {} is smaller than a row of blocks ({} bytes)",
+                pitch, row_pitch
+            ),
+            SceneError::SurfaceLayout {
+                width,
+                height,
+                row_pitch,
+                size,
+                expected_width,
+                expected_height,
+                expected_row_pitch,
+                expected_size,
+                ..
+            } => write!(
+                f,
+                "{}x{} with {} bytes in rows of {}, expected {}x{} with {} bytes in rows of {}",
+                width,
+                height,
+                size,
+                row_pitch,
+                expected_width,
+                expected_height,
+                expected_size,
+                expected_row_pitch
+            ),
+            SceneError::MissingTexture { texture, count, .. } => {
+                write!(f, "uses texture {} but there are only {}", texture, count)
+            }
+            SceneError::MissingMaterial {
+                material, count, ..
+            } => write!(f, "uses material {} but there are only {}", material, count),
+            SceneError::IndexRange {
+                start,
+                count,
+                total,
+                ..
+            } => write!(
+                f,
+                "indices {}..{} are outside the {} indices",
+                start,
+                *start as u64 + *count as u64,
+                total
+            ),
+            SceneError::VertexRange { vertex, count, .. } => {
+                write!(f, "uses vertex {} but there are only {}", vertex, count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+impl From<SceneError> for io::Error {
+    fn from(error: SceneError) -> Self {
+        match error {
+            SceneError::Io(e) => e,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Reads a scene from the start of `reader`, which must hold nothing
+    /// else, and checks it with `validate`.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Self, SceneError> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut magic = Vec::new();
+        reader
+            .by_ref()
+            .take(SCENE_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        if magic != SCENE_MAGIC {
+            return Err(SceneError::NotAScene);
+        }
+        let version = read_u32(reader)?;
+        if version != SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(version));
+        }
+        check_range(SceneEntry::Header, 0, HEADER_SIZE, file_size)?;
+
+        let expected_size = read_u64(reader)?;
+        if expected_size != file_size {
+            return Err(SceneError::FileSize {
+                expected: expected_size,
+                actual: file_size,
+            });
+        }
+        let vertex_stride = read_u32(reader)?;
+        let index_format = DXGI_FORMAT(read_u32(reader)? as i32);
+        let element_count = read_u32(reader)?;
+        let texture_count = read_u32(reader)?;
+        let material_count = read_u32(reader)?;
+        let draw_count = read_u32(reader)?;
+        let vertex_range = (read_u64(reader)?, read_u64(reader)?);
+        let index_range = (read_u64(reader)?, read_u64(reader)?);
+
+        let mut vertex_layout = Vec::new();
+        for index in 0..element_count as usize {
+            let name_length = read_u32(reader)? as u64;
+            let mut name = Vec::new();
+            reader.by_ref().take(name_length).read_to_end(&mut name)?;
+            if name.len() as u64 != name_length {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            vertex_layout.push(VertexElement {
+                semantic_name: String::from_utf8(name).map_err(|_| SceneError::SemanticName {
+                    entry: SceneEntry::Element(index),
+                })?,
+                semantic_index: read_u32(reader)?,
+                format: DXGI_FORMAT(read_u32(reader)? as i32),
+                offset: read_u32(reader)?,
+            });
+        }
+
+        let mut texture_headers = Vec::new();
+        for index in 0..texture_count as usize {
+            let format = DXGI_FORMAT(read_u32(reader)? as i32);
+            let width = read_u32(reader)?;
+            let height = read_u32(reader)?;
+            let mip_count = read_u32(reader)? as usize;
+            check_texture(index, format, width, height, mip_count)?;
+            let mut mips = Vec::new();
+            for _ in 0..mip_count {
+                mips.push((read_u64(reader)?, read_u64(reader)?));
+            }
+            texture_headers.push((format, width, height, mips));
+        }
+
+        let mut materials = Vec::new();
+        for _ in 0..material_count {
+            materials.push(Material {
+                diffuse: read_texture_index(reader)?,
+                normal: read_texture_index(reader)?,
+                specular: read_texture_index(reader)?,
+            });
+        }
+
+        let mut draws = Vec::new();
+        for _ in 0..draw_count {
+            draws.push(Draw {
+                material: read_u32(reader)?,
+                index_start: read_u32(reader)?,
+                index_count: read_u32(reader)?,
+                vertex_base: read_u32(reader)? as i32,
+            });
+        }
+
+        // Everything the tables point at is checked before any of it is read,
+        // so a corrupt offset or size can't cause a huge allocation.
+        for (texture, (format, width, height, mips)) in texture_headers.iter().enumerate() {
+            for (mip, &(offset, pitch)) in mips.iter().enumerate() {
+                check_stored_surface(
+                    SceneEntry::Mip { texture, mip },
+                    *format,
+                    mip_size(*width, mip as u32),
+                    mip_size(*height, mip as u32),
+                    offset,
+                    pitch,
+                    file_size,
+                )?;
+            }
+        }
+        check_range(
+            SceneEntry::Vertices,
+            vertex_range.0,
+            vertex_range.1,
+            file_size,
+        )?;
+        check_range(SceneEntry::Indices, index_range.0, index_range.1, file_size)?;
+
+        let mut textures = Vec::new();
+        for (format, width, height, mips) in texture_headers {
+            let mut subresources = Vec::new();
+            for (level, (offset, pitch)) in mips.into_iter().enumerate() {
+                let level = level as u32;
+                subresources.push(Subresource::read(
+                    reader,
+                    offset,
+                    format,
+                    mip_size(width, level),
+                    mip_size(height, level),
+                    pitch,
+                )?);
+            }
+            textures.push(Texture {
+                format,
+                width,
+                height,
+                subresources,
+            });
+        }
+
+        let scene = Scene {
+            textures,
+            vertex_layout,
+            vertex_stride,
+            index_format,
+            vertices: read_range(reader, vertex_range)?,
+            indices: read_range(reader, index_range)?,
+            materials,
+            draws,
+        };
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Checks that the vertex layout fits the stride, that every mip matches
+    /// its format's block layout, and that every material, draw and index
+    /// refers to something that exists.
+    pub fn validate(&self) -> Result<(), SceneError> {
+        for (index, element) in self.vertex_layout.iter().enumerate() {
+            let entry = SceneEntry::Element(index);
+            let size = match FormatLayout::of(element.format) {
+                Some(layout) if !layout.is_block_compressed() => layout.bytes_per_block,
+                _ => {
+                    return Err(SceneError::UnsupportedFormat {
+                        entry,
+                        format: element.format,
+                    })
+                }
+            };
+            if element.offset as u64 + size as u64 > self.vertex_stride as u64 {
+                return Err(SceneError::ElementOutsideVertex {
+                    entry,
+                    offset: element.offset,
+                    size,
+                    stride: self.vertex_stride,
+                });
+            }
+        }
+        check_stream(SceneEntry::Vertices, &self.vertices, self.vertex_stride)?;
+
+        let index_size = match self.index_format {
+            DXGI_FORMAT_R16_UINT => 2,
+            DXGI_FORMAT_R32_UINT => 4,
+            format => {
+                return Err(SceneError::UnsupportedFormat {
+                    entry: SceneEntry::Indices,
+                    format,
+                })
+            }
+        };
+        check_stream(SceneEntry::Indices, &self.indices, index_size)?;
+
+        for (index, texture) in self.textures.iter().enumerate() {
+            let layout = check_texture(
+                index,
+                texture.format,
+                texture.width,
+                texture.height,
+                texture.subresources.len(),
+            )?;
+            for (mip, subresource) in texture.subresources.iter().enumerate() {
+                let width = mip_size(texture.width, mip as u32);
+                let height = mip_size(texture.height, mip as u32);
+                let row_pitch = layout.row_pitch(width);
+                let size = layout.surface_size(width, height);
+                if subresource.width != width
+                    || subresource.height != height
+                    || subresource.row_pitch != row_pitch
+                    || subresource.data.len() as u64 != size
+                {
+                    return Err(SceneError::SurfaceLayout {
+                        entry: SceneEntry::Mip {
+                            texture: index,
+                            mip,
+                        },
+                        width: subresource.width,
+                        height: subresource.height,
+                        row_pitch: subresource.row_pitch,
+                        size: subresource.data.len() as u64,
+                        expected_width: width,
+                        expected_height: height,
+                        expected_row_pitch: row_pitch,
+                        expected_size: size,
+                    });
+                }
+            }
+        }
+
+        for (index, material) in self.materials.iter().enumerate() {
+            let textures = [material.diffuse, material.normal, material.specular];
+            for texture in textures.iter().flatten() {
+                if *texture as usize >= self.textures.len() {
+                    return Err(SceneError::MissingTexture {
+                        entry: SceneEntry::Material(index),
+                        texture: *texture,
+                        count: self.textures.len(),
+                    });
+                }
+            }
+        }
+
+        let index_total = (self.indices.len() / index_size as usize) as u64;
+        let vertex_count = match self.vertex_stride {
+            0 => 0,
+            stride => (self.vertices.len() / stride as usize) as u64,
+        };
+        for (index, draw) in self.draws.iter().enumerate() {
+            let entry = SceneEntry::Draw(index);
+            if draw.material as usize >= self.materials.len() {
+                return Err(SceneError::MissingMaterial {
+                    entry,
+                    material: draw.material,
+                    count: self.materials.len(),
+                });
+            }
+
+            let start = draw.index_start as u64;
+            let end = start + draw.index_count as u64;
+            if end > index_total {
+                return Err(SceneError::IndexRange {
+                    entry,
+                    start: draw.index_start,
+                    count: draw.index_count,
+                    total: index_total,
+                });
+            }
+
+            let bytes = &self.indices
+                [(start * index_size as u64) as usize..(end * index_size as u64) as usize];
+            for bytes in bytes.chunks_exact(index_size as usize) {
+                let index = match *bytes {
+                    [a, b] => u16::from_le_bytes([a, b]) as i64,
+                    [a, b, c, d] => u32::from_le_bytes([a, b, c, d]) as i64,
+                    _ => unreachable!(),
+                };
+                let vertex = draw.vertex_base as i64 + index;
+                if vertex < 0 || vertex as u64 >= vertex_count {
+                    return Err(SceneError::VertexRange {
+                        entry,
+                        vertex,
+                        count: vertex_count,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the scene after checking it with `validate`.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.validate()?;
+
+        // The tables hold the offsets of the data that follows them, so they
+        // are laid out once to find out how big they are.
+        let tables_size = self.encode_tables(&[])?.len() as u64;
+
+        let mut offset = align(HEADER_SIZE + tables_size);
+        let mut mip_offsets = Vec::new();
+        for texture in &self.textures {
+            for subresource in &texture.subresources {
+                mip_offsets.push(offset);
+                offset = align(offset + subresource.data.len() as u64);
+            }
+        }
+        let vertex_range = (offset, self.vertices.len() as u64);
+        offset = align(offset + vertex_range.1);
+        let index_range = (offset, self.indices.len() as u64);
+        let file_size = offset + index_range.1;
+
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(SCENE_MAGIC);
+        header.extend_from_slice(&SCENE_VERSION.to_le_bytes());
+        header.extend_from_slice(&file_size.to_le_bytes());
+        header.extend_from_slice(&self.vertex_stride.to_le_bytes());
+        header.extend_from_slice(&(self.index_format.0 as u32).to_le_bytes());
+        for count in [
+            self.vertex_layout.len(),
+            self.textures.len(),
+            self.materials.len(),
+            self.draws.len(),
+        ] {
+            header.extend_from_slice(&to_u32(count, "table")?.to_le_bytes());
+        }
+        for (offset, size) in [vertex_range, index_range] {
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(&size.to_le_bytes());
+        }
+        debug_assert_eq!(header.len() as u64, HEADER_SIZE);
+
+        let mut position = 0;
+        let mut emit = |writer: &mut W, at: u64, bytes: &[u8]| -> io::Result<()> {
+            writer.write_all(&vec![0; (at - position) as usize])?;
+            writer.write_all(bytes)?;
+            position = at + bytes.len() as u64;
+            Ok(())
+        };
+
+        emit(writer, 0, &header)?;
+        emit(writer, HEADER_SIZE, &self.encode_tables(&mip_offsets)?)?;
+        let subresources = self.textures.iter().flat_map(|t| &t.subresources);
+        for (subresource, &offset) in subresources.zip(&mip_offsets) {
+            emit(writer, offset, &subresource.data)?;
+        }
+        emit(writer, vertex_range.0, &self.vertices)?;
+        emit(writer, index_range.0, &self.indices)?;
+        Ok(())
+    }
+
+    fn encode_tables(&self, mip_offsets: &[u64]) -> io::Result<Vec<u8>> {
+        let mut tables = Vec::new();
+
+        for element in &self.vertex_layout {
+            let name = element.semantic_name.as_bytes();
+            tables.extend_from_slice(&to_u32(name.len(), "semantic name")?.to_le_bytes());
+            tables.extend_from_slice(name);
+            tables.extend_from_slice(&element.semantic_index.to_le_bytes());
+            tables.extend_from_slice(&(element.format.0 as u32).to_le_bytes());
+            tables.extend_from_slice(&element.offset.to_le_bytes());
+        }
+
+        let mut mip_offsets = mip_offsets.iter();
+        for texture in &self.textures {
+            tables.extend_from_slice(&(texture.format.0 as u32).to_le_bytes());
+            tables.extend_from_slice(&texture.width.to_le_bytes());
+            tables.extend_from_slice(&texture.height.to_le_bytes());
+            tables.extend_from_slice(&to_u32(texture.subresources.len(), "mip")?.to_le_bytes());
+            for subresource in &texture.subresources {
+                let offset = mip_offsets.next().copied().unwrap_or(0);
+                tables.extend_from_slice(&offset.to_le_bytes());
+                tables.extend_from_slice(&subresource.row_pitch.to_le_bytes());
+            }
+        }
+
+        for material in &self.materials {
+            for texture in [material.diffuse, material.normal, material.specular] {
+                let index = texture.map_or(-1, |index| index as i32);
+                tables.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+
+        for draw in &self.draws {
+            tables.extend_from_slice(&draw.material.to_le_bytes());
+            tables.extend_from_slice(&draw.index_start.to_le_bytes());
+            tables.extend_from_slice(&draw.index_count.to_le_bytes());
+            tables.extend_from_slice(&draw.vertex_base.to_le_bytes());
+        }
+
+        Ok(tables)
+    }
+}
+
+fn align(offset: u64) -> u64 {
+    offset.div_ceil(DATA_ALIGNMENT) * DATA_ALIGNMENT
+}
+
+/// Checks that a surface stored at `offset`, with `pitch` bytes from the
+/// start of one row of blocks to the next, has texels, fits the format's block
+/// layout and lies within a file of `file_size` bytes.
+pub fn check_stored_surface(
+    entry: SceneEntry,
+    format: DXGI_FORMAT,
+    width: u32,
+    height: u32,
+    offset: u64,
+    pitch: u64,
+    file_size: u64,
+) -> Result<(), SceneError> {
+    let layout = FormatLayout::of(format).ok_or(SceneError::UnsupportedFormat { entry, format })?;
+    if width == 0 || height == 0 {
+        return Err(SceneError::EmptyTexture {
+            entry,
+            width,
+            height,
+        });
+    }
+    let row_pitch = layout.row_pitch(width);
+    if pitch < row_pitch {
+        return Err(SceneError::PitchTooSmall {
+            entry,
+            pitch,
+            row_pitch,
+        });
+    }
+
+    let rows = layout.num_rows(height) as u64;
+    let size = pitch.saturating_mul(rows - 1).saturating_add(row_pitch);
+    check_range(entry, offset, size, file_size)
+}
+
+pub(crate) fn check_range(
+    entry: SceneEntry,
+    offset: u64,
+    size: u64,
+    file_size: u64,
+) -> Result<(), SceneError> {
+    if offset.checked_add(size).is_none_or(|end| end > file_size) {
+        return Err(SceneError::OutOfBounds {
+            entry,
+            offset,
+            size,
+            file_size,
+        });
+    }
+    Ok(())
+}
+
+fn check_texture(
+    index: usize,
+    format: DXGI_FORMAT,
+    width: u32,
+    height: u32,
+    mip_count: usize,
+) -> Result<FormatLayout, SceneError> {
+    let entry = SceneEntry::Texture(index);
+    let layout = FormatLayout::of(format).ok_or(SceneError::UnsupportedFormat { entry, format })?;
+    if width == 0 || height == 0 {
+        return Err(SceneError::EmptyTexture {
+            entry,
+            width,
+            height,
+        });
+    }
+    let max = (u32::BITS - width.max(height).leading_zeros()) as usize;
+    if mip_count == 0 || mip_count > max {
+        return Err(SceneError::MipCount {
+            entry,
+            count: mip_count,
+            max,
+        });
+    }
+    Ok(layout)
+}
+
+fn check_stream(entry: SceneEntry, data: &[u8], element_size: u32) -> Result<(), SceneError> {
+    if !data.is_empty() && data.len() % element_size as usize != 0 {
+        return Err(SceneError::StreamSize {
+            entry,
+            size: data.len() as u64,
+            element_size,
+        });
+    }
+    Ok(())
+}
+
+fn to_u32(count: usize, what: &str) -> io::Result<u32> {
+    count
+        .try_into()
+        .map_err(|_| invalid_data(format!("too many {} entries", what)))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_texture_index<R: Read>(reader: &mut R) -> io::Result<Option<u32>> {
+    let index = read_u32(reader)? as i32;
+    Ok(if index < 0 { None } else { Some(index as u32) })
+}
+
+pub(crate) fn read_range<R: Read + Seek>(
+    reader: &mut R,
+    (offset, size): (u64, u64),
+) -> io::Result<Vec<u8>> {
+    let mut data = vec![0; size as usize];
+    reader.seek(io::SeekFrom::Start(offset))?;
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A 2x2 texture, one triangle and one draw that uses them.
+    fn scene() -> Scene {
+        Scene {
+            textures: vec![Texture {
+                format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                width: 2,
+                height: 2,
+                subresources: vec![Subresource {
+                    width: 2,
+                    height: 2,
+                    row_pitch: 8,
+                    data: (0..16).collect(),
+                }],
+            }],
+            vertex_layout: vec![VertexElement::new(
+                "POSITION",
+                DXGI_FORMAT_R32G32B32_FLOAT,
+                0,
+            )],
+            vertex_stride: 12,
+            index_format: DXGI_FORMAT_R16_UINT,
+            vertices: (0..9u32).flat_map(|i| (i as f32).to_le_bytes()).collect(),
+            indices: [0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()).collect(),
+            materials: vec![Material {
+                diffuse: Some(0),
+                normal: None,
+                specular: None,
+            }],
+            draws: vec![Draw {
+                material: 0,
+                index_start: 0,
+                index_count: 3,
+                vertex_base: 0,
+            }],
+        }
+    }
+
+    // Where things are in the file `scene` writes.
+    const FILE_SIZE: usize = 230;
+    const VERTEX_RANGE: usize = 44;
+    const MIP_OFFSET: usize = 116;
+    const MATERIAL: usize = 132;
+    const DRAW: usize = 144;
+
+    fn file() -> Vec<u8> {
+        let mut file = Vec::new();
+        scene().write(&mut file).unwrap();
+        file
+    }
+
+    fn patch(file: &mut [u8], at: usize, bytes: &[u8]) {
+        file[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn read(file: Vec<u8>) -> Result<Scene, SceneError> {
+        Scene::read(&mut Cursor::new(file))
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = file();
+        assert_eq!(file.len(), FILE_SIZE);
+        assert_eq!(&file[..8], SCENE_MAGIC);
+        assert_eq!(read(file).unwrap(), scene());
+    }
+
+    #[test]
+    fn truncated_files() {
+        let file = file();
+        for size in 0..file.len() {
+            let actual = size as u64;
+            match read(file[..size].to_vec()) {
+                Err(SceneError::NotAScene) if size < 8 => (),
+                Err(SceneError::Io(e)) if size < 12 => {
+                    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+                }
+                Err(SceneError::OutOfBounds {
+                    entry: SceneEntry::Header,
+                    offset: 0,
+                    size: HEADER_SIZE,
+                    file_size,
+                }) if actual < HEADER_SIZE => assert_eq!(file_size, actual),
+                Err(SceneError::FileSize {
+                    expected,
+                    actual: a,
+                }) => {
+                    assert_eq!((expected, a), (FILE_SIZE as u64, actual))
+                }
+                result => panic!("{} bytes: {:?}", size, result),
+            }
+        }
+    }
+
+    #[test]
+    fn bad_magic_and_version() {
+        let mut file = file();
+        file[0] = b'X';
+        assert!(matches!(read(file), Err(SceneError::NotAScene)));
+
+        let mut file = self::file();
+        patch(&mut file, 8, &(SCENE_VERSION + 1).to_le_bytes());
+        match read(file) {
+            Err(SceneError::UnsupportedVersion(version)) => {
+                assert_eq!(version, SCENE_VERSION + 1)
+            }
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn offsets_outside_the_file() {
+        let mut file = file();
+        patch(&mut file, MIP_OFFSET, &224u64.to_le_bytes());
+        match read(file) {
+            Err(SceneError::OutOfBounds {
+                entry: SceneEntry::Mip { texture: 0, mip: 0 },
+                offset: 224,
+                size: 16,
+                file_size: 230,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+
+        let mut file = self::file();
+        patch(&mut file, VERTEX_RANGE, &200u64.to_le_bytes());
+        match read(file) {
+            Err(SceneError::OutOfBounds {
+                entry: SceneEntry::Vertices,
+                offset: 200,
+                size: 36,
+                file_size: 230,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+
+        // An end past u64::MAX is out of bounds too, not an overflow.
+        let mut file = self::file();
+        patch(&mut file, VERTEX_RANGE, &u64::MAX.to_le_bytes());
+        match read(file) {
+            Err(SceneError::OutOfBounds {
+                entry: SceneEntry::Vertices,
+                offset: u64::MAX,
+                size: 36,
+                file_size: 230,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn draw_outside_the_indices() {
+        let mut file = file();
+        patch(&mut file, DRAW + 8, &4u32.to_le_bytes());
+        match read(file) {
+            Err(SceneError::IndexRange {
+                entry: SceneEntry::Draw(0),
+                start: 0,
+                count: 4,
+                total: 3,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn missing_materials_and_textures() {
+        let mut file = file();
+        patch(&mut file, DRAW, &1u32.to_le_bytes());
+        match read(file) {
+            Err(SceneError::MissingMaterial {
+                entry: SceneEntry::Draw(0),
+                material: 1,
+                count: 1,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+
+        let mut file = self::file();
+        patch(&mut file, MATERIAL, &5u32.to_le_bytes());
+        match read(file) {
+            Err(SceneError::MissingTexture {
+                entry: SceneEntry::Material(0),
+                texture: 5,
+                count: 1,
+            }) => (),
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn stored_surfaces_must_have_texels() {
+        let entry = SceneEntry::Mip { texture: 0, mip: 0 };
+        let check = |width, height| {
+            check_stored_surface(entry, DXGI_FORMAT_BC1_UNORM, width, height, 0, 8, 64)
+        };
+        assert!(check(4, 4).is_ok());
+        for (width, height) in [(4, 0), (0, 4), (0, 0)] {
+            match check(width, height) {
+                Err(SceneError::EmptyTexture {
+                    entry: SceneEntry::Mip { texture: 0, mip: 0 },
+                    width: w,
+                    height: h,
+                }) => assert_eq!((w, h), (width, height)),
+                result => panic!("{}x{}: {:?}", width, height, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Compiling HLSL. `ShaderCompiler` hides the differences between FXC (SM5
+//! and earlier, via d3dcompiler_47.dll) and DXC (SM6, via the command line
+//! compiler), and both report problems as structured `Diagnostic`s.
+
+use crate::dxbc;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+mod cache;
+pub use cache::*;
+
+mod diagnostics;
+pub use diagnostics::*;
+
+mod dxc;
+pub use dxc::*;
+
+mod files;
+pub use files::*;
+
+mod permutations;
+pub use permutations::*;
+
+mod reload;
+pub use reload::*;
+
+#[cfg(windows)]
+mod fxc;
+#[cfg(windows)]
+pub use fxc::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Optimization {
+    Skip,
+    Level0,
+    Level1,
+    Level2,
+    Level3,
+}
+
+/// What to compile from a source file, and how.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompileOptions {
+    pub entry_point: String,
+    /// The target, e.g. `vs_5_0` or `ps_6_0`.
+    pub profile: String,
+    pub defines: Vec<(String, String)>,
+    /// Searched, in order, for includes that are not found next to the file
+    /// that includes them.
+    pub include_dirs: Vec<PathBuf>,
+    pub debug: bool,
+    pub optimization: Optimization,
+}
+
+impl CompileOptions {
+    /// Debug builds of the samples get debug info and no optimization, so that
+    /// shaders can be stepped through in the graphics debugging tools.
+    pub fn new(entry_point: &str, profile: &str) -> Self {
+        CompileOptions {
+            entry_point: entry_point.to_string(),
+            profile: profile.to_string(),
+            defines: Vec::new(),
+            include_dirs: Vec::new(),
+            debug: cfg!(debug_assertions),
+            optimization: if cfg!(debug_assertions) {
+                Optimization::Skip
+            } else {
+                Optimization::Level3
+            },
+        }
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn include_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.include_dirs.push(dir.into());
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn optimization(mut self, optimization: Optimization) -> Self {
+        self.optimization = optimization;
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ShaderSource<'a> {
+    File(&'a Path),
+    /// Source held in memory. `name` is used in diagnostics and to resolve
+    /// relative includes.
+    Text {
+        name: &'a str,
+        text: &'a str,
+    },
+    /// A file read, along with everything it includes, from `files` rather
+    /// than the disk.
+    Virtual {
+        files: &'a dyn ShaderFileSystem,
+        path: &'a str,
+    },
+}
+
+impl ShaderSource<'_> {
+    pub fn name(&self) -> String {
+        match self {
+            ShaderSource::File(path) => path.display().to_string(),
+            ShaderSource::Text { name, .. } => name.to_string(),
+            ShaderSource::Virtual { path, .. } => normalize_path(path),
+        }
+    }
+}
+
+impl fmt::Debug for ShaderSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderSource::File(path) => f.debug_tuple("File").field(path).finish(),
+            ShaderSource::Text { name, .. } => f.debug_struct("Text").field("name", name).finish(),
+            ShaderSource::Virtual { path, .. } => {
+                f.debug_struct("Virtual").field("path", path).finish()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompiledShader {
+    pub bytecode: Vec<u8>,
+    /// Warnings reported while compiling.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CompiledShader {
+    pub fn reflect(&self) -> dxbc::Result<dxbc::ShaderReflection> {
+        dxbc::Container::parse(&self.bytecode)?.reflect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    /// What was being compiled, e.g. `shaders.hlsl (VSMain, vs_5_0)`.
+    pub context: String,
+    /// Set when the compiler could not be run at all, or failed without
+    /// saying why.
+    pub message: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CompileError {
+    pub fn new(source: &ShaderSource, options: &CompileOptions) -> Self {
+        CompileError {
+            context: format!(
+                "{} ({}, {})",
+                source.name(),
+                options.entry_point,
+                options.profile
+            ),
+            message: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn with_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn with_output(mut self, output: &str) -> Self {
+        self.diagnostics = parse_diagnostics(output);
+        if self.diagnostics.is_empty() && !output.trim().is_empty() {
+            self.message = Some(output.trim().to_string());
+        }
+        self
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to compile {}", self.context)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[cfg(windows)]
+impl From<CompileError> for windows::core::Error {
+    fn from(error: CompileError) -> Self {
+        windows::core::Error::new(windows::Win32::Foundation::E_FAIL, error.to_string())
+    }
+}
+
+pub trait ShaderCompiler {
+    /// Names the compiler and its version, so that cached output from one
+    /// compiler is never used in place of another's.
+    fn identity(&self) -> String;
+
+    fn compile(
+        &self,
+        source: &ShaderSource,
+        options: &CompileOptions,
+    ) -> Result<CompiledShader, CompileError>;
+
+    fn compile_file(
+        &self,
+        path: &Path,
+        options: &CompileOptions,
+    ) -> Result<CompiledShader, CompileError> {
+        self.compile(&ShaderSource::File(path), options)
+    }
+
+    fn compile_virtual(
+        &self,
+        files: &dyn ShaderFileSystem,
+        path: &str,
+        options: &CompileOptions,
+    ) -> Result<CompiledShader, CompileError> {
+        self.compile(&ShaderSource::Virtual { files, path }, options)
+    }
+}
@@ -0,0 +1,270 @@
+use super::*;
+use crate::cache::{CacheDir, ContentHash, ContentHasher};
+
+/// Reuses bytecode from earlier runs. Entries are keyed by
+/// `shader_cache_key`, so editing a shader, or anything it includes, just
+/// means a miss.
+///
+/// Warnings are only reported when a shader is actually compiled; cache hits
+/// come back with no diagnostics.
+pub struct CachedCompiler<C> {
+    compiler: C,
+    identity: String,
+    cache: CacheDir,
+}
+
+impl<C: ShaderCompiler> CachedCompiler<C> {
+    pub fn new(compiler: C, cache: CacheDir) -> Self {
+        CachedCompiler {
+            identity: compiler.identity(),
+            compiler,
+            cache,
+        }
+    }
+
+    /// Caches in `shaders` under `CacheDir::default_root()`.
+    pub fn with_default_cache(compiler: C) -> Self {
+        Self::new(compiler, CacheDir::new(CacheDir::default_root(), "shaders"))
+    }
+
+    pub fn cache(&self) -> &CacheDir {
+        &self.cache
+    }
+
+    pub fn compiler(&self) -> &C {
+        &self.compiler
+    }
+}
+
+impl<C: ShaderCompiler> ShaderCompiler for CachedCompiler<C> {
+    fn identity(&self) -> String {
+        self.identity.clone()
+    }
+
+    fn compile(
+        &self,
+        source: &ShaderSource,
+        options: &CompileOptions,
+    ) -> Result<CompiledShader, CompileError> {
+        let key = shader_cache_key(&self.identity, source, options);
+
+        if let Some(bytecode) = key.as_ref().and_then(|key| self.cache.load(key)) {
+            return Ok(CompiledShader {
+                bytecode,
+                diagnostics: Vec::new(),
+            });
+        }
+
+        let shader = self.compiler.compile(source, options)?;
+        if let Some(key) = key {
+            // Failing to write the cache only costs time on the next run.
+            let _ = self.cache.store(&key, &shader.bytecode);
+        }
+        Ok(shader)
+    }
+}
+
+/// Hashes everything that decides what `source` compiles to: the compiler,
+/// the text of the source and of every file it includes, and the options.
+/// Returns `None` if the source cannot be read, leaving the compiler to
+/// report that.
+pub fn shader_cache_key(
+    compiler_identity: &str,
+    source: &ShaderSource,
+    options: &CompileOptions,
+) -> Option<ContentHash> {
+    let (files, root, root_text): (&dyn ShaderFileSystem, String, Option<&str>) = match source {
+        ShaderSource::File(path) => (&DiskFiles, path_to_shader_path(path), None),
+        ShaderSource::Text { name, text } => (&DiskFiles, normalize_path(name), Some(text)),
+        ShaderSource::Virtual { files, path } => (*files, normalize_path(path), None),
+    };
+
+    let resolver = IncludeResolver::new(files, &options.include_dirs);
+    let dependencies = match root_text {
+        Some(text) => resolver.dependencies_of_text(&root, text),
+        None => resolver.dependencies(&root),
+    };
+    if dependencies.files.is_empty() {
+        return None;
+    }
+
+    let mut hasher = ContentHasher::new();
+    hasher.write_str(compiler_identity);
+    hash_options(&mut hasher, options);
+
+    hasher.write_u64(dependencies.files.len() as u64);
+    for (index, path) in dependencies.files.iter().enumerate() {
+        let text = match root_text {
+            Some(text) if index == 0 => text.to_string(),
+            _ => files.read(path)?,
+        };
+        hasher.write_str(path);
+        hasher.write_str(&text);
+    }
+
+    // An include that cannot be found now might be found once it is created,
+    // or once an include directory changes; both change the key.
+    hasher.write_u64(dependencies.unresolved.len() as u64);
+    for name in &dependencies.unresolved {
+        hasher.write_str(name);
+    }
+
+    Some(hasher.finish())
+}
+
+fn hash_options(hasher: &mut ContentHasher, options: &CompileOptions) {
+    hasher.write_str(&options.entry_point);
+    hasher.write_str(&options.profile);
+
+    hasher.write_u64(options.defines.len() as u64);
+    for (name, value) in &options.defines {
+        hasher.write_str(name);
+        hasher.write_str(value);
+    }
+
+    hasher.write_u64(options.include_dirs.len() as u64);
+    for dir in &options.include_dirs {
+        hasher.write_str(&path_to_shader_path(dir));
+    }
+
+    hasher.write_bool(options.debug);
+    hasher.write_u32(match options.optimization {
+        Optimization::Skip => 0,
+        Optimization::Level0 => 1,
+        Optimization::Level1 => 2,
+        Optimization::Level2 => 3,
+        Optimization::Level3 => 4,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(sources: &[(&str, &'static str)]) -> EmbeddedShaders {
+        sources
+            .iter()
+            .fold(EmbeddedShaders::new(), |files, (path, text)| {
+                files.with(path, text)
+            })
+    }
+
+    fn key(files: &EmbeddedShaders, options: &CompileOptions) -> ContentHash {
+        shader_cache_key(
+            "fxc 10.1",
+            &ShaderSource::Virtual {
+                files,
+                path: "shaders/scene.hlsl",
+            },
+            options,
+        )
+        .unwrap()
+    }
+
+    fn scene_files() -> EmbeddedShaders {
+        files(&[
+            ("shaders/scene.hlsl", "#include \"common.hlsli\"\nscene"),
+            ("shaders/common.hlsli", "common"),
+        ])
+    }
+
+    #[test]
+    fn keys_are_stable() {
+        let options = CompileOptions::new("PSMain", "ps_5_0");
+        assert_eq!(
+            key(&scene_files(), &options),
+            key(&scene_files(), &options.clone())
+        );
+    }
+
+    #[test]
+    fn every_option_changes_the_key() {
+        let files = scene_files();
+        let options = CompileOptions::new("PSMain", "ps_5_0")
+            .debug(false)
+            .optimization(Optimization::Level3);
+        let variants = [
+            options.clone(),
+            CompileOptions {
+                entry_point: "VSMain".to_string(),
+                ..options.clone()
+            },
+            CompileOptions {
+                profile: "ps_5_1".to_string(),
+                ..options.clone()
+            },
+            options.clone().define("SHADOWS", ""),
+            options.clone().define("SHADOWS", "1"),
+            options.clone().define("SHADOWS", "1").define("FOG", "1"),
+            options.clone().include_dir("include"),
+            options.clone().debug(true),
+            options.clone().optimization(Optimization::Level2),
+            options.clone().optimization(Optimization::Skip),
+        ];
+
+        let keys: Vec<ContentHash> = variants.iter().map(|o| key(&files, o)).collect();
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        let other_compiler = shader_cache_key(
+            "dxc 1.8",
+            &ShaderSource::Virtual {
+                files: &files,
+                path: "shaders/scene.hlsl",
+            },
+            &options,
+        );
+        assert_ne!(other_compiler, Some(keys[0]));
+    }
+
+    #[test]
+    fn source_and_include_changes_change_the_key() {
+        let options = CompileOptions::new("PSMain", "ps_5_0").include_dir("include");
+        let original = key(&scene_files(), &options);
+
+        let edited_include = scene_files().with("shaders/common.hlsli", "common v2");
+        assert_ne!(key(&edited_include, &options), original);
+
+        // Files that aren't included don't matter.
+        let unused = scene_files().with("shaders/unused.hlsli", "unused");
+        assert_eq!(key(&unused, &options), original);
+
+        let unresolved =
+            scene_files().with("shaders/scene.hlsl", "#include <missing.hlsli>\nscene");
+        let found = unresolved.clone().with("include/missing.hlsli", "found");
+        assert_ne!(key(&found, &options), key(&unresolved, &options));
+    }
+
+    #[test]
+    fn text_sources_match_files_with_the_same_contents() {
+        let files = files(&[("shaders/scene.hlsl", "scene")]);
+        let options = CompileOptions::new("PSMain", "ps_5_0");
+
+        let text = shader_cache_key(
+            "fxc 10.1",
+            &ShaderSource::Text {
+                name: r"shaders\scene.hlsl",
+                text: "scene",
+            },
+            &options,
+        );
+        assert_eq!(text, Some(key(&files, &options)));
+    }
+
+    #[test]
+    fn unreadable_sources_have_no_key() {
+        let options = CompileOptions::new("PSMain", "ps_5_0");
+        let missing = shader_cache_key(
+            "fxc 10.1",
+            &ShaderSource::Virtual {
+                files: &EmbeddedShaders::new(),
+                path: "shaders/scene.hlsl",
+            },
+            &options,
+        );
+        assert_eq!(missing, None);
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// One message from the compiler, such as
+/// `shaders.hlsl(12,5-18): error X3004: undeclared identifier 'foo'`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    /// `X3004` for FXC, or the warning flag (e.g. `-Wconversion`) for DXC.
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    // Written in the FXC style, which editors know how to jump to.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file)?;
+            match (self.line, self.column) {
+                (Some(line), Some(column)) => write!(f, "({},{})", line, column)?,
+                (Some(line), None) => write!(f, "({})", line)?,
+                _ => (),
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.severity)?;
+        if let Some(code) = &self.code {
+            write!(f, " {}", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Extracts the diagnostics from FXC or DXC output. Lines that are not
+/// diagnostics, such as the source excerpts DXC prints under each message, are
+/// skipped.
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    output.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<Diagnostic> {
+    let line = line.trim_end();
+
+    // Either "<location>: <severity>..." or just "<severity>...". Locations
+    // can contain colons themselves (C:\foo.hlsl:1:2), so try each split.
+    let splits = line.match_indices(": ").map(|(i, _)| i);
+    std::iter::once(None)
+        .chain(splits.map(Some))
+        .find_map(|split| {
+            let (location, rest) = match split {
+                None => ("", line),
+                Some(i) => (&line[..i], &line[i + 2..]),
+            };
+            let (severity, code, message) = parse_severity(rest)?;
+            let (file, line, column) = parse_location(location);
+            Some(Diagnostic {
+                file,
+                line,
+                column,
+                severity,
+                code,
+                message,
+            })
+        })
+}
+
+const SEVERITIES: &[(&str, Severity)] = &[
+    ("fatal error", Severity::Error),
+    ("internal error", Severity::Error),
+    ("error", Severity::Error),
+    ("warning", Severity::Warning),
+    ("note", Severity::Note),
+];
+
+fn parse_severity(text: &str) -> Option<(Severity, Option<String>, String)> {
+    let (after, severity) = SEVERITIES
+        .iter()
+        .find_map(|(word, severity)| Some((text.strip_prefix(word)?, *severity)))?;
+
+    let (mut code, message) = if let Some(message) = after.strip_prefix(':') {
+        (None, message.trim_start())
+    } else {
+        // FXC puts a code between the severity and the colon: "error X3004:"
+        let after = after.strip_prefix(' ')?;
+        let colon = after.find(':')?;
+        let code = &after[..colon];
+        if code.is_empty() || code.contains(' ') {
+            return None;
+        }
+        (Some(code.to_string()), after[colon + 1..].trim_start())
+    };
+
+    // DXC appends the warning flag: "... [-Wconversion]"
+    let mut message = message;
+    if code.is_none() && message.ends_with(']') {
+        if let Some(open) = message.rfind(" [-W") {
+            code = Some(message[open + 2..message.len() - 1].to_string());
+            message = &message[..open];
+        }
+    }
+
+    Some((severity, code, message.to_string()))
+}
+
+fn parse_location(location: &str) -> (Option<String>, Option<u32>, Option<u32>) {
+    if location.is_empty() {
+        return (None, None, None);
+    }
+
+    // FXC: file(line,column) or file(line,column-end_column)
+    if location.ends_with(')') {
+        if let Some(open) = location.rfind('(') {
+            let mut numbers = location[open + 1..location.len() - 1].split(',');
+            let line = numbers.next().and_then(|l| l.trim().parse().ok());
+            if line.is_some() {
+                let column = numbers
+                    .next()
+                    .and_then(|c| c.split('-').next())
+                    .and_then(|c| c.trim().parse().ok());
+                return (Some(location[..open].to_string()), line, column);
+            }
+        }
+    }
+
+    // DXC: file:line:column or file:line
+    if let Some((rest, last)) = split_trailing_number(location) {
+        return match split_trailing_number(rest) {
+            Some((file, line)) => (Some(file.to_string()), Some(line), Some(last)),
+            None => (Some(rest.to_string()), Some(last), None),
+        };
+    }
+
+    (Some(location.to_string()), None, None)
+}
+
+fn split_trailing_number(text: &str) -> Option<(&str, u32)> {
+    let colon = text.rfind(':')?;
+    let number = text[colon + 1..].parse().ok()?;
+    Some((&text[..colon], number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        location: Option<(&str, u32, Option<u32>)>,
+        severity: Severity,
+        code: Option<&str>,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            file: location.map(|(file, _, _)| file.to_string()),
+            line: location.map(|(_, line, _)| line),
+            column: location.and_then(|(_, _, column)| column),
+            severity,
+            code: code.map(str::to_string),
+            message: message.to_string(),
+        }
+    }
+
+    // fxc /T ps_5_0 /E PSMain shaders.hlsl
+    const FXC_OUTPUT: &str = r"C:\src\shaders\shaders.hlsl(12,5-18): error X3004: undeclared identifier 'foo'
+C:\src\shaders\shaders.hlsl(20,12-13): warning X3206: implicit truncation of vector type
+C:\src\shaders\common.hlsli(7,1): error X3000: syntax error: unexpected token '}'
+shaders.hlsl(31,9): warning X4000: use of potentially uninitialized variable (color)
+error X3501: 'PSMain': entrypoint not found
+
+compilation failed; no code produced
+";
+
+    #[test]
+    fn fxc_output() {
+        assert_eq!(
+            parse_diagnostics(FXC_OUTPUT),
+            [
+                diagnostic(
+                    Some((r"C:\src\shaders\shaders.hlsl", 12, Some(5))),
+                    Severity::Error,
+                    Some("X3004"),
+                    "undeclared identifier 'foo'"
+                ),
+                diagnostic(
+                    Some((r"C:\src\shaders\shaders.hlsl", 20, Some(12))),
+                    Severity::Warning,
+                    Some("X3206"),
+                    "implicit truncation of vector type"
+                ),
+                diagnostic(
+                    Some((r"C:\src\shaders\common.hlsli", 7, Some(1))),
+                    Severity::Error,
+                    Some("X3000"),
+                    "syntax error: unexpected token '}'"
+                ),
+                diagnostic(
+                    Some(("shaders.hlsl", 31, Some(9))),
+                    Severity::Warning,
+                    Some("X4000"),
+                    "use of potentially uninitialized variable (color)"
+                ),
+                diagnostic(
+                    None,
+                    Severity::Error,
+                    Some("X3501"),
+                    "'PSMain': entrypoint not found"
+                ),
+            ]
+        );
+    }
+
+    // dxc -T ps_6_0 -E PSMain shaders.hlsl
+    const DXC_OUTPUT: &str = r#"shaders.hlsl:12:5: error: use of undeclared identifier 'foo'
+    foo = 1;
+    ^
+shaders.hlsl:20:16: warning: implicit truncation of vector type [-Wconversion]
+    float2 uv = input.position;
+               ^
+In file included from shaders.hlsl:1:
+C:\src\shaders\common.hlsli:4:7: error: redefinition of 'gScale'
+float gScale;
+      ^
+C:\src\shaders\common.hlsli:2:7: note: previous definition is here
+float gScale;
+      ^
+shaders.hlsl:28:12: error: no matching function for call to 'mul'
+    return mul(a, b);
+           ^~~
+note: candidate template ignored: couldn't infer template argument 'T'
+shaders.hlsl:3:10: fatal error: 'missing.hlsli' file not found
+#include "missing.hlsli"
+         ^~~~~~~~~~~~~~~
+1 warning and 4 errors generated.
+"#;
+
+    #[test]
+    fn dxc_output() {
+        assert_eq!(
+            parse_diagnostics(DXC_OUTPUT),
+            [
+                diagnostic(
+                    Some(("shaders.hlsl", 12, Some(5))),
+                    Severity::Error,
+                    None,
+                    "use of undeclared identifier 'foo'"
+                ),
+                diagnostic(
+                    Some(("shaders.hlsl", 20, Some(16))),
+                    Severity::Warning,
+                    Some("-Wconversion"),
+                    "implicit truncation of vector type"
+                ),
+                diagnostic(
+                    Some((r"C:\src\shaders\common.hlsli", 4, Some(7))),
+                    Severity::Error,
+                    None,
+                    "redefinition of 'gScale'"
+                ),
+                diagnostic(
+                    Some((r"C:\src\shaders\common.hlsli", 2, Some(7))),
+                    Severity::Note,
+                    None,
+                    "previous definition is here"
+                ),
+                diagnostic(
+                    Some(("shaders.hlsl", 28, Some(12))),
+                    Severity::Error,
+                    None,
+                    "no matching function for call to 'mul'"
+                ),
+                diagnostic(
+                    None,
+                    Severity::Note,
+                    None,
+                    "candidate template ignored: couldn't infer template argument 'T'"
+                ),
+                diagnostic(
+                    Some(("shaders.hlsl", 3, Some(10))),
+                    Severity::Error,
+                    None,
+                    "'missing.hlsli' file not found"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn locations_without_columns() {
+        assert_eq!(
+            parse_diagnostics(
+                "shaders.hlsl:7: warning: empty file\nshaders.hlsl(9): error X1000: oops"
+            ),
+            [
+                diagnostic(
+                    Some(("shaders.hlsl", 7, None)),
+                    Severity::Warning,
+                    None,
+                    "empty file"
+                ),
+                diagnostic(
+                    Some(("shaders.hlsl", 9, None)),
+                    Severity::Error,
+                    Some("X1000"),
+                    "oops"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn display_uses_the_fxc_style() {
+        let diagnostics = parse_diagnostics(DXC_OUTPUT);
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "shaders.hlsl(20,16): warning -Wconversion: implicit truncation of vector type"
+        );
+        assert_eq!(
+            parse_diagnostics(FXC_OUTPUT)[4].to_string(),
+            "error X3501: 'PSMain': entrypoint not found"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use super::*;
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Compiles SM6 shaders by running `dxc`, which ships with the Windows SDK
+/// and the Vulkan SDK. Running it as a process rather than loading
+/// dxcompiler.dll means this works anywhere DXC is installed.
+#[derive(Debug, Clone)]
+pub struct DxcCompiler {
+    pub executable: PathBuf,
+}
+
+impl Default for DxcCompiler {
+    /// Uses the `dxc` found on the PATH.
+    fn default() -> Self {
+        DxcCompiler {
+            executable: PathBuf::from("dxc"),
+        }
+    }
+}
+
+impl DxcCompiler {
+    pub fn new<P: Into<PathBuf>>(executable: P) -> Self {
+        DxcCompiler {
+            executable: executable.into(),
+        }
+    }
+
+    fn arguments(&self, options: &CompileOptions, input: &Path, output: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "-nologo".into(),
+            "-T".into(),
+            options.profile.as_str().into(),
+            "-E".into(),
+            options.entry_point.as_str().into(),
+            "-Fo".into(),
+            output.into(),
+        ];
+
+        for (name, value) in &options.defines {
+            args.push("-D".into());
+            args.push(if value.is_empty() {
+                name.into()
+            } else {
+                format!("{}={}", name, value).into()
+            });
+        }
+
+        for dir in &options.include_dirs {
+            args.push("-I".into());
+            args.push(dir.into());
+        }
+
+        if options.debug {
+            args.push("-Zi".into());
+            args.push("-Qembed_debug".into());
+        }
+
+        args.push(
+            match options.optimization {
+                Optimization::Skip => "-Od",
+                Optimization::Level0 => "-O0",
+                Optimization::Level1 => "-O1",
+                Optimization::Level2 => "-O2",
+                Optimization::Level3 => "-O3",
+            }
+            .into(),
+        );
+
+        args.push(input.into());
+        args
+    }
+}
+
+impl ShaderCompiler for DxcCompiler {
+    fn identity(&self) -> String {
+        let version = Command::new(&self.executable)
+            .arg("--version")
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_default();
+        format!("dxc {} {}", self.executable.display(), version)
+    }
+
+    fn compile(
+        &self,
+        source: &ShaderSource,
+        options: &CompileOptions,
+    ) -> Result<CompiledShader, CompileError> {
+        let error = || CompileError::new(source, options);
+
+        let work_dir = WorkDir::new().map_err(|e| {
+            error().with_message(format!("could not create temporary directory: {}", e))
+        })?;
+        let output = work_dir.path.join("shader.dxil");
+
+        // DXC only reads files, so text is written out under its own name to
+        // keep diagnostics readable. Includes are still found relative to
+        // where the name says it lives.
+        let mut options = options.clone();
+        let mut work_dir_prefix = None;
+        let input = match source {
+            ShaderSource::File(path) => path.to_path_buf(),
+            ShaderSource::Text { name, text } => {
+                let name = Path::new(name);
+                if let Some(dir) = name.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    options.include_dirs.insert(0, dir.to_path_buf());
+                }
+                let input = work_dir.path.join(
+                    name.file_name()
+                        .unwrap_or_else(|| OsStr::new("shader.hlsl")),
+                );
+                fs::write(&input, text).map_err(|e| {
+                    error().with_message(format!("could not write {}: {}", input.display(), e))
+                })?;
+                input
+            }
+            ShaderSource::Virtual { files, path } => {
+                let input = materialize(*files, path, &mut options, &work_dir.path)
+                    .map_err(|e| error().with_message(e))?;
+                // The work directory is only for this compile, so report
+                // problems against the virtual paths instead.
+                work_dir_prefix = Some(work_dir.path.clone());
+                input
+            }
+        };
+
+        let result = Command::new(&self.executable)
+            .args(self.arguments(&options, &input, &output))
+            .output()
+            .map_err(|e| {
+                error().with_message(format!(
+                    "could not run {}: {}",
+                    self.executable.display(),
+                    e
+                ))
+            })?;
+
+        let mut text = String::from_utf8_lossy(&result.stderr).into_owned()
+            + &String::from_utf8_lossy(&result.stdout);
+        if let Some(prefix) = work_dir_prefix {
+            let prefix = format!("{}{}", prefix.display(), std::path::MAIN_SEPARATOR);
+            text = text.replace(&prefix, "");
+        }
+
+        if !result.status.success() {
+            return Err(error().with_output(&text));
+        }
+
+        let bytecode = fs::read(&output).map_err(|e| {
+            error().with_message(format!("could not read {}: {}", output.display(), e))
+        })?;
+
+        Ok(CompiledShader {
+            bytecode,
+            diagnostics: parse_diagnostics(&text),
+        })
+    }
+}
+
+/// Writes `path` and everything it includes under `dir`, keeping their
+/// relative layout so DXC resolves includes to the same files the resolver
+/// did. Include directories inside the virtual file system are redirected to
+/// their copies.
+fn materialize(
+    files: &dyn ShaderFileSystem,
+    path: &str,
+    options: &mut CompileOptions,
+    dir: &Path,
+) -> std::result::Result<PathBuf, String> {
+    let resolver = IncludeResolver::new(files, &options.include_dirs);
+    let dependencies = resolver.dependencies(path);
+    let root = dependencies
+        .files
+        .first()
+        .ok_or_else(|| format!("could not read {}", path))?
+        .clone();
+
+    let local = |path: &str| -> Option<PathBuf> {
+        if path.starts_with('/') || path.contains(':') || path.split('/').any(|c| c == "..") {
+            None
+        } else {
+            Some(dir.join(path))
+        }
+    };
+
+    for file in &dependencies.files {
+        if let Some(destination) = local(file) {
+            let text = files
+                .read(file)
+                .ok_or_else(|| format!("could not read {}", file))?;
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&destination, text)
+                .map_err(|e| format!("could not write {}: {}", destination.display(), e))?;
+        }
+    }
+
+    for include_dir in options.include_dirs.iter_mut() {
+        if let Some(copy) = local(&path_to_shader_path(include_dir)) {
+            *include_dir = copy;
+        }
+    }
+
+    local(&root).ok_or_else(|| format!("{} is outside the shader file system", root))
+}
+
+/// A temporary directory that is removed when dropped.
+struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    fn new() -> std::io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "d3dx12-dxc-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(WorkDir { path })
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
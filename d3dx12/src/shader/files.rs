@@ -0,0 +1,547 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Somewhere shader sources can be read from. Paths use `/` separators and
+/// have already been normalized by `normalize_path`.
+pub trait ShaderFileSystem {
+    fn read(&self, path: &str) -> Option<String>;
+}
+
+/// Sources compiled into the binary, usually with `embed_shaders!`.
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddedShaders {
+    files: HashMap<String, &'static str>,
+}
+
+impl EmbeddedShaders {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with(mut self, path: &str, text: &'static str) -> Self {
+        self.files.insert(normalize_path(path), text);
+        self
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+}
+
+impl ShaderFileSystem for EmbeddedShaders {
+    fn read(&self, path: &str) -> Option<String> {
+        self.files.get(path).map(|text| text.to_string())
+    }
+}
+
+/// Embeds shader sources with `include_str!`. Paths are relative to `dir`,
+/// which is relative to the crate's manifest directory, and are the names
+/// the shaders use to include each other.
+///
+/// ```ignore
+/// let shaders = d3dx12::embed_shaders!("src/rendering", ["scene.hlsl", "common.hlsli"]);
+/// ```
+#[macro_export]
+macro_rules! embed_shaders {
+    ($dir:literal, [$($path:literal),* $(,)?]) => {
+        $crate::shader::EmbeddedShaders::new()
+            $(.with(
+                $path,
+                include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $path)),
+            ))*
+    };
+}
+
+/// Sources held in memory that can be changed after they have been handed
+/// out. Clones share the same files, so one can be given to, say, a
+/// `ShaderReloader` while another is used to edit them.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFiles {
+    files: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl MemoryFiles {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn write(&self, path: &str, text: &str) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(normalize_path(path), text.to_string());
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.files.lock().unwrap().remove(&normalize_path(path));
+    }
+}
+
+impl ShaderFileSystem for MemoryFiles {
+    fn read(&self, path: &str) -> Option<String> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+}
+
+/// Sources under a directory on disk.
+#[derive(Debug, Clone)]
+pub struct ShaderDirectory {
+    pub root: PathBuf,
+}
+
+impl ShaderDirectory {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        ShaderDirectory { root: root.into() }
+    }
+}
+
+impl ShaderFileSystem for ShaderDirectory {
+    fn read(&self, path: &str) -> Option<String> {
+        if is_absolute(path) {
+            return None;
+        }
+        fs::read_to_string(self.root.join(path)).ok()
+    }
+}
+
+/// Paths on disk, as given; relative ones are relative to the working
+/// directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskFiles;
+
+impl ShaderFileSystem for DiskFiles {
+    fn read(&self, path: &str) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+}
+
+/// Tries each file system in turn, so that, for example, sources on disk can
+/// override embedded ones.
+#[derive(Default)]
+pub struct SearchPath {
+    layers: Vec<Box<dyn ShaderFileSystem>>,
+}
+
+impl SearchPath {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with<F: ShaderFileSystem + 'static>(mut self, files: F) -> Self {
+        self.layers.push(Box::new(files));
+        self
+    }
+}
+
+impl ShaderFileSystem for SearchPath {
+    fn read(&self, path: &str) -> Option<String> {
+        self.layers.iter().find_map(|layer| layer.read(path))
+    }
+}
+
+fn is_absolute(path: &str) -> bool {
+    path.starts_with('/') || path.as_bytes().get(1) == Some(&b':')
+}
+
+/// Converts separators to `/` and removes `.` and `..` components where
+/// possible, so that the same file always gets the same name.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let absolute = path.starts_with('/');
+    // `..` can't climb above the root or a drive letter.
+    let rooted = is_absolute(&path);
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => match components.last() {
+                Some(&last) if last != ".." && !last.ends_with(':') => {
+                    components.pop();
+                }
+                _ if rooted => (),
+                _ => components.push(".."),
+            },
+            _ => components.push(component),
+        }
+    }
+
+    let joined = components.join("/");
+    if absolute {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
+/// Resolves `name` relative to the directory `dir`.
+pub fn join_path(dir: &str, name: &str) -> String {
+    if is_absolute(name) || dir.is_empty() {
+        normalize_path(name)
+    } else {
+        normalize_path(&format!("{}/{}", dir, name))
+    }
+}
+
+/// The directory part of a normalized path; empty for files at the root.
+pub fn parent_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) => "/",
+        Some(i) => &path[..i],
+        None => "",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncludeKind {
+    /// `#include "file"`
+    Local,
+    /// `#include <file>`
+    System,
+}
+
+/// Finds the `#include` directives in a source file. The preprocessor is not
+/// run, so includes inside `#if` blocks are all reported.
+pub fn scan_includes(text: &str) -> Vec<(IncludeKind, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let rest = line.trim_start().strip_prefix('#')?.trim_start();
+            let rest = rest.strip_prefix("include")?.trim_start();
+            let (kind, close) = match rest.chars().next()? {
+                '"' => (IncludeKind::Local, '"'),
+                '<' => (IncludeKind::System, '>'),
+                _ => return None,
+            };
+            let end = rest[1..].find(close)?;
+            Some((kind, rest[1..1 + end].to_string()))
+        })
+        .collect()
+}
+
+/// Resolves includes the way the compilers do: local includes are looked for
+/// next to the including file first, then everything goes through the include
+/// directories in order.
+pub struct IncludeResolver<'a> {
+    files: &'a dyn ShaderFileSystem,
+    include_dirs: Vec<String>,
+}
+
+/// The files a shader pulls in, as found by `IncludeResolver::dependencies`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependencies {
+    /// Normalized paths of every file reached, starting with the root.
+    pub files: Vec<String>,
+    /// Includes that could not be found, as written in the source.
+    pub unresolved: Vec<String>,
+    /// Every path that was tried for the unresolved includes; creating any of
+    /// them changes the result.
+    pub missing: Vec<String>,
+}
+
+impl<'a> IncludeResolver<'a> {
+    pub fn new(files: &'a dyn ShaderFileSystem, include_dirs: &[PathBuf]) -> Self {
+        IncludeResolver {
+            files,
+            include_dirs: include_dirs
+                .iter()
+                .map(|dir| normalize_path(&dir.to_string_lossy()))
+                .collect(),
+        }
+    }
+
+    pub fn read(&self, path: &str) -> Option<(String, String)> {
+        let path = normalize_path(path);
+        let text = self.files.read(&path)?;
+        Some((path, text))
+    }
+
+    /// Returns the normalized path and contents of the file that `name`, as
+    /// included from `parent`, refers to.
+    pub fn resolve(&self, kind: IncludeKind, name: &str, parent: &str) -> Option<(String, String)> {
+        self.candidates(kind, name, parent).find_map(|path| {
+            let text = self.files.read(&path)?;
+            Some((path, text))
+        })
+    }
+
+    /// The paths `resolve` tries, in order.
+    pub fn candidates<'b>(
+        &'b self,
+        kind: IncludeKind,
+        name: &'b str,
+        parent: &str,
+    ) -> impl Iterator<Item = String> + 'b {
+        let local = match kind {
+            IncludeKind::Local => Some(join_path(parent_path(parent), name)),
+            IncludeKind::System => None,
+        };
+
+        local.into_iter().chain(
+            self.include_dirs
+                .iter()
+                .map(move |dir| join_path(dir, name)),
+        )
+    }
+
+    /// Walks the include tree under `root`.
+    pub fn dependencies(&self, root: &str) -> Dependencies {
+        match self.read(root) {
+            Some((path, text)) => self.dependencies_of_text(&path, &text),
+            None => Dependencies {
+                files: Vec::new(),
+                unresolved: vec![root.to_string()],
+                missing: vec![normalize_path(root)],
+            },
+        }
+    }
+
+    /// Walks the include tree of `text`, which is not in the file system but
+    /// resolves its includes as though it were at `path`.
+    pub fn dependencies_of_text(&self, path: &str, text: &str) -> Dependencies {
+        let mut dependencies = Dependencies::default();
+        let mut pending = vec![(normalize_path(path), text.to_string())];
+
+        while let Some((path, text)) = pending.pop() {
+            if dependencies.files.contains(&path) {
+                continue;
+            }
+            for (kind, name) in scan_includes(&text) {
+                match self.resolve(kind, &name, &path) {
+                    Some(file) => pending.push(file),
+                    None => {
+                        for candidate in self.candidates(kind, &name, &path) {
+                            if !dependencies.missing.contains(&candidate) {
+                                dependencies.missing.push(candidate);
+                            }
+                        }
+                        if !dependencies.unresolved.contains(&name) {
+                            dependencies.unresolved.push(name)
+                        }
+                    }
+                }
+            }
+            dependencies.files.push(path);
+        }
+
+        dependencies
+    }
+}
+
+/// Converts a path on disk to the form used by `ShaderFileSystem`.
+pub fn path_to_shader_path(path: &Path) -> String {
+    normalize_path(&path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(sources: &[(&str, &'static str)]) -> EmbeddedShaders {
+        sources
+            .iter()
+            .fold(EmbeddedShaders::new(), |files, (path, text)| {
+                files.with(path, text)
+            })
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            normalize_path(r"shaders\common\lighting.hlsli"),
+            "shaders/common/lighting.hlsli"
+        );
+        assert_eq!(
+            normalize_path("./shaders//./scene.hlsl"),
+            "shaders/scene.hlsl"
+        );
+        assert_eq!(
+            normalize_path("shaders/common/../scene.hlsl"),
+            "shaders/scene.hlsl"
+        );
+        assert_eq!(
+            normalize_path("../shared/../../common.hlsli"),
+            "../../common.hlsli"
+        );
+        assert_eq!(
+            normalize_path("/shaders/../../common.hlsli"),
+            "/common.hlsli"
+        );
+        assert_eq!(
+            normalize_path(r"C:\shaders\..\..\common.hlsli"),
+            "C:/common.hlsli"
+        );
+    }
+
+    #[test]
+    fn joins_paths() {
+        assert_eq!(
+            join_path("shaders/scene", "../common.hlsli"),
+            "shaders/common.hlsli"
+        );
+        assert_eq!(join_path("", "./common.hlsli"), "common.hlsli");
+        assert_eq!(
+            join_path("shaders", "/include/common.hlsli"),
+            "/include/common.hlsli"
+        );
+        assert_eq!(
+            join_path("shaders", "C:/include/common.hlsli"),
+            "C:/include/common.hlsli"
+        );
+        assert_eq!(parent_path("shaders/scene.hlsl"), "shaders");
+        assert_eq!(parent_path("/scene.hlsl"), "/");
+        assert_eq!(parent_path("scene.hlsl"), "");
+    }
+
+    #[test]
+    fn scans_includes() {
+        let text = r#"
+#include "common.hlsli"
+  #  include <lighting.hlsli>
+#if SHADOWS
+#include "shadows/pcf.hlsli" // comment
+#endif
+#include MACRO_PATH
+#include "unterminated
+#define INCLUDE "not an include"
+"#;
+        assert_eq!(
+            scan_includes(text),
+            [
+                (IncludeKind::Local, "common.hlsli".to_string()),
+                (IncludeKind::System, "lighting.hlsli".to_string()),
+                (IncludeKind::Local, "shadows/pcf.hlsli".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn local_includes_are_found_next_to_the_includer_first() {
+        let files = files(&[
+            ("shaders/common.hlsli", "// local"),
+            ("include/common.hlsli", "// include dir"),
+            ("include/lighting.hlsli", "// lighting"),
+        ]);
+        let resolver = IncludeResolver::new(&files, &[PathBuf::from("include")]);
+
+        let (path, text) = resolver
+            .resolve(IncludeKind::Local, "common.hlsli", "shaders/scene.hlsl")
+            .unwrap();
+        assert_eq!(
+            (path.as_str(), text.as_str()),
+            ("shaders/common.hlsli", "// local")
+        );
+
+        let (path, _) = resolver
+            .resolve(IncludeKind::Local, "lighting.hlsli", "shaders/scene.hlsl")
+            .unwrap();
+        assert_eq!(path, "include/lighting.hlsli");
+    }
+
+    #[test]
+    fn system_includes_only_search_the_include_dirs() {
+        let files = files(&[
+            ("shaders/common.hlsli", "// local"),
+            ("first/common.hlsli", "// first"),
+            ("second/common.hlsli", "// second"),
+            ("second/only.hlsli", "// second only"),
+        ]);
+        let resolver = IncludeResolver::new(
+            &files,
+            &[PathBuf::from("first"), PathBuf::from(r"second\.")],
+        );
+
+        let (path, _) = resolver
+            .resolve(IncludeKind::System, "common.hlsli", "shaders/scene.hlsl")
+            .unwrap();
+        assert_eq!(path, "first/common.hlsli");
+
+        let (path, _) = resolver
+            .resolve(IncludeKind::System, "only.hlsli", "shaders/scene.hlsl")
+            .unwrap();
+        assert_eq!(path, "second/only.hlsli");
+
+        let empty = IncludeResolver::new(&files, &[]);
+        assert!(empty
+            .resolve(IncludeKind::System, "common.hlsli", "shaders/scene.hlsl")
+            .is_none());
+    }
+
+    #[test]
+    fn walks_dependencies_through_cycles() {
+        let files = files(&[
+            (
+                "shaders/scene.hlsl",
+                "#include \"common/a.hlsli\"\n#include \"common/b.hlsli\"",
+            ),
+            ("shaders/common/a.hlsli", "#include \"b.hlsli\""),
+            (
+                "shaders/common/b.hlsli",
+                "#include \"../common/a.hlsli\"\n#include \"../scene.hlsl\"",
+            ),
+        ]);
+        let resolver = IncludeResolver::new(&files, &[]);
+
+        let mut dependencies = resolver.dependencies(r"shaders\scene.hlsl");
+        assert_eq!(dependencies.files[0], "shaders/scene.hlsl");
+        dependencies.files.sort();
+        assert_eq!(
+            dependencies,
+            Dependencies {
+                files: vec![
+                    "shaders/common/a.hlsli".to_string(),
+                    "shaders/common/b.hlsli".to_string(),
+                    "shaders/scene.hlsl".to_string(),
+                ],
+                unresolved: Vec::new(),
+                missing: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn reports_unresolved_includes() {
+        let files = files(&[
+            (
+                "shaders/scene.hlsl",
+                "#include \"common.hlsli\"\n#include \"gone/lighting.hlsli\"",
+            ),
+            (
+                "shaders/common.hlsli",
+                "#include <missing.hlsli>\n#include \"missing.hlsli\"",
+            ),
+        ]);
+        let resolver = IncludeResolver::new(&files, &[PathBuf::from("include")]);
+
+        let dependencies = resolver.dependencies("shaders/scene.hlsl");
+        assert_eq!(
+            dependencies.files,
+            ["shaders/scene.hlsl", "shaders/common.hlsli"]
+        );
+        assert_eq!(
+            dependencies.unresolved,
+            ["gone/lighting.hlsli", "missing.hlsli"]
+        );
+        assert_eq!(
+            dependencies.missing,
+            [
+                "shaders/gone/lighting.hlsli",
+                "include/gone/lighting.hlsli",
+                "include/missing.hlsli",
+                "shaders/missing.hlsli",
+            ]
+        );
+
+        assert_eq!(
+            resolver.dependencies("shaders/missing.hlsl"),
+            Dependencies {
+                files: Vec::new(),
+                unresolved: vec!["shaders/missing.hlsl".to_string()],
+                missing: vec!["shaders/missing.hlsl".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use super::*;
+use crate::blob_bytes;
+use std::{cell::RefCell, collections::HashMap, ffi::CString};
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::{E_FAIL, E_INVALIDARG},
+        Graphics::Direct3D::{Fxc::*, *},
+    },
+};
+
+/// Compiles SM5 and earlier shaders with d3dcompiler_47.dll.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FxcCompiler;
+
+// d3dcompiler.h defines this as a combination of the other two levels.
+const D3DCOMPILE_OPTIMIZATION_LEVEL2: u32 =
+    D3DCOMPILE_OPTIMIZATION_LEVEL0 | D3DCOMPILE_OPTIMIZATION_LEVEL3;
+
+fn compile_flags(options: &CompileOptions) -> u32 {
+    let mut flags = match options.optimization {
+        Optimization::Skip => D3DCOMPILE_SKIP_OPTIMIZATION,
+        Optimization::Level0 => D3DCOMPILE_OPTIMIZATION_LEVEL0,
+        Optimization::Level1 => D3DCOMPILE_OPTIMIZATION_LEVEL1,
+        Optimization::Level2 => D3DCOMPILE_OPTIMIZATION_LEVEL2,
+        Optimization::Level3 => D3DCOMPILE_OPTIMIZATION_LEVEL3,
+    };
+    if options.debug {
+        flags |= D3DCOMPILE_DEBUG;
+    }
+    flags
+}
+
+impl ShaderCompiler for FxcCompiler {
+    fn identity(&self) -> String {
+        // d3dcompiler_47.dll has not changed in years, and is what D3DCompile
+        // links against.
+        "fxc d3dcompiler_47".to_string()
+    }
+
+    fn compile(
+        &self,
+        source: &ShaderSource,
+        options: &CompileOptions,
+    ) -> std::result::Result<CompiledShader, CompileError> {
+        let error = || CompileError::new(source, options);
+        let c_string = |s: &str| {
+            CString::new(s)
+                .map_err(|_| error().with_message(format!("'{}' contains a NUL character", s)))
+        };
+
+        // Everything goes through the same resolver, so that includes are
+        // found the same way whether the shader came from disk or not.
+        let (files, root): (&dyn ShaderFileSystem, String) = match source {
+            ShaderSource::File(path) => (&DiskFiles, path_to_shader_path(path)),
+            ShaderSource::Text { name, .. } => (&DiskFiles, normalize_path(name)),
+            ShaderSource::Virtual { files, path } => (*files, normalize_path(path)),
+        };
+        let text = match source {
+            ShaderSource::Text { text, .. } => text.to_string(),
+            _ => files
+                .read(&root)
+                .ok_or_else(|| error().with_message(format!("could not read {}", root)))?,
+        };
+
+        let source_name = c_string(&source.name())?;
+        let entry_point = c_string(&options.entry_point)?;
+        let profile = c_string(&options.profile)?;
+
+        let defines = options
+            .defines
+            .iter()
+            .map(|(name, value)| Ok((c_string(name)?, c_string(value)?)))
+            .collect::<std::result::Result<Vec<_>, CompileError>>()?;
+        let macros: Vec<D3D_SHADER_MACRO> = defines
+            .iter()
+            .map(|(name, value)| D3D_SHADER_MACRO {
+                Name: PCSTR(name.as_ptr() as _),
+                Definition: PCSTR(value.as_ptr() as _),
+            })
+            .chain(std::iter::once(D3D_SHADER_MACRO::default()))
+            .collect();
+
+        let handler = IncludeHandler {
+            resolver: IncludeResolver::new(files, &options.include_dirs),
+            root,
+            open_files: RefCell::new(HashMap::new()),
+        };
+        let include = ID3DInclude::new(&handler);
+
+        let mut code = None;
+        let mut messages = None;
+        let result = unsafe {
+            D3DCompile(
+                text.as_ptr() as _,
+                text.len(),
+                PCSTR(source_name.as_ptr() as _),
+                Some(macros.as_ptr()),
+                &*include,
+                PCSTR(entry_point.as_ptr() as _),
+                PCSTR(profile.as_ptr() as _),
+                compile_flags(options),
+                0,
+                &mut code,
+                Some(&mut messages),
+            )
+        };
+
+        let output = messages
+            .map(|blob| {
+                String::from_utf8_lossy(blob_bytes(&blob))
+                    .trim_end_matches('\0')
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        match (result, code) {
+            (Ok(()), Some(code)) => Ok(CompiledShader {
+                bytecode: blob_bytes(&code).to_vec(),
+                diagnostics: parse_diagnostics(&output),
+            }),
+            (Ok(()), None) => Err(error().with_message("no bytecode was produced")),
+            (Err(e), _) => {
+                if output.is_empty() {
+                    Err(error().with_message(e.message()))
+                } else {
+                    Err(error().with_output(&output))
+                }
+            }
+        }
+    }
+}
+
+/// Hands the compiler the files found by an `IncludeResolver`.
+struct IncludeHandler<'a> {
+    resolver: IncludeResolver<'a>,
+    root: String,
+    // The contents of each file handed to the compiler, and its path, keyed by
+    // the address the compiler sees.
+    open_files: RefCell<HashMap<usize, (Vec<u8>, String)>>,
+}
+
+impl ID3DInclude_Impl for IncludeHandler<'_> {
+    fn Open(
+        &self,
+        include_type: D3D_INCLUDE_TYPE,
+        file_name: &PCSTR,
+        parent_data: *const core::ffi::c_void,
+        data: *mut *mut core::ffi::c_void,
+        bytes: *mut u32,
+    ) -> Result<()> {
+        let file_name = unsafe { file_name.to_string() }.map_err(|_| Error::from(E_INVALIDARG))?;
+
+        let parent = self
+            .open_files
+            .borrow()
+            .get(&(parent_data as usize))
+            .map(|(_, path)| path.clone())
+            .unwrap_or_else(|| self.root.clone());
+
+        let kind = if include_type == D3D_INCLUDE_LOCAL {
+            IncludeKind::Local
+        } else {
+            IncludeKind::System
+        };
+
+        let (path, text) = self
+            .resolver
+            .resolve(kind, &file_name, &parent)
+            .ok_or_else(|| Error::from(E_FAIL))?;
+
+        let contents = text.into_bytes();
+        unsafe {
+            *data = contents.as_ptr() as _;
+            *bytes = contents.len() as u32;
+        }
+        self.open_files
+            .borrow_mut()
+            .insert(contents.as_ptr() as usize, (contents, path));
+        Ok(())
+    }
+
+    fn Close(&self, data: *const core::ffi::c_void) -> Result<()> {
+        self.open_files.borrow_mut().remove(&(data as usize));
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use super::*;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+};
+
+/// Identifies one variant of a shader by the defines it is compiled with.
+/// Implementing this on a small struct or enum means variants are looked up by
+/// meaning rather than by strings:
+///
+/// ```ignore
+/// #[derive(Clone, PartialEq, Eq, Hash)]
+/// struct Key { shadow_pass: bool, num_lights: u32 }
+///
+/// impl PermutationKey for Key {
+///     fn defines(&self) -> Vec<(String, String)> {
+///         let mut defines = vec![("NUM_LIGHTS".into(), self.num_lights.to_string())];
+///         if self.shadow_pass {
+///             defines.push(("SHADOW_PASS".into(), "1".into()));
+///         }
+///         defines
+///     }
+/// }
+/// ```
+pub trait PermutationKey: Clone + Eq + Hash {
+    fn defines(&self) -> Vec<(String, String)>;
+}
+
+/// A key for when the defines are only known at run time, e.g. from a config
+/// file. Defines are kept sorted so that the order they were added in does not
+/// matter.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DefineSet(BTreeMap<String, String>);
+
+impl DefineSet {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Defines `name` as `1`, for `#ifdef` style toggles.
+    pub fn flag(self, name: &str) -> Self {
+        self.define(name, "1")
+    }
+}
+
+impl PermutationKey for DefineSet {
+    fn defines(&self) -> Vec<(String, String)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// The variants of one entry point in one source, compiled on demand. Defines
+/// that do not affect the output (for example a toggle only the pixel shader
+/// looks at, when compiling the vertex shader) lead to identical bytecode,
+/// which is only stored once.
+///
+/// Debug builds embed the defines in the debug info, so there is less sharing
+/// when `CompileOptions::debug` is set.
+pub struct ShaderPermutations<'a, K> {
+    compiler: &'a dyn ShaderCompiler,
+    source: ShaderSource<'a>,
+    options: CompileOptions,
+    variants: HashMap<K, usize>,
+    shaders: Vec<CompiledShader>,
+    shaders_by_hash: HashMap<u64, Vec<usize>>,
+}
+
+impl<'a, K: PermutationKey> ShaderPermutations<'a, K> {
+    /// `options` applies to every variant; each key's defines are added to
+    /// its defines.
+    pub fn new(
+        compiler: &'a dyn ShaderCompiler,
+        source: ShaderSource<'a>,
+        options: CompileOptions,
+    ) -> Self {
+        ShaderPermutations {
+            compiler,
+            source,
+            options,
+            variants: HashMap::new(),
+            shaders: Vec::new(),
+            shaders_by_hash: HashMap::new(),
+        }
+    }
+
+    /// Returns the variant for `key`, compiling it if this is the first time
+    /// it has been asked for.
+    pub fn compile(&mut self, key: &K) -> Result<&CompiledShader, CompileError> {
+        if let Some(&index) = self.variants.get(key) {
+            return Ok(&self.shaders[index]);
+        }
+
+        let mut options = self.options.clone();
+        options.defines.extend(key.defines());
+        let shader = self.compiler.compile(&self.source, &options)?;
+
+        let index = self.insert(shader);
+        self.variants.insert(key.clone(), index);
+        Ok(&self.shaders[index])
+    }
+
+    /// Compiles every variant up front, so that errors are found at startup
+    /// rather than the first time a feature is turned on.
+    pub fn compile_all<'k, I>(&mut self, keys: I) -> Result<(), CompileError>
+    where
+        I: IntoIterator<Item = &'k K>,
+        K: 'k,
+    {
+        for key in keys {
+            self.compile(key)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &K) -> Option<&CompiledShader> {
+        self.index(key).map(|index| &self.shaders[index])
+    }
+
+    /// Keys that compiled to the same bytecode have the same index, so it can
+    /// be used to share pipeline states between them too.
+    pub fn index(&self, key: &K) -> Option<usize> {
+        self.variants.get(key).copied()
+    }
+
+    /// The number of variants compiled so far.
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// The distinct shaders, indexed by `index`.
+    pub fn shaders(&self) -> &[CompiledShader] {
+        &self.shaders
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.variants.keys()
+    }
+
+    fn insert(&mut self, shader: CompiledShader) -> usize {
+        let mut hasher = DefaultHasher::new();
+        shader.bytecode.hash(&mut hasher);
+        let shaders = &mut self.shaders;
+        let candidates = self.shaders_by_hash.entry(hasher.finish()).or_default();
+
+        if let Some(&index) = candidates
+            .iter()
+            .find(|&&index| shaders[index].bytecode == shader.bytecode)
+        {
+            return index;
+        }
+
+        let index = shaders.len();
+        candidates.push(index);
+        shaders.push(shader);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Compiles each variant to the text of its defines, and remembers the
+    /// defines it was asked to compile.
+    #[derive(Default)]
+    struct EchoCompiler {
+        compiled: RefCell<Vec<Vec<(String, String)>>>,
+    }
+
+    impl ShaderCompiler for EchoCompiler {
+        fn identity(&self) -> String {
+            "echo".to_string()
+        }
+
+        fn compile(
+            &self,
+            _source: &ShaderSource,
+            options: &CompileOptions,
+        ) -> Result<CompiledShader, CompileError> {
+            self.compiled.borrow_mut().push(options.defines.clone());
+            Ok(CompiledShader {
+                bytecode: format!("{:?}", options.defines).into_bytes(),
+                diagnostics: Vec::new(),
+            })
+        }
+    }
+
+    fn define(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn define_sets_are_keyed_by_their_contents() {
+        let compiler = EchoCompiler::default();
+        let source = ShaderSource::Text {
+            name: "scene.hlsl",
+            text: "",
+        };
+        let mut permutations =
+            ShaderPermutations::new(&compiler, source, CompileOptions::new("main", "ps_5_0"));
+
+        let keys = [
+            DefineSet::new().flag("SHADOWS").define("NUM_LIGHTS", "4"),
+            // The same defines in another order.
+            DefineSet::new().define("NUM_LIGHTS", "4").flag("SHADOWS"),
+            // And with duplicates.
+            DefineSet::new()
+                .flag("SHADOWS")
+                .define("NUM_LIGHTS", "4")
+                .flag("SHADOWS"),
+            // A define given twice keeps its last value.
+            DefineSet::new()
+                .define("NUM_LIGHTS", "2")
+                .flag("SHADOWS")
+                .define("NUM_LIGHTS", "4"),
+        ];
+        permutations.compile_all(&keys).unwrap();
+
+        assert_eq!(permutations.len(), 1);
+        for key in &keys {
+            assert_eq!(permutations.index(key), Some(0));
+        }
+        assert_eq!(
+            *compiler.compiled.borrow(),
+            [vec![define("NUM_LIGHTS", "4"), define("SHADOWS", "1")]]
+        );
+
+        let other = DefineSet::new().define("NUM_LIGHTS", "2").flag("SHADOWS");
+        permutations.compile(&other).unwrap();
+        assert_eq!(permutations.len(), 2);
+        assert_eq!(permutations.index(&other), Some(1));
+    }
+}
@@ -0,0 +1,430 @@
+use super::*;
+use crate::cache::ContentHash;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PipelineId(usize);
+
+/// What one `ShaderReloader::poll` did.
+#[derive(Debug, Clone, Default)]
+pub struct Reload {
+    /// Shaders that compiled; `ShaderReloader::shader` now returns the new
+    /// bytecode.
+    pub recompiled: Vec<ShaderId>,
+    /// Shaders that failed to compile. They keep their previous bytecode.
+    pub failed: Vec<(ShaderId, CompileError)>,
+    /// Pipelines to rebuild: at least one of their shaders was recompiled, and
+    /// none of them is currently failing.
+    pub pipelines: Vec<PipelineId>,
+}
+
+impl Reload {
+    pub fn is_empty(&self) -> bool {
+        self.recompiled.is_empty() && self.failed.is_empty()
+    }
+}
+
+struct TrackedShader {
+    path: String,
+    options: CompileOptions,
+    /// Every file the shader was built from, including itself, and the paths
+    /// its missing includes were looked for at.
+    dependencies: Vec<String>,
+    shader: CompiledShader,
+    error: Option<CompileError>,
+}
+
+/// Recompiles shaders when their source, or anything they include, changes,
+/// and works out which pipelines have to be rebuilt as a result.
+///
+/// Changes are found by comparing file contents rather than timestamps, so
+/// this works the same with any `ShaderFileSystem`. Pipelines themselves are
+/// left to the caller, which only needs to recreate the ones `poll` lists.
+pub struct ShaderReloader {
+    files: Box<dyn ShaderFileSystem>,
+    compiler: Box<dyn ShaderCompiler>,
+    shaders: Vec<TrackedShader>,
+    pipelines: Vec<Vec<ShaderId>>,
+    file_hashes: HashMap<String, Option<ContentHash>>,
+}
+
+fn hash_file(files: &dyn ShaderFileSystem, path: &str) -> Option<ContentHash> {
+    files
+        .read(path)
+        .map(|text| ContentHash::of(text.as_bytes()))
+}
+
+impl ShaderReloader {
+    pub fn new<F, C>(files: F, compiler: C) -> Self
+    where
+        F: ShaderFileSystem + 'static,
+        C: ShaderCompiler + 'static,
+    {
+        ShaderReloader {
+            files: Box::new(files),
+            compiler: Box::new(compiler),
+            shaders: Vec::new(),
+            pipelines: Vec::new(),
+            file_hashes: HashMap::new(),
+        }
+    }
+
+    /// Compiles the shader straight away. Unlike later recompiles, a failure
+    /// here is returned, since there is no previous version to fall back on.
+    pub fn add_shader(
+        &mut self,
+        path: &str,
+        options: CompileOptions,
+    ) -> Result<ShaderId, CompileError> {
+        let path = normalize_path(path);
+        let shader = self.compiler.compile(
+            &ShaderSource::Virtual {
+                files: &*self.files,
+                path: &path,
+            },
+            &options,
+        )?;
+
+        let dependencies = self.track(&path, &options);
+        self.shaders.push(TrackedShader {
+            path,
+            options,
+            dependencies,
+            shader,
+            error: None,
+        });
+        Ok(ShaderId(self.shaders.len() - 1))
+    }
+
+    pub fn add_pipeline(&mut self, shaders: &[ShaderId]) -> PipelineId {
+        self.pipelines.push(shaders.to_vec());
+        PipelineId(self.pipelines.len() - 1)
+    }
+
+    /// The most recent bytecode that compiled.
+    pub fn shader(&self, id: ShaderId) -> &CompiledShader {
+        &self.shaders[id.0].shader
+    }
+
+    /// Why the shader's latest source does not compile, if it does not.
+    pub fn error(&self, id: ShaderId) -> Option<&CompileError> {
+        self.shaders[id.0].error.as_ref()
+    }
+
+    pub fn pipeline_shaders(&self, id: PipelineId) -> &[ShaderId] {
+        &self.pipelines[id.0]
+    }
+
+    pub fn shaders_using(&self, path: &str) -> Vec<ShaderId> {
+        let path = normalize_path(path);
+        (0..self.shaders.len())
+            .filter(|&index| self.shaders[index].dependencies.contains(&path))
+            .map(ShaderId)
+            .collect()
+    }
+
+    pub fn pipelines_using(&self, shader: ShaderId) -> Vec<PipelineId> {
+        (0..self.pipelines.len())
+            .filter(|&index| self.pipelines[index].contains(&shader))
+            .map(PipelineId)
+            .collect()
+    }
+
+    pub fn tracked_files(&self) -> impl Iterator<Item = &str> {
+        self.file_hashes.keys().map(String::as_str)
+    }
+
+    /// Checks every tracked file, and recompiles the shaders that depend on
+    /// the ones that changed.
+    pub fn poll(&mut self) -> Reload {
+        let files = &*self.files;
+        let changed: Vec<String> = self
+            .file_hashes
+            .iter_mut()
+            .filter_map(|(path, hash)| {
+                let current = hash_file(files, path);
+                if current == *hash {
+                    None
+                } else {
+                    *hash = current;
+                    Some(path.clone())
+                }
+            })
+            .collect();
+
+        let mut reload = Reload::default();
+        if changed.is_empty() {
+            return reload;
+        }
+
+        for index in 0..self.shaders.len() {
+            let tracked = &self.shaders[index];
+            if !tracked.dependencies.iter().any(|d| changed.contains(d)) {
+                continue;
+            }
+
+            let (path, options) = (tracked.path.clone(), tracked.options.clone());
+            let result = self.compiler.compile(
+                &ShaderSource::Virtual {
+                    files: &*self.files,
+                    path: &path,
+                },
+                &options,
+            );
+
+            // Includes may have been added or removed whether or not the
+            // shader compiled.
+            let dependencies = self.track(&path, &options);
+            let tracked = &mut self.shaders[index];
+            tracked.dependencies = dependencies;
+
+            match result {
+                Ok(shader) => {
+                    tracked.shader = shader;
+                    tracked.error = None;
+                    reload.recompiled.push(ShaderId(index));
+                }
+                Err(error) => {
+                    tracked.error = Some(error.clone());
+                    reload.failed.push((ShaderId(index), error));
+                }
+            }
+        }
+
+        reload.pipelines = (0..self.pipelines.len())
+            .filter(|&index| {
+                let shaders = &self.pipelines[index];
+                shaders.iter().any(|id| reload.recompiled.contains(id))
+                    && shaders.iter().all(|id| self.shaders[id.0].error.is_none())
+            })
+            .map(PipelineId)
+            .collect();
+
+        reload
+    }
+
+    /// Starts watching everything `path` includes, returning the list. Paths
+    /// that missing includes could be at are watched too, so that creating
+    /// one retries the compile.
+    fn track(&mut self, path: &str, options: &CompileOptions) -> Vec<String> {
+        let files = &*self.files;
+        let Dependencies {
+            files: mut dependencies,
+            missing,
+            ..
+        } = IncludeResolver::new(files, &options.include_dirs).dependencies(path);
+        dependencies.extend(missing);
+
+        for file in &dependencies {
+            self.file_hashes
+                .entry(file.clone())
+                .or_insert_with(|| hash_file(files, file));
+        }
+        dependencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for a real compiler: the "bytecode" is the source with its
+    /// includes pasted in, and any line starting with `error` fails.
+    struct FakeCompiler;
+
+    impl ShaderCompiler for FakeCompiler {
+        fn identity(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn compile(
+            &self,
+            source: &ShaderSource,
+            options: &CompileOptions,
+        ) -> Result<CompiledShader, CompileError> {
+            let error = || CompileError::new(source, options);
+            let (files, path) = match source {
+                ShaderSource::Virtual { files, path } => (*files, *path),
+                _ => return Err(error().with_message("not virtual")),
+            };
+
+            let resolver = IncludeResolver::new(files, &options.include_dirs);
+            let dependencies = resolver.dependencies(path);
+            if let Some(name) = dependencies.unresolved.first() {
+                return Err(error().with_output(&format!(
+                    "{}(1,10): error X1507: failed to open source file: '{}'",
+                    path, name
+                )));
+            }
+
+            let mut bytecode = Vec::new();
+            for file in &dependencies.files {
+                let text = files.read(file).unwrap();
+                if text.lines().any(|line| line.starts_with("error")) {
+                    return Err(
+                        error().with_output(&format!("{}(1,1): error X3000: syntax error", file))
+                    );
+                }
+                bytecode.extend_from_slice(text.as_bytes());
+            }
+            Ok(CompiledShader {
+                bytecode,
+                diagnostics: Vec::new(),
+            })
+        }
+    }
+
+    fn options() -> CompileOptions {
+        CompileOptions::new("main", "ps_5_0").include_dir("include")
+    }
+
+    fn bytecode(reloader: &ShaderReloader, id: ShaderId) -> &str {
+        std::str::from_utf8(&reloader.shader(id).bytecode).unwrap()
+    }
+
+    #[test]
+    fn unchanged_files_do_nothing() {
+        let files = MemoryFiles::new();
+        files.write("shaders/scene.hlsl", "scene");
+        let mut reloader = ShaderReloader::new(files.clone(), FakeCompiler);
+        reloader
+            .add_shader("shaders/scene.hlsl", options())
+            .unwrap();
+
+        assert!(reloader.poll().is_empty());
+        files.write("shaders/scene.hlsl", "scene");
+        assert!(reloader.poll().is_empty());
+    }
+
+    #[test]
+    fn first_compile_errors_are_returned() {
+        let files = MemoryFiles::new();
+        files.write("shaders/broken.hlsl", "error");
+        let mut reloader = ShaderReloader::new(files, FakeCompiler);
+
+        let error = reloader
+            .add_shader("shaders/broken.hlsl", options())
+            .unwrap_err();
+        assert_eq!(
+            error.errors().next().unwrap().code.as_deref(),
+            Some("X3000")
+        );
+    }
+
+    #[test]
+    fn include_changes_recompile_their_users() {
+        let files = MemoryFiles::new();
+        files.write("shaders/vs.hlsl", "#include \"common.hlsli\"\nvs");
+        files.write("shaders/ps.hlsl", "#include <lighting.hlsli>\nps");
+        files.write("shaders/common.hlsli", "common");
+        files.write("include/lighting.hlsli", "lighting");
+
+        let mut reloader = ShaderReloader::new(files.clone(), FakeCompiler);
+        let vs = reloader.add_shader("shaders/vs.hlsl", options()).unwrap();
+        let ps = reloader.add_shader("shaders/ps.hlsl", options()).unwrap();
+        let both = reloader.add_pipeline(&[vs, ps]);
+        let ps_only = reloader.add_pipeline(&[ps]);
+
+        assert_eq!(reloader.shaders_using("shaders/common.hlsli"), [vs]);
+        assert_eq!(reloader.shaders_using(r"include\lighting.hlsli"), [ps]);
+        assert_eq!(reloader.pipelines_using(ps), [both, ps_only]);
+
+        files.write("shaders/common.hlsli", "common v2");
+        let reload = reloader.poll();
+        assert_eq!(reload.recompiled, [vs]);
+        assert_eq!(reload.pipelines, [both]);
+        assert!(bytecode(&reloader, vs).contains("common v2"));
+
+        files.write("include/lighting.hlsli", "lighting v2");
+        let reload = reloader.poll();
+        assert_eq!(reload.recompiled, [ps]);
+        assert_eq!(reload.pipelines, [both, ps_only]);
+    }
+
+    #[test]
+    fn failures_keep_the_previous_bytecode_and_hold_back_pipelines() {
+        let files = MemoryFiles::new();
+        files.write("shaders/vs.hlsl", "vs");
+        files.write("shaders/ps.hlsl", "ps");
+
+        let mut reloader = ShaderReloader::new(files.clone(), FakeCompiler);
+        let vs = reloader.add_shader("shaders/vs.hlsl", options()).unwrap();
+        let ps = reloader.add_shader("shaders/ps.hlsl", options()).unwrap();
+        let pipeline = reloader.add_pipeline(&[vs, ps]);
+
+        files.write("shaders/ps.hlsl", "error");
+        let reload = reloader.poll();
+        assert_eq!(reload.failed.len(), 1);
+        assert_eq!(reload.failed[0].0, ps);
+        assert!(reload.pipelines.is_empty());
+        assert_eq!(bytecode(&reloader, ps), "ps");
+        assert!(reloader.error(ps).is_some());
+
+        // The pipeline still has a failing shader, so it isn't rebuilt.
+        files.write("shaders/vs.hlsl", "vs v2");
+        let reload = reloader.poll();
+        assert_eq!(reload.recompiled, [vs]);
+        assert!(reload.pipelines.is_empty());
+
+        files.write("shaders/ps.hlsl", "ps v2");
+        let reload = reloader.poll();
+        assert_eq!(reload.recompiled, [ps]);
+        assert_eq!(reload.pipelines, [pipeline]);
+        assert!(reloader.error(ps).is_none());
+    }
+
+    #[test]
+    fn added_and_removed_includes_are_tracked() {
+        let files = MemoryFiles::new();
+        files.write("shaders/ps.hlsl", "ps");
+        files.write("shaders/extra.hlsli", "extra");
+
+        let mut reloader = ShaderReloader::new(files.clone(), FakeCompiler);
+        let ps = reloader.add_shader("shaders/ps.hlsl", options()).unwrap();
+        assert!(reloader.shaders_using("shaders/extra.hlsli").is_empty());
+
+        files.write("shaders/ps.hlsl", "#include \"extra.hlsli\"\nps");
+        assert_eq!(reloader.poll().recompiled, [ps]);
+        assert_eq!(reloader.shaders_using("shaders/extra.hlsli"), [ps]);
+
+        files.write("shaders/extra.hlsli", "extra v2");
+        assert_eq!(reloader.poll().recompiled, [ps]);
+
+        files.write("shaders/ps.hlsl", "ps");
+        assert_eq!(reloader.poll().recompiled, [ps]);
+        assert!(reloader.shaders_using("shaders/extra.hlsli").is_empty());
+    }
+
+    #[test]
+    fn creating_a_missing_include_recompiles() {
+        let files = MemoryFiles::new();
+        files.write("shaders/ps.hlsl", "ps");
+
+        let mut reloader = ShaderReloader::new(files.clone(), FakeCompiler);
+        let ps = reloader.add_shader("shaders/ps.hlsl", options()).unwrap();
+
+        files.write("shaders/ps.hlsl", "#include \"shadows.hlsli\"\nps");
+        let reload = reloader.poll();
+        assert_eq!(reload.failed.len(), 1);
+        assert_eq!(
+            reload.failed[0].1.errors().next().unwrap().code.as_deref(),
+            Some("X1507")
+        );
+        assert!(reloader.poll().is_empty());
+
+        // Either place the include could be found counts.
+        files.write("include/shadows.hlsli", "shadows");
+        let reload = reloader.poll();
+        assert_eq!(reload.recompiled, [ps]);
+        assert!(bytecode(&reloader, ps).contains("shadows"));
+
+        // System includes are only looked for in the include directories.
+        files.write("shaders/ps.hlsl", "#include <missing.hlsli>\nps");
+        assert_eq!(reloader.poll().failed.len(), 1);
+        files.write("include/missing.hlsli", "found");
+        assert_eq!(reloader.poll().recompiled, [ps]);
+    }
+}
@@ -0,0 +1,545 @@
+//! Texture data on the CPU: how formats lay out their texels, and surfaces
+//! held in memory ready to be uploaded.
+
+use crate::format::*;
+use std::{
+    borrow::Cow,
+    io::{self, Read, Seek, SeekFrom},
+};
+#[cfg(windows)]
+use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::DXGI_SAMPLE_DESC};
+
+/// How a format packs texels. Block-compressed formats store 4x4 blocks;
+/// everything else is treated as 1x1 blocks of one texel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatLayout {
+    pub block_width: u32,
+    pub block_height: u32,
+    pub bytes_per_block: u32,
+}
+
+impl FormatLayout {
+    /// Returns `None` for formats with no simple layout, such as planar video
+    /// formats, and for `DXGI_FORMAT_UNKNOWN`.
+    pub fn of(format: DXGI_FORMAT) -> Option<Self> {
+        let texel = |bytes| FormatLayout {
+            block_width: 1,
+            block_height: 1,
+            bytes_per_block: bytes,
+        };
+        let block = |bytes| FormatLayout {
+            block_width: 4,
+            block_height: 4,
+            bytes_per_block: bytes,
+        };
+
+        Some(match format {
+            DXGI_FORMAT_R32G32B32A32_TYPELESS
+            | DXGI_FORMAT_R32G32B32A32_FLOAT
+            | DXGI_FORMAT_R32G32B32A32_UINT
+            | DXGI_FORMAT_R32G32B32A32_SINT => texel(16),
+
+            DXGI_FORMAT_R32G32B32_TYPELESS
+            | DXGI_FORMAT_R32G32B32_FLOAT
+            | DXGI_FORMAT_R32G32B32_UINT
+            | DXGI_FORMAT_R32G32B32_SINT => texel(12),
+
+            DXGI_FORMAT_R16G16B16A16_TYPELESS
+            | DXGI_FORMAT_R16G16B16A16_FLOAT
+            | DXGI_FORMAT_R16G16B16A16_UNORM
+            | DXGI_FORMAT_R16G16B16A16_UINT
+            | DXGI_FORMAT_R16G16B16A16_SNORM
+            | DXGI_FORMAT_R16G16B16A16_SINT
+            | DXGI_FORMAT_R32G32_TYPELESS
+            | DXGI_FORMAT_R32G32_FLOAT
+            | DXGI_FORMAT_R32G32_UINT
+            | DXGI_FORMAT_R32G32_SINT => texel(8),
+
+            DXGI_FORMAT_R10G10B10A2_TYPELESS
+            | DXGI_FORMAT_R10G10B10A2_UNORM
+            | DXGI_FORMAT_R10G10B10A2_UINT
+            | DXGI_FORMAT_R11G11B10_FLOAT
+            | DXGI_FORMAT_R8G8B8A8_TYPELESS
+            | DXGI_FORMAT_R8G8B8A8_UNORM
+            | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+            | DXGI_FORMAT_R8G8B8A8_UINT
+            | DXGI_FORMAT_R8G8B8A8_SNORM
+            | DXGI_FORMAT_R8G8B8A8_SINT
+            | DXGI_FORMAT_B8G8R8A8_TYPELESS
+            | DXGI_FORMAT_B8G8R8A8_UNORM
+            | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8X8_TYPELESS
+            | DXGI_FORMAT_B8G8R8X8_UNORM
+            | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB
+            | DXGI_FORMAT_R16G16_TYPELESS
+            | DXGI_FORMAT_R16G16_FLOAT
+            | DXGI_FORMAT_R16G16_UNORM
+            | DXGI_FORMAT_R16G16_UINT
+            | DXGI_FORMAT_R16G16_SNORM
+            | DXGI_FORMAT_R16G16_SINT
+            | DXGI_FORMAT_R32_TYPELESS
+            | DXGI_FORMAT_D32_FLOAT
+            | DXGI_FORMAT_R32_FLOAT
+            | DXGI_FORMAT_R32_UINT
+            | DXGI_FORMAT_R32_SINT
+            | DXGI_FORMAT_D24_UNORM_S8_UINT
+            | DXGI_FORMAT_R9G9B9E5_SHAREDEXP => texel(4),
+
+            DXGI_FORMAT_R8G8_TYPELESS
+            | DXGI_FORMAT_R8G8_UNORM
+            | DXGI_FORMAT_R8G8_UINT
+            | DXGI_FORMAT_R8G8_SNORM
+            | DXGI_FORMAT_R8G8_SINT
+            | DXGI_FORMAT_R16_TYPELESS
+            | DXGI_FORMAT_R16_FLOAT
+            | DXGI_FORMAT_D16_UNORM
+            | DXGI_FORMAT_R16_UNORM
+            | DXGI_FORMAT_R16_UINT
+            | DXGI_FORMAT_R16_SNORM
+            | DXGI_FORMAT_R16_SINT
+            | DXGI_FORMAT_B5G6R5_UNORM
+            | DXGI_FORMAT_B5G5R5A1_UNORM
+            | DXGI_FORMAT_B4G4R4A4_UNORM => texel(2),
+
+            DXGI_FORMAT_R8_TYPELESS
+            | DXGI_FORMAT_R8_UNORM
+            | DXGI_FORMAT_R8_UINT
+            | DXGI_FORMAT_R8_SNORM
+            | DXGI_FORMAT_R8_SINT
+            | DXGI_FORMAT_A8_UNORM => texel(1),
+
+            DXGI_FORMAT_BC1_TYPELESS
+            | DXGI_FORMAT_BC1_UNORM
+            | DXGI_FORMAT_BC1_UNORM_SRGB
+            | DXGI_FORMAT_BC4_TYPELESS
+            | DXGI_FORMAT_BC4_UNORM
+            | DXGI_FORMAT_BC4_SNORM => block(8),
+
+            DXGI_FORMAT_BC2_TYPELESS
+            | DXGI_FORMAT_BC2_UNORM
+            | DXGI_FORMAT_BC2_UNORM_SRGB
+            | DXGI_FORMAT_BC3_TYPELESS
+            | DXGI_FORMAT_BC3_UNORM
+            | DXGI_FORMAT_BC3_UNORM_SRGB
+            | DXGI_FORMAT_BC5_TYPELESS
+            | DXGI_FORMAT_BC5_UNORM
+            | DXGI_FORMAT_BC5_SNORM
+            | DXGI_FORMAT_BC6H_TYPELESS
+            | DXGI_FORMAT_BC6H_UF16
+            | DXGI_FORMAT_BC6H_SF16
+            | DXGI_FORMAT_BC7_TYPELESS
+            | DXGI_FORMAT_BC7_UNORM
+            | DXGI_FORMAT_BC7_UNORM_SRGB => block(16),
+
+            _ => return None,
+        })
+    }
+
+    pub fn is_block_compressed(&self) -> bool {
+        self.block_width > 1 || self.block_height > 1
+    }
+
+    /// The size of one row of blocks with no padding.
+    pub fn row_pitch(&self, width: u32) -> u64 {
+        width.div_ceil(self.block_width).max(1) as u64 * self.bytes_per_block as u64
+    }
+
+    /// The number of rows of blocks.
+    pub fn num_rows(&self, height: u32) -> u32 {
+        height.div_ceil(self.block_height).max(1)
+    }
+
+    pub fn surface_size(&self, width: u32, height: u32) -> u64 {
+        self.row_pitch(width) * self.num_rows(height) as u64
+    }
+}
+
+/// One mip level, with its rows of blocks packed together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subresource {
+    pub width: u32,
+    pub height: u32,
+    pub row_pitch: u64,
+    pub data: Vec<u8>,
+}
+
+impl Subresource {
+    pub fn num_rows(&self) -> usize {
+        if self.row_pitch == 0 {
+            0
+        } else {
+            self.data.len() / self.row_pitch as usize
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.data.chunks_exact(self.row_pitch.max(1) as usize)
+    }
+
+    /// Borrows the data as one subresource of a `TextureData`.
+    pub fn as_data(&self) -> SubresourceData<'_> {
+        SubresourceData {
+            data: Cow::Borrowed(&self.data),
+            row_pitch: self.row_pitch,
+            slice_pitch: self.data.len() as u64,
+        }
+    }
+}
+
+/// A 2D texture in memory, with its mip levels in order from the largest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Texture {
+    pub format: DXGI_FORMAT,
+    pub width: u32,
+    pub height: u32,
+    pub subresources: Vec<Subresource>,
+}
+
+impl Texture {
+    /// Reads a single surface stored at `offset` with `pitch` bytes between
+    /// the starts of its rows. Any padding at the end of each row is dropped.
+    pub fn read_2d<R: Read + Seek>(
+        reader: &mut R,
+        offset: u64,
+        format: DXGI_FORMAT,
+        width: u32,
+        height: u32,
+        pitch: u64,
+    ) -> io::Result<Self> {
+        Ok(Texture {
+            format,
+            width,
+            height,
+            subresources: vec![Subresource::read(
+                reader, offset, format, width, height, pitch,
+            )?],
+        })
+    }
+}
+
+impl Subresource {
+    /// As `Texture::read_2d`, for one mip level.
+    pub fn read<R: Read + Seek>(
+        reader: &mut R,
+        offset: u64,
+        format: DXGI_FORMAT,
+        width: u32,
+        height: u32,
+        pitch: u64,
+    ) -> io::Result<Self> {
+        let layout = FormatLayout::of(format).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported texture format {}", format.0),
+            )
+        })?;
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}x{} texture has no texels", width, height),
+            ));
+        }
+        let row_pitch = layout.row_pitch(width);
+        if pitch < row_pitch {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "pitch {} is smaller than a {} texel wide row ({} bytes)",
+                    pitch, width, row_pitch
+                ),
+            ));
+        }
+
+        // Check that the whole surface is there before making room for it, so
+        // that a bad size can't ask for more memory than the input could fill.
+        let num_rows = layout.num_rows(height) as u64;
+        let end = pitch
+            .checked_mul(num_rows - 1)
+            .and_then(|size| size.checked_add(row_pitch))
+            .and_then(|size| size.checked_add(offset));
+        let input_size = reader.seek(SeekFrom::End(0))?;
+        if !matches!(end, Some(end) if end <= input_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{} rows of {} bytes at offset {} run past the end of the input ({} bytes)",
+                    num_rows, pitch, offset, input_size
+                ),
+            ));
+        }
+
+        let mut data = vec![0; (row_pitch * num_rows) as usize];
+        reader.seek(SeekFrom::Start(offset))?;
+        if pitch == row_pitch {
+            reader.read_exact(&mut data)?;
+        } else {
+            for (i, row) in data.chunks_exact_mut(row_pitch as usize).enumerate() {
+                reader.seek(SeekFrom::Start(offset + i as u64 * pitch))?;
+                reader.read_exact(row)?;
+            }
+        }
+
+        Ok(Subresource {
+            width,
+            height,
+            row_pitch,
+            data,
+        })
+    }
+}
+
+/// One subresource of a `TextureData`, laid out as `D3D12_SUBRESOURCE_DATA`
+/// describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubresourceData<'a> {
+    pub data: Cow<'a, [u8]>,
+    /// From the start of one row of blocks to the next.
+    pub row_pitch: u64,
+    /// From the start of one depth slice to the next. For 1D and 2D textures
+    /// this is the size of the whole subresource.
+    pub slice_pitch: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureDimension {
+    Texture1D,
+    Texture2D,
+    Texture3D,
+}
+
+/// The parts of a `D3D12_RESOURCE_DESC` that a texture file decides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureDesc {
+    pub dimension: TextureDimension,
+    pub format: DXGI_FORMAT,
+    pub width: u32,
+    pub height: u32,
+    /// The depth of a 3D texture, or the number of array slices of any other.
+    pub depth_or_array_size: u16,
+    pub mip_levels: u16,
+}
+
+impl TextureDesc {
+    /// A single 2D texture with one mip.
+    pub fn tex2d(format: DXGI_FORMAT, width: u32, height: u32) -> Self {
+        TextureDesc {
+            dimension: TextureDimension::Texture2D,
+            format,
+            width,
+            height,
+            depth_or_array_size: 1,
+            mip_levels: 1,
+        }
+    }
+}
+
+/// With one sample per texel, no flags, and the layout left to the driver.
+#[cfg(windows)]
+impl From<TextureDesc> for D3D12_RESOURCE_DESC {
+    fn from(desc: TextureDesc) -> Self {
+        D3D12_RESOURCE_DESC {
+            Dimension: match desc.dimension {
+                TextureDimension::Texture1D => D3D12_RESOURCE_DIMENSION_TEXTURE1D,
+                TextureDimension::Texture2D => D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+                TextureDimension::Texture3D => D3D12_RESOURCE_DIMENSION_TEXTURE3D,
+            },
+            Alignment: 0,
+            Width: desc.width as u64,
+            Height: desc.height,
+            DepthOrArraySize: desc.depth_or_array_size,
+            MipLevels: desc.mip_levels,
+            Format: desc.format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: D3D12_RESOURCE_FLAG_NONE,
+        }
+    }
+}
+
+/// A texture of any dimension as a file holds it: how to create the
+/// resource, and the data for every subresource in the order D3D12 numbers
+/// them, which is each mip of the first array slice, then each mip of the
+/// next.
+#[derive(Debug, Clone)]
+pub struct TextureData<'a> {
+    pub desc: TextureDesc,
+    /// The array slices are the faces of cubes, six to a cube, in the order
+    /// +X, -X, +Y, -Y, +Z, -Z.
+    pub is_cube: bool,
+    pub subresources: Vec<SubresourceData<'a>>,
+}
+
+impl<'a> TextureData<'a> {
+    /// Reads a DDS or a KTX2 file, whichever `bytes` holds.
+    pub fn read(bytes: &'a [u8]) -> io::Result<Self> {
+        if bytes.starts_with(crate::dds::DDS_MAGIC) {
+            Ok(crate::dds::read(bytes)?)
+        } else if bytes.starts_with(crate::ktx2::KTX2_MAGIC) {
+            Ok(crate::ktx2::read(bytes)?)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a DDS or KTX2 file",
+            ))
+        }
+    }
+
+    pub fn array_size(&self) -> u32 {
+        if self.desc.dimension == TextureDimension::Texture3D {
+            1
+        } else {
+            self.desc.depth_or_array_size as u32
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        if self.desc.dimension == TextureDimension::Texture3D {
+            self.desc.depth_or_array_size as u32
+        } else {
+            1
+        }
+    }
+
+    pub fn subresource(&self, mip: u32, array_slice: u32) -> Option<&SubresourceData<'a>> {
+        if mip >= self.desc.mip_levels as u32 {
+            return None;
+        }
+        self.subresources
+            .get((array_slice * self.desc.mip_levels as u32 + mip) as usize)
+    }
+
+    pub fn into_owned(self) -> TextureData<'static> {
+        TextureData {
+            desc: self.desc,
+            is_cube: self.is_cube,
+            subresources: self
+                .subresources
+                .into_iter()
+                .map(|subresource| SubresourceData {
+                    data: Cow::Owned(subresource.data.into_owned()),
+                    row_pitch: subresource.row_pitch,
+                    slice_pitch: subresource.slice_pitch,
+                })
+                .collect(),
+        }
+    }
+
+    /// Copies the mips of the first array slice of a 2D texture into a
+    /// `Texture`, dropping any padding at the ends of rows. Returns `None` for
+    /// other dimensions.
+    pub fn to_texture(&self) -> Option<Texture> {
+        if self.desc.dimension != TextureDimension::Texture2D {
+            return None;
+        }
+        let layout = FormatLayout::of(self.desc.format)?;
+        let width = self.desc.width;
+        let height = self.desc.height;
+
+        let subresources = (0..self.desc.mip_levels as u32)
+            .map(|mip| {
+                let source = self.subresource(mip, 0)?;
+                let (width, height) = (mip_size(width, mip), mip_size(height, mip));
+                let row_pitch = layout.row_pitch(width);
+                let mut data = Vec::with_capacity(layout.surface_size(width, height) as usize);
+                for row in 0..layout.num_rows(height) as u64 {
+                    let start = (row * source.row_pitch) as usize;
+                    data.extend_from_slice(source.data.get(start..start + row_pitch as usize)?);
+                }
+                Some(Subresource {
+                    width,
+                    height,
+                    row_pitch,
+                    data,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Texture {
+            format: self.desc.format,
+            width,
+            height,
+            subresources,
+        })
+    }
+}
+
+/// A 2D texture with one array slice, and a mip for each subresource.
+impl From<Texture> for TextureData<'static> {
+    fn from(texture: Texture) -> Self {
+        TextureData {
+            desc: TextureDesc {
+                mip_levels: texture.subresources.len() as u16,
+                ..TextureDesc::tex2d(texture.format, texture.width, texture.height)
+            },
+            is_cube: false,
+            subresources: texture
+                .subresources
+                .into_iter()
+                .map(|subresource| SubresourceData {
+                    slice_pitch: subresource.data.len() as u64,
+                    row_pitch: subresource.row_pitch,
+                    data: Cow::Owned(subresource.data),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The size of mip `level` of a dimension that is `size` at the top level.
+pub fn mip_size(size: u32, level: u32) -> u32 {
+    size.checked_shr(level).unwrap_or(0).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_padded_rows() {
+        let file: Vec<u8> = (0..64).collect();
+        let texture =
+            Texture::read_2d(&mut Cursor::new(&file), 4, DXGI_FORMAT_R8G8_UNORM, 3, 3, 10).unwrap();
+        let subresource = &texture.subresources[0];
+        assert_eq!(subresource.row_pitch, 6);
+        assert_eq!(subresource.num_rows(), 3);
+        let rows: Vec<&[u8]> = subresource.rows().collect();
+        assert_eq!(rows, [&file[4..10], &file[14..20], &file[24..30]]);
+    }
+
+    #[test]
+    fn surfaces_must_fit_in_the_input() {
+        let file = vec![0; 64];
+        let read = |offset, width, height, pitch| {
+            Texture::read_2d(
+                &mut Cursor::new(&file),
+                offset,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+                width,
+                height,
+                pitch,
+            )
+        };
+
+        assert!(read(0, 4, 4, 16).is_ok());
+        // The last row doesn't need its padding.
+        assert!(read(0, 4, 3, 24).is_ok());
+        for (offset, width, height, pitch) in [
+            (1, 4, 4, 16),
+            (0, 4, 4, 17),
+            (0, u32::MAX, u32::MAX, u64::MAX),
+            (u64::MAX, 1, 1, 4),
+        ] {
+            let error = read(offset, width, height, pitch).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        for (width, height, pitch) in [(4, 1, 15), (0, 4, 16), (4, 0, 16), (0, 0, 0)] {
+            let error = read(0, width, height, pitch).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::ring_allocator::{RingAllocator, CONSTANT_BUFFER_ALIGNMENT};
+use crate::{HeapProperties, ResourceDesc};
+use windows::core::*;
+use windows::Win32::Graphics::Direct3D12::*;
+
+/// A buffer in an upload heap that stays mapped, with space handed out by a
+/// `RingAllocator`. Call `finish_frame` with the fence value each frame
+/// signals, and `retire` with the value the fence has reached before
+/// allocating for the next one.
+pub struct UploadHeap {
+    buffer: ID3D12Resource,
+    cpu_address: *mut u8,
+    gpu_address: u64,
+    allocator: RingAllocator,
+}
+
+/// Space in an `UploadHeap`, which stays valid until its frame is retired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadAllocation {
+    /// From the start of the heap's buffer, for copies from it.
+    pub offset: u64,
+    pub size: u64,
+    pub gpu_address: u64,
+    pub cpu_address: *mut u8,
+}
+
+impl UploadAllocation {
+    /// A view of the whole allocation as a constant buffer.
+    pub fn constant_buffer_view(&self) -> D3D12_CONSTANT_BUFFER_VIEW_DESC {
+        D3D12_CONSTANT_BUFFER_VIEW_DESC {
+            BufferLocation: self.gpu_address,
+            SizeInBytes: self.size as u32,
+        }
+    }
+}
+
+impl UploadHeap {
+    pub fn new(device: &ID3D12Device, capacity: u64) -> Result<Self> {
+        let mut buffer = None;
+        let buffer: ID3D12Resource = unsafe {
+            device.CreateCommittedResource(
+                &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
+                D3D12_HEAP_FLAG_NONE,
+                &D3D12_RESOURCE_DESC::buffer(capacity as usize),
+                D3D12_RESOURCE_STATE_GENERIC_READ,
+                None,
+                &mut buffer,
+            )
+        }
+        .and(Ok(buffer.unwrap()))?;
+
+        // The buffer stays mapped until it is released. The CPU never reads
+        // it.
+        let mut cpu_address = std::ptr::null_mut();
+        unsafe {
+            buffer.Map(
+                0,
+                Some(&D3D12_RANGE { Begin: 0, End: 0 }),
+                Some(&mut cpu_address),
+            )
+        }?;
+        let gpu_address = unsafe { buffer.GetGPUVirtualAddress() };
+
+        Ok(UploadHeap {
+            buffer,
+            cpu_address: cpu_address.cast(),
+            gpu_address,
+            allocator: RingAllocator::new(capacity),
+        })
+    }
+
+    pub fn buffer(&self) -> &ID3D12Resource {
+        &self.buffer
+    }
+
+    /// `size` bytes aligned to `alignment`, or `None` if the heap is full
+    /// until more frames are retired.
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<UploadAllocation> {
+        let offset = self.allocator.allocate(size, alignment)?;
+        Some(UploadAllocation {
+            offset,
+            size,
+            gpu_address: self.gpu_address + offset,
+            cpu_address: unsafe { self.cpu_address.add(offset as usize) },
+        })
+    }
+
+    /// Allocates space for `data` and copies it in.
+    pub fn upload(&mut self, data: &[u8], alignment: u64) -> Option<UploadAllocation> {
+        let allocation = self.allocate(data.len() as u64, alignment)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), allocation.cpu_address, data.len());
+        }
+        Some(allocation)
+    }
+
+    /// Allocates a constant buffer, rounded up to a whole number of 256 byte
+    /// blocks as constant buffer views need, and copies `constants` into it.
+    pub fn upload_constants<T: Copy>(&mut self, constants: &T) -> Option<UploadAllocation> {
+        let size = std::mem::size_of::<T>() as u64;
+        let allocation = self.allocate(
+            size.next_multiple_of(CONSTANT_BUFFER_ALIGNMENT),
+            CONSTANT_BUFFER_ALIGNMENT,
+        )?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                (constants as *const T).cast::<u8>(),
+                allocation.cpu_address,
+                size as usize,
+            );
+        }
+        Some(allocation)
+    }
+
+    /// See `RingAllocator::finish_frame`.
+    pub fn finish_frame(&mut self, fence_value: u64) {
+        self.allocator.finish_frame(fence_value);
+    }
+
+    /// See `RingAllocator::retire`.
+    pub fn retire(&mut self, completed_fence_value: u64) {
+        self.allocator.retire(completed_fence_value);
+    }
+}
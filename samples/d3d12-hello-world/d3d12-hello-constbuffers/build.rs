@@ -1,5 +0,0 @@
-use d3dx12::build::*;
-
-fn main() {
-    copy_data_file("src/hello-constbuffers-shaders.hlsl");
-}
@@ -1,3 +1,4 @@
+use d3dx12::shader::{CompileOptions, FxcCompiler, ShaderCompiler};
 use d3dx12::*;
 use dxsample::*;
 use std::convert::TryInto;
@@ -5,7 +6,7 @@ use windows::{
     core::*,
     Win32::{
         Foundation::*,
-        Graphics::{Direct3D::Fxc::*, Direct3D::*, Direct3D12::*, Dxgi::Common::*, Dxgi::*},
+        Graphics::{Direct3D::*, Direct3D12::*, Dxgi::Common::*, Dxgi::*},
     },
 };
 
@@ -37,11 +38,13 @@ mod d3d12_hello_constbuffers {
         pso: ID3D12PipelineState,
         command_list: ID3D12GraphicsCommandList,
         _vertex_buffer: ID3D12Resource,
-        constant_buffer: ConstantBuffer<SceneConstantBuffer>,
+        upload_heap: UploadHeap,
+        constants: SceneConstantBuffer,
         vbv: D3D12_VERTEX_BUFFER_VIEW,
     }
 
     #[repr(C, align(256))]
+    #[derive(Clone, Copy)]
     struct SceneConstantBuffer {
         offset: [f32; 4],
     }
@@ -52,52 +55,6 @@ mod d3d12_hello_constbuffers {
         0
     );
 
-    struct ConstantBuffer<T> {
-        pub resource: ID3D12Resource,
-        pub data: T,
-        mapped: *mut T,
-    }
-
-    impl<T> ConstantBuffer<T> {
-        pub fn new(device: &ID3D12Device, initial_data: T) -> Result<Self> {
-            let mut resource: Option<ID3D12Resource> = None;
-            unsafe {
-                device.CreateCommittedResource(
-                    &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
-                    D3D12_HEAP_FLAG_NONE,
-                    &D3D12_RESOURCE_DESC::buffer(std::mem::size_of::<T>()),
-                    D3D12_RESOURCE_STATE_GENERIC_READ,
-                    None,
-                    &mut resource,
-                )
-            }?;
-            let resource = resource.unwrap();
-
-            let mut mapped = std::ptr::null_mut();
-            unsafe {
-                // We're going to this mapped for the duration of the process.
-                resource.Map(0, None, Some(&mut mapped))?;
-            }
-
-            let mapped = mapped as *mut T;
-            unsafe {
-                std::ptr::copy_nonoverlapping(&initial_data, mapped, 1);
-            }
-
-            Ok(ConstantBuffer {
-                resource,
-                data: initial_data,
-                mapped,
-            })
-        }
-
-        pub fn update(&self) {
-            unsafe {
-                std::ptr::copy_nonoverlapping(&self.data, self.mapped, 1);
-            }
-        }
-    }
-
     impl DXSample for Sample {
         fn new(command_line: &SampleCommandLine) -> Result<Self> {
             let (dxgi_factory, device) = create_device(command_line)?;
@@ -161,11 +118,11 @@ mod d3d12_hello_constbuffers {
                     Ok(render_target)
                 })?;
 
-            let constant_buffer = ConstantBuffer::<SceneConstantBuffer>::new(
+            // Each frame's constants are uploaded as it is rendered, and the
+            // view is pointed at them.
+            let upload_heap = UploadHeap::new(
                 &self.device,
-                SceneConstantBuffer {
-                    offset: [0.0, 0.0, 0.0, 0.0],
-                },
+                (FRAME_COUNT * std::mem::size_of::<SceneConstantBuffer>()) as u64,
             )?;
 
             let cbv_heap = CbvSrvUavDescriptorHeap::new(
@@ -174,14 +131,6 @@ mod d3d12_hello_constbuffers {
                 D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
             )?;
 
-            unsafe {
-                cbv_heap.create_constant_buffer_view(
-                    &self.device,
-                    &D3D12_CONSTANT_BUFFER_VIEW_DESC::entire_resource(&constant_buffer.resource),
-                    0,
-                );
-            }
-
             let viewport = D3D12_VIEWPORT {
                 TopLeftX: 0.0,
                 TopLeftY: 0.0,
@@ -234,7 +183,10 @@ mod d3d12_hello_constbuffers {
                 pso,
                 command_list,
                 _vertex_buffer: vertex_buffer,
-                constant_buffer,
+                upload_heap,
+                constants: SceneConstantBuffer {
+                    offset: [0.0, 0.0, 0.0, 0.0],
+                },
                 vbv,
             });
 
@@ -250,12 +202,22 @@ mod d3d12_hello_constbuffers {
             const TRANSLATION_SPEED: f32 = 0.005;
             const OFFSET_BOUNDS: f32 = 1.25;
 
-            let offset = &mut resources.constant_buffer.data.offset;
+            let offset = &mut resources.constants.offset;
             offset[0] += TRANSLATION_SPEED;
             if offset[0] > OFFSET_BOUNDS {
                 offset[0] = -OFFSET_BOUNDS;
             }
-            resources.constant_buffer.update();
+            let constants = resources
+                .upload_heap
+                .upload_constants(&resources.constants)
+                .unwrap();
+            unsafe {
+                resources.cbv_heap.create_constant_buffer_view(
+                    &self.device,
+                    &constants.constant_buffer_view(),
+                    0,
+                );
+            }
 
             populate_command_list(resources).unwrap();
 
@@ -406,48 +368,20 @@ mod d3d12_hello_constbuffers {
         device: &ID3D12Device,
         root_signature: &ID3D12RootSignature,
     ) -> Result<ID3D12PipelineState> {
-        let compile_flags = if cfg!(debug_assertions) {
-            D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION
-        } else {
-            0
-        };
-
-        let exe_path = std::env::current_exe().ok().unwrap();
-        let asset_path = exe_path.parent().unwrap();
-        let shaders_hlsl_path = asset_path.join("hello-constbuffers-shaders.hlsl");
-        let shaders_hlsl: HSTRING = shaders_hlsl_path.to_str().unwrap().into();
+        let shaders = embed_shaders!("src", ["hello-constbuffers-shaders.hlsl"]);
+        let shaders_hlsl_path = "hello-constbuffers-shaders.hlsl";
 
-        let mut vertex_shader = None;
-        let vertex_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("VSMain"),
-                s!("vs_5_0"),
-                compile_flags,
-                0,
-                &mut vertex_shader,
-                None,
-            )
-        }
-        .and(Ok(vertex_shader.unwrap()))?;
+        let vertex_shader = FxcCompiler.compile_virtual(
+            &shaders,
+            shaders_hlsl_path,
+            &CompileOptions::new("VSMain", "vs_5_0"),
+        )?;
 
-        let mut pixel_shader = None;
-        let pixel_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("PSMain"),
-                s!("ps_5_0"),
-                compile_flags,
-                0,
-                &mut pixel_shader,
-                None,
-            )
-        }
-        .and(Ok(pixel_shader.unwrap()))?;
+        let pixel_shader = FxcCompiler.compile_virtual(
+            &shaders,
+            shaders_hlsl_path,
+            &CompileOptions::new("PSMain", "ps_5_0"),
+        )?;
 
         let mut input_element_descs: [D3D12_INPUT_ELEMENT_DESC; 2] = [
             D3D12_INPUT_ELEMENT_DESC {
@@ -476,8 +410,8 @@ mod d3d12_hello_constbuffers {
                 NumElements: input_element_descs.len() as u32,
             },
             pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
-            VS: D3D12_SHADER_BYTECODE::from_blob(&vertex_shader),
-            PS: D3D12_SHADER_BYTECODE::from_blob(&pixel_shader),
+            VS: D3D12_SHADER_BYTECODE::from_bytes(&vertex_shader.bytecode),
+            PS: D3D12_SHADER_BYTECODE::from_bytes(&pixel_shader.bytecode),
             RasterizerState: D3D12_RASTERIZER_DESC::reasonable_default(),
             BlendState: D3D12_BLEND_DESC::reasonable_default(),
             DepthStencilState: D3D12_DEPTH_STENCIL_DESC::default(),
@@ -561,7 +495,11 @@ mod d3d12_hello_constbuffers {
         // D3D12HelloFrameBuffering sample illustrates how to use fences for
         // efficient resource usage and to maximize GPU utilization.
 
-        resources.command_queue.signal_and_wait_for_gpu().unwrap();
+        let fence_value = resources.command_queue.enqueue_signal().unwrap();
+        resources.command_queue.wait_for_gpu(fence_value).unwrap();
+        resources.upload_heap.finish_frame(fence_value);
+        resources.upload_heap.retire(fence_value);
+
         resources.frame_index = unsafe { resources.swap_chain.GetCurrentBackBufferIndex() }
             .try_into()
             .unwrap();
@@ -1,10 +1,11 @@
+use d3dx12::shader::{CompileOptions, FxcCompiler, ShaderCompiler};
 use d3dx12::*;
 use dxsample::*;
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
-        Graphics::{Direct3D::Fxc::*, Direct3D::*, Direct3D12::*, Dxgi::Common::*, Dxgi::*},
+        Graphics::{Direct3D::*, Direct3D12::*, Dxgi::Common::*, Dxgi::*},
     },
 };
 
@@ -274,49 +275,20 @@ mod d3d12_hello_frame_buffering {
         device: &ID3D12Device,
         root_signature: &ID3D12RootSignature,
     ) -> Result<ID3D12PipelineState> {
-        let compile_flags = if cfg!(debug_assertions) {
-            D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION
-        } else {
-            0
-        };
-
-        let exe_path = std::env::current_exe().ok().unwrap();
-        let asset_path = exe_path.parent().unwrap();
-        let shaders_hlsl_path = asset_path.join("hello-frame-buffering-shaders.hlsl");
-        let shaders_hlsl = shaders_hlsl_path.to_str().unwrap();
-        let shaders_hlsl: HSTRING = shaders_hlsl.into();
+        let shaders = embed_shaders!("src", ["hello-frame-buffering-shaders.hlsl"]);
+        let shaders_hlsl_path = "hello-frame-buffering-shaders.hlsl";
 
-        let mut vertex_shader = None;
-        let vertex_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("VSMain"),
-                s!("vs_5_0"),
-                compile_flags,
-                0,
-                &mut vertex_shader,
-                None,
-            )
-        }
-        .and(Ok(vertex_shader.unwrap()))?;
+        let vertex_shader = FxcCompiler.compile_virtual(
+            &shaders,
+            shaders_hlsl_path,
+            &CompileOptions::new("VSMain", "vs_5_0"),
+        )?;
 
-        let mut pixel_shader = None;
-        let pixel_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("PSMain"),
-                s!("ps_5_0"),
-                compile_flags,
-                0,
-                &mut pixel_shader,
-                None,
-            )
-        }
-        .and(Ok(pixel_shader.unwrap()))?;
+        let pixel_shader = FxcCompiler.compile_virtual(
+            &shaders,
+            shaders_hlsl_path,
+            &CompileOptions::new("PSMain", "ps_5_0"),
+        )?;
 
         let mut input_element_descs: [D3D12_INPUT_ELEMENT_DESC; 2] = [
             D3D12_INPUT_ELEMENT_DESC {
@@ -345,8 +317,8 @@ mod d3d12_hello_frame_buffering {
                 NumElements: input_element_descs.len() as u32,
             },
             pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
-            VS: D3D12_SHADER_BYTECODE::from_blob(&vertex_shader),
-            PS: D3D12_SHADER_BYTECODE::from_blob(&pixel_shader),
+            VS: D3D12_SHADER_BYTECODE::from_bytes(&vertex_shader.bytecode),
+            PS: D3D12_SHADER_BYTECODE::from_bytes(&pixel_shader.bytecode),
             RasterizerState: D3D12_RASTERIZER_DESC::reasonable_default(),
             BlendState: D3D12_BLEND_DESC::reasonable_default(),
             DepthStencilState: D3D12_DEPTH_STENCIL_DESC::default(),
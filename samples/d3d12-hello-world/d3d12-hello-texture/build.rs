@@ -1,5 +0,0 @@
-use d3dx12::build::copy_data_file;
-
-fn main() {
-    copy_data_file("src/hello-texture-shaders.hlsl");
-}
@@ -1,16 +1,18 @@
+use d3dx12::shader::{CompileOptions, FxcCompiler, ShaderCompiler};
 use d3dx12::*;
+use d3dx12::{
+    dds, image, ktx2,
+    mips::{self, MipFilter},
+    procedural::Pattern,
+    texture::{TextureData, TextureDimension},
+};
 use dxsample::*;
-use std::convert::TryInto;
+use std::{convert::TryInto, path::Path};
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
-        Graphics::{
-            Direct3D::{Fxc::*, *},
-            Direct3D12::*,
-            Dxgi::Common::*,
-            Dxgi::*,
-        },
+        Graphics::{Direct3D::*, Direct3D12::*, Dxgi::Common::*, Dxgi::*},
     },
 };
 
@@ -19,12 +21,13 @@ mod d3d12_hello_texture {
     use super::*;
 
     const FRAME_COUNT: usize = 2;
-    const TEXTURE_WIDTH: u64 = 256;
+    const TEXTURE_WIDTH: u32 = 256;
     const TEXTURE_HEIGHT: u32 = 256;
 
     pub struct Sample {
         dxgi_factory: IDXGIFactory4,
         device: ID3D12Device,
+        texture_data: TextureData<'static>,
         resources: Option<Resources>,
     }
 
@@ -50,9 +53,16 @@ mod d3d12_hello_texture {
         fn new(command_line: &SampleCommandLine) -> Result<Self> {
             let (dxgi_factory, device) = create_device(command_line)?;
 
+            // A PNG or TGA file named on the command line replaces the
+            // checkerboard.
+            let texture_data = with_mips(generate_texture_data(
+                command_line.arguments.first().map(Path::new),
+            )?);
+
             Ok(Sample {
                 dxgi_factory,
                 device,
+                texture_data,
                 resources: None,
             })
         }
@@ -152,12 +162,7 @@ mod d3d12_hello_texture {
 
             let (vertex_buffer, vbv) = create_vertex_buffer(&self.device, aspect_ratio)?;
 
-            let texture = create_texture(
-                &self.device,
-                &mut command_queue,
-                &command_allocator,
-                &command_list,
-            )?;
+            let texture = create_texture(&self.device, &self.texture_data, &mut command_queue)?;
 
             unsafe {
                 srv_heap.create_shader_resource_view(
@@ -166,7 +171,7 @@ mod d3d12_hello_texture {
                     Some(&D3D12_SHADER_RESOURCE_VIEW_DESC::texture2d(
                         texture.GetDesc().Format,
                         D3D12_TEX2D_SRV {
-                            MipLevels: 1,
+                            MipLevels: self.texture_data.desc.mip_levels as u32,
                             ..Default::default()
                         },
                     )),
@@ -367,48 +372,20 @@ mod d3d12_hello_texture {
         device: &ID3D12Device,
         root_signature: &ID3D12RootSignature,
     ) -> Result<ID3D12PipelineState> {
-        let compile_flags = if cfg!(debug_assertions) {
-            D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION
-        } else {
-            0
-        };
-
-        let exe_path = std::env::current_exe().ok().unwrap();
-        let asset_path = exe_path.parent().unwrap();
-        let shaders_hlsl_path = asset_path.join("hello-texture-shaders.hlsl");
-        let shaders_hlsl: HSTRING = shaders_hlsl_path.to_str().unwrap().into();
+        let shaders = embed_shaders!("src", ["hello-texture-shaders.hlsl"]);
+        let shaders_hlsl_path = "hello-texture-shaders.hlsl";
 
-        let mut vertex_shader = None;
-        let vertex_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("VSMain"),
-                s!("vs_5_0"),
-                compile_flags,
-                0,
-                &mut vertex_shader,
-                None,
-            )
-        }
-        .and(Ok(vertex_shader.unwrap()))?;
+        let vertex_shader = FxcCompiler.compile_virtual(
+            &shaders,
+            shaders_hlsl_path,
+            &CompileOptions::new("VSMain", "vs_5_0"),
+        )?;
 
-        let mut pixel_shader = None;
-        let pixel_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("PSMain"),
-                s!("ps_5_0"),
-                compile_flags,
-                0,
-                &mut pixel_shader,
-                None,
-            )
-        }
-        .and(Ok(pixel_shader.unwrap()))?;
+        let pixel_shader = FxcCompiler.compile_virtual(
+            &shaders,
+            shaders_hlsl_path,
+            &CompileOptions::new("PSMain", "ps_5_0"),
+        )?;
 
         let mut input_element_descs: [D3D12_INPUT_ELEMENT_DESC; 2] = [
             D3D12_INPUT_ELEMENT_DESC {
@@ -437,8 +414,8 @@ mod d3d12_hello_texture {
                 NumElements: input_element_descs.len() as u32,
             },
             pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
-            VS: D3D12_SHADER_BYTECODE::from_blob(&vertex_shader),
-            PS: D3D12_SHADER_BYTECODE::from_blob(&pixel_shader),
+            VS: D3D12_SHADER_BYTECODE::from_bytes(&vertex_shader.bytecode),
+            PS: D3D12_SHADER_BYTECODE::from_bytes(&pixel_shader.bytecode),
             RasterizerState: D3D12_RASTERIZER_DESC::reasonable_default(),
             BlendState: D3D12_BLEND_DESC::reasonable_default(),
             DepthStencilState: D3D12_DEPTH_STENCIL_DESC::default(),
@@ -518,12 +495,10 @@ mod d3d12_hello_texture {
 
     fn create_texture(
         device: &ID3D12Device,
+        texture_data: &TextureData,
         command_queue: &mut SynchronizedCommandQueue,
-        command_allocator: &ID3D12CommandAllocator,
-        command_list: &ID3D12GraphicsCommandList,
     ) -> Result<ID3D12Resource> {
-        let texture_desc =
-            D3D12_RESOURCE_DESC::tex2d(DXGI_FORMAT_R8G8B8A8_UNORM, TEXTURE_WIDTH, TEXTURE_HEIGHT);
+        let texture_desc = D3D12_RESOURCE_DESC::from(texture_data.desc);
 
         let mut texture = None;
         let texture: ID3D12Resource = unsafe {
@@ -538,121 +513,70 @@ mod d3d12_hello_texture {
         }
         .and(Ok(texture.unwrap()))?;
 
-        let mut placed_subresource_footprint = D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
-            ..Default::default()
-        };
-        let mut upload_buffer_size = 0;
+        let mut uploads = UploadBatch::new(device);
+        uploads.upload_texture(
+            &texture,
+            0,
+            &texture_data.subresources,
+            D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+        )?;
 
-        unsafe {
-            device.GetCopyableFootprints(
-                &texture_desc,
-                0,
-                1,
-                0,
-                Some(&mut placed_subresource_footprint),
-                None,
-                None,
-                Some(&mut upload_buffer_size),
-            );
-        }
+        // Wait for the GPU to finish any creation work before returning. The
+        // upload buffer is freed once it has.
+        uploads.submit(command_queue)?.wait()?;
 
-        let mut upload_buffer = None;
-        let upload_buffer: ID3D12Resource = unsafe {
-            device.CreateCommittedResource(
-                &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
-                D3D12_HEAP_FLAG_NONE,
-                &D3D12_RESOURCE_DESC::buffer(upload_buffer_size as usize),
-                D3D12_RESOURCE_STATE_GENERIC_READ,
-                None,
-                &mut upload_buffer,
-            )
-        }
-        .and(Ok(upload_buffer.unwrap()))?;
-
-        unsafe {
-            let mut upload_data = std::ptr::null_mut();
-            upload_buffer.Map(0, None, Some(&mut upload_data))?;
+        Ok(texture)
+    }
 
-            generate_texture_data(
-                upload_data.cast(),
-                &texture_desc,
-                &placed_subresource_footprint.Footprint,
-            );
+    /// Loads the texture from a DDS, KTX2, PNG or TGA file, or generates a
+    /// checkerboard if there is no file. DDS and KTX2 files keep their format
+    /// and mips, so they can be block-compressed; the others are RGBA8 with
+    /// one mip.
+    fn generate_texture_data(path: Option<&Path>) -> Result<TextureData<'static>> {
+        if let Some(path) = path {
+            let error =
+                |message: String| Error::new(E_FAIL, format!("{}: {}", path.display(), message));
+            let bytes = std::fs::read(path).map_err(|e| error(e.to_string()))?;
+            if bytes.starts_with(dds::DDS_MAGIC) || bytes.starts_with(ktx2::KTX2_MAGIC) {
+                let texture_data = TextureData::read(&bytes).map_err(|e| error(e.to_string()))?;
+                if texture_data.desc.dimension != TextureDimension::Texture2D
+                    || texture_data.desc.depth_or_array_size != 1
+                {
+                    return Err(error(
+                        "only 2D textures with one array slice can be shown".into(),
+                    ));
+                }
+                return Ok(texture_data.into_owned());
+            }
 
-            upload_buffer.Unmap(0, None);
+            // The swap chain isn't sRGB, so neither is the texture; the
+            // colors go through to the screen as the file has them.
+            return image::read(&bytes, false).map_err(|e| error(e.to_string()));
         }
 
-        unsafe {
-            command_list.Reset(command_allocator, None)?;
-            command_list.CopyTextureRegion(
-                &D3D12_TEXTURE_COPY_LOCATION {
-                    pResource: std::mem::transmute_copy(&texture),
-                    Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
-                    Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
-                        SubresourceIndex: 0,
-                    },
-                },
-                0,
-                0,
-                0,
-                &D3D12_TEXTURE_COPY_LOCATION {
-                    pResource: std::mem::transmute_copy(&upload_buffer),
-                    Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
-                    Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
-                        PlacedFootprint: placed_subresource_footprint,
-                    },
-                },
-                None,
-            );
-            command_list.ResourceBarrier(&[transition_barrier(
-                &texture,
-                D3D12_RESOURCE_STATE_COPY_DEST,
-                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
-            )]);
-        }
-
-        unsafe {
-            command_list.Close()?;
-            command_queue.ExecuteCommandLists(&[Some(command_list.cast().unwrap())]);
+        // Red and white. Blending is off, so the red cells' alpha of 0
+        // doesn't show.
+        let checker = Pattern::Checker {
+            cells: [8, 8],
+            colors: [[1.0, 0.0, 0.0, 0.0], [1.0; 4]],
         };
-
-        // Wait for the GPU to finish any creation work before returning
-        command_queue.signal_and_wait_for_gpu()?;
-
-        // We must wait until the GPU has finished before we can drop the upload
-        // buffer. This is needed because clippy encourages you to remove the
-        // clone on the last definite use of upload_buffer it sees, which is not
-        // good!
-        drop(upload_buffer);
-
-        Ok(texture)
+        let texture = checker
+            .to_texture(DXGI_FORMAT_R8G8B8A8_UNORM, TEXTURE_WIDTH, TEXTURE_HEIGHT)
+            .unwrap();
+        Ok(TextureData::from(texture))
     }
 
-    unsafe fn generate_texture_data(
-        mut dest: *mut u8,
-        desc: &D3D12_RESOURCE_DESC,
-        footprint: &D3D12_SUBRESOURCE_FOOTPRINT,
-    ) {
-        std::assert_eq!(desc.Format, DXGI_FORMAT_R8G8B8A8_UNORM);
-
-        let cell_width = desc.Width >> 3;
-        let cell_height = desc.Height >> 3;
-
-        for row in 0..desc.Height {
-            let mut p: *mut u32 = dest.cast();
-            for x in 0..desc.Width {
-                let cell_x = x / cell_width;
-                let cell_y = u64::from(row / cell_height);
-                if cell_x % 2 == cell_y % 2 {
-                    p.write(0x000000FF);
-                } else {
-                    p.write(0xFFFFFFFF);
-                }
-                p = p.offset(1);
-            }
-
-            dest = dest.offset(footprint.RowPitch as isize);
+    /// Fills in the mips of a texture that only has its top level, so that
+    /// it doesn't shimmer when it's drawn small. Textures that already have
+    /// mips, or whose format can't be filtered, are kept as they are.
+    fn with_mips(texture_data: TextureData<'static>) -> TextureData<'static> {
+        if texture_data.desc.mip_levels != 1 {
+            return texture_data;
         }
+        texture_data
+            .to_texture()
+            .and_then(|texture| mips::generate_mips(&texture, MipFilter::Kaiser))
+            .map_or(texture_data, TextureData::from)
     }
 
     fn wait_for_previous_frame(resources: &mut Resources) {
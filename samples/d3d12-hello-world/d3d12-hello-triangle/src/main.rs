@@ -1,3 +1,7 @@
+use d3dx12::shader::{
+    CompileOptions, CompiledShader, FxcCompiler, SearchPath, ShaderDirectory, ShaderFileSystem,
+    ShaderId, ShaderReloader,
+};
 use d3dx12::*;
 use dxsample::*;
 use windows::{
@@ -5,7 +9,7 @@ use windows::{
     Win32::{
         Foundation::*,
         Graphics::{
-            Direct3D::{Fxc::*, *},
+            Direct3D::*,
             Direct3D12::*,
             Dxgi::{Common::*, *},
         },
@@ -36,6 +40,9 @@ mod d3d12_hello_triangle {
         command_allocator: ID3D12CommandAllocator,
         root_signature: ID3D12RootSignature,
         pso: ID3D12PipelineState,
+        shader_reloader: ShaderReloader,
+        vertex_shader: ShaderId,
+        pixel_shader: ShaderId,
         command_list: ID3D12GraphicsCommandList,
         _vertex_buffer: ID3D12Resource,
         vbv: D3D12_VERTEX_BUFFER_VIEW,
@@ -126,7 +133,20 @@ mod d3d12_hello_triangle {
             }?;
 
             let root_signature = create_root_signature(&self.device)?;
-            let pso = create_pipeline_state(&self.device, &root_signature)?;
+
+            let mut shader_reloader = ShaderReloader::new(shader_files(), FxcCompiler);
+            let vertex_shader = shader_reloader
+                .add_shader(SHADERS_HLSL_PATH, CompileOptions::new("VSMain", "vs_5_0"))?;
+            let pixel_shader = shader_reloader
+                .add_shader(SHADERS_HLSL_PATH, CompileOptions::new("PSMain", "ps_5_0"))?;
+            shader_reloader.add_pipeline(&[vertex_shader, pixel_shader]);
+
+            let pso = create_pipeline_state(
+                &self.device,
+                &root_signature,
+                shader_reloader.shader(vertex_shader),
+                shader_reloader.shader(pixel_shader),
+            )?;
 
             let command_list: ID3D12GraphicsCommandList = unsafe {
                 self.device.CreateCommandList(
@@ -153,6 +173,9 @@ mod d3d12_hello_triangle {
                 command_allocator,
                 root_signature,
                 pso,
+                shader_reloader,
+                vertex_shader,
+                pixel_shader,
                 command_list,
                 _vertex_buffer: vertex_buffer,
                 vbv,
@@ -180,6 +203,36 @@ mod d3d12_hello_triangle {
             wait_for_previous_frame(resources);
         }
 
+        fn reload_shaders(&mut self) {
+            let resources = match &mut self.resources {
+                Some(it) => it,
+                _ => return,
+            };
+
+            // A shader that no longer compiles keeps the pipeline it had, so
+            // the sample carries on running while the error is fixed.
+            let reload = resources.shader_reloader.poll();
+            for (_, error) in &reload.failed {
+                eprintln!("{}", error);
+            }
+
+            if !reload.pipelines.is_empty() {
+                let pso = create_pipeline_state(
+                    &self.device,
+                    &resources.root_signature,
+                    resources.shader_reloader.shader(resources.vertex_shader),
+                    resources.shader_reloader.shader(resources.pixel_shader),
+                );
+
+                // Every frame is waited for before the next one starts, so
+                // nothing is still using the old pipeline.
+                match pso {
+                    Ok(pso) => resources.pso = pso,
+                    Err(e) => eprintln!("failed to rebuild pipeline state: {}", e),
+                }
+            }
+        }
+
         fn title(&self) -> String {
             "D3D12 Hello Triangle".into()
         }
@@ -265,53 +318,26 @@ mod d3d12_hello_triangle {
         }
     }
 
+    const SHADERS_HLSL_PATH: &str = "hello-triangle-shaders.hlsl";
+
+    /// The shaders in the source tree are preferred, so that edits to them
+    /// show up while the sample is running; the embedded copies are used when
+    /// the sample has been moved elsewhere.
+    fn shader_files() -> impl ShaderFileSystem {
+        SearchPath::new()
+            .with(ShaderDirectory::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src"
+            )))
+            .with(embed_shaders!("src", ["hello-triangle-shaders.hlsl"]))
+    }
+
     fn create_pipeline_state(
         device: &ID3D12Device,
         root_signature: &ID3D12RootSignature,
+        vertex_shader: &CompiledShader,
+        pixel_shader: &CompiledShader,
     ) -> Result<ID3D12PipelineState> {
-        let compile_flags = if cfg!(debug_assertions) {
-            D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION
-        } else {
-            0
-        };
-
-        let exe_path = std::env::current_exe().ok().unwrap();
-        let asset_path = exe_path.parent().unwrap();
-        let shaders_hlsl_path = asset_path.join("hello-triangle-shaders.hlsl");
-        let shaders_hlsl: HSTRING = shaders_hlsl_path.to_str().unwrap().into();
-
-        let mut vertex_shader = None;
-        let vertex_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("VSMain"),
-                s!("vs_5_0"),
-                compile_flags,
-                0,
-                &mut vertex_shader,
-                None,
-            )
-        }
-        .and(Ok(vertex_shader.unwrap()))?;
-
-        let mut pixel_shader = None;
-        let pixel_shader = unsafe {
-            D3DCompileFromFile(
-                &shaders_hlsl,
-                None,
-                None,
-                s!("PSMain"),
-                s!("ps_5_0"),
-                compile_flags,
-                0,
-                &mut pixel_shader,
-                None,
-            )
-        }
-        .and(Ok(pixel_shader.unwrap()))?;
-
         let mut input_element_descs: [D3D12_INPUT_ELEMENT_DESC; 2] = [
             D3D12_INPUT_ELEMENT_DESC {
                 SemanticName: s!("POSITION"),
@@ -339,8 +365,8 @@ mod d3d12_hello_triangle {
                 NumElements: input_element_descs.len() as u32,
             },
             pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
-            VS: D3D12_SHADER_BYTECODE::from_blob(&vertex_shader),
-            PS: D3D12_SHADER_BYTECODE::from_blob(&pixel_shader),
+            VS: D3D12_SHADER_BYTECODE::from_bytes(&vertex_shader.bytecode),
+            PS: D3D12_SHADER_BYTECODE::from_bytes(&pixel_shader.bytecode),
             RasterizerState: D3D12_RASTERIZER_DESC::reasonable_default(),
             BlendState: D3D12_BLEND_DESC::reasonable_default(),
             DepthStencilState: D3D12_DEPTH_STENCIL_DESC::default(),
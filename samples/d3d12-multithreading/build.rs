@@ -1,6 +1,8 @@
-use d3dx12::build::copy_data_file;
+use d3dx12::build::AssetSet;
 
 fn main() {
-    copy_data_file("squidroom.bin");
-    copy_data_file("src/rendering/multithreading-shaders.hlsl");
+    AssetSet::new()
+        .file("squidroom.bin")
+        .deploy()
+        .expect("deploy assets");
 }
@@ -0,0 +1,55 @@
+//! Converts SquidRoom.bin into a scene file that the sample can load:
+//!
+//! ```text
+//! scene-pack SquidRoom.bin assets/SquidRoom.scene
+//! ```
+//!
+//! SquidRoom.bin only has the top level of each texture, so the rest of the
+//! mips are generated here.
+
+use d3dx12::mips::{generate_mips, MipFilter};
+use std::process::exit;
+
+// Shared with the sample, which reads SquidRoom.bin directly when there is no
+// scene file yet.
+#[path = "../../squidroom.rs"]
+mod squidroom;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: scene-pack <SquidRoom.bin> <output.scene>");
+        exit(2);
+    }
+
+    let mut scene = match squidroom::load(&args[0]) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            exit(1);
+        }
+    };
+
+    for (index, texture) in scene.textures.iter_mut().enumerate() {
+        match generate_mips(texture, MipFilter::Kaiser) {
+            Some(mipped) => *texture = mipped,
+            None => eprintln!(
+                "{}: can't generate mips for texture {} (format {})",
+                args[0], index, texture.format.0
+            ),
+        }
+    }
+
+    if let Err(e) = scene.save(&args[1]) {
+        eprintln!("{}: {}", args[1], e);
+        exit(1);
+    }
+
+    println!(
+        "{}: {} textures, {} materials, {} draws",
+        args[1],
+        scene.textures.len(),
+        scene.materials.len(),
+        scene.draws.len()
+    );
+}
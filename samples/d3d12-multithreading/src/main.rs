@@ -11,22 +11,44 @@
 
 use camera::{Camera, ViewAndProjectionMatrices};
 use cgmath::{point3, vec3, Deg, InnerSpace, Matrix3, Rad, Transform};
-use dxsample::{run_sample, DXSample, SampleCommandLine};
+use d3dx12::{
+    gltf,
+    mips::{self, MipFilter},
+    procedural::Pattern,
+    scene::Scene,
+    texture::{Texture, TextureData},
+};
+use dxsample::{run_sample, AssetLocator, DXSample, SampleCommandLine};
 use rendering::*;
+use std::path::{Path, PathBuf};
 use timer::Timer;
 use windows::core::*;
 use windows::Win32::UI::Input::KeyboardAndMouse::*;
 use windows::Win32::{
-    Foundation::HWND,
+    Foundation::{E_FAIL, HWND},
     Graphics::Dxgi::{
-        DXGIDeclareAdapterRemovalSupport, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
+        Common::DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, DXGIDeclareAdapterRemovalSupport,
+        DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET,
     },
 };
 
 mod camera;
 mod rendering;
+mod squidroom;
 mod timer;
 
+/// Loaded when no scene is given on the command line. Make it with
+/// `cargo run --bin scene-pack -- SquidRoom.bin assets/SquidRoom.scene` from
+/// the root of the workspace; until then, the SquidRoom.bin that the build
+/// script deploys is read directly.
+const DEFAULT_SCENE: &str = "SquidRoom.scene";
+
+const SQUIDROOM_BIN: &str = "squidroom.bin";
+
+/// The width and height of the texture that stands in for an image that can't
+/// be decoded.
+const PLACEHOLDER_SIZE: u32 = 256;
+
 #[derive(Default)]
 struct MultithreadingApp {
     command_line: SampleCommandLine,
@@ -54,8 +76,11 @@ struct InputState {
 }
 
 impl DXSample for MultithreadingApp {
-    fn new(_command_line: &SampleCommandLine) -> Result<Self> {
-        Ok(MultithreadingApp::default())
+    fn new(command_line: &SampleCommandLine) -> Result<Self> {
+        Ok(MultithreadingApp {
+            command_line: command_line.clone(),
+            ..Default::default()
+        })
     }
 
     fn bind_to_window(&mut self, hwnd: &HWND) -> Result<()> {
@@ -173,16 +198,88 @@ fn is_device_removed(e: &Error) -> bool {
 impl MultithreadingApp {
     fn create_resources(&mut self) -> Result<()> {
         let (width, height) = self.window_size();
+        let scene = load_scene(&self.command_line)?;
         self.renderer = Some(Renderer::new(
             &self.command_line,
             &self.hwnd,
             width as u32,
             height as u32,
+            &scene,
         )?);
         Ok(())
     }
 }
 
+/// Loads the scene named on the command line, or the default one. A name that
+/// is not the path of a file is looked for as an asset. `.gltf` and `.glb`
+/// files are imported, with only those of their textures that are DDS, KTX2 or
+/// PNG files; see `decode_image`.
+fn load_scene(command_line: &SampleCommandLine) -> Result<Scene> {
+    let locator = AssetLocator::new();
+    let find = |name: &str| {
+        if Path::new(name).is_file() {
+            Ok(PathBuf::from(name))
+        } else {
+            locator.locate(name)
+        }
+    };
+    let path = match command_line.arguments.first() {
+        Some(name) => find(name)?,
+        None => match find(DEFAULT_SCENE) {
+            Ok(path) => path,
+            Err(_) => return load_squidroom(&locator),
+        },
+    };
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let scene = if ["gltf", "glb"]
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(extension))
+    {
+        gltf::load(&path, decode_image).map_err(|e| e.to_string())
+    } else {
+        Scene::load(&path).map_err(|e| e.to_string())
+    };
+    scene.map_err(|e| Error::new(E_FAIL, format!("{}: {}", path.display(), e)))
+}
+
+/// Reads SquidRoom.bin through the tables that describe it, generating the mips
+/// that scene-pack would have.
+fn load_squidroom(locator: &AssetLocator) -> Result<Scene> {
+    let path = locator.locate(SQUIDROOM_BIN)?;
+    let mut scene = squidroom::load(&path)
+        .map_err(|e| Error::new(E_FAIL, format!("{}: {}", path.display(), e)))?;
+    for texture in &mut scene.textures {
+        if let Some(mipped) = mips::generate_mips(texture, MipFilter::Kaiser) {
+            *texture = mipped;
+        }
+    }
+    Ok(scene)
+}
+
+/// Decodes an image, filling in its mips if it only has its top level. Color
+/// images that can't be decoded are replaced with a UV grid, so that they
+/// stand out; other images are left out.
+fn decode_image(image: &gltf::Image) -> std::io::Result<Option<Texture>> {
+    let texture = match image.mime_type {
+        Some("image/vnd-ms.dds" | "image/ktx2") => TextureData::read(image.data)?.to_texture(),
+        Some("image/png") => d3dx12::image::read(image.data, image.srgb)?.to_texture(),
+        _ if image.srgb => Pattern::UvGrid { cells: 8 }.to_texture(
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            PLACEHOLDER_SIZE,
+            PLACEHOLDER_SIZE,
+        ),
+        _ => None,
+    };
+    Ok(texture.map(|texture| {
+        if texture.subresources.len() == 1 {
+            mips::generate_mips(&texture, MipFilter::Kaiser).unwrap_or(texture)
+        } else {
+            texture
+        }
+    }))
+}
+
 fn main() -> Result<()> {
     unsafe { DXGIDeclareAdapterRemovalSupport() }?;
     run_sample::<MultithreadingApp>()?;
@@ -1,6 +1,7 @@
 use array_init::try_array_init;
 use async_std::task;
 use cgmath::{point3, vec3, vec4, Deg, Matrix4, Point3, SquareMatrix, Vector3, Vector4, Zero};
+use d3dx12::scene::Scene;
 use d3dx12::*;
 use dxsample::*;
 use static_assertions::const_assert_eq;
@@ -8,7 +9,7 @@ use std::sync::Arc;
 use windows::{
     core::*,
     Win32::{
-        Foundation::{HWND, RECT},
+        Foundation::{E_OUTOFMEMORY, HWND, RECT},
         Graphics::{Direct3D12::*, Dxgi::Common::*, Dxgi::*},
     },
 };
@@ -20,15 +21,16 @@ struct SendableID3D12GraphicsCommandList(ID3D12GraphicsCommandList);
 unsafe impl Send for SendableID3D12GraphicsCommandList {}
 unsafe impl Sync for SendableID3D12GraphicsCommandList {}
 
-mod squidroom;
-use squidroom::*;
+mod scene;
+use scene::*;
 
 const FRAME_COUNT: usize = 2;
 const NULL_DESCRIPTOR_COUNT: usize = 2;
-const TEXTURE_DESCRIPTOR_COUNT: usize = squidroom::TEXTURE_COUNT;
 const PER_FRAME_GPU_DESCRIPTOR_COUNT: usize = 3;
-const GPU_DESCRIPTOR_COUNT: usize =
-    NULL_DESCRIPTOR_COUNT + TEXTURE_DESCRIPTOR_COUNT + FRAME_COUNT * PER_FRAME_GPU_DESCRIPTOR_COUNT;
+
+/// Room for the two constant buffers of each frame in flight, and as much
+/// again for what wrapping around the end of the heap can skip.
+const UPLOAD_HEAP_SIZE: usize = 2 * FRAME_COUNT * 2 * std::mem::size_of::<SceneConstantBuffer>();
 
 pub struct Renderer {
     _device: ID3D12Device,
@@ -48,6 +50,7 @@ pub struct Frames {
     frames: [Frame; FRAME_COUNT],
     idle_command_lists: Vec<ID3D12GraphicsCommandList>,
     command_lists: Vec<ID3D12GraphicsCommandList>,
+    upload_heap: UploadHeap,
 }
 
 pub struct Frame {
@@ -62,18 +65,19 @@ struct FrameRenderData {
     resources: Arc<Resources>,
     render_target: ID3D12Resource,
     shadow_texture: ID3D12Resource,
-    shadow_cb: ID3D12Resource,
-    scene_cb: ID3D12Resource,
-    shadow_cb_ptr: *mut SceneConstantBuffer,
-    scene_cb_ptr: *mut SceneConstantBuffer,
     render_target_view: D3D12_CPU_DESCRIPTOR_HANDLE,
     shadow_depth_view: D3D12_CPU_DESCRIPTOR_HANDLE,
+    /// Pointed at this frame's constants in the upload heap each time it
+    /// starts.
+    shadow_cbv: D3D12_CPU_DESCRIPTOR_HANDLE,
+    scene_cbv: D3D12_CPU_DESCRIPTOR_HANDLE,
     shadow_cbv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
     scene_srv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
     scene_cbv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
 }
 
 #[repr(C, align(256))]
+#[derive(Clone, Copy)]
 struct SceneConstantBuffer {
     model: Matrix4<f32>,
     view: Matrix4<f32>,
@@ -143,6 +147,7 @@ impl Renderer {
         hwnd: &HWND,
         width: u32,
         height: u32,
+        scene: &Scene,
     ) -> Result<Self> {
         let viewport = D3D12_VIEWPORT {
             TopLeftX: 0.0,
@@ -168,9 +173,12 @@ impl Renderer {
         let swap_chain = create_swap_chain(&factory, &command_queue.queue, hwnd, width, height)?;
         let rtv_descriptor_heap = RtvDescriptorHeap::new(&device, FRAME_COUNT)?;
         let dsv_descriptor_heap = DsvDescriptorHeap::new(&device, FRAME_COUNT + 1)?;
+        let scene_descriptor_count = Resources::descriptor_count(scene);
         let gpu_descriptor_heap = CbvSrvUavDescriptorHeap::new(
             &device,
-            GPU_DESCRIPTOR_COUNT,
+            NULL_DESCRIPTOR_COUNT
+                + scene_descriptor_count
+                + FRAME_COUNT * PER_FRAME_GPU_DESCRIPTOR_COUNT,
             D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
         )?;
 
@@ -235,7 +243,8 @@ impl Renderer {
         let resources = Arc::new(Resources::new(
             &device,
             &mut command_queue,
-            gpu_descriptor_heap.slice(2),
+            scene,
+            gpu_descriptor_heap.slice(NULL_DESCRIPTOR_COUNT),
             null_srv_table,
             depth_stencil,
             depth_stencil_view,
@@ -246,7 +255,7 @@ impl Renderer {
             swap_chain,
             &rtv_descriptor_heap,
             &dsv_descriptor_heap.slice(1),
-            &gpu_descriptor_heap.slice(NULL_DESCRIPTOR_COUNT + TEXTURE_DESCRIPTOR_COUNT),
+            &gpu_descriptor_heap.slice(NULL_DESCRIPTOR_COUNT + scene_descriptor_count),
             resources,
         )?;
 
@@ -264,7 +273,12 @@ impl Renderer {
 
     pub fn render(&mut self, state: &State) -> Result<()> {
         let render_data = self.frames.start_frame(&self.command_queue)?;
-        render_data.set_constant_buffers(&self.viewport, state);
+        render_data.set_constant_buffers(
+            &self.frames.device,
+            &mut self.frames.upload_heap,
+            &self.viewport,
+            state,
+        )?;
 
         macro_rules! spawn_async_render_task {
             ( $cl:ident $(, $render_data:ident )?, $block:block ) => {{
@@ -472,6 +486,7 @@ impl Frames {
             })
         })?;
 
+        let upload_heap = UploadHeap::new(device, UPLOAD_HEAP_SIZE as u64)?;
         let device = device.cast()?;
 
         Ok(Frames {
@@ -481,6 +496,7 @@ impl Frames {
             frames,
             idle_command_lists: Default::default(),
             command_lists: Default::default(),
+            upload_heap,
         })
     }
 
@@ -490,6 +506,8 @@ impl Frames {
     ) -> Result<Arc<FrameRenderData>> {
         let frame = &mut self.frames[self.current_index];
         frame.start(command_queue)?;
+        self.upload_heap
+            .retire(unsafe { command_queue.fence.GetCompletedValue() });
         Ok(frame.render_data.clone())
     }
 
@@ -500,6 +518,7 @@ impl Frames {
 
         let frame = &mut self.frames[self.current_index];
         frame.end(command_queue)?;
+        self.upload_heap.finish_frame(frame.fence_value);
 
         self.current_index = unsafe { self.swap_chain.GetCurrentBackBufferIndex() } as usize;
 
@@ -596,45 +615,6 @@ impl FrameRenderData {
         }
         .and(Ok(shadow_texture.unwrap()))?;
 
-        let cb_size = std::mem::size_of::<SceneConstantBuffer>();
-        let cb_desc = D3D12_RESOURCE_DESC::buffer(cb_size);
-        let mut shadow_cb = None;
-        let shadow_cb: ID3D12Resource = unsafe {
-            device.CreateCommittedResource(
-                &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
-                D3D12_HEAP_FLAG_NONE,
-                &cb_desc,
-                D3D12_RESOURCE_STATE_GENERIC_READ,
-                None,
-                &mut shadow_cb,
-            )
-        }
-        .and(Ok(shadow_cb.unwrap()))?;
-
-        let mut scene_cb = None;
-        let scene_cb: ID3D12Resource = unsafe {
-            device.CreateCommittedResource(
-                &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
-                D3D12_HEAP_FLAG_NONE,
-                &cb_desc,
-                D3D12_RESOURCE_STATE_GENERIC_READ,
-                None,
-                &mut scene_cb,
-            )
-        }
-        .and(Ok(scene_cb.unwrap()))?;
-
-        let mut shadow_cb_ptr = std::ptr::null_mut();
-        let mut scene_cb_ptr = std::ptr::null_mut();
-
-        unsafe {
-            shadow_cb.Map(0, None, Some(&mut shadow_cb_ptr))?;
-            scene_cb.Map(0, None, Some(&mut scene_cb_ptr))?;
-        }
-
-        let shadow_cb_ptr = shadow_cb_ptr.cast();
-        let scene_cb_ptr = scene_cb_ptr.cast();
-
         let shadow_srv_descriptor_handles = gpu_descriptor_heap.get_descriptor_handles(0);
         let shadow_cbv_descriptor_handles = gpu_descriptor_heap.get_descriptor_handles(1);
         let scene_cbv_descriptor_handles = gpu_descriptor_heap.get_descriptor_handles(2);
@@ -664,41 +644,30 @@ impl FrameRenderData {
                 )),
                 shadow_srv_descriptor_handles.cpu,
             );
-
-            device.CreateConstantBufferView(
-                Some(&D3D12_CONSTANT_BUFFER_VIEW_DESC {
-                    BufferLocation: shadow_cb.GetGPUVirtualAddress(),
-                    SizeInBytes: cb_size as u32,
-                }),
-                shadow_cbv_descriptor_handles.cpu,
-            );
-
-            device.CreateConstantBufferView(
-                Some(&D3D12_CONSTANT_BUFFER_VIEW_DESC {
-                    BufferLocation: scene_cb.GetGPUVirtualAddress(),
-                    SizeInBytes: cb_size as u32,
-                }),
-                scene_cbv_descriptor_handles.cpu,
-            );
         }
 
         Ok(FrameRenderData {
             resources,
             render_target,
             shadow_texture,
-            shadow_cb,
-            scene_cb,
-            shadow_cb_ptr,
-            scene_cb_ptr,
             render_target_view,
             shadow_depth_view,
+            shadow_cbv: shadow_cbv_descriptor_handles.cpu,
+            scene_cbv: scene_cbv_descriptor_handles.cpu,
             shadow_cbv_table: shadow_cbv_descriptor_handles.gpu,
             scene_srv_table: shadow_srv_descriptor_handles.gpu,
             scene_cbv_table: scene_cbv_descriptor_handles.gpu,
         })
     }
 
-    fn set_constant_buffers(&self, viewport: &D3D12_VIEWPORT, state: &State) {
+    /// Uploads this frame's constants, and points its views at them.
+    fn set_constant_buffers(
+        &self,
+        device: &ID3D12Device,
+        upload_heap: &mut UploadHeap,
+        viewport: &D3D12_VIEWPORT,
+        state: &State,
+    ) -> Result<()> {
         // Scale down the world a bit.
         let scale_down = Matrix4::from_scale(0.1);
 
@@ -737,10 +706,18 @@ impl FrameRenderData {
             ..Default::default()
         };
 
-        unsafe {
-            self.scene_cb_ptr.write(scene_constants);
-            self.shadow_cb_ptr.write(shadow_constants);
+        for (constants, view) in [
+            (scene_constants, self.scene_cbv),
+            (shadow_constants, self.shadow_cbv),
+        ] {
+            let allocation = upload_heap
+                .upload_constants(&constants)
+                .ok_or_else(|| Error::new(E_OUTOFMEMORY, "the upload heap is full"))?;
+            unsafe {
+                device.CreateConstantBufferView(Some(&allocation.constant_buffer_view()), view);
+            }
         }
+        Ok(())
     }
 
     fn set_shadow_pass_state(&self, cl: &ID3D12GraphicsCommandList) {
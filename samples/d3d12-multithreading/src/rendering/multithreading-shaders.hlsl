@@ -39,13 +39,17 @@ cbuffer SceneConstantBuffer : register(b0)
     LightState lights[NUM_LIGHTS];
 };
 
+// SHADOW_PASS builds a vertex shader that only outputs position, for
+// rendering the shadow map without a pixel shader.
 struct PSInput
 {
     float4 position : SV_POSITION;
+#ifndef SHADOW_PASS
     float4 worldpos : POSITION;
     float2 uv : TEXCOORD0;
     float3 normal : NORMAL;
     float3 tangent : TANGENT;
+#endif
 };
 
 
@@ -138,22 +142,27 @@ PSInput VSMain(float3 position : POSITION, float3 normal : NORMAL, float2 uv : T
 
     float4 newPosition = float4(position, 1.0f);
 
-    normal.z *= -1.0f;
     newPosition = mul(newPosition, model);
 
+#ifndef SHADOW_PASS
+    normal.z *= -1.0f;
     result.worldpos = newPosition;
+#endif
 
     newPosition = mul(newPosition, view);
     newPosition = mul(newPosition, projection);
 
     result.position = newPosition;
+#ifndef SHADOW_PASS
     result.uv = uv;
     result.normal = normal;
     result.tangent = tangent;
+#endif
 
     return result;
 }
 
+#ifndef SHADOW_PASS
 float4 PSMain(PSInput input) : SV_TARGET
 {
     float4 diffuseColor = diffuseMap.Sample(sampleWrap, input.uv);
@@ -172,3 +181,4 @@ float4 PSMain(PSInput input) : SV_TARGET
 
     return diffuseColor * saturate(totalLight);
 }
+#endif
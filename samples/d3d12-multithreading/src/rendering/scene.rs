@@ -0,0 +1,610 @@
+use array_init::array_init;
+use d3dx12::scene::{Draw, Scene, VertexElement};
+use d3dx12::shader::{
+    CachedCompiler, CompileOptions, CompiledShader, FxcCompiler, PermutationKey, ShaderCompiler,
+    ShaderPermutations, ShaderSource,
+};
+use d3dx12::texture::Texture;
+use d3dx12::*;
+use dxsample::{SynchronizedCommandQueue, UploadBatch};
+use std::ffi::CString;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::{E_FAIL, E_INVALIDARG, RECT},
+        Graphics::{
+            Direct3D::{ID3DBlob, D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST},
+            Direct3D12::*,
+            Dxgi::Common::*,
+        },
+    },
+};
+
+pub struct Resources {
+    _textures: Vec<ID3D12Resource>,
+    pub _depth_stencil: ID3D12Resource,
+    pub depth_stencil_view: D3D12_CPU_DESCRIPTOR_HANDLE,
+    _geometry_buffer: ID3D12Resource,
+    vertex_buffer_view: D3D12_VERTEX_BUFFER_VIEW,
+    index_buffer_view: D3D12_INDEX_BUFFER_VIEW,
+    draws: Vec<Draw>,
+    root_signature: ID3D12RootSignature,
+    gpu_descriptor_heap: CbvSrvUavDescriptorHeap,
+    descriptor_heaps: [Option<ID3D12DescriptorHeap>; 2],
+    sampler_descriptor_table: D3D12_GPU_DESCRIPTOR_HANDLE,
+    pub null_srv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
+    pub scene_pso: ID3D12PipelineState,
+    pub shadow_map_pso: ID3D12PipelineState,
+}
+
+/// Each material has a diffuse and a normal map descriptor, in that order.
+const DESCRIPTORS_PER_MATERIAL: usize = 2;
+
+impl Resources {
+    /// How many descriptors `new` needs in `gpu_descriptor_heap`.
+    pub fn descriptor_count(scene: &Scene) -> usize {
+        scene.materials.len() * DESCRIPTORS_PER_MATERIAL
+    }
+
+    pub fn new(
+        device: &ID3D12Device,
+        command_queue: &mut SynchronizedCommandQueue,
+        scene: &Scene,
+        gpu_descriptor_heap: CbvSrvUavDescriptorHeap,
+        null_srv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
+        depth_stencil: ID3D12Resource,
+        depth_stencil_view: D3D12_CPU_DESCRIPTOR_HANDLE,
+    ) -> Result<Resources> {
+        let sampler_descriptor_heap = create_samplers(device)?;
+        let sampler_descriptor_table = sampler_descriptor_heap.start_gpu_handle();
+
+        let mut uploads = UploadBatch::new(device);
+        let textures = load_textures(device, &mut uploads, &scene.textures)?;
+        create_material_views(device, &gpu_descriptor_heap, scene, &textures);
+        let (geometry_buffer, index_data_offset) = load_geometry(device, &mut uploads, scene)?;
+        uploads.submit(command_queue)?.wait()?;
+        let geometry_va = unsafe { geometry_buffer.GetGPUVirtualAddress() };
+
+        let (root_signature, root_signature_layout, root_signature_blob) =
+            create_root_signature(device)?;
+        let input_layout = InputLayout::new(&scene.vertex_layout)?;
+        let (scene_pso, shadow_map_pso) = create_pipeline_states(
+            device,
+            &root_signature,
+            &root_signature_layout,
+            &root_signature_blob,
+            &input_layout.elements,
+        )?;
+
+        let descriptor_heaps = [
+            Some(gpu_descriptor_heap.heap.clone()),
+            Some(sampler_descriptor_heap.heap),
+        ];
+
+        Ok(Resources {
+            _textures: textures,
+            _depth_stencil: depth_stencil,
+            depth_stencil_view,
+            _geometry_buffer: geometry_buffer,
+            vertex_buffer_view: D3D12_VERTEX_BUFFER_VIEW {
+                BufferLocation: geometry_va,
+                SizeInBytes: scene.vertices.len() as u32,
+                StrideInBytes: scene.vertex_stride,
+            },
+            index_buffer_view: D3D12_INDEX_BUFFER_VIEW {
+                BufferLocation: geometry_va + index_data_offset as u64,
+                SizeInBytes: scene.indices.len() as u32,
+                Format: scene.index_format,
+            },
+            draws: scene.draws.clone(),
+            root_signature,
+            gpu_descriptor_heap,
+            descriptor_heaps,
+            sampler_descriptor_table,
+            null_srv_table,
+            scene_pso,
+            shadow_map_pso,
+        })
+    }
+
+    pub fn set_common_pipeline_state(
+        &self,
+        cl: &ID3D12GraphicsCommandList,
+        viewport: D3D12_VIEWPORT,
+        scissor_rect: RECT, // TODO: where is D3D12_RECT?
+    ) {
+        unsafe {
+            cl.SetGraphicsRootSignature(&self.root_signature);
+
+            cl.SetDescriptorHeaps(&self.descriptor_heaps);
+
+            cl.RSSetViewports(&[viewport]);
+            cl.RSSetScissorRects(&[scissor_rect]);
+            cl.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            cl.IASetVertexBuffers(0, Some(&[self.vertex_buffer_view]));
+            cl.IASetIndexBuffer(Some(&self.index_buffer_view));
+            cl.SetGraphicsRootDescriptorTable(3, self.sampler_descriptor_table);
+
+            // Render targets and depth stencil are set elsewhere because the
+            // depth stencil depends on the frame resource being used.
+
+            // Constant buffers are set elsewhere because they depend on the
+            // frame resource being used.
+
+            // SRVs are set elsewhere because they change based on the object
+            // being drawn.
+        }
+    }
+
+    pub fn draw(
+        &self,
+        cl: &ID3D12GraphicsCommandList,
+        task_index: usize,
+        num_tasks: usize,
+        set_srvs: bool,
+    ) {
+        for draw in self.draws.iter().skip(task_index).step_by(num_tasks) {
+            if set_srvs {
+                unsafe {
+                    cl.SetGraphicsRootDescriptorTable(
+                        0,
+                        self.gpu_descriptor_heap.get_gpu_descriptor_handle(
+                            draw.material as usize * DESCRIPTORS_PER_MATERIAL,
+                        ),
+                    );
+                }
+            }
+
+            unsafe {
+                cl.DrawIndexedInstanced(draw.index_count, 1, draw.index_start, draw.vertex_base, 0);
+            }
+        }
+    }
+}
+
+fn create_samplers(device: &ID3D12Device) -> Result<SamplerDescriptorHeap> {
+    unsafe {
+        let sampler_descriptor_heap =
+            SamplerDescriptorHeap::new(device, 2, D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE)?;
+
+        let address_mode = |m| D3D12_SAMPLER_DESC {
+            AddressU: m,
+            AddressV: m,
+            AddressW: m,
+            MaxLOD: D3D12_FLOAT32_MAX,
+            MaxAnisotropy: 1,
+            ComparisonFunc: D3D12_COMPARISON_FUNC_ALWAYS,
+            ..Default::default()
+        };
+
+        // Describe and create the wrapping sampler, which is used for
+        // sampling diffuse/normal maps.
+        device.CreateSampler(
+            &D3D12_SAMPLER_DESC {
+                Filter: D3D12_FILTER_MIN_MAG_MIP_LINEAR,
+                ..address_mode(D3D12_TEXTURE_ADDRESS_MODE_WRAP)
+            },
+            sampler_descriptor_heap.get_cpu_descriptor_handle(0),
+        );
+
+        // Describe and create the point clamping sampler, which is used for
+        // the shadow map.
+        device.CreateSampler(
+            &D3D12_SAMPLER_DESC {
+                Filter: D3D12_FILTER_MIN_MAG_MIP_POINT,
+                ..address_mode(D3D12_TEXTURE_ADDRESS_MODE_CLAMP)
+            },
+            sampler_descriptor_heap.get_cpu_descriptor_handle(1),
+        );
+
+        Ok(sampler_descriptor_heap)
+    }
+}
+
+/// Creates every texture, and queues uploads of all of its mips.
+fn load_textures(
+    device: &ID3D12Device,
+    uploads: &mut UploadBatch,
+    textures: &[Texture],
+) -> Result<Vec<ID3D12Resource>> {
+    textures
+        .iter()
+        .map(|texture| {
+            let desc = D3D12_RESOURCE_DESC {
+                MipLevels: texture.subresources.len() as u16,
+                ..D3D12_RESOURCE_DESC::tex2d(texture.format, texture.width as u64, texture.height)
+            };
+            let mut resource = None;
+            let resource: ID3D12Resource = unsafe {
+                device.CreateCommittedResource(
+                    &HeapProperties::default(),
+                    D3D12_HEAP_FLAG_NONE,
+                    &desc,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                    None,
+                    &mut resource,
+                )
+            }
+            .and(Ok(resource.unwrap()))?;
+
+            let subresources: Vec<_> = texture.subresources.iter().map(|s| s.as_data()).collect();
+            uploads.upload_texture(
+                &resource,
+                0,
+                &subresources,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+            )?;
+            Ok(resource)
+        })
+        .collect()
+}
+
+/// Fills in each material's descriptors. Textures a material does not have
+/// get null descriptors, which read as zero.
+fn create_material_views(
+    device: &ID3D12Device,
+    descriptor_heap: &CbvSrvUavDescriptorHeap,
+    scene: &Scene,
+    textures: &[ID3D12Resource],
+) {
+    for (index, material) in scene.materials.iter().enumerate() {
+        for (slot, texture) in [material.diffuse, material.normal].iter().enumerate() {
+            let format = texture.map_or(DXGI_FORMAT_R8G8B8A8_UNORM, |t| {
+                scene.textures[t as usize].format
+            });
+            let mip_levels = texture.map_or(1, |t| scene.textures[t as usize].subresources.len());
+            let desc = D3D12_SHADER_RESOURCE_VIEW_DESC::texture2d(
+                format,
+                D3D12_TEX2D_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: mip_levels as u32,
+                    PlaneSlice: 0,
+                    ResourceMinLODClamp: 0.0,
+                },
+            );
+            unsafe {
+                descriptor_heap.create_shader_resource_view(
+                    device,
+                    texture.map(|t| &textures[t as usize]),
+                    Some(&desc),
+                    index * DESCRIPTORS_PER_MATERIAL + slot,
+                );
+            }
+        }
+    }
+}
+
+fn load_geometry(
+    device: &ID3D12Device,
+    uploads: &mut UploadBatch,
+    scene: &Scene,
+) -> Result<(ID3D12Resource, usize)> {
+    // Index buffers have to be aligned to the size of an index.
+    let index_data_offset = scene.vertices.len().div_ceil(4) * 4;
+    let buffer_size = index_data_offset + scene.indices.len();
+
+    let mut geometry_buffer = None;
+    let geometry_buffer: ID3D12Resource = unsafe {
+        device.CreateCommittedResource(
+            &HeapProperties::default(),
+            D3D12_HEAP_FLAG_NONE,
+            &D3D12_RESOURCE_DESC::buffer(buffer_size),
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            None,
+            &mut geometry_buffer,
+        )
+    }
+    .and(Ok(geometry_buffer.unwrap()))?;
+
+    // Vertices first, then indices, as the buffer views expect
+    let state_after =
+        D3D12_RESOURCE_STATE_VERTEX_AND_CONSTANT_BUFFER | D3D12_RESOURCE_STATE_INDEX_BUFFER;
+    uploads.upload_buffer(&geometry_buffer, 0, &scene.vertices, state_after);
+    uploads.upload_buffer(
+        &geometry_buffer,
+        index_data_offset as u64,
+        &scene.indices,
+        state_after,
+    );
+    Ok((geometry_buffer, index_data_offset))
+}
+
+#[repr(C)] // This has the same repr as D3D12_ROOT_PARAMETER1
+#[allow(non_camel_case_types)]
+struct D3D12_ROOT_PARAMETER1_WRAPPER<'a> {
+    value: D3D12_ROOT_PARAMETER1, // <-- this does not have a lifetime, but we have safely hidden it
+    lifetime: core::marker::PhantomData<&'a [D3D12_DESCRIPTOR_RANGE1]>, // <-- this pretends to hold the lifetime
+}
+
+/// Also returns the serialized root signature, which identifies it in the
+/// pipeline cache.
+fn create_root_signature(
+    device: &ID3D12Device,
+) -> Result<(ID3D12RootSignature, RootSignatureLayout, Vec<u8>)> {
+    // 2 frequently changed diffuse + normal textures - using registers t1 and t2.
+    let diffuse_normal_srv_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: 2,
+        BaseShaderRegister: 1,
+        RegisterSpace: 0,
+        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_DATA_STATIC,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+
+    // 1 frequently changed constant buffer.
+    let cbv_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
+        NumDescriptors: 1,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_DATA_STATIC,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+
+    // 1 infrequently changed shadow texture - starting in register t0.
+    let shadow_srv_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        NumDescriptors: 1,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_NONE,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+
+    // 2 static samplers.
+    let samplers_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
+        NumDescriptors: 2,
+        BaseShaderRegister: 0,
+        RegisterSpace: 0,
+        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_NONE,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+    }];
+
+    fn descriptor_table(
+        ranges: &mut [D3D12_DESCRIPTOR_RANGE1],
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) -> D3D12_ROOT_PARAMETER1_WRAPPER<'_> {
+        D3D12_ROOT_PARAMETER1_WRAPPER {
+            value: D3D12_ROOT_PARAMETER1 {
+                ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+                ShaderVisibility: visibility,
+                Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                    DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE1 {
+                        NumDescriptorRanges: ranges.len() as u32,
+                        pDescriptorRanges: ranges.as_mut_ptr(),
+                    },
+                },
+            },
+            lifetime: core::marker::PhantomData, // make believe
+        }
+    }
+
+    let root_parameters = &mut [
+        descriptor_table(diffuse_normal_srv_range, D3D12_SHADER_VISIBILITY_PIXEL),
+        descriptor_table(cbv_range, D3D12_SHADER_VISIBILITY_ALL),
+        descriptor_table(shadow_srv_range, D3D12_SHADER_VISIBILITY_PIXEL),
+        descriptor_table(samplers_range, D3D12_SHADER_VISIBILITY_PIXEL),
+    ];
+
+    let desc = D3D12_VERSIONED_ROOT_SIGNATURE_DESC {
+        Version: D3D_ROOT_SIGNATURE_VERSION_1_1,
+        Anonymous: D3D12_VERSIONED_ROOT_SIGNATURE_DESC_0 {
+            Desc_1_1: D3D12_ROOT_SIGNATURE_DESC1 {
+                NumParameters: root_parameters.len() as u32,
+                pParameters: root_parameters.as_ptr() as _,
+                NumStaticSamplers: 0,
+                pStaticSamplers: std::ptr::null_mut(),
+                Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+            },
+        },
+    };
+
+    // Record the layout while the ranges it points at are still alive, so the
+    // pipeline states can be checked against it.
+    let layout = unsafe { RootSignatureLayout::from_versioned_desc(&desc) };
+
+    let mut signature: Option<ID3DBlob> = None;
+    let mut error: Option<ID3DBlob> = None;
+
+    unsafe {
+        D3D12SerializeVersionedRootSignature(&desc, &mut signature, Some(&mut error))?;
+        let signature = signature.expect("root signature");
+        let root_signature = device.CreateRootSignature(0, blob_bytes(&signature))?;
+        Ok((root_signature, layout, blob_bytes(&signature).to_vec()))
+    }
+}
+
+fn create_pipeline_states(
+    device: &ID3D12Device,
+    root_signature: &ID3D12RootSignature,
+    root_signature_layout: &RootSignatureLayout,
+    root_signature_blob: &[u8],
+    input_layout: &[D3D12_INPUT_ELEMENT_DESC],
+) -> Result<(ID3D12PipelineState, ID3D12PipelineState)> {
+    let shaders = embed_shaders!("src/rendering", ["multithreading-shaders.hlsl"]);
+    let shaders_hlsl_path = "multithreading-shaders.hlsl";
+
+    // Compiling the shaders and pipelines dominates startup, so both are
+    // cached across runs.
+    let compiler = CachedCompiler::with_default_cache(FxcCompiler);
+    let mut pipeline_cache = PipelineCache::with_default_cache(device);
+
+    let mut vertex_shaders = ShaderPermutations::new(
+        &compiler,
+        ShaderSource::Virtual {
+            files: &shaders,
+            path: shaders_hlsl_path,
+        },
+        CompileOptions::new("VSMain", "vs_5_0"),
+    );
+    vertex_shaders.compile_all(&[ScenePass::Scene, ScenePass::Shadow])?;
+    let vertex_shader = vertex_shaders.get(&ScenePass::Scene).unwrap();
+    let shadow_vertex_shader = vertex_shaders.get(&ScenePass::Shadow).unwrap();
+
+    let pixel_shader = compiler.compile_virtual(
+        &shaders,
+        shaders_hlsl_path,
+        &CompileOptions::new("PSMain", "ps_5_0"),
+    )?;
+
+    // Catch mismatches between the shaders, the scene's vertex layout and the
+    // root signature here rather than as debug layer messages at draw time.
+    let layout = GraphicsPipelineLayout {
+        input_layout,
+        root_signature: root_signature_layout,
+        constant_buffers: &[ConstantBufferBinding {
+            space: 0,
+            register: 0,
+            size: std::mem::size_of::<super::SceneConstantBuffer>() as u32,
+        }],
+    };
+    let mut errors = validate_graphics_pipeline(
+        &[
+            &reflect_shader(vertex_shader)?,
+            &reflect_shader(&pixel_shader)?,
+        ],
+        &layout,
+    );
+    errors.extend(validate_graphics_pipeline(
+        &[&reflect_shader(shadow_vertex_shader)?],
+        &layout,
+    ));
+    if !errors.is_empty() {
+        let message: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        return Err(Error::new(E_INVALIDARG, message.join("\n")));
+    }
+
+    let default_stencil_op = D3D12_DEPTH_STENCILOP_DESC {
+        StencilFailOp: D3D12_STENCIL_OP_KEEP,
+        StencilDepthFailOp: D3D12_STENCIL_OP_KEEP,
+        StencilPassOp: D3D12_STENCIL_OP_KEEP,
+        StencilFunc: D3D12_COMPARISON_FUNC_ALWAYS,
+    };
+
+    let input_layout = D3D12_INPUT_LAYOUT_DESC {
+        pInputElementDescs: input_layout.as_ptr() as _,
+        NumElements: input_layout.len() as u32,
+    };
+
+    let pso_desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
+        pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
+        VS: D3D12_SHADER_BYTECODE::from_bytes(&vertex_shader.bytecode),
+        PS: D3D12_SHADER_BYTECODE::from_bytes(&pixel_shader.bytecode),
+        BlendState: D3D12_BLEND_DESC::reasonable_default(),
+        SampleMask: u32::MAX,
+        RasterizerState: D3D12_RASTERIZER_DESC::reasonable_default(),
+        DepthStencilState: D3D12_DEPTH_STENCIL_DESC {
+            DepthEnable: true.into(),
+            DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ALL,
+            DepthFunc: D3D12_COMPARISON_FUNC_LESS_EQUAL,
+            StencilEnable: false.into(),
+            StencilReadMask: D3D12_DEFAULT_STENCIL_READ_MASK as u8,
+            StencilWriteMask: D3D12_DEFAULT_STENCIL_WRITE_MASK as u8,
+            FrontFace: default_stencil_op,
+            BackFace: default_stencil_op,
+        },
+        InputLayout: input_layout,
+        PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
+        NumRenderTargets: 1,
+        RTVFormats: array_init(|i| {
+            if i == 0 {
+                DXGI_FORMAT_R8G8B8A8_UNORM
+            } else {
+                DXGI_FORMAT_UNKNOWN
+            }
+        }),
+        DSVFormat: DXGI_FORMAT_D32_FLOAT,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        ..Default::default()
+    };
+
+    let pso = unsafe { pipeline_cache.create_graphics_pipeline(&pso_desc, root_signature_blob) }?;
+
+    // Alter the description and create the PSO for rendering the shadow map.
+    // The shadow map does not use a pixel shader or render targets.
+    let pso_shadow_desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
+        VS: D3D12_SHADER_BYTECODE::from_bytes(&shadow_vertex_shader.bytecode),
+        PS: Default::default(),
+        NumRenderTargets: 0,
+        RTVFormats: [DXGI_FORMAT_UNKNOWN; 8],
+        ..pso_desc
+    };
+
+    let pso_shadow =
+        unsafe { pipeline_cache.create_graphics_pipeline(&pso_shadow_desc, root_signature_blob) }?;
+
+    // Failing to write the cache only costs time on the next run.
+    if let Err(e) = pipeline_cache.save() {
+        eprintln!("failed to save the pipeline cache: {}", e);
+    }
+
+    Ok((pso, pso_shadow))
+}
+
+/// Which pass a vertex shader is for. The shadow pass only needs positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ScenePass {
+    Scene,
+    Shadow,
+}
+
+impl PermutationKey for ScenePass {
+    fn defines(&self) -> Vec<(String, String)> {
+        match self {
+            ScenePass::Scene => Vec::new(),
+            ScenePass::Shadow => vec![("SHADOW_PASS".to_string(), "1".to_string())],
+        }
+    }
+}
+
+fn reflect_shader(shader: &CompiledShader) -> Result<dxbc::ShaderReflection> {
+    shader
+        .reflect()
+        .map_err(|e| Error::new(E_FAIL, e.to_string()))
+}
+
+/// Input element descriptions for a scene's vertex layout, along with the
+/// semantic names they point at.
+struct InputLayout {
+    _names: Vec<CString>,
+    elements: Vec<D3D12_INPUT_ELEMENT_DESC>,
+}
+
+impl InputLayout {
+    fn new(vertex_layout: &[VertexElement]) -> Result<Self> {
+        let names = vertex_layout
+            .iter()
+            .map(|element| {
+                CString::new(element.semantic_name.as_str()).map_err(|_| {
+                    Error::new(
+                        E_INVALIDARG,
+                        format!("bad semantic name '{}'", element.semantic_name),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let elements = vertex_layout
+            .iter()
+            .zip(&names)
+            .map(|(element, name)| D3D12_INPUT_ELEMENT_DESC {
+                SemanticName: PCSTR(name.as_ptr() as _),
+                SemanticIndex: element.semantic_index,
+                Format: element.format,
+                InputSlot: 0,
+                AlignedByteOffset: element.offset,
+                InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+                InstanceDataStepRate: 0,
+            })
+            .collect();
+
+        Ok(InputLayout {
+            _names: names,
+            elements,
+        })
+    }
+}
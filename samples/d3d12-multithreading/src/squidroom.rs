@@ -1,699 +1,32 @@
-use array_init::{array_init, try_array_init};
-use d3dx12::*;
-use dxsample::SynchronizedCommandQueue;
-use std::env;
-use std::{fs::File, os::windows::prelude::FileExt};
-use windows::{
-    core::*,
-    Win32::{
-        Foundation::RECT,
-        Graphics::{
-            Direct3D::{
-                Fxc::{
-                    D3DCompileFromFile, D3DCOMPILE_DEBUG, D3DCOMPILE_OPTIMIZATION_LEVEL3,
-                    D3DCOMPILE_SKIP_OPTIMIZATION,
-                },
-                ID3DBlob, D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
-            },
-            Direct3D12::*,
-            Dxgi::Common::*,
-        },
-    },
+//! The layout of SquidRoom.bin, which has no header of its own: generated
+//! tables of where each texture is and how the geometry is drawn.
+
+use d3dx12::raw_scene::{RawDraw, RawSceneLayout, RawTexture};
+use d3dx12::scene::{standard_vertex_layout, Scene, SceneError, STANDARD_VERTEX_STRIDE};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
 };
+use windows::Win32::Graphics::Dxgi::Common::*;
 
-const DATA_FILE_NAME: &str = "SquidRoom.bin";
-
-pub struct Resources {
-    _textures: [ID3D12Resource; TEXTURE_COUNT],
-    pub _depth_stencil: ID3D12Resource,
-    pub depth_stencil_view: D3D12_CPU_DESCRIPTOR_HANDLE,
-    _geometry_buffer: ID3D12Resource,
-    vertex_buffer_view: D3D12_VERTEX_BUFFER_VIEW,
-    index_buffer_view: D3D12_INDEX_BUFFER_VIEW,
-    root_signature: ID3D12RootSignature,
-    gpu_descriptor_heap: CbvSrvUavDescriptorHeap,
-    descriptor_heaps: [Option<ID3D12DescriptorHeap>; 2],
-    sampler_descriptor_table: D3D12_GPU_DESCRIPTOR_HANDLE,
-    pub null_srv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
-    pub scene_pso: ID3D12PipelineState,
-    pub shadow_map_pso: ID3D12PipelineState,
-}
-
-pub const TEXTURE_COUNT: usize = TEXTURES.len();
-
-impl Resources {
-    pub fn new(
-        device: &ID3D12Device,
-        command_queue: &mut SynchronizedCommandQueue,
-        gpu_descriptor_heap: CbvSrvUavDescriptorHeap,
-        null_srv_table: D3D12_GPU_DESCRIPTOR_HANDLE,
-        depth_stencil: ID3D12Resource,
-        depth_stencil_view: D3D12_CPU_DESCRIPTOR_HANDLE,
-    ) -> Result<Resources> {
-        let sampler_descriptor_heap = create_samplers(device)?;
-        let sampler_descriptor_table = sampler_descriptor_heap.start_gpu_handle();
-
-        let mut file_path = env::current_exe().expect("failed to get current executable path");
-        file_path.pop(); // Remove the executable name from the path
-        file_path.push(DATA_FILE_NAME);
-
-        let file = File::open(file_path).expect("failed to open data file");
-
-        let textures = load_textures(device, command_queue, &gpu_descriptor_heap, &file)?;
-        let geometry_buffer = load_geometry(device, command_queue, &file)?;
-        let geometry_va = unsafe { geometry_buffer.GetGPUVirtualAddress() };
-
-        let root_signature = create_root_signature(device)?;
-        let (scene_pso, shadow_map_pso) = create_pipeline_states(device, &root_signature)?;
-
-        let descriptor_heaps = [
-            Some(gpu_descriptor_heap.heap.clone()),
-            Some(sampler_descriptor_heap.heap),
-        ];
-
-        Ok(Resources {
-            _textures: textures,
-            _depth_stencil: depth_stencil,
-            depth_stencil_view,
-            _geometry_buffer: geometry_buffer,
-            vertex_buffer_view: D3D12_VERTEX_BUFFER_VIEW {
-                BufferLocation: geometry_va,
-                SizeInBytes: VERTEX_DATA_SIZE as u32,
-                StrideInBytes: STANDARD_VERTEX_STRIDE,
-            },
-            index_buffer_view: D3D12_INDEX_BUFFER_VIEW {
-                BufferLocation: geometry_va + VERTEX_DATA_SIZE as u64,
-                SizeInBytes: INDEX_DATA_SIZE as u32,
-                Format: STANDARD_INDEX_FORMAT,
-            },
-            root_signature,
-            gpu_descriptor_heap,
-            descriptor_heaps,
-            sampler_descriptor_table,
-            null_srv_table,
-            scene_pso,
-            shadow_map_pso,
-        })
-    }
-
-    pub fn set_common_pipeline_state(
-        &self,
-        cl: &ID3D12GraphicsCommandList,
-        viewport: D3D12_VIEWPORT,
-        scissor_rect: RECT, // TODO: where is D3D12_RECT?
-    ) {
-        unsafe {
-            cl.SetGraphicsRootSignature(&self.root_signature);
-
-            cl.SetDescriptorHeaps(&self.descriptor_heaps);
-
-            cl.RSSetViewports(&[viewport]);
-            cl.RSSetScissorRects(&[scissor_rect]);
-            cl.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-            cl.IASetVertexBuffers(0, Some(&[self.vertex_buffer_view]));
-            cl.IASetIndexBuffer(Some(&self.index_buffer_view));
-            cl.SetGraphicsRootDescriptorTable(3, self.sampler_descriptor_table);
-
-            // Render targets and depth stencil are set elsewhere because the
-            // depth stencil depends on the frame resource being used.
-
-            // Constant buffers are set elsewhere because they depend on the
-            // frame resource being used.
-
-            // SRVs are set elsewhere because they change based on the object
-            // being drawn.
-        }
-    }
-
-    pub fn draw(
-        &self,
-        cl: &ID3D12GraphicsCommandList,
-        task_index: usize,
-        num_tasks: usize,
-        set_srvs: bool,
-    ) {
-        for i in (task_index..DRAWS.len()).step_by(num_tasks) {
-            let params = &DRAWS[i];
-            if set_srvs {
-                unsafe {
-                    cl.SetGraphicsRootDescriptorTable(
-                        0,
-                        self.gpu_descriptor_heap
-                            .get_gpu_descriptor_handle(params.diffuse_texture_index),
-                    );
-                }
-            }
-
-            unsafe {
-                cl.DrawIndexedInstanced(
-                    params.index_count,
-                    1,
-                    params.index_start,
-                    params.vertex_base,
-                    0,
-                );
-            }
-        }
-    }
-}
-
-fn create_samplers(device: &ID3D12Device) -> Result<SamplerDescriptorHeap> {
-    unsafe {
-        let sampler_descriptor_heap =
-            SamplerDescriptorHeap::new(device, 2, D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE)?;
-
-        let address_mode = |m| D3D12_SAMPLER_DESC {
-            AddressU: m,
-            AddressV: m,
-            AddressW: m,
-            MaxLOD: D3D12_FLOAT32_MAX,
-            MaxAnisotropy: 1,
-            ComparisonFunc: D3D12_COMPARISON_FUNC_ALWAYS,
-            ..Default::default()
-        };
-
-        // Describe and create the wrapping sampler, which is used for
-        // sampling diffuse/normal maps.
-        device.CreateSampler(
-            &D3D12_SAMPLER_DESC {
-                Filter: D3D12_FILTER_MIN_MAG_MIP_LINEAR,
-                ..address_mode(D3D12_TEXTURE_ADDRESS_MODE_WRAP)
-            },
-            sampler_descriptor_heap.get_cpu_descriptor_handle(0),
-        );
-
-        // Describe and create the point clamping sampler, which is used for
-        // the shadow map.
-        device.CreateSampler(
-            &D3D12_SAMPLER_DESC {
-                Filter: D3D12_FILTER_MIN_MAG_MIP_POINT,
-                ..address_mode(D3D12_TEXTURE_ADDRESS_MODE_CLAMP)
-            },
-            sampler_descriptor_heap.get_cpu_descriptor_handle(1),
-        );
-
-        Ok(sampler_descriptor_heap)
-    }
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+    read(&mut BufReader::new(File::open(path)?))
 }
 
-fn load_textures(
-    device: &ID3D12Device,
-    command_queue: &mut SynchronizedCommandQueue,
-    descriptor_heap: &CbvSrvUavDescriptorHeap,
-    file: &File,
-) -> Result<[ID3D12Resource; TEXTURE_COUNT]> {
-    let mut upload_buffer_size = 0;
-    let data: [_; TEXTURE_COUNT] = array_init(|i| {
-        // make sure we're aligned
-        const ALIGNMENT: u64 = D3D12_TEXTURE_DATA_PLACEMENT_ALIGNMENT as u64;
-        if upload_buffer_size % ALIGNMENT != 0 {
-            upload_buffer_size = ((upload_buffer_size / ALIGNMENT) + 1) * ALIGNMENT;
-        }
-        let texture = &TEXTURES[i];
-        let desc = D3D12_RESOURCE_DESC::tex2d(texture.format, texture.width, texture.height);
-        let mut layout = D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default();
-        let mut total_bytes = 0;
-        let mut num_rows = 0;
-        unsafe {
-            device.GetCopyableFootprints(
-                &desc,
-                0,
-                1,
-                upload_buffer_size,
-                Some(&mut layout),
-                Some(&mut num_rows),
-                None,
-                Some(&mut total_bytes),
-            );
-        }
-        upload_buffer_size += total_bytes;
-        (texture, desc, layout, num_rows, total_bytes)
-    });
-
-    let mut upload_buffer = None;
-    let upload_buffer: ID3D12Resource = unsafe {
-        device.CreateCommittedResource(
-            &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
-            D3D12_HEAP_FLAG_NONE,
-            &D3D12_RESOURCE_DESC::buffer(upload_buffer_size as usize),
-            D3D12_RESOURCE_STATE_GENERIC_READ,
-            None,
-            &mut upload_buffer,
-        )
-    }
-    .and(Ok(upload_buffer.unwrap()))?;
-
-    // Populate the upload buffer with data from the file
-    let mut ptr = std::ptr::null_mut();
-    unsafe {
-        upload_buffer.Map(0, Some(&D3D12_RANGE { Begin: 0, End: 0 }), Some(&mut ptr))?;
-    }
-    let ptr: *mut u8 = ptr.cast();
-
-    for (texture, _, layout, num_rows, total_bytes) in data.iter() {
-        let buf = unsafe {
-            std::slice::from_raw_parts_mut(
-                ptr.offset(layout.Offset as isize),
-                *total_bytes as usize,
-            )
-        };
-
-        let footprint = &layout.Footprint;
-
-        if texture.pitch == footprint.RowPitch {
-            // If the pitches match we can read it in one go
-            file.seek_read(buf, texture.offset as u64)
-                .expect("seek_read");
-        } else {
-            // The pitches don't match so we need to read a row at a time
-            for i in 0..*num_rows {
-                let row_read_pos = (texture.offset + i * texture.pitch) as u64;
-                let row_write_pos = layout.Offset as isize + (i * footprint.RowPitch) as isize;
-
-                let buf = unsafe {
-                    std::slice::from_raw_parts_mut(
-                        ptr.offset(row_write_pos),
-                        texture.pitch as usize,
-                    )
-                };
-
-                let pad_bytes = (footprint.RowPitch - texture.pitch) as usize;
-                let pad = unsafe {
-                    std::slice::from_raw_parts_mut(
-                        ptr.offset(row_write_pos + texture.pitch as isize),
-                        pad_bytes,
-                    )
-                };
-
-                file.seek_read(buf, row_read_pos).expect("seek_read");
-                pad.fill(0);
-            }
-        }
-    }
-
-    unsafe {
-        upload_buffer.Unmap(0, None);
-    }
-
-    let allocator: ID3D12CommandAllocator =
-        unsafe { device.CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_DIRECT) }?;
-    let cl: ID3D12GraphicsCommandList =
-        unsafe { device.CreateCommandList(0, D3D12_COMMAND_LIST_TYPE_DIRECT, &allocator, None) }?;
-
-    let resources = try_array_init(|i| -> Result<ID3D12Resource> {
-        let (_, desc, layout, _, _) = &data[i];
-
-        let mut resource = None;
-        let resource: ID3D12Resource = unsafe {
-            device.CreateCommittedResource(
-                &HeapProperties::default(),
-                D3D12_HEAP_FLAG_NONE,
-                desc,
-                D3D12_RESOURCE_STATE_COPY_DEST,
-                None,
-                &mut resource,
-            )
-        }
-        .and(Ok(resource.unwrap()))?;
-
-        unsafe {
-            device.CreateShaderResourceView(
-                &resource,
-                Some(&D3D12_SHADER_RESOURCE_VIEW_DESC::texture2d(
-                    desc.Format,
-                    D3D12_TEX2D_SRV {
-                        MostDetailedMip: 0,
-                        MipLevels: 1,
-                        PlaneSlice: 0,
-                        ResourceMinLODClamp: 0.0,
-                    },
-                )),
-                descriptor_heap.get_cpu_descriptor_handle(i),
-            );
-        }
-
-        unsafe {
-            cl.CopyTextureRegion(
-                &D3D12_TEXTURE_COPY_LOCATION {
-                    pResource: std::mem::transmute_copy(&resource),
-                    Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
-                    Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
-                        SubresourceIndex: 0,
-                    },
-                },
-                0,
-                0,
-                0,
-                &D3D12_TEXTURE_COPY_LOCATION {
-                    pResource: std::mem::transmute_copy(&upload_buffer),
-                    Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
-                    Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
-                        PlacedFootprint: *layout,
-                    },
-                },
-                None,
-            );
-        }
-
-        Ok(resource)
-    })?;
-
-    unsafe {
-        let barriers: [D3D12_RESOURCE_BARRIER; TEXTURE_COUNT] =
-            array_init::from_iter(resources.iter().map(|r| {
-                transition_barrier(
-                    r,
-                    D3D12_RESOURCE_STATE_COPY_DEST,
-                    D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
-                )
-            }))
-            .expect("made barriers");
-
-        cl.ResourceBarrier(&barriers);
+/// Reads SquidRoom.bin, checking first that everything the tables describe
+/// is in the file.
+pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Scene, SceneError> {
+    RawSceneLayout {
+        textures: &TEXTURES,
+        draws: &DRAWS,
+        vertex_layout: standard_vertex_layout(),
+        vertex_stride: STANDARD_VERTEX_STRIDE,
+        index_format: DXGI_FORMAT_R32_UINT,
+        vertices: (VERTEX_DATA_OFFSET, VERTEX_DATA_SIZE),
+        indices: (INDEX_DATA_OFFSET, INDEX_DATA_SIZE),
     }
-
-    unsafe { cl.Close() }?;
-    command_queue.execute_command_lists(&[cl]);
-    command_queue.signal_and_wait_for_gpu()?;
-    Ok(resources)
-}
-
-fn load_geometry(
-    device: &ID3D12Device,
-    command_queue: &mut SynchronizedCommandQueue,
-    file: &File,
-) -> Result<ID3D12Resource> {
-    let buffer_size = VERTEX_DATA_SIZE + INDEX_DATA_SIZE;
-
-    let mut upload_buffer = None;
-    let upload_buffer: ID3D12Resource = unsafe {
-        device.CreateCommittedResource(
-            &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
-            D3D12_HEAP_FLAG_NONE,
-            &D3D12_RESOURCE_DESC::buffer(buffer_size),
-            D3D12_RESOURCE_STATE_GENERIC_READ,
-            None,
-            &mut upload_buffer,
-        )
-    }
-    .and(Ok(upload_buffer.unwrap()))?;
-
-    // Load all the geometry data from the file
-    unsafe {
-        let mut ptr = std::ptr::null_mut();
-        upload_buffer.Map(0, Some(&D3D12_RANGE { Begin: 0, End: 0 }), Some(&mut ptr))?;
-
-        std::assert_eq!(VERTEX_DATA_OFFSET + VERTEX_DATA_SIZE, INDEX_DATA_OFFSET);
-        file.seek_read(
-            std::slice::from_raw_parts_mut(ptr as _, buffer_size),
-            VERTEX_DATA_OFFSET as u64,
-        )
-        .expect("read geometry data");
-
-        upload_buffer.Unmap(0, None);
-    }
-
-    // Copy this to VRAM
-    let mut geometry_buffer = None;
-    let geometry_buffer: ID3D12Resource = unsafe {
-        device.CreateCommittedResource(
-            &HeapProperties::default(),
-            D3D12_HEAP_FLAG_NONE,
-            &D3D12_RESOURCE_DESC::buffer(buffer_size),
-            D3D12_RESOURCE_STATE_COMMON,
-            None,
-            &mut geometry_buffer,
-        )
-    }
-    .and(Ok(geometry_buffer.unwrap()))?;
-
-    unsafe {
-        let allocator: ID3D12CommandAllocator =
-            device.CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_DIRECT)?;
-        let cl: ID3D12GraphicsCommandList =
-            device.CreateCommandList(0, D3D12_COMMAND_LIST_TYPE_DIRECT, &allocator, None)?;
-
-        cl.CopyResource(&geometry_buffer, &upload_buffer);
-        cl.Close()?;
-
-        command_queue.execute_command_lists(&[cl]);
-        command_queue.signal_and_wait_for_gpu()?;
-    }
-    Ok(geometry_buffer)
-}
-
-#[repr(C)] // This has the same repr as D3D12_ROOT_PARAMETER1
-#[allow(non_camel_case_types)]
-struct D3D12_ROOT_PARAMETER1_WRAPPER<'a> {
-    value: D3D12_ROOT_PARAMETER1, // <-- this does not have a lifetime, but we have safely hidden it
-    lifetime: core::marker::PhantomData<&'a [D3D12_DESCRIPTOR_RANGE1]>, // <-- this pretends to hold the lifetime
-}
-
-fn create_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSignature> {
-    // 2 frequently changed diffuse + normal textures - using registers t1 and t2.
-    let diffuse_normal_srv_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
-        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
-        NumDescriptors: 2,
-        BaseShaderRegister: 1,
-        RegisterSpace: 0,
-        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_DATA_STATIC,
-        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
-    }];
-
-    // 1 frequently changed constant buffer.
-    let cbv_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
-        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
-        NumDescriptors: 1,
-        BaseShaderRegister: 0,
-        RegisterSpace: 0,
-        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_DATA_STATIC,
-        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
-    }];
-
-    // 1 infrequently changed shadow texture - starting in register t0.
-    let shadow_srv_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
-        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
-        NumDescriptors: 1,
-        BaseShaderRegister: 0,
-        RegisterSpace: 0,
-        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_NONE,
-        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
-    }];
-
-    // 2 static samplers.
-    let samplers_range = &mut [D3D12_DESCRIPTOR_RANGE1 {
-        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
-        NumDescriptors: 2,
-        BaseShaderRegister: 0,
-        RegisterSpace: 0,
-        Flags: D3D12_DESCRIPTOR_RANGE_FLAG_NONE,
-        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
-    }];
-
-    fn descriptor_table(
-        ranges: &mut [D3D12_DESCRIPTOR_RANGE1],
-        visibility: D3D12_SHADER_VISIBILITY,
-    ) -> D3D12_ROOT_PARAMETER1_WRAPPER {
-        D3D12_ROOT_PARAMETER1_WRAPPER {
-            value: D3D12_ROOT_PARAMETER1 {
-                ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
-                ShaderVisibility: visibility,
-                Anonymous: D3D12_ROOT_PARAMETER1_0 {
-                    DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE1 {
-                        NumDescriptorRanges: ranges.len() as u32,
-                        pDescriptorRanges: ranges.as_mut_ptr(),
-                    },
-                },
-            },
-            lifetime: core::marker::PhantomData, // make believe
-        }
-    }
-
-    let root_parameters = &mut [
-        descriptor_table(diffuse_normal_srv_range, D3D12_SHADER_VISIBILITY_PIXEL),
-        descriptor_table(cbv_range, D3D12_SHADER_VISIBILITY_ALL),
-        descriptor_table(shadow_srv_range, D3D12_SHADER_VISIBILITY_PIXEL),
-        descriptor_table(samplers_range, D3D12_SHADER_VISIBILITY_PIXEL),
-    ];
-
-    let desc = D3D12_VERSIONED_ROOT_SIGNATURE_DESC {
-        Version: D3D_ROOT_SIGNATURE_VERSION_1_1,
-        Anonymous: D3D12_VERSIONED_ROOT_SIGNATURE_DESC_0 {
-            Desc_1_1: D3D12_ROOT_SIGNATURE_DESC1 {
-                NumParameters: root_parameters.len() as u32,
-                pParameters: root_parameters.as_ptr() as _,
-                NumStaticSamplers: 0,
-                pStaticSamplers: std::ptr::null_mut(),
-                Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
-            },
-        },
-    };
-
-    let mut signature: Option<ID3DBlob> = None;
-    let mut error: Option<ID3DBlob> = None;
-
-    unsafe {
-        D3D12SerializeVersionedRootSignature(&desc, &mut signature, Some(&mut error))?;
-        let signature = signature.expect("root signature");
-        let root_signature = device.CreateRootSignature(
-            0,
-            std::slice::from_raw_parts(
-                signature.GetBufferPointer() as _,
-                signature.GetBufferSize(),
-            ),
-        )?;
-        Ok(root_signature)
-    }
-}
-
-fn create_pipeline_states(
-    device: &ID3D12Device,
-    root_signature: &ID3D12RootSignature,
-) -> Result<(ID3D12PipelineState, ID3D12PipelineState)> {
-    let compile_flags = if cfg!(debug_assertions) {
-        // Enable better shader debugging with the graphics debugging tools.
-        D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION
-    } else {
-        D3DCOMPILE_OPTIMIZATION_LEVEL3
-    };
-
-    let exe_path = std::env::current_exe().ok().unwrap();
-    let asset_path = exe_path.parent().unwrap();
-    let shaders_hlsl_path = asset_path.join("multithreading-shaders.hlsl");
-    let shaders_hlsl: HSTRING = shaders_hlsl_path.to_str().unwrap().into();
-
-    let mut vertex_shader = None;
-    let vertex_shader = unsafe {
-        D3DCompileFromFile(
-            &shaders_hlsl,
-            None,
-            None,
-            s!("VSMain"),
-            s!("vs_5_0"),
-            compile_flags,
-            0,
-            &mut vertex_shader,
-            None,
-        )
-    }
-    .and(Ok(vertex_shader.unwrap()))?;
-
-    let mut pixel_shader = None;
-    let pixel_shader = unsafe {
-        D3DCompileFromFile(
-            &shaders_hlsl,
-            None,
-            None,
-            s!("PSMain"),
-            s!("ps_5_0"),
-            compile_flags,
-            0,
-            &mut pixel_shader,
-            None,
-        )
-    }
-    .and(Ok(pixel_shader.unwrap()))?;
-
-    let default_stencil_op = D3D12_DEPTH_STENCILOP_DESC {
-        StencilFailOp: D3D12_STENCIL_OP_KEEP,
-        StencilDepthFailOp: D3D12_STENCIL_OP_KEEP,
-        StencilPassOp: D3D12_STENCIL_OP_KEEP,
-        StencilFunc: D3D12_COMPARISON_FUNC_ALWAYS,
-    };
-
-    // Make our own copy of elements, and keep input_layout in its own binding
-    // so that it remains valid for the duration of this function.
-    let mut elements = STANDARD_VERTEX_DESCRIPTION;
-    let input_layout = D3D12_INPUT_LAYOUT_DESC {
-        pInputElementDescs: elements.as_mut_ptr(),
-        NumElements: elements.len() as u32,
-    };
-
-    let pso_desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
-        pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
-        VS: D3D12_SHADER_BYTECODE::from_blob(&vertex_shader),
-        PS: D3D12_SHADER_BYTECODE::from_blob(&pixel_shader),
-        BlendState: D3D12_BLEND_DESC::reasonable_default(),
-        SampleMask: u32::MAX,
-        RasterizerState: D3D12_RASTERIZER_DESC::reasonable_default(),
-        DepthStencilState: D3D12_DEPTH_STENCIL_DESC {
-            DepthEnable: true.into(),
-            DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ALL,
-            DepthFunc: D3D12_COMPARISON_FUNC_LESS_EQUAL,
-            StencilEnable: false.into(),
-            StencilReadMask: D3D12_DEFAULT_STENCIL_READ_MASK as u8,
-            StencilWriteMask: D3D12_DEFAULT_STENCIL_WRITE_MASK as u8,
-            FrontFace: default_stencil_op,
-            BackFace: default_stencil_op,
-        },
-        InputLayout: input_layout,
-        PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
-        NumRenderTargets: 1,
-        RTVFormats: array_init(|i| {
-            if i == 0 {
-                DXGI_FORMAT_R8G8B8A8_UNORM
-            } else {
-                DXGI_FORMAT_UNKNOWN
-            }
-        }),
-        DSVFormat: DXGI_FORMAT_D32_FLOAT,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            Quality: 0,
-        },
-        ..Default::default()
-    };
-
-    let pso = unsafe { device.CreateGraphicsPipelineState(&pso_desc) }?;
-
-    // Alter the description and create the PSO for rendering the shadow map.
-    // The shadow map does not use a pixel shader or render targets.
-    let pso_shadow_desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
-        PS: Default::default(),
-        NumRenderTargets: 0,
-        RTVFormats: [DXGI_FORMAT_UNKNOWN; 8],
-        ..pso_desc
-    };
-
-    let pso_shadow = unsafe { device.CreateGraphicsPipelineState(&pso_shadow_desc) }?;
-
-    Ok((pso, pso_shadow))
-}
-
-macro_rules! input_element_desc {
-    { $( { $name:literal, $semantic_index:expr, $format:expr, $slot:expr, $offset:expr, $class:expr, $rate:expr } ),* }
-    => { [
-        $( D3D12_INPUT_ELEMENT_DESC{
-            SemanticName: s!($name),
-            SemanticIndex: $semantic_index,
-            Format: $format,
-            InputSlot: $slot,
-            AlignedByteOffset: $offset,
-            InputSlotClass: $class,
-            InstanceDataStepRate: $rate
-        },
-        )* ]
-    };
-}
-
-const STANDARD_VERTEX_DESCRIPTION: [D3D12_INPUT_ELEMENT_DESC; 4] = input_element_desc! {
-    { "POSITION", 0, DXGI_FORMAT_R32G32B32_FLOAT, 0, 0,  D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA, 0 },
-    { "NORMAL",   0, DXGI_FORMAT_R32G32B32_FLOAT, 0, 12, D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA, 0 },
-    { "TEXCOORD", 0, DXGI_FORMAT_R32G32_FLOAT,    0, 24, D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA, 0 },
-    { "TANGENT",  0, DXGI_FORMAT_R32G32B32_FLOAT, 0, 32, D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA, 0 }
-};
-
-const STANDARD_VERTEX_STRIDE: u32 = 44;
-
-const STANDARD_INDEX_FORMAT: DXGI_FORMAT = DXGI_FORMAT_R32_UINT;
-
-struct TextureResource {
-    width: u64,
-    height: u32,
-    format: DXGI_FORMAT,
-    offset: u32,
-    _size: u32,
-    pitch: u32,
+    .read(reader)
 }
 
 macro_rules! textures_array {
@@ -706,12 +39,11 @@ macro_rules! textures_array {
     {
         [
         $(
-            TextureResource {
+            RawTexture {
                 width: $width,
                 height: $height,
                 format: $format,
                 offset: $offset,
-                _size: $size,
                 pitch: $pitch
             }
 
@@ -720,13 +52,13 @@ macro_rules! textures_array {
     };
 }
 
-struct DrawParameters {
-    diffuse_texture_index: usize,
-    _normal_texture_index: isize,
-    _specular_texture_index: isize,
-    index_start: u32,
-    index_count: u32,
-    vertex_base: i32,
+/// The tables use -1 for draws without a normal or specular map.
+const fn texture_index(index: i32) -> Option<u32> {
+    if index < 0 {
+        None
+    } else {
+        Some(index as u32)
+    }
 }
 
 macro_rules! draws_array {
@@ -743,10 +75,10 @@ macro_rules! draws_array {
     =>
     {
         [ $(
-            DrawParameters {
-                diffuse_texture_index: $dti,
-                _normal_texture_index: $nti,
-                _specular_texture_index: $sti,
+            RawDraw {
+                diffuse: Some($dti),
+                normal: texture_index($nti),
+                specular: texture_index($sti),
                 index_start: $is,
                 index_count: $ic,
                 vertex_base: $vb,
@@ -755,12 +87,12 @@ macro_rules! draws_array {
     }
 }
 
-const VERTEX_DATA_OFFSET: usize = 30277640;
-const VERTEX_DATA_SIZE: usize = 9685808;
-const INDEX_DATA_OFFSET: usize = 39963448;
-const INDEX_DATA_SIZE: usize = 3056844;
+const VERTEX_DATA_OFFSET: u64 = 30277640;
+const VERTEX_DATA_SIZE: u64 = 9685808;
+const INDEX_DATA_OFFSET: u64 = 39963448;
+const INDEX_DATA_SIZE: u64 = 3056844;
 
-const TEXTURES: [TextureResource; 74] = textures_array! {
+const TEXTURES: [RawTexture; 74] = textures_array! {
     {   512,   512,   1,       DXGI_FORMAT_BC1_UNORM, { { 0, 131072, 1024 }, } }, // squard room platform_3_diff_512.dds
     {   512,   512,   1,       DXGI_FORMAT_BC1_UNORM, { { 131072, 131072, 1024 }, } }, // squard room platform_3_norm_512.dds
     {  1024,  1024,   1,       DXGI_FORMAT_BC1_UNORM, { { 262144, 524288, 2048 }, } }, // squard room platform_2_diff_1024.dds
@@ -837,7 +169,7 @@ const TEXTURES: [TextureResource; 74] = textures_array! {
     {  1024,  1024,   1,       DXGI_FORMAT_BC1_UNORM, { { 29753352, 524288, 2048 }, } },// Golfclub_nm_1024.dds
 };
 
-static DRAWS: [DrawParameters; 1025] = draws_array! {
+static DRAWS: [RawDraw; 1025] = draws_array! {
     {   0,   1,  -1,        0,    15198,        0 }, // subset0_squard_room_platform_3_dif1
     {   2,   3,  -1,    15198,      438,     6051 }, // subset0_squard_room_platform_2_dif
     {   2,   3,  -1,    15636,      300,     6164 }, // subset0_squard_room_platform_2_dif
@@ -0,0 +1,130 @@
+use d3dx12::build::AssetManifest;
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+};
+
+/// Directories, separated as in `PATH`, that are searched before any other.
+pub const ASSET_DIR_VARIABLE: &str = "DXSAMPLE_ASSET_DIR";
+
+/// Finds the data files a sample loads at run time. `new` searches, in order:
+///
+/// 1. the directories in `DXSAMPLE_ASSET_DIR`,
+/// 2. the directory the executable is in,
+/// 3. the `assets` directory at the root of the workspace,
+/// 4. the manifest the build script wrote next to the executable. Names are
+///    matched against it ignoring case, and each entry's source file is tried
+///    if the deployed copy has gone.
+#[derive(Debug, Clone, Default)]
+pub struct AssetLocator {
+    dirs: Vec<PathBuf>,
+    manifest: Option<(PathBuf, AssetManifest)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetNotFound {
+    pub name: String,
+    /// Every path that was looked at, in order.
+    pub tried: Vec<PathBuf>,
+}
+
+impl fmt::Display for AssetNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "cannot find asset '{}'; tried:", self.name)?;
+        for path in &self.tried {
+            writeln!(f, "    {}", path.display())?;
+        }
+        write!(
+            f,
+            "set {} to the directory that contains it",
+            ASSET_DIR_VARIABLE
+        )
+    }
+}
+
+impl std::error::Error for AssetNotFound {}
+
+impl From<AssetNotFound> for windows::core::Error {
+    fn from(error: AssetNotFound) -> Self {
+        windows::core::Error::new(windows::Win32::Foundation::E_FAIL, error.to_string())
+    }
+}
+
+impl AssetLocator {
+    pub fn new() -> Self {
+        let mut dirs = Vec::new();
+        if let Some(paths) = env::var_os(ASSET_DIR_VARIABLE) {
+            dirs.extend(env::split_paths(&paths).filter(|dir| !dir.as_os_str().is_empty()));
+        }
+
+        let exe = env::current_exe().ok();
+        let exe_dir = exe.as_deref().and_then(Path::parent);
+        dirs.extend(exe_dir.map(Path::to_path_buf));
+        dirs.extend(workspace_assets_dir());
+
+        let manifest = exe.as_deref().and_then(|exe| {
+            let dir = exe.parent()?;
+            let package = exe.file_stem()?.to_str()?;
+            let manifest = AssetManifest::read(dir.join(AssetManifest::file_name(package))).ok()?;
+            Some((dir.to_path_buf(), manifest))
+        });
+
+        AssetLocator { dirs, manifest }
+    }
+
+    /// Searches only `dirs`, in order.
+    pub fn with_dirs<I: IntoIterator<Item = PathBuf>>(dirs: I) -> Self {
+        AssetLocator {
+            dirs: dirs.into_iter().collect(),
+            manifest: None,
+        }
+    }
+
+    /// Searches `manifest` after the directories. Its paths are relative to
+    /// `dir`.
+    pub fn with_manifest(mut self, dir: PathBuf, manifest: AssetManifest) -> Self {
+        self.manifest = Some((dir, manifest));
+        self
+    }
+
+    pub fn locate(&self, name: &str) -> Result<PathBuf, AssetNotFound> {
+        let mut tried = Vec::new();
+
+        for dir in &self.dirs {
+            let path = dir.join(name);
+            if path.is_file() {
+                return Ok(path);
+            }
+            tried.push(path);
+        }
+
+        if let Some((dir, manifest)) = &self.manifest {
+            let name = name.replace('\\', "/");
+            for entry in manifest
+                .entries
+                .iter()
+                .filter(|entry| entry.path.eq_ignore_ascii_case(&name))
+            {
+                for path in [dir.join(&entry.path), entry.source.clone()] {
+                    if path.is_file() {
+                        return Ok(path);
+                    }
+                    tried.push(path);
+                }
+            }
+        }
+
+        Err(AssetNotFound {
+            name: name.to_string(),
+            tried,
+        })
+    }
+}
+
+fn workspace_assets_dir() -> Option<PathBuf> {
+    // This crate is at samples/dxsample.
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .ancestors()
+        .nth(2)
+        .map(|root| root.join("assets"))
+}
@@ -1,4 +1,5 @@
 use std::ffi::CString;
+use std::time::{Duration, Instant};
 use windows::core::*;
 use windows::Win32::Graphics::Direct3D::{D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_11_0};
 use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
@@ -10,6 +11,11 @@ use windows::Win32::{
     UI::WindowsAndMessaging::*,
 };
 
+mod assets;
+mod upload;
+pub use assets::*;
+pub use upload::*;
+
 pub trait DXSample {
     fn new(command_line: &SampleCommandLine) -> Result<Self>
     where
@@ -22,6 +28,10 @@ pub trait DXSample {
     fn on_key_up(&mut self, _key: VIRTUAL_KEY) {}
     fn on_key_down(&mut self, _key: VIRTUAL_KEY) {}
 
+    /// Called between frames, a few times a second, so that samples can pick
+    /// up shaders edited while they are running.
+    fn reload_shaders(&mut self) {}
+
     fn title(&self) -> String {
         "D3D12 Hello Triangle".into()
     }
@@ -34,20 +44,30 @@ pub trait DXSample {
 #[derive(Clone, Default)]
 pub struct SampleCommandLine {
     pub use_warp_device: bool,
+    /// Everything that is not a recognized flag, such as file names.
+    pub arguments: Vec<String>,
 }
 
 pub fn build_command_line() -> SampleCommandLine {
     let mut use_warp_device = false;
+    let mut arguments = Vec::new();
 
-    for arg in std::env::args() {
+    for arg in std::env::args().skip(1) {
         if arg.eq_ignore_ascii_case("-warp") || arg.eq_ignore_ascii_case("/warp") {
             use_warp_device = true;
+        } else {
+            arguments.push(arg);
         }
     }
 
-    SampleCommandLine { use_warp_device }
+    SampleCommandLine {
+        use_warp_device,
+        arguments,
+    }
 }
 
+const SHADER_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
 pub fn run_sample<S>() -> Result<()>
 where
     S: DXSample,
@@ -108,6 +128,8 @@ where
 
     let _ = unsafe { ShowWindow(hwnd, SW_SHOW) };
 
+    let mut last_reload = Instant::now();
+
     loop {
         let mut message = MSG::default();
 
@@ -121,6 +143,11 @@ where
                 break;
             }
         }
+
+        if last_reload.elapsed() >= SHADER_RELOAD_INTERVAL {
+            sample.reload_shaders();
+            last_reload = Instant::now();
+        }
     }
 
     Ok(())
@@ -0,0 +1,501 @@
+use crate::SynchronizedCommandQueue;
+use d3dx12::texture::SubresourceData;
+use d3dx12::{transition_barrier, HeapProperties, ResourceDesc};
+use windows::core::*;
+use windows::Win32::{
+    Foundation::{E_INVALIDARG, HANDLE},
+    Graphics::Direct3D12::*,
+};
+
+/// Gathers uploads to buffers and textures so that they all go through one
+/// upload buffer and one command list, rather than each making its own and
+/// waiting for the GPU.
+///
+/// Destinations have to be in `D3D12_RESOURCE_STATE_COPY_DEST`, the state to
+/// create them in, until the batch is submitted. Once its copies are done,
+/// each is transitioned to the state given for it.
+pub struct UploadBatch {
+    device: ID3D12Device,
+    staging: Staging,
+    copies: Vec<UploadCopy>,
+    transitions: Vec<(ID3D12Resource, D3D12_RESOURCE_STATES)>,
+}
+
+enum UploadCopy {
+    Buffer {
+        destination: ID3D12Resource,
+        destination_offset: u64,
+        source_offset: u64,
+        size: u64,
+    },
+    Texture {
+        destination: ID3D12Resource,
+        subresource: u32,
+        footprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT,
+    },
+}
+
+impl UploadBatch {
+    pub fn new(device: &ID3D12Device) -> Self {
+        UploadBatch {
+            device: device.clone(),
+            staging: Staging::default(),
+            copies: Vec::new(),
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    /// Copies `data` into `destination`, a buffer, starting `offset` bytes in.
+    pub fn upload_buffer(
+        &mut self,
+        destination: &ID3D12Resource,
+        offset: u64,
+        data: &[u8],
+        state_after: D3D12_RESOURCE_STATES,
+    ) {
+        self.copies.push(UploadCopy::Buffer {
+            destination: destination.clone(),
+            destination_offset: offset,
+            source_offset: self.staging.push_buffer(data),
+            size: data.len() as u64,
+        });
+        self.transition(destination, state_after);
+    }
+
+    /// Copies `subresources` into those of `destination`, a texture, starting
+    /// with `first_subresource`. Fails if the texture doesn't have that many,
+    /// or if any of them is too small for the part of the texture it fills.
+    pub fn upload_texture(
+        &mut self,
+        destination: &ID3D12Resource,
+        first_subresource: u32,
+        subresources: &[SubresourceData],
+        state_after: D3D12_RESOURCE_STATES,
+    ) -> Result<()> {
+        let desc = unsafe { destination.GetDesc() };
+        let array_size = if desc.Dimension == D3D12_RESOURCE_DIMENSION_TEXTURE3D {
+            1
+        } else {
+            desc.DepthOrArraySize as u32
+        };
+        let count = subresources.len();
+        if first_subresource as usize + count > desc.MipLevels as usize * array_size as usize {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "more subresources than the texture has",
+            ));
+        }
+
+        let mut layouts = vec![D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default(); count];
+        let mut num_rows = vec![0; count];
+        let mut row_sizes = vec![0; count];
+        let mut total_bytes = 0;
+        unsafe {
+            self.device.GetCopyableFootprints(
+                &desc,
+                first_subresource,
+                count as u32,
+                self.staging.texture_offset(),
+                Some(layouts.as_mut_ptr()),
+                Some(num_rows.as_mut_ptr()),
+                Some(row_sizes.as_mut_ptr()),
+                Some(&mut total_bytes),
+            );
+        }
+
+        let footprints: Vec<_> = (0..count)
+            .map(|index| TextureFootprint {
+                layout: layouts[index],
+                num_rows: num_rows[index],
+                row_size: row_sizes[index],
+            })
+            .collect();
+        if let Err(index) = self
+            .staging
+            .push_texture(&footprints, total_bytes, subresources)
+        {
+            return Err(Error::new(
+                E_INVALIDARG,
+                format!(
+                    "subresource {} is too small",
+                    first_subresource as usize + index
+                ),
+            ));
+        }
+
+        for (index, layout) in layouts.into_iter().enumerate() {
+            self.copies.push(UploadCopy::Texture {
+                destination: destination.clone(),
+                subresource: first_subresource + index as u32,
+                footprint: layout,
+            });
+        }
+        self.transition(destination, state_after);
+        Ok(())
+    }
+
+    /// Records the copies, and the transitions after them, on a command list
+    /// of its own, and executes it on `queue`, which has to be a direct queue.
+    pub fn submit(self, queue: &mut SynchronizedCommandQueue) -> Result<UploadToken> {
+        let mut upload_buffer = None;
+        let upload_buffer: ID3D12Resource = unsafe {
+            self.device.CreateCommittedResource(
+                &D3D12_HEAP_PROPERTIES::standard(D3D12_HEAP_TYPE_UPLOAD),
+                D3D12_HEAP_FLAG_NONE,
+                &D3D12_RESOURCE_DESC::buffer(self.staging.data.len().max(1)),
+                D3D12_RESOURCE_STATE_GENERIC_READ,
+                None,
+                &mut upload_buffer,
+            )
+        }
+        .and(Ok(upload_buffer.unwrap()))?;
+
+        unsafe {
+            let mut ptr = std::ptr::null_mut();
+            upload_buffer.Map(0, Some(&D3D12_RANGE { Begin: 0, End: 0 }), Some(&mut ptr))?;
+            let data = &self.staging.data;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.cast(), data.len());
+            upload_buffer.Unmap(0, None);
+        }
+
+        let allocator: ID3D12CommandAllocator = unsafe {
+            self.device
+                .CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_DIRECT)
+        }?;
+        let cl: ID3D12GraphicsCommandList = unsafe {
+            self.device
+                .CreateCommandList(0, D3D12_COMMAND_LIST_TYPE_DIRECT, &allocator, None)
+        }?;
+
+        for copy in &self.copies {
+            match copy {
+                UploadCopy::Buffer {
+                    destination,
+                    destination_offset,
+                    source_offset,
+                    size,
+                } => unsafe {
+                    cl.CopyBufferRegion(
+                        destination,
+                        *destination_offset,
+                        &upload_buffer,
+                        *source_offset,
+                        *size,
+                    );
+                },
+                UploadCopy::Texture {
+                    destination,
+                    subresource,
+                    footprint,
+                } => unsafe {
+                    cl.CopyTextureRegion(
+                        &D3D12_TEXTURE_COPY_LOCATION {
+                            pResource: std::mem::transmute_copy(destination),
+                            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+                            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                                SubresourceIndex: *subresource,
+                            },
+                        },
+                        0,
+                        0,
+                        0,
+                        &D3D12_TEXTURE_COPY_LOCATION {
+                            pResource: std::mem::transmute_copy(&upload_buffer),
+                            Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+                            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                                PlacedFootprint: *footprint,
+                            },
+                        },
+                        None,
+                    );
+                },
+            }
+        }
+
+        let barriers: Vec<_> = self
+            .transitions
+            .iter()
+            .filter(|(_, state_after)| *state_after != D3D12_RESOURCE_STATE_COPY_DEST)
+            .map(|(resource, state_after)| {
+                transition_barrier(resource, D3D12_RESOURCE_STATE_COPY_DEST, *state_after)
+            })
+            .collect();
+        if !barriers.is_empty() {
+            unsafe { cl.ResourceBarrier(&barriers) };
+        }
+
+        unsafe { cl.Close() }?;
+        queue.execute_command_lists(std::slice::from_ref(&cl));
+        let fence_value = queue.enqueue_signal()?;
+
+        Ok(UploadToken {
+            fence: queue.fence.clone(),
+            fence_value,
+            _upload_buffer: upload_buffer,
+            _allocator: allocator,
+            _command_list: cl,
+        })
+    }
+
+    /// Later uploads to the same resource replace its state.
+    fn transition(&mut self, resource: &ID3D12Resource, state_after: D3D12_RESOURCE_STATES) {
+        match self.transitions.iter_mut().find(|(r, _)| r == resource) {
+            Some((_, state)) => *state = state_after,
+            None => self.transitions.push((resource.clone(), state_after)),
+        }
+    }
+}
+
+/// The contents of an upload buffer, laid out as the copies out of it will
+/// read them. None of this needs a device, so it's kept apart from
+/// `UploadBatch`.
+#[derive(Default)]
+struct Staging {
+    data: Vec<u8>,
+}
+
+/// Where a subresource goes in the upload buffer, as `GetCopyableFootprints`
+/// describes it.
+#[derive(Clone, Copy)]
+struct TextureFootprint {
+    layout: D3D12_PLACED_SUBRESOURCE_FOOTPRINT,
+    num_rows: u32,
+    row_size: u64,
+}
+
+impl Staging {
+    /// Appends `data`, and returns the offset it's at.
+    fn push_buffer(&mut self, data: &[u8]) -> u64 {
+        let offset = self.data.len() as u64;
+        self.data.extend_from_slice(data);
+        offset
+    }
+
+    /// Where the next texture's footprints start: each starts on the
+    /// placement alignment, and `GetCopyableFootprints` keeps that for the
+    /// rest as long as the first does.
+    fn texture_offset(&self) -> u64 {
+        const ALIGNMENT: u64 = D3D12_TEXTURE_DATA_PLACEMENT_ALIGNMENT as u64;
+        (self.data.len() as u64).div_ceil(ALIGNMENT) * ALIGNMENT
+    }
+
+    /// Copies `subresources` into `footprints`, which were laid out from
+    /// `texture_offset` and take up `total_bytes` from there. Each source
+    /// row goes at its footprint's row pitch, and each depth slice after
+    /// the previous slice's rows. Returns the index of the first subresource
+    /// too small for its footprint, and leaves the data as it was.
+    fn push_texture(
+        &mut self,
+        footprints: &[TextureFootprint],
+        total_bytes: u64,
+        subresources: &[SubresourceData],
+    ) -> std::result::Result<(), usize> {
+        let start = self.data.len();
+        self.data
+            .resize((self.texture_offset() + total_bytes) as usize, 0);
+        for (index, (footprint, source)) in footprints.iter().zip(subresources).enumerate() {
+            let (num_rows, row_size) = (footprint.num_rows as usize, footprint.row_size as usize);
+            let layout = &footprint.layout;
+            let row_pitch = layout.Footprint.RowPitch as usize;
+            for slice in 0..layout.Footprint.Depth as usize {
+                for row in 0..num_rows {
+                    let source_offset =
+                        slice * source.slice_pitch as usize + row * source.row_pitch as usize;
+                    let Some(source_row) = source.data.get(source_offset..source_offset + row_size)
+                    else {
+                        self.data.truncate(start);
+                        return Err(index);
+                    };
+                    let offset = layout.Offset as usize + (slice * num_rows + row) * row_pitch;
+                    self.data[offset..offset + row_size].copy_from_slice(source_row);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps a submitted `UploadBatch`'s upload buffer and command list alive
+/// until the GPU has finished with them. Dropping the token before then waits
+/// for it to.
+#[must_use]
+pub struct UploadToken {
+    fence: ID3D12Fence,
+    fence_value: u64,
+    _upload_buffer: ID3D12Resource,
+    _allocator: ID3D12CommandAllocator,
+    _command_list: ID3D12GraphicsCommandList,
+}
+
+impl UploadToken {
+    /// The value the queue's fence reaches once the uploads are done.
+    pub fn fence_value(&self) -> u64 {
+        self.fence_value
+    }
+
+    pub fn is_complete(&self) -> bool {
+        unsafe { self.fence.GetCompletedValue() >= self.fence_value }
+    }
+
+    /// Blocks until the uploads are done, and then frees the upload buffer.
+    pub fn wait(self) -> Result<()> {
+        if !self.is_complete() {
+            // With no event to set, this returns once the fence gets there.
+            unsafe {
+                self.fence
+                    .SetEventOnCompletion(self.fence_value, HANDLE::default())
+            }?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UploadToken {
+    fn drop(&mut self) {
+        if !self.is_complete() {
+            let _ = unsafe {
+                self.fence
+                    .SetEventOnCompletion(self.fence_value, HANDLE::default())
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn footprint(
+        offset: u64,
+        row_pitch: u32,
+        depth: u32,
+        num_rows: u32,
+        row_size: u64,
+    ) -> TextureFootprint {
+        TextureFootprint {
+            layout: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
+                Offset: offset,
+                Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
+                    Depth: depth,
+                    RowPitch: row_pitch,
+                    ..Default::default()
+                },
+            },
+            num_rows,
+            row_size,
+        }
+    }
+
+    fn subresource(data: &[u8], row_pitch: u64, slice_pitch: u64) -> SubresourceData<'_> {
+        SubresourceData {
+            data: Cow::Borrowed(data),
+            row_pitch,
+            slice_pitch,
+        }
+    }
+
+    #[test]
+    fn buffers_follow_each_other() {
+        let mut staging = Staging::default();
+        assert_eq!(staging.push_buffer(&[1, 2, 3]), 0);
+        assert_eq!(staging.push_buffer(&[]), 3);
+        assert_eq!(staging.push_buffer(&[4, 5]), 3);
+        assert_eq!(staging.push_buffer(&[6]), 5);
+        assert_eq!(staging.data, [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn textures_start_on_the_placement_alignment() {
+        let alignment = D3D12_TEXTURE_DATA_PLACEMENT_ALIGNMENT as usize;
+        for &(size, offset) in &[
+            (0, 0),
+            (1, alignment),
+            (alignment, alignment),
+            (alignment + 1, alignment * 2),
+        ] {
+            let staging = Staging {
+                data: vec![0; size],
+            };
+            assert_eq!(staging.texture_offset(), offset as u64);
+        }
+    }
+
+    #[test]
+    fn rows_and_slices_go_at_the_footprint_pitches() {
+        let mut staging = Staging::default();
+        staging.push_buffer(&[1, 2, 3]);
+        let base = staging.texture_offset();
+        assert_eq!(base, 512);
+
+        // Two slices of two 3-byte rows, stored with a byte of padding after
+        // each row, and then a single 2-byte row.
+        let volume = [
+            10, 11, 12, 0, 13, 14, 15, 0, //
+            20, 21, 22, 0, 23, 24, 25, 0,
+        ];
+        let footprints = [
+            footprint(base, 256, 2, 2, 3),
+            footprint(base + 1024, 256, 1, 1, 2),
+        ];
+        staging
+            .push_texture(
+                &footprints,
+                1024 + 2,
+                &[subresource(&volume, 4, 8), subresource(&[30, 31], 2, 2)],
+            )
+            .unwrap();
+
+        let data = &staging.data;
+        assert_eq!(data.len(), 512 + 1024 + 2);
+        assert_eq!(data[..3], [1, 2, 3]);
+        let rows = [
+            (512, [10, 11, 12]),
+            (512 + 256, [13, 14, 15]),
+            (512 + 512, [20, 21, 22]),
+            (512 + 768, [23, 24, 25]),
+        ];
+        for &(offset, row) in &rows {
+            assert_eq!(data[offset..offset + 3], row);
+        }
+        assert_eq!(data[1536..], [30, 31]);
+        // Everything else is padding.
+        let written: usize = rows.len() * 3 + 3 + 2;
+        assert_eq!(data.iter().filter(|&&byte| byte != 0).count(), written);
+    }
+
+    #[test]
+    fn too_small_subresources_leave_the_data_as_it_was() {
+        let mut staging = Staging::default();
+        staging.push_buffer(&[1, 2, 3]);
+        let base = staging.texture_offset();
+        let footprints = [
+            footprint(base, 256, 1, 2, 4),
+            footprint(base + 512, 256, 1, 2, 4),
+        ];
+        let full = [5; 8];
+        // The last row is a byte short.
+        let short = [6; 7];
+        assert_eq!(
+            staging.push_texture(
+                &footprints,
+                512 + 260,
+                &[subresource(&full, 4, 8), subresource(&short, 4, 8)],
+            ),
+            Err(1)
+        );
+        assert_eq!(staging.data, [1, 2, 3]);
+
+        // Rows are read at the source's row pitch, so a pitch that runs past
+        // the data fails too.
+        assert_eq!(
+            staging.push_texture(&footprints[..1], 260, &[subresource(&full, 5, 10)]),
+            Err(0)
+        );
+        assert_eq!(staging.data, [1, 2, 3]);
+    }
+}